@@ -125,14 +125,16 @@ pub fn lookup_float(key: &str, t: &Tuple) -> f64 {
 /// --- Built-in operator definitions ---
 
 /// dump_tuple “operator”
-pub fn op_dump_tuple(show_reset: bool, mut out: Box<dyn Write + Send + Sync>) -> Operator {
-    let show_reset = show_reset;
+pub fn op_dump_tuple(show_reset: bool, out: Box<dyn Write + Send + Sync>) -> Operator {
+    let out = Arc::new(Mutex::new(out));
+    let reset_out = Arc::clone(&out);
     Operator {
         next: Box::new(move |tup: &Tuple| {
-            let _ = dump_tuple(&mut *out, tup);
+            let _ = dump_tuple(&mut *out.lock().unwrap(), tup);
         }),
         reset: Box::new(move |tup: &Tuple| {
             if show_reset {
+                let mut out = reset_out.lock().unwrap();
                 let _ = dump_tuple(&mut *out, tup);
                 let _ = writeln!(&mut *out, "[reset]");
             }
@@ -144,11 +146,13 @@ pub fn op_dump_tuple(show_reset: bool, mut out: Box<dyn Write + Send + Sync>) ->
 pub fn op_dump_csv(
     static_field: Option<(String,String)>,
     header: bool,
-    mut out: Box<dyn Write + Send + Sync>
+    out: Box<dyn Write + Send + Sync>
 ) -> Operator {
     let first = Arc::new(Mutex::new(header));
+    let out = Mutex::new(out);
     Operator {
         next: Box::new(move |tup: &Tuple| {
+            let mut out = out.lock().unwrap();
             let mut first = first.lock().unwrap();
             if *first {
                 if let Some((ref k,_)) = static_field { write!(&mut *out, "{},", k).ok(); }
@@ -167,8 +171,13 @@ pub fn op_dump_csv(
 }
 
 /// “epoch” operator: resets every epoch_width seconds
+///
+/// Each boundary crossed calls the downstream *reset* exactly once, with a
+/// tuple holding only `key_out => eid` of the epoch that just closed.  The
+/// downstream operator is shared between our next and reset closures.
 pub fn op_epoch(epoch_width: f64, key_out: String) -> OpCreator {
     Box::new(move |next_op: Operator| {
+        let next_op = Arc::new(next_op);
         let boundary = Arc::new(Mutex::new(0.0));
         let eid = Arc::new(Mutex::new(0));
         Operator {
@@ -176,38 +185,36 @@ pub fn op_epoch(epoch_width: f64, key_out: String) -> OpCreator {
                 let boundary = Arc::clone(&boundary);
                 let eid = Arc::clone(&eid);
                 let key_out = key_out.clone();
-                let next_op = next_op.next.clone();
-                let reset_op = next_op.clone();
+                let next_op = Arc::clone(&next_op);
                 Box::new(move |tup: &Tuple| {
                     let time = float_of_op_result(&tup["time"]);
                     let mut b = boundary.lock().unwrap();
                     let mut e = eid.lock().unwrap();
                     if *b == 0.0 {
                         *b = time + epoch_width;
-                    } else if time >= *b {
-                        while time >= *b {
-                            let mut reset_tup = Tuple::new();
-                            reset_tup.insert(key_out.clone(), OpResult::Int(*e));
-                            reset_op(&reset_tup);
-                            *b += epoch_width;
-                            *e += 1;
-                        }
+                    }
+                    while time >= *b {
+                        let mut reset_tup = Tuple::new();
+                        reset_tup.insert(key_out.clone(), OpResult::Int(*e));
+                        (next_op.reset)(&reset_tup);
+                        *b += epoch_width;
+                        *e += 1;
                     }
                     let mut out_tup = tup.clone();
                     out_tup.insert(key_out.clone(), OpResult::Int(*e));
-                    next_op(&out_tup);
+                    (next_op.next)(&out_tup);
                 })
             },
             reset: {
                 let boundary = Arc::clone(&boundary);
                 let eid = Arc::clone(&eid);
                 let key_out = key_out.clone();
-                let next_reset = next_op.reset.clone();
+                let next_op = Arc::clone(&next_op);
                 Box::new(move |_tup: &Tuple| {
                     let mut reset_tup = Tuple::new();
                     let e = *eid.lock().unwrap();
                     reset_tup.insert(key_out.clone(), OpResult::Int(e));
-                    next_reset(&reset_tup);
+                    (next_op.reset)(&reset_tup);
                     *boundary.lock().unwrap() = 0.0;
                     *eid.lock().unwrap() = 0;
                 })
@@ -220,15 +227,17 @@ pub fn op_epoch(epoch_width: f64, key_out: String) -> OpCreator {
 pub fn op_filter<F>(pred: F) -> OpCreator
 where F: Fn(&Tuple) -> bool + Send + Sync + 'static
 {
+    let pred = Arc::new(pred);
     Box::new(move |next_op: Operator| {
-        let pred = pred.clone();
+        let pred = Arc::clone(&pred);
+        let Operator { next, reset } = next_op;
         Operator {
             next: Box::new(move |tup: &Tuple| {
                 if pred(tup) {
-                    next_op.next(tup);
+                    next(tup);
                 }
             }),
-            reset: next_op.reset.clone(),
+            reset,
         }
     })
 }
@@ -237,14 +246,54 @@ where F: Fn(&Tuple) -> bool + Send + Sync + 'static
 pub fn op_map<F>(func: F) -> OpCreator
 where F: Fn(&Tuple) -> Tuple + Send + Sync + 'static
 {
+    let func = Arc::new(func);
     Box::new(move |next_op: Operator| {
-        let func = func.clone();
+        let func = Arc::clone(&func);
+        let Operator { next, reset } = next_op;
         Operator {
             next: Box::new(move |tup: &Tuple| {
                 let t2 = func(tup);
-                next_op.next(&t2);
+                next(&t2);
             }),
-            reset: next_op.reset.clone(),
+            reset,
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sink that records every tuple passed to next and reset.
+    fn recording_sink() -> (Operator, Arc<Mutex<Vec<Tuple>>>, Arc<Mutex<Vec<Tuple>>>) {
+        let nexts = Arc::new(Mutex::new(Vec::new()));
+        let resets = Arc::new(Mutex::new(Vec::new()));
+        let (n, r) = (Arc::clone(&nexts), Arc::clone(&resets));
+        let op = Operator {
+            next: Box::new(move |tup: &Tuple| n.lock().unwrap().push(tup.clone())),
+            reset: Box::new(move |tup: &Tuple| r.lock().unwrap().push(tup.clone())),
+        };
+        (op, nexts, resets)
+    }
+
+    fn at(time: f64) -> Tuple {
+        tuple_of_list(vec![("time".to_string(), OpResult::Float(time))])
+    }
+
+    #[test]
+    fn epoch_resets_downstream_once_per_boundary() {
+        let (sink, nexts, resets) = recording_sink();
+        let op = op_epoch(1.0, "eid".to_string())(sink);
+        for time in [100.0, 100.5, 101.2, 103.5] {
+            (op.next)(&at(time));
+        }
+        (op.reset)(&Tuple::new());
+
+        let emitted: Vec<i32> = nexts.lock().unwrap().iter().map(|t| lookup_int("eid", t)).collect();
+        assert_eq!(emitted, vec![0, 0, 1, 3]);
+        let resets = resets.lock().unwrap();
+        let reset_eids: Vec<i32> = resets.iter().map(|t| lookup_int("eid", t)).collect();
+        assert_eq!(reset_eids, vec![0, 1, 2, 3]);
+        assert!(resets.iter().all(|t| t.len() == 1));
+    }
+}
@@ -663,6 +663,40 @@ pub fn split(left: Box<dyn Operator>, right: Box<dyn Operator>) -> Box<dyn Opera
     Box::new(SplitOp::new(left, right))
 }
 
+// --- Shared Operator ---
+// A cloneable handle to one downstream operator, so several query chains can
+// feed the same sink (fan-in). Every clone forwards to the same operator.
+#[derive(Clone)]
+pub struct SharedOp {
+    inner: Rc<RefCell<Box<dyn Operator>>>,
+}
+
+impl SharedOp {
+    pub fn new(op: Box<dyn Operator>) -> Self {
+        Self { inner: Rc::new(RefCell::new(op)) }
+    }
+
+    /// Returns a boxed handle that can be passed as a query's `next_op`.
+    pub fn handle(&self) -> Box<dyn Operator> {
+        Box::new(self.clone())
+    }
+}
+
+impl Operator for SharedOp {
+    fn next(&mut self, tup: Tuple) {
+        self.inner.borrow_mut().next(tup);
+    }
+
+    fn reset(&mut self, tup: Tuple) {
+        self.inner.borrow_mut().reset(tup);
+    }
+}
+
+/// Creates a `SharedOp` wrapping `op`.
+pub fn shared(op: Box<dyn Operator>) -> SharedOp {
+    SharedOp::new(op)
+}
+
 // --- Join Operator ---
 // Joins tuples from two streams based on keys and epoch IDs.
 // This is complex due to shared state.
@@ -2,8 +2,6 @@ use std::{
     io::{stdout, Write},
     net::Ipv4Addr,
     str::FromStr,
-    rc::Rc,
-    cell::RefCell
 };
 
 // Modules defined in other files
@@ -18,7 +16,7 @@ use utils::{
 };
 use builtins::{
     epoch, groupby, filter, map, distinct, split, join, dump_tuple, dump_as_csv,
-    meta_meter, key_geq_int, rename_filtered_keys, dump_walts_csv, read_walts_csv, // Add read/write CSV if needed
+    meta_meter, key_geq_int, rename_filtered_keys, dump_walts_csv, read_walts_csv, shared, // Add read/write CSV if needed
 };
 
 // --- Query Definitions ---
@@ -605,32 +603,32 @@ fn run_queries() {
 
     // --- Define the final sink/output operator ---
     // Let's use a simple tuple dump to stdout for demonstration
-    // We wrap it in Rc<RefCell> because some queries (joins) need shared access to the sink.
-    let final_sink = Rc::new(RefCell::new(dump_tuple(stdout(), false)));
+    // Every query writes into the same sink, so we share it and hand each query its own handle.
+    let final_sink = shared(dump_tuple(stdout(), false));
 
 
     // --- Instantiate queries ---
     // Queries that return a single operator
     let mut single_op_queries: Vec<Box<dyn Operator>> = vec![
-        ident(final_sink.borrow().clone()), // Requires clone because dump_tuple isn't Copy
-        count_pkts(final_sink.borrow().clone()),
-        pkts_per_src_dst(final_sink.borrow().clone()),
-        distinct_srcs(final_sink.borrow().clone()),
-        tcp_new_cons(final_sink.borrow().clone()),
-        ssh_brute_force(final_sink.borrow().clone()),
-        super_spreader(final_sink.borrow().clone()),
-        port_scan(final_sink.borrow().clone()),
-        ddos(final_sink.borrow().clone()),
-        q3(final_sink.borrow().clone()),
-        q4(final_sink.borrow().clone()),
+        ident(final_sink.handle()),
+        count_pkts(final_sink.handle()),
+        pkts_per_src_dst(final_sink.handle()),
+        distinct_srcs(final_sink.handle()),
+        tcp_new_cons(final_sink.handle()),
+        ssh_brute_force(final_sink.handle()),
+        super_spreader(final_sink.handle()),
+        port_scan(final_sink.handle()),
+        ddos(final_sink.handle()),
+        q3(final_sink.handle()),
+        q4(final_sink.handle()),
     ];
 
     // Queries that return multiple operators (due to joins)
     let mut multi_op_queries_starts: Vec<Vec<Box<dyn Operator>>> = vec![
-        syn_flood_sonata(final_sink.borrow().clone()),
-        completed_flows(final_sink.borrow().clone()),
-        slowloris(final_sink.borrow().clone()),
-        join_test(final_sink.borrow().clone()),
+        syn_flood_sonata(final_sink.handle()),
+        completed_flows(final_sink.handle()),
+        slowloris(final_sink.handle()),
+        join_test(final_sink.handle()),
     ];
 
     // Flatten the multi-op starts into the main list
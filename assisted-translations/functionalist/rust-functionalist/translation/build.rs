@@ -0,0 +1,11 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/sonata.proto");
+        let mut config: prost_build::Config = prost_build::Config::new();
+        config.protoc_executable(protoc_bin_vendored::protoc_bin_path().unwrap());
+        tonic_build::configure()
+            .compile_protos_with_config(config, &["proto/sonata.proto"], &["proto"])
+            .unwrap();
+    }
+}
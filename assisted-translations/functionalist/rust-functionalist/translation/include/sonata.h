@@ -0,0 +1,41 @@
+#ifndef SONATA_H
+#define SONATA_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SONATA_OK 0
+#define SONATA_ERR_NULL -1
+#define SONATA_ERR_DECODE -2
+#define SONATA_ERR_PANIC -3
+
+typedef struct SonataPipeline SonataPipeline;
+
+/* Called once per output tuple with a NUL-terminated JSON object that is
+ * only valid for the duration of the call. */
+typedef void (*sonata_sink_fn)(void *user_data, const char *tuple_json, size_t len);
+
+/* `query` is a DSL query or the name of a built-in demo query. Returns NULL
+ * if the query does not compile. */
+SonataPipeline *sonata_pipeline_new(const char *query, bool decapsulate,
+                                    sonata_sink_fn sink, void *user_data);
+
+/* Feeds one Ethernet frame captured at `ts` seconds. */
+int sonata_pipeline_feed_packet(SonataPipeline *pipeline, const uint8_t *data,
+                                size_t len, double ts);
+
+/* Closes the current window, emitting any pending tuples. */
+int sonata_pipeline_flush(SonataPipeline *pipeline);
+
+void sonata_pipeline_free(SonataPipeline *pipeline);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
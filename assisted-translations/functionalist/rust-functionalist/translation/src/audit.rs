@@ -0,0 +1,216 @@
+#![allow(dead_code)]
+
+use crate::error::StreamError;
+use crate::utils::{Headers, OpResult, OperatorRef};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::mem;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Invariant {
+    ResetEidIncreases,
+    NoNextAfterFinalReset,
+    NextCarriesEpochKey,
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Invariant::ResetEidIncreases => "reset eid must strictly increase",
+            Invariant::NoNextAfterFinalReset => "no next() after the final reset",
+            Invariant::NextCarriesEpochKey => "next() tuples must carry the epoch key",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub operator: String,
+    pub invariant: Invariant,
+    pub count: usize,
+    pub first: String,
+}
+
+#[derive(Default)]
+struct OperatorAudit {
+    last_reset_eid: Option<i32>,
+    scoped: bool,
+    finished: bool,
+}
+
+#[derive(Default)]
+struct AuditState {
+    finishing: Cell<bool>,
+    violations: RefCell<BTreeMap<(usize, Invariant), Violation>>,
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    eid_key: Rc<String>,
+    names: Rc<RefCell<Vec<String>>>,
+    state: Rc<AuditState>,
+}
+
+impl AuditLog {
+    pub fn new(eid_key: &str) -> Self {
+        AuditLog {
+            eid_key: Rc::new(eid_key.to_string()),
+            names: Rc::new(RefCell::new(Vec::new())),
+            state: Rc::new(AuditState::default()),
+        }
+    }
+
+    pub fn eid_key(&self) -> &str {
+        &self.eid_key
+    }
+
+    pub fn operators(&self) -> Vec<String> {
+        self.names.borrow().clone()
+    }
+
+    pub fn violations(&self) -> Vec<Violation> {
+        self.state.violations.borrow().values().cloned().collect()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.state.violations.borrow().is_empty()
+    }
+
+    pub fn report(&self, outc: &mut dyn Write) -> Result<(), StreamError> {
+        let names = self.names.borrow();
+        let violations = self.state.violations.borrow();
+        writeln!(
+            outc,
+            "audit: {} operator(s) checked, {} violation(s)",
+            names.len(),
+            violations
+                .values()
+                .map(|v: &Violation| v.count)
+                .sum::<usize>()
+        )?;
+        for violation in violations.values() {
+            writeln!(
+                outc,
+                "{}: {} ({}x), first: {}",
+                violation.operator, violation.invariant, violation.count, violation.first
+            )?;
+        }
+        Ok(())
+    }
+
+    fn register(&self, label: &str) -> usize {
+        let mut names = self.names.borrow_mut();
+        let dupes: usize = names
+            .iter()
+            .filter(|name: &&String| name.split(" #").next() == Some(label))
+            .count();
+        names.push(match dupes {
+            0 => label.to_string(),
+            n => format!("{} #{}", label, n + 1),
+        });
+        names.len() - 1
+    }
+
+    fn record(&self, op: usize, invariant: Invariant, detail: impl FnOnce() -> String) {
+        self.state
+            .violations
+            .borrow_mut()
+            .entry((op, invariant))
+            .or_insert_with(|| Violation {
+                operator: self.names.borrow()[op].clone(),
+                invariant,
+                count: 0,
+                first: detail(),
+            })
+            .count += 1;
+    }
+
+    fn eid_of(&self, headers: &Headers) -> Option<i32> {
+        match headers.get(self.eid_key.as_str()) {
+            Some(OpResult::Int(eid)) => Some(*eid),
+            _ => None,
+        }
+    }
+}
+
+fn audit_operator(op: &OperatorRef, id: usize, is_root: bool, log: &AuditLog) {
+    let audit: Rc<RefCell<OperatorAudit>> = Rc::new(RefCell::new(OperatorAudit::default()));
+    let (next_audit, next_log) = (Rc::clone(&audit), log.clone());
+    let reset_log: AuditLog = log.clone();
+    let mut node = op.borrow_mut();
+    let mut inner_next: Box<dyn FnMut(&mut Headers) + 'static> =
+        mem::replace(&mut node.next, Box::new(|_: &mut Headers| {}));
+    let mut inner_reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        mem::replace(&mut node.reset, Box::new(|_: &mut Headers| {}));
+
+    node.next = Box::new(move |headers: &mut Headers| {
+        let eid: Option<i32> = next_log.eid_of(headers);
+        {
+            let mut audit = next_audit.borrow_mut();
+            if audit.finished {
+                next_log.record(id, Invariant::NoNextAfterFinalReset, || {
+                    format!("next({:?}) after the final reset", eid)
+                });
+            }
+            match eid {
+                Some(_) => audit.scoped = true,
+                None if audit.scoped => {
+                    next_log.record(id, Invariant::NextCarriesEpochKey, || {
+                        format!(
+                            "tuple without '{}' after earlier tuples had it",
+                            next_log.eid_key
+                        )
+                    });
+                }
+                None => {}
+            }
+        }
+        inner_next(headers)
+    });
+
+    node.reset = Box::new(move |headers: &mut Headers| {
+        if is_root {
+            reset_log.state.finishing.set(true);
+        }
+        let eid: Option<i32> = reset_log.eid_of(headers);
+        {
+            let mut audit = audit.borrow_mut();
+            if let Some(eid) = eid {
+                audit.scoped = true;
+                if let Some(last) = audit.last_reset_eid.filter(|last: &i32| eid <= *last) {
+                    reset_log.record(id, Invariant::ResetEidIncreases, || {
+                        format!("reset({}) after reset({})", eid, last)
+                    });
+                }
+                audit.last_reset_eid = Some(eid);
+            }
+        }
+        inner_reset(headers);
+        if reset_log.state.finishing.get() {
+            audit.borrow_mut().finished = true;
+        }
+    });
+}
+
+pub fn audit_operators(root: &OperatorRef, log: &AuditLog) {
+    let mut seen: HashSet<*const ()> = HashSet::new();
+    let mut pending: Vec<OperatorRef> = vec![Rc::clone(root)];
+    while let Some(op) = pending.pop() {
+        if !seen.insert(Rc::as_ptr(&op) as *const ()) {
+            continue;
+        }
+        let (label, downstream): (String, Vec<OperatorRef>) = {
+            let node = op.borrow();
+            (node.label.clone(), node.downstream.clone())
+        };
+        let is_root: bool = Rc::ptr_eq(&op, root);
+        if !label.is_empty() || is_root {
+            let name: &str = if label.is_empty() { "source" } else { &label };
+            audit_operator(&op, log.register(name), is_root, log);
+        }
+        pending.extend(downstream.into_iter().rev());
+    }
+}
@@ -0,0 +1,454 @@
+#![allow(dead_code)]
+
+use crate::bpf::KernelFilter;
+use crate::builtins::{Cmp, FilterFunc, get_mapped_int};
+use crate::keys::WellKnownKey;
+use crate::traffic_gen::{Scenario, generate};
+use crate::utils::{Headers, OpResult, Operator, OperatorRef, TCP_SYN};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub const LANES: usize = 64;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    pub fn zeros(len: usize) -> Self {
+        Bitmap {
+            words: vec![0; len.div_ceil(LANES)],
+            len,
+        }
+    }
+
+    pub fn ones(len: usize) -> Self {
+        let mut bitmap: Bitmap = Bitmap {
+            words: vec![u64::MAX; len.div_ceil(LANES)],
+            len,
+        };
+        if let Some(last) = bitmap.words.last_mut()
+            && !len.is_multiple_of(LANES)
+        {
+            *last = (1u64 << (len % LANES)) - 1;
+        }
+        bitmap
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        i < self.len && (self.words[i / LANES] >> (i % LANES)) & 1 == 1
+    }
+
+    pub fn set(&mut self, i: usize) {
+        if i < self.len {
+            self.words[i / LANES] |= 1 << (i % LANES);
+        }
+    }
+
+    pub fn and(&mut self, other: &Bitmap) {
+        for (word, other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= *other;
+        }
+    }
+
+    pub fn or(&mut self, other: &Bitmap) {
+        for (word, other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= *other;
+        }
+    }
+
+    pub fn not(&mut self) {
+        let ones: Bitmap = Bitmap::ones(self.len);
+        for (word, mask) in self.words.iter_mut().zip(ones.words.iter()) {
+            *word = !*word & *mask;
+        }
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, word)| {
+            let mut word: u64 = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit: usize = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * LANES + bit)
+            })
+        })
+    }
+}
+
+fn mask_where(values: &[i32], pred: impl Fn(i32) -> bool) -> Bitmap {
+    let mut words: Vec<u64> = Vec::with_capacity(values.len().div_ceil(LANES));
+    let chunks = values.chunks_exact(LANES);
+    let rest: &[i32] = chunks.remainder();
+    for chunk in chunks {
+        let mut word: u64 = 0;
+        for (j, v) in chunk.iter().enumerate() {
+            word |= (pred(*v) as u64) << j;
+        }
+        words.push(word);
+    }
+    if !rest.is_empty() {
+        let mut word: u64 = 0;
+        for (j, v) in rest.iter().enumerate() {
+            word |= (pred(*v) as u64) << j;
+        }
+        words.push(word);
+    }
+    Bitmap {
+        words,
+        len: values.len(),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn mask_simd(values: &[i32], cmp: IntCmp, x: i32) -> Option<Bitmap> {
+    // SAFETY: SSE2 is part of the x86_64 baseline, so every x86_64 CPU has it.
+    Some(unsafe { mask_sse2(values, cmp, x) })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+fn mask_sse2(values: &[i32], cmp: IntCmp, x: i32) -> Bitmap {
+    use std::arch::x86_64::{
+        __m128i, _mm_and_si128, _mm_castsi128_ps, _mm_cmpeq_epi32, _mm_cmpgt_epi32,
+        _mm_cmplt_epi32, _mm_loadu_si128, _mm_movemask_ps, _mm_set1_epi32,
+    };
+
+    let negate: bool = matches!(cmp, IntCmp::Ne | IntCmp::Le | IntCmp::Ge);
+    let chunks = values.chunks_exact(LANES);
+    let rest: &[i32] = chunks.remainder();
+    let mut words: Vec<u64> = Vec::with_capacity(values.len().div_ceil(LANES));
+    let needle: __m128i = _mm_set1_epi32(x);
+    for chunk in chunks {
+        let mut word: u64 = 0;
+        for (j, quad) in chunk.chunks_exact(4).enumerate() {
+            // SAFETY: `quad` is exactly four i32s, and loadu has no alignment requirement.
+            let v: __m128i = unsafe { _mm_loadu_si128(quad.as_ptr() as *const __m128i) };
+            let hits: __m128i = match cmp {
+                IntCmp::Eq | IntCmp::Ne => _mm_cmpeq_epi32(v, needle),
+                IntCmp::Lt | IntCmp::Ge => _mm_cmplt_epi32(v, needle),
+                IntCmp::Gt | IntCmp::Le => _mm_cmpgt_epi32(v, needle),
+                IntCmp::AllBits => _mm_cmpeq_epi32(_mm_and_si128(v, needle), needle),
+            };
+            word |= (_mm_movemask_ps(_mm_castsi128_ps(hits)) as u64) << (j * 4);
+        }
+        words.push(if negate { !word } else { word });
+    }
+    if !rest.is_empty() {
+        let tail: Bitmap = mask_scalar(rest, cmp, x);
+        words.extend(tail.words);
+    }
+    Bitmap {
+        words,
+        len: values.len(),
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn mask_simd(_values: &[i32], _cmp: IntCmp, _x: i32) -> Option<Bitmap> {
+    None
+}
+
+pub fn mask_scalar(values: &[i32], cmp: IntCmp, x: i32) -> Bitmap {
+    match cmp {
+        IntCmp::Eq => mask_where(values, |v: i32| v == x),
+        IntCmp::Ne => mask_where(values, |v: i32| v != x),
+        IntCmp::Lt => mask_where(values, |v: i32| v < x),
+        IntCmp::Le => mask_where(values, |v: i32| v <= x),
+        IntCmp::Gt => mask_where(values, |v: i32| v > x),
+        IntCmp::Ge => mask_where(values, |v: i32| v >= x),
+        IntCmp::AllBits => mask_where(values, |v: i32| v & x == x),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntCmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    AllBits,
+}
+
+impl From<Cmp> for IntCmp {
+    fn from(cmp: Cmp) -> IntCmp {
+        match cmp {
+            Cmp::Eq => IntCmp::Eq,
+            Cmp::Ne => IntCmp::Ne,
+            Cmp::Lt => IntCmp::Lt,
+            Cmp::Le => IntCmp::Le,
+            Cmp::Gt => IntCmp::Gt,
+            Cmp::Ge => IntCmp::Ge,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColumnPredicate {
+    pub key: WellKnownKey,
+    pub cmp: IntCmp,
+    pub value: i32,
+}
+
+impl ColumnPredicate {
+    pub fn new(key: WellKnownKey, cmp: IntCmp, value: i32) -> Self {
+        ColumnPredicate { key, cmp, value }
+    }
+
+    pub fn mask(&self, values: &[i32]) -> Bitmap {
+        mask_simd(values, self.cmp, self.value)
+            .unwrap_or_else(|| mask_scalar(values, self.cmp, self.value))
+    }
+}
+
+pub struct IntColumn {
+    pub values: Vec<i32>,
+    pub present: Bitmap,
+}
+
+impl IntColumn {
+    pub fn of_rows(key: WellKnownKey, rows: &[Headers]) -> Self {
+        let mut present: Bitmap = Bitmap::zeros(rows.len());
+        let values: Vec<i32> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, headers)| match headers.get_known(key) {
+                Some(OpResult::Int(v)) => {
+                    present.set(i);
+                    *v
+                }
+                _ => 0,
+            })
+            .collect();
+        IntColumn { values, present }
+    }
+}
+
+pub struct ColumnBatch {
+    pub rows: Vec<Headers>,
+    columns: Vec<(WellKnownKey, IntColumn)>,
+}
+
+impl ColumnBatch {
+    pub fn new(rows: Vec<Headers>) -> Self {
+        ColumnBatch {
+            rows,
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn column(&mut self, key: WellKnownKey) -> &IntColumn {
+        let idx: usize = match self.columns.iter().position(|(k, _)| *k == key) {
+            Some(idx) => idx,
+            None => {
+                self.columns
+                    .push((key, IntColumn::of_rows(key, &self.rows)));
+                self.columns.len() - 1
+            }
+        };
+        &self.columns[idx].1
+    }
+
+    pub fn select(&mut self, predicates: &[ColumnPredicate]) -> Bitmap {
+        let mut selection: Bitmap = Bitmap::ones(self.len());
+        for pred in predicates.iter() {
+            let column: &IntColumn = self.column(pred.key);
+            selection.and(&column.present);
+            selection.and(&pred.mask(&column.values));
+        }
+        selection
+    }
+
+    pub fn into_selected(mut self, predicates: &[ColumnPredicate]) -> Vec<Headers> {
+        let selection: Bitmap = self.select(predicates);
+        self.take_rows(&selection)
+    }
+
+    fn filter_mask(&mut self, filter: &KernelFilter) -> Bitmap {
+        match filter {
+            KernelFilter::Accept => Bitmap::ones(self.len()),
+            KernelFilter::Reject => Bitmap::zeros(self.len()),
+            KernelFilter::Test(key, cmp, k) => {
+                ColumnPredicate::new(*key, IntCmp::from(*cmp), *k as i32)
+                    .mask(&self.column(*key).values)
+            }
+            KernelFilter::Not(inner) => {
+                let mut mask: Bitmap = self.filter_mask(inner);
+                mask.not();
+                mask
+            }
+            KernelFilter::And(a, b) => {
+                let mut mask: Bitmap = self.filter_mask(a);
+                mask.and(&self.filter_mask(b));
+                mask
+            }
+            KernelFilter::Or(a, b) => {
+                let mut mask: Bitmap = self.filter_mask(a);
+                mask.or(&self.filter_mask(b));
+                mask
+            }
+        }
+    }
+
+    pub fn select_filter(&mut self, filter: &KernelFilter) -> Bitmap {
+        let mut keys: Vec<WellKnownKey> = Vec::new();
+        filter_keys(filter, &mut keys);
+        let mut incomplete: Bitmap = Bitmap::ones(self.len());
+        for key in keys {
+            incomplete.and(&self.column(key).present);
+        }
+        incomplete.not();
+        let mut selection: Bitmap = self.filter_mask(filter);
+        selection.or(&incomplete);
+        selection
+    }
+
+    pub fn into_filtered(mut self, filter: &KernelFilter) -> Vec<Headers> {
+        if *filter == KernelFilter::Accept {
+            return self.rows;
+        }
+        let selection: Bitmap = self.select_filter(filter);
+        self.take_rows(&selection)
+    }
+
+    fn take_rows(self, selection: &Bitmap) -> Vec<Headers> {
+        let mut rows: Vec<Option<Headers>> = self.rows.into_iter().map(Some).collect();
+        selection
+            .iter_ones()
+            .filter_map(|i| rows[i].take())
+            .collect()
+    }
+}
+
+fn filter_keys(filter: &KernelFilter, keys: &mut Vec<WellKnownKey>) {
+    match filter {
+        KernelFilter::Test(key, _, _) if !keys.contains(key) => keys.push(*key),
+        KernelFilter::Not(inner) => filter_keys(inner, keys),
+        KernelFilter::And(a, b) | KernelFilter::Or(a, b) => {
+            filter_keys(a, keys);
+            filter_keys(b, keys);
+        }
+        _ => {}
+    }
+}
+
+pub fn create_batch_filter_operator(
+    predicates: Vec<ColumnPredicate>,
+    batch_size: usize,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let batch_size: usize = batch_size.max(1);
+    let buffer: Rc<RefCell<Vec<Headers>>> = Rc::new(RefCell::new(Vec::new()));
+    let predicates: Rc<Vec<ColumnPredicate>> = Rc::new(predicates);
+    let reset_buffer_ref = Rc::clone(&buffer);
+    let reset_predicates_ref = Rc::clone(&predicates);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let flush =
+        |buffer: &RefCell<Vec<Headers>>, predicates: &[ColumnPredicate], next_op: &OperatorRef| {
+            let rows: Vec<Headers> = std::mem::take(&mut *buffer.borrow_mut());
+            for mut headers in ColumnBatch::new(rows).into_selected(predicates) {
+                (next_op.borrow_mut().next)(&mut headers)
+            }
+        };
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        buffer.borrow_mut().push(headers.clone());
+        if buffer.borrow().len() >= batch_size {
+            flush(&buffer, &predicates, &next_op);
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        flush(&reset_buffer_ref, &reset_predicates_ref, &next_op_ref_clone);
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("batch_filter")
+            .with_downstream(downstream),
+    ))
+}
+
+pub struct FilterBenchmark {
+    pub selected: usize,
+    pub per_tuple: Duration,
+    pub column_build: Duration,
+    pub columnar: Duration,
+}
+
+pub fn benchmark_filter(packets: usize, batch_size: usize) -> FilterBenchmark {
+    let rows: Vec<Headers> = generate(7, 10.0, &[Scenario::Background { packets }]);
+    let predicates: Vec<ColumnPredicate> = vec![
+        ColumnPredicate::new(WellKnownKey::Ipv4Proto, IntCmp::Eq, 6),
+        ColumnPredicate::new(WellKnownKey::L4Flags, IntCmp::Eq, TCP_SYN),
+        ColumnPredicate::new(WellKnownKey::L4Dport, IntCmp::Eq, 80),
+    ];
+    let closure: FilterFunc = Box::new(|headers: &Headers| {
+        get_mapped_int(WellKnownKey::Ipv4Proto, headers) == 6
+            && get_mapped_int(WellKnownKey::L4Flags, headers) == TCP_SYN
+            && get_mapped_int(WellKnownKey::L4Dport, headers) == 80
+    });
+
+    let start: Instant = Instant::now();
+    let per_tuple: usize = rows.iter().filter(|headers| closure(headers)).count();
+    let per_tuple_time: Duration = start.elapsed();
+
+    let mut batches: Vec<ColumnBatch> = rows
+        .chunks(batch_size.max(1))
+        .map(|chunk: &[Headers]| ColumnBatch::new(chunk.to_vec()))
+        .collect();
+    let start: Instant = Instant::now();
+    for batch in batches.iter_mut() {
+        for pred in predicates.iter() {
+            batch.column(pred.key);
+        }
+    }
+    let column_build: Duration = start.elapsed();
+    let batched: usize = batches
+        .iter_mut()
+        .map(|batch: &mut ColumnBatch| batch.select(&predicates).count_ones())
+        .sum();
+    let columnar: Duration = start.elapsed();
+
+    assert_eq!(per_tuple, batched);
+    FilterBenchmark {
+        selected: per_tuple,
+        per_tuple: per_tuple_time,
+        column_build,
+        columnar,
+    }
+}
@@ -0,0 +1,274 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::builtins::tuple_time;
+use crate::clock::{ClockRef, elapsed_clock};
+use crate::keys::WellKnownKey;
+use crate::schema::{FieldType, Schema, SchemaError};
+use crate::utils::{Headers, OpResult, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub const DEFAULT_BIFLOW_TIMEOUT: f64 = 60.0;
+
+pub fn counterpart(key: &str) -> Option<String> {
+    [("sport", "dport"), ("src", "dst")]
+        .iter()
+        .find_map(|(a, b)| {
+            if let Some(prefix) = key.strip_suffix(a) {
+                Some(format!("{}{}", prefix, b))
+            } else {
+                key.strip_suffix(b)
+                    .map(|prefix: &str| format!("{}{}", prefix, a))
+            }
+        })
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BiflowKeys {
+    pub pairs: Vec<(String, String)>,
+    pub shared: Vec<String>,
+}
+
+impl BiflowKeys {
+    pub fn of_fields(key_fields: &[String]) -> Self {
+        let mut keys: BiflowKeys = BiflowKeys::default();
+        for key in key_fields.iter() {
+            let paired: bool = keys.pairs.iter().any(|(src, dst)| src == key || dst == key);
+            match counterpart(key).filter(|other: &String| key_fields.contains(other)) {
+                _ if paired => {}
+                Some(other) => keys.pairs.push((key.clone(), other)),
+                None => keys.shared.push(key.clone()),
+            }
+        }
+        keys
+    }
+
+    fn side(&self, headers: &Headers, forward: bool) -> Vec<OpResult> {
+        self.pairs
+            .iter()
+            .map(|(src, dst)| {
+                let key: &String = if forward { src } else { dst };
+                headers.get(key).cloned().unwrap_or(OpResult::Empty)
+            })
+            .collect()
+    }
+
+    pub fn oriented(&self, headers: &Headers, forward: bool) -> Headers {
+        let mut key: Headers = Headers::new();
+        for (src, dst) in self.pairs.iter() {
+            let (from, to): (&String, &String) = if forward { (src, dst) } else { (dst, src) };
+            if let Some(val) = headers.get(from) {
+                key.insert(src.clone(), val.clone());
+            }
+            if let Some(val) = headers.get(to) {
+                key.insert(dst.clone(), val.clone());
+            }
+        }
+        for shared in self.shared.iter() {
+            if let Some(val) = headers.get(shared) {
+                key.insert(shared.clone(), val.clone());
+            }
+        }
+        key
+    }
+
+    pub fn canonical(&self, headers: &Headers) -> (Headers, bool) {
+        let src: Vec<OpResult> = self.side(headers, true);
+        let dst: Vec<OpResult> = self.side(headers, false);
+        let forward: bool = src
+            .iter()
+            .map(OpResult::to_string)
+            .le(dst.iter().map(OpResult::to_string));
+        (self.oriented(headers, forward), forward)
+    }
+
+    pub fn check(&self, input: &Schema, stage: &str) -> Result<Schema, SchemaError> {
+        let mut output: Schema = Schema::new();
+        for key in self
+            .pairs
+            .iter()
+            .flat_map(|(src, dst)| [src, dst])
+            .chain(self.shared.iter())
+        {
+            output = output.with(key, input.require(key, FieldType::Any, stage)?);
+        }
+        Ok(output
+            .with("time", FieldType::Float)
+            .with("duration", FieldType::Float)
+            .with("fwd_packets", FieldType::Int)
+            .with("fwd_bytes", FieldType::Int)
+            .with("rev_packets", FieldType::Int)
+            .with("rev_bytes", FieldType::Int))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Biflow {
+    pub key: Headers,
+    pub initiator_forward: bool,
+    pub start: f64,
+    pub last: f64,
+    pub fwd_packets: i32,
+    pub fwd_bytes: i32,
+    pub rev_packets: i32,
+    pub rev_bytes: i32,
+}
+
+impl Biflow {
+    pub fn into_headers(self, keys: &BiflowKeys) -> Headers {
+        let mut headers: Headers = if self.initiator_forward {
+            self.key
+        } else {
+            keys.oriented(&self.key, false)
+        };
+        headers.insert(
+            WellKnownKey::Time.into(),
+            OpResult::Float(OrderedFloat(self.start)),
+        );
+        headers.insert(
+            "duration".to_string(),
+            OpResult::Float(OrderedFloat(self.last - self.start)),
+        );
+        headers.insert("fwd_packets".to_string(), OpResult::Int(self.fwd_packets));
+        headers.insert("fwd_bytes".to_string(), OpResult::Int(self.fwd_bytes));
+        headers.insert("rev_packets".to_string(), OpResult::Int(self.rev_packets));
+        headers.insert("rev_bytes".to_string(), OpResult::Int(self.rev_bytes));
+        headers
+    }
+}
+
+pub struct BiflowTable {
+    pub keys: BiflowKeys,
+    pub timeout: f64,
+    pub flows: HashMap<Headers, Biflow>,
+    last_sweep: f64,
+}
+
+impl BiflowTable {
+    pub fn new(keys: BiflowKeys, timeout: f64) -> Self {
+        BiflowTable {
+            keys,
+            timeout,
+            flows: HashMap::new(),
+            last_sweep: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn push(&mut self, headers: &Headers, time: f64) -> Vec<Headers> {
+        let mut expired: Vec<Headers> = self.sweep(time);
+        let (key, forward) = self.keys.canonical(headers);
+        let bytes: i32 = match headers.get_known(WellKnownKey::Ipv4Len) {
+            Some(OpResult::Int(len)) => *len,
+            _ => 0,
+        };
+        if let Some(flow) = self
+            .flows
+            .get(&key)
+            .filter(|flow: &&Biflow| time - flow.last >= self.timeout)
+            .cloned()
+        {
+            self.flows.remove(&key);
+            expired.push(flow.into_headers(&self.keys));
+        }
+        let flow: &mut Biflow = self.flows.entry(key.clone()).or_insert_with(|| Biflow {
+            key,
+            initiator_forward: forward,
+            start: time,
+            last: time,
+            fwd_packets: 0,
+            fwd_bytes: 0,
+            rev_packets: 0,
+            rev_bytes: 0,
+        });
+        flow.last = time;
+        if forward == flow.initiator_forward {
+            flow.fwd_packets += 1;
+            flow.fwd_bytes = flow.fwd_bytes.saturating_add(bytes);
+        } else {
+            flow.rev_packets += 1;
+            flow.rev_bytes = flow.rev_bytes.saturating_add(bytes);
+        }
+        expired
+    }
+
+    pub fn sweep(&mut self, now: f64) -> Vec<Headers> {
+        if now - self.last_sweep < self.timeout.min(1.0) {
+            return Vec::new();
+        }
+        self.last_sweep = now;
+        let timeout: f64 = self.timeout;
+        let expired: Vec<Headers> = self
+            .flows
+            .iter()
+            .filter(|(_, flow)| now - flow.last >= timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        self.drain(expired)
+    }
+
+    pub fn flush(&mut self) -> Vec<Headers> {
+        let keys: Vec<Headers> = self.flows.keys().cloned().collect();
+        self.drain(keys)
+    }
+
+    fn drain(&mut self, keys: Vec<Headers>) -> Vec<Headers> {
+        let mut flows: Vec<Biflow> = keys
+            .iter()
+            .filter_map(|key: &Headers| self.flows.remove(key))
+            .collect();
+        flows.sort_by(|a: &Biflow, b: &Biflow| a.start.total_cmp(&b.start));
+        flows
+            .into_iter()
+            .map(|flow: Biflow| flow.into_headers(&self.keys))
+            .collect()
+    }
+}
+
+pub fn create_biflow_operator(
+    key_fields: Vec<String>,
+    timeout: f64,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_biflow_operator_with_clock(key_fields, timeout, elapsed_clock(), next_op)
+}
+
+pub fn create_biflow_operator_with_clock(
+    key_fields: Vec<String>,
+    timeout: f64,
+    clock: ClockRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("biflow({}s)", timeout);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let table: Rc<RefCell<BiflowTable>> = Rc::new(RefCell::new(BiflowTable::new(
+        BiflowKeys::of_fields(&key_fields),
+        timeout,
+    )));
+    let reset_table_ref = Rc::clone(&table);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = tuple_time(headers, clock.as_ref());
+        let flows: Vec<Headers> = table.borrow_mut().push(headers, time);
+        for mut flow in flows {
+            (next_op.borrow_mut().next)(&mut flow)
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let flows: Vec<Headers> = reset_table_ref.borrow_mut().flush();
+        for mut flow in flows {
+            (next_op_ref_clone.borrow_mut().next)(&mut flow)
+        }
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
@@ -0,0 +1,23 @@
+use std::net::SocketAddr;
+
+use tonic::transport::Server;
+use translation::server::SonataService;
+use translation::server::proto::sonata_server::SonataServer;
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:50051";
+
+#[tokio::main]
+async fn main() {
+    let addr: SocketAddr = std::env::args()
+        .nth(1)
+        .as_deref()
+        .unwrap_or(DEFAULT_ADDR)
+        .parse()
+        .unwrap();
+    eprintln!("sonata-server listening on {}", addr);
+    Server::builder()
+        .add_service(SonataServer::new(SonataService))
+        .serve(addr)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,320 @@
+#![allow(dead_code)]
+
+use crate::builtins::Cmp;
+use crate::dsl::{Operand, Predicate};
+use crate::error::StreamError;
+use crate::keys::WellKnownKey;
+use crate::packet::{
+    ETHERTYPE_ARP, ETHERTYPE_IPV4, ETHERTYPE_QINQ, ETHERTYPE_VLAN, IPPROTO_TCP, IPPROTO_UDP,
+    IPV4_FRAGMENT_OFFSET,
+};
+use crate::utils::OpResult;
+
+pub const BPF_ACCEPT_LEN: u32 = 0x40000;
+pub const BPF_MAX_JUMP: usize = u8::MAX as usize;
+
+const LD_IMM: u16 = 0x00;
+const LD_MEM: u16 = 0x60;
+const LD_H_ABS: u16 = 0x28;
+const LD_B_ABS: u16 = 0x30;
+const LD_H_IND: u16 = 0x48;
+const LD_B_IND: u16 = 0x50;
+const LDX_B_MSH: u16 = 0xb1;
+const ST: u16 = 0x02;
+const JA: u16 = 0x05;
+const JEQ: u16 = 0x15;
+const JGT: u16 = 0x25;
+const JGE: u16 = 0x35;
+const JSET: u16 = 0x45;
+const RET: u16 = 0x06;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BpfInsn {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KernelFilter {
+    Accept,
+    Reject,
+    Test(WellKnownKey, Cmp, u32),
+    Not(Box<KernelFilter>),
+    And(Box<KernelFilter>, Box<KernelFilter>),
+    Or(Box<KernelFilter>, Box<KernelFilter>),
+}
+
+fn scratch_slot(key: WellKnownKey) -> Option<u32> {
+    match key {
+        WellKnownKey::Ipv4Proto => Some(0),
+        WellKnownKey::L4Sport => Some(1),
+        WellKnownKey::L4Dport => Some(2),
+        WellKnownKey::L4Flags => Some(3),
+        _ => None,
+    }
+}
+
+fn flip(cmp: Cmp) -> Cmp {
+    match cmp {
+        Cmp::Eq => Cmp::Eq,
+        Cmp::Ne => Cmp::Ne,
+        Cmp::Lt => Cmp::Gt,
+        Cmp::Le => Cmp::Ge,
+        Cmp::Gt => Cmp::Lt,
+        Cmp::Ge => Cmp::Le,
+    }
+}
+
+impl KernelFilter {
+    pub fn negate(f: KernelFilter) -> KernelFilter {
+        match f {
+            KernelFilter::Accept => KernelFilter::Reject,
+            KernelFilter::Reject => KernelFilter::Accept,
+            KernelFilter::Not(inner) => *inner,
+            f => KernelFilter::Not(Box::new(f)),
+        }
+    }
+
+    pub fn and(a: KernelFilter, b: KernelFilter) -> KernelFilter {
+        match (a, b) {
+            (KernelFilter::Reject, _) | (_, KernelFilter::Reject) => KernelFilter::Reject,
+            (KernelFilter::Accept, f) | (f, KernelFilter::Accept) => f,
+            (a, b) => KernelFilter::And(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn or(a: KernelFilter, b: KernelFilter) -> KernelFilter {
+        match (a, b) {
+            (KernelFilter::Accept, _) | (_, KernelFilter::Accept) => KernelFilter::Accept,
+            (KernelFilter::Reject, f) | (f, KernelFilter::Reject) => f,
+            (a, b) => KernelFilter::Or(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn test(key: WellKnownKey, cmp: Cmp, value: i32) -> KernelFilter {
+        match (u32::try_from(value), cmp) {
+            (Ok(k), cmp) => KernelFilter::Test(key, cmp, k),
+            (Err(_), Cmp::Ne | Cmp::Gt | Cmp::Ge) => KernelFilter::Accept,
+            (Err(_), Cmp::Eq | Cmp::Lt | Cmp::Le) => KernelFilter::Reject,
+        }
+    }
+
+    fn exact(pred: &Predicate) -> Option<KernelFilter> {
+        match pred {
+            Predicate::Compare(Operand::Field(key), cmp, Operand::Literal(OpResult::Int(k))) => {
+                let key: WellKnownKey = WellKnownKey::of_str(key)?;
+                scratch_slot(key)?;
+                Some(KernelFilter::test(key, *cmp, *k))
+            }
+            Predicate::Compare(Operand::Literal(OpResult::Int(k)), cmp, Operand::Field(key)) => {
+                let key: WellKnownKey = WellKnownKey::of_str(key)?;
+                scratch_slot(key)?;
+                Some(KernelFilter::test(key, flip(*cmp), *k))
+            }
+            Predicate::Not(p) => KernelFilter::exact(p).map(KernelFilter::negate),
+            Predicate::And(a, b) => Some(KernelFilter::and(
+                KernelFilter::exact(a)?,
+                KernelFilter::exact(b)?,
+            )),
+            Predicate::Or(a, b) => Some(KernelFilter::or(
+                KernelFilter::exact(a)?,
+                KernelFilter::exact(b)?,
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn of_predicate(pred: &Predicate) -> KernelFilter {
+        match pred {
+            Predicate::And(a, b) => {
+                KernelFilter::and(KernelFilter::of_predicate(a), KernelFilter::of_predicate(b))
+            }
+            Predicate::Or(a, b) => {
+                KernelFilter::or(KernelFilter::of_predicate(a), KernelFilter::of_predicate(b))
+            }
+            pred => KernelFilter::exact(pred).unwrap_or(KernelFilter::Accept),
+        }
+    }
+
+    pub fn of_queries(queries: &[Vec<Predicate>]) -> KernelFilter {
+        queries
+            .iter()
+            .map(|filters: &Vec<Predicate>| {
+                filters
+                    .iter()
+                    .map(KernelFilter::of_predicate)
+                    .fold(KernelFilter::Accept, KernelFilter::and)
+            })
+            .reduce(KernelFilter::or)
+            .unwrap_or(KernelFilter::Accept)
+    }
+
+    pub fn compile(&self) -> Result<Vec<BpfInsn>, StreamError> {
+        let mut asm: Assembler = Assembler::default();
+        let (accept, reject) = (asm.label(), asm.label());
+        let (arp, ipv4, eval) = (asm.label(), asm.label(), asm.label());
+        let (transport, tcp, ports) = (asm.label(), asm.label(), asm.label());
+
+        asm.op(LD_H_ABS, 12);
+        asm.jump(JEQ, ETHERTYPE_ARP as u32, arp, None);
+        asm.jump(JEQ, ETHERTYPE_VLAN as u32, accept, None);
+        asm.jump(JEQ, ETHERTYPE_QINQ as u32, accept, None);
+        asm.jump(JEQ, ETHERTYPE_IPV4 as u32, ipv4, Some(reject));
+
+        asm.place(arp);
+        asm.op(LD_IMM, 0);
+        for slot in 0..4 {
+            asm.op(ST, slot);
+        }
+        asm.ja(eval);
+
+        asm.place(ipv4);
+        asm.op(LD_B_ABS, 23);
+        asm.op(ST, 0);
+        asm.op(LD_IMM, 0);
+        for slot in 1..4 {
+            asm.op(ST, slot);
+        }
+        asm.op(LD_H_ABS, 20);
+        asm.jump(JSET, IPV4_FRAGMENT_OFFSET as u32, eval, Some(transport));
+
+        asm.place(transport);
+        asm.op(LDX_B_MSH, 14);
+        asm.op(LD_MEM, 0);
+        asm.jump(JEQ, IPPROTO_TCP as u32, tcp, None);
+        asm.jump(JEQ, IPPROTO_UDP as u32, ports, Some(eval));
+
+        asm.place(tcp);
+        asm.op(LD_B_IND, 27);
+        asm.op(ST, 3);
+        asm.place(ports);
+        asm.op(LD_H_IND, 14);
+        asm.op(ST, 1);
+        asm.op(LD_H_IND, 16);
+        asm.op(ST, 2);
+
+        asm.place(eval);
+        asm.emit(self, accept, reject);
+        asm.place(accept);
+        asm.op(RET, BPF_ACCEPT_LEN);
+        asm.place(reject);
+        asm.op(RET, 0);
+        asm.finish()
+    }
+}
+
+pub fn string_of_program(program: &[BpfInsn]) -> String {
+    program
+        .iter()
+        .enumerate()
+        .map(|(i, insn)| {
+            format!(
+                "({:03}) code 0x{:02x} jt {} jf {} k 0x{:x}\n",
+                i, insn.code, insn.jt, insn.jf, insn.k
+            )
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Assembler {
+    insns: Vec<BpfInsn>,
+    jumps: Vec<(usize, usize, Option<usize>)>,
+    labels: Vec<Option<usize>>,
+}
+
+impl Assembler {
+    fn label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place(&mut self, label: usize) {
+        self.labels[label] = Some(self.insns.len());
+    }
+
+    fn op(&mut self, code: u16, k: u32) {
+        self.insns.push(BpfInsn {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        });
+    }
+
+    fn jump(&mut self, code: u16, k: u32, on_true: usize, on_false: Option<usize>) {
+        self.jumps.push((self.insns.len(), on_true, on_false));
+        self.op(code, k);
+    }
+
+    fn ja(&mut self, target: usize) {
+        self.jumps.push((self.insns.len(), target, None));
+        self.op(JA, 0);
+    }
+
+    fn emit(&mut self, filter: &KernelFilter, on_true: usize, on_false: usize) {
+        match filter {
+            KernelFilter::Accept => self.ja(on_true),
+            KernelFilter::Reject => self.ja(on_false),
+            KernelFilter::Test(key, cmp, k) => {
+                self.op(LD_MEM, scratch_slot(*key).unwrap_or(0));
+                let (code, on_true, on_false) = match cmp {
+                    Cmp::Eq => (JEQ, on_true, on_false),
+                    Cmp::Ne => (JEQ, on_false, on_true),
+                    Cmp::Gt => (JGT, on_true, on_false),
+                    Cmp::Ge => (JGE, on_true, on_false),
+                    Cmp::Lt => (JGE, on_false, on_true),
+                    Cmp::Le => (JGT, on_false, on_true),
+                };
+                self.jump(code, *k, on_true, Some(on_false));
+            }
+            KernelFilter::Not(inner) => self.emit(inner, on_false, on_true),
+            KernelFilter::And(a, b) => {
+                let rhs: usize = self.label();
+                self.emit(a, rhs, on_false);
+                self.place(rhs);
+                self.emit(b, on_true, on_false);
+            }
+            KernelFilter::Or(a, b) => {
+                let rhs: usize = self.label();
+                self.emit(a, on_true, rhs);
+                self.place(rhs);
+                self.emit(b, on_true, on_false);
+            }
+        }
+    }
+
+    fn offset(&self, from: usize, label: usize) -> Result<usize, StreamError> {
+        match self.labels[label] {
+            Some(to) if to > from => Ok(to - from - 1),
+            _ => Err(StreamError::config(
+                "kernel filter jumps to an unplaced label".to_string(),
+            )),
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<BpfInsn>, StreamError> {
+        for (at, on_true, on_false) in std::mem::take(&mut self.jumps) {
+            let jt: usize = self.offset(at, on_true)?;
+            if self.insns[at].code == JA {
+                self.insns[at].k = jt as u32;
+                continue;
+            }
+            let jf: usize = match on_false {
+                Some(label) => self.offset(at, label)?,
+                None => 0,
+            };
+            if jt > BPF_MAX_JUMP || jf > BPF_MAX_JUMP {
+                return Err(StreamError::config(
+                    "kernel filter is too large for classic BPF jumps".to_string(),
+                ));
+            }
+            self.insns[at].jt = jt as u8;
+            self.insns[at].jf = jf as u8;
+        }
+        Ok(self.insns)
+    }
+}
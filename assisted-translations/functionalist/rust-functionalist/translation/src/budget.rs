@@ -0,0 +1,225 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use crate::builtins::order_headers;
+use crate::error::{InputKind, StateError, StreamError};
+use crate::reducers::Summary;
+use crate::state::OperatorFault;
+use crate::utils::{Headers, OpResult};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem::size_of;
+use std::rc::Rc;
+use std::str::FromStr;
+
+pub const EVICTION_LOW_WATER: f64 = 0.9;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    #[default]
+    EarlyFlush,
+    Evict,
+    Error,
+}
+
+impl FromStr for BudgetAction {
+    type Err = StreamError;
+
+    fn from_str(s: &str) -> Result<Self, StreamError> {
+        match s {
+            "flush" | "early_flush" => Ok(BudgetAction::EarlyFlush),
+            "evict" => Ok(BudgetAction::Evict),
+            "error" => Ok(BudgetAction::Error),
+            other => Err(StreamError::unknown(
+                InputKind::Value,
+                "budget action",
+                other,
+            )),
+        }
+    }
+}
+
+pub fn parse_bytes(word: &str) -> Result<usize, StreamError> {
+    let lower: String = word.to_ascii_lowercase();
+    let (num, scale) = if let Some(n) = lower.strip_suffix("gb") {
+        (n, 1 << 30)
+    } else if let Some(n) = lower.strip_suffix("mb") {
+        (n, 1 << 20)
+    } else if let Some(n) = lower.strip_suffix("kb") {
+        (n, 1 << 10)
+    } else if let Some(n) = lower.strip_suffix('b') {
+        (n, 1)
+    } else {
+        (lower.as_str(), 1)
+    };
+    num.parse::<f64>()
+        .ok()
+        .filter(|n: &f64| *n >= 0.0)
+        .map(|n: f64| (n * scale as f64) as usize)
+        .ok_or_else(|| StreamError::invalid(InputKind::Value, "byte size", word))
+}
+
+pub fn approx_op_result_bytes(val: &OpResult) -> usize {
+    size_of::<OpResult>()
+        + match val {
+            OpResult::Str(s) => s.len(),
+            OpResult::Summary(summary) => {
+                size_of::<Summary>()
+                    + summary.centroids.len() * size_of::<(OrderedFloat<f64>, i64)>()
+            }
+            _ => 0,
+        }
+}
+
+pub fn approx_headers_bytes(headers: &Headers) -> usize {
+    headers
+        .iter()
+        .map(|(key, val)| size_of::<String>() + key.len() + approx_op_result_bytes(val))
+        .sum()
+}
+
+pub trait BudgetKey: Clone + Eq + Hash {
+    fn approx_bytes(&self) -> usize;
+    fn order(&self, other: &Self) -> Ordering;
+}
+
+impl BudgetKey for Headers {
+    fn approx_bytes(&self) -> usize {
+        approx_headers_bytes(self)
+    }
+
+    fn order(&self, other: &Self) -> Ordering {
+        order_headers(self, other)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryBudget {
+    pub limit_bytes: usize,
+    pub action: BudgetAction,
+    used: Rc<Cell<usize>>,
+    peak: Rc<Cell<usize>>,
+    evictions: Rc<Cell<usize>>,
+    early_flushes: Rc<Cell<usize>>,
+    fault: OperatorFault,
+}
+
+impl MemoryBudget {
+    pub fn new(limit_bytes: usize, action: BudgetAction) -> Self {
+        MemoryBudget {
+            limit_bytes,
+            action,
+            used: Rc::new(Cell::new(0)),
+            peak: Rc::new(Cell::new(0)),
+            evictions: Rc::new(Cell::new(0)),
+            early_flushes: Rc::new(Cell::new(0)),
+            fault: OperatorFault::new(),
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    pub fn peak(&self) -> usize {
+        self.peak.get()
+    }
+
+    pub fn evictions(&self) -> usize {
+        self.evictions.get()
+    }
+
+    pub fn early_flushes(&self) -> usize {
+        self.early_flushes.get()
+    }
+
+    pub fn fault(&self) -> &OperatorFault {
+        &self.fault
+    }
+
+    pub fn charge(&self, bytes: usize) {
+        let used: usize = self.used.get() + bytes;
+        self.used.set(used);
+        self.peak.set(self.peak.get().max(used));
+    }
+
+    pub fn release(&self, bytes: usize) {
+        self.used.set(self.used.get().saturating_sub(bytes));
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.used.get() > self.limit_bytes
+    }
+
+    pub fn low_water(&self) -> usize {
+        (self.limit_bytes as f64 * EVICTION_LOW_WATER) as usize
+    }
+
+    pub fn exceeded_error(&self, label: &str) -> StateError {
+        StateError::BudgetExceeded {
+            label: label.to_string(),
+            limit: self.limit_bytes,
+        }
+    }
+
+    pub fn record_flush(&self) {
+        self.early_flushes.set(self.early_flushes.get() + 1);
+    }
+
+    pub fn record_evictions(&self, n: usize) {
+        self.evictions.set(self.evictions.get() + n);
+    }
+
+    pub fn evict<K: BudgetKey, V>(
+        &self,
+        table: &mut HashMap<K, V>,
+        value_bytes: impl Fn(&V) -> usize,
+    ) -> Vec<(K, V)> {
+        let mut keys: Vec<K> = table.keys().cloned().collect();
+        keys.sort_by(K::order);
+        let mut evicted: Vec<(K, V)> = Vec::new();
+        let low_water: usize = self.low_water();
+        for key in keys {
+            if self.used.get() <= low_water {
+                break;
+            }
+            if let Some(val) = table.remove(&key) {
+                self.release(key.approx_bytes() + value_bytes(&val));
+                evicted.push((key, val));
+            }
+        }
+        self.record_evictions(evicted.len());
+        evicted
+    }
+
+    pub fn enforce<K: BudgetKey, V>(
+        &self,
+        label: &str,
+        table: &mut HashMap<K, V>,
+        value_bytes: impl Fn(&V) -> usize,
+    ) -> Result<Vec<(K, V)>, StateError> {
+        if !self.exceeded() {
+            return Ok(Vec::new());
+        }
+        match self.action {
+            BudgetAction::EarlyFlush => {
+                self.record_flush();
+                let flushed: Vec<(K, V)> = table.drain().collect();
+                for (key, val) in flushed.iter() {
+                    self.release(key.approx_bytes() + value_bytes(val));
+                }
+                Ok(flushed)
+            }
+            BudgetAction::Evict => {
+                self.evict(table, value_bytes);
+                Ok(Vec::new())
+            }
+            BudgetAction::Error => Err(self.exceeded_error(label)),
+        }
+    }
+}
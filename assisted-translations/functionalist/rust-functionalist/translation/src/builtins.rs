@@ -1,18 +1,47 @@
 #![allow(dead_code)]
 
 use ordered_float::OrderedFloat;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::budget::{
+    BudgetAction, BudgetKey, MemoryBudget, approx_headers_bytes, approx_op_result_bytes,
+};
+use crate::clock::{Clock, ClockRef, elapsed_clock, system_clock};
+use crate::dsl::{MapExpr, parse_map_expr};
+use crate::error::{InputKind, StateError, StreamError};
+use crate::group_key::{GroupKey, GroupTable};
+use crate::keys::{HeaderKey, WellKnownKey};
+use crate::plan::create_noop_operator;
+use crate::reducers::{
+    MultiReductionFunc, finalize_headers, finalize_op_result, get_mapped_number, multi_reduce,
+};
+use crate::schema::{FieldType, Schema, SchemaError};
+use crate::state::{BackendFactory, DRAIN_CHUNK, OperatorFault, StateBackend, memory_backend};
+use crate::stats::{PipelineStats, StatsRef};
+use crate::trace::EpochSpan;
+use crate::trace_event;
+use crate::traffic_gen::TrafficRng;
+use crate::tuple;
 use crate::utils::{
-    Headers, OpResult, Operator, OperatorRef, dump_headers, float_of_op_result, int_of_op_result,
-    string_of_op_result,
+    EpochState, Headers, OpResult, Operator, OperatorRef, compare_op_results, dump_headers,
+    float_of_op_result, int_of_op_result, string_of_headers, string_of_op_result,
+    tcp_flags_of_string, tcp_flags_to_strings,
 };
-use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::File;
-use std::io::{Error, ErrorKind, Write, stdout};
+use std::hash::{Hash, Hasher};
+use std::io::{Write, stdout};
+use std::mem;
 use std::net::Ipv4Addr;
 use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub fn create_dump_operator(show_reset: bool, outc: Box<dyn Write>) -> OperatorRef {
     let outc = Rc::new(RefCell::new(outc));
@@ -33,7 +62,53 @@ pub fn create_dump_operator(show_reset: bool, outc: Box<dyn Write>) -> OperatorR
                 ()
             }
         });
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label("dump")))
+}
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub trailing_delimiter: bool,
+    pub null: String,
+    pub header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            trailing_delimiter: false,
+            null: String::new(),
+            header: true,
+        }
+    }
+}
+
+pub fn csv_field(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn csv_value(val: Option<&OpResult>, options: &CsvOptions) -> String {
+    match val {
+        Some(OpResult::Empty) | None => csv_field(&options.null, options.delimiter),
+        Some(val) => csv_field(&string_of_op_result(val), options.delimiter),
+    }
+}
+
+pub fn write_csv_row(
+    outc: &mut dyn Write,
+    fields: Vec<String>,
+    options: &CsvOptions,
+) -> Result<(), StreamError> {
+    let mut row: String = fields.join(&options.delimiter.to_string());
+    if options.trailing_delimiter {
+        row.push(options.delimiter);
+    }
+    Ok(writeln!(outc, "{}", row)?)
 }
 
 pub fn dump_as_csv(
@@ -41,73 +116,255 @@ pub fn dump_as_csv(
     header: Option<bool>,
     outc: Box<dyn Write>,
 ) -> Operator {
-    let outc = Rc::new(RefCell::new(outc));
-    let mut first: bool = header.unwrap_or(true);
+    let options: CsvOptions = CsvOptions {
+        header: header.unwrap_or(true),
+        ..CsvOptions::default()
+    };
+    dump_as_csv_with_options(static_field, None, options, outc)
+}
+
+pub fn dump_as_csv_with_options(
+    static_field: Option<(String, String)>,
+    columns: Option<Vec<String>>,
+    options: CsvOptions,
+    mut outc: Box<dyn Write>,
+) -> Operator {
+    let mut columns: Option<Vec<String>> = columns;
+    let mut first: bool = options.header;
 
     let next: Box<dyn FnMut(&mut Headers) -> () + 'static> =
         Box::new(move |headers: &mut Headers| {
+            let cols: &Vec<String> =
+                columns.get_or_insert_with(|| headers.keys().cloned().collect());
+
             if first {
-                match &static_field {
-                    Some((key, _)) => {
-                        writeln!(outc.borrow_mut(), "{}", key).unwrap();
-                    }
-                    None => (),
+                let mut names: Vec<String> = Vec::new();
+                if let Some((key, _)) = &static_field {
+                    names.push(csv_field(key, options.delimiter));
                 }
+                names.extend(cols.iter().map(|key| csv_field(key, options.delimiter)));
+                write_csv_row(&mut outc, names, &options).unwrap();
                 first = false;
             }
 
-            for (key, _) in headers.iter_mut() {
-                writeln!(outc.borrow_mut(), "{}, ", key).unwrap();
-            }
-            writeln!(outc.borrow_mut(), "\n").unwrap();
-
-            match &static_field {
-                Some((_, val)) => {
-                    writeln!(outc.borrow_mut(), "{}", val).unwrap();
-                }
-                None => (),
-            }
-
-            for (_, val) in headers.iter_mut() {
-                writeln!(outc.borrow_mut(), "{}, ", val).unwrap();
+            let mut vals: Vec<String> = Vec::new();
+            if let Some((_, val)) = &static_field {
+                vals.push(csv_field(val, options.delimiter));
             }
-            writeln!(outc.borrow_mut(), "\n").unwrap();
+            vals.extend(cols.iter().map(|key| csv_value(headers.get(key), &options)));
+            write_csv_row(&mut outc, vals, &options).unwrap();
         });
 
     let reset: Box<dyn FnMut(&mut Headers) -> () + 'static> =
         Box::new(move |_headers: &mut Headers| ());
 
-    Operator::new(next, reset)
+    Operator::new(next, reset).with_label("csv")
 }
 
 pub fn dump_walts_csv(filename: String) -> OperatorRef {
     let mut outc: Box<dyn Write> = Box::new(stdout());
     let mut first: bool = true;
+    let options: CsvOptions = CsvOptions {
+        header: false,
+        ..CsvOptions::default()
+    };
+    let columns: [&str; 7] = [
+        "src_ip",
+        "dst_ip",
+        "src_l4_port",
+        "dst_l4_port",
+        "packet_count",
+        "byte_count",
+        "epoch_id",
+    ];
 
     let next: Box<dyn FnMut(&mut Headers) -> () + 'static> =
         Box::new(move |headers: &mut Headers| {
             if first {
-                outc = Box::new(File::open(&filename).unwrap());
+                outc = Box::new(File::create(&filename).unwrap());
                 first = false;
             }
-            writeln!(
-                outc,
-                "{}, {}, {}, {}, {}, {}, {}\n",
-                string_of_op_result(headers.get("src_ip").unwrap_or(&OpResult::Empty)),
-                string_of_op_result(headers.get("dst_ip").unwrap_or(&OpResult::Empty)),
-                string_of_op_result(headers.get("src_l4_port").unwrap_or(&OpResult::Empty)),
-                string_of_op_result(headers.get("dst_l4_port").unwrap_or(&OpResult::Empty)),
-                string_of_op_result(headers.get("packet_count").unwrap_or(&OpResult::Empty)),
-                string_of_op_result(headers.get("byte_count").unwrap_or(&OpResult::Empty)),
-                string_of_op_result(headers.get("epoch_id").unwrap_or(&OpResult::Empty)),
-            )
-            .unwrap();
+            let vals: Vec<String> = columns
+                .iter()
+                .map(|key| csv_value(headers.get(*key), &options))
+                .collect();
+            write_csv_row(&mut outc, vals, &options).unwrap();
         });
 
     let reset: Box<dyn FnMut(&mut Headers) -> () + 'static> =
         Box::new(move |_headers: &mut Headers| ());
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+    Rc::new(RefCell::new(
+        Operator::new(next, reset).with_label("walts_csv"),
+    ))
+}
+
+pub fn render_table(rows: &[Headers], max_rows: usize) -> String {
+    let mut columns: Vec<String> = Vec::new();
+    for row in rows.iter() {
+        for key in row.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    let shown: &[Headers] = &rows[..rows.len().min(max_rows)];
+    let cells: Vec<Vec<String>> = shown
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|key| row.get(key).map(string_of_op_result).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .fold(key.chars().count(), usize::max)
+        })
+        .collect();
+
+    let separator: String = widths.iter().fold(String::from("+"), |mut acc, w| {
+        acc.push_str(&"-".repeat(w + 2));
+        acc.push('+');
+        acc
+    });
+    let format_row = |vals: &[String]| -> String {
+        vals.iter()
+            .zip(widths.iter())
+            .fold(String::from("|"), |mut acc, (val, w)| {
+                acc.push_str(&format!(" {:<width$} |", val, width = w));
+                acc
+            })
+    };
+
+    let mut table: String = String::new();
+    table.push_str(&format!(
+        "{}\n{}\n{}\n",
+        separator,
+        format_row(&columns),
+        separator
+    ));
+    for row in cells.iter() {
+        table.push_str(&format!("{}\n", format_row(row)));
+    }
+    table.push_str(&format!("{}\n", separator));
+    if rows.len() > shown.len() {
+        table.push_str(&format!("... {} more rows\n", rows.len() - shown.len()));
+    }
+    table
+}
+
+pub fn dump_table(outc: Box<dyn Write>, max_rows: usize) -> OperatorRef {
+    let outc = Rc::new(RefCell::new(outc));
+    let rows: Rc<RefCell<Vec<Headers>>> = Rc::new(RefCell::new(Vec::new()));
+    let rows_ref_clone = Rc::clone(&rows);
+    let mut epoch_count: i32 = 0;
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| rows.borrow_mut().push(headers.clone()));
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let eid: i32 = match headers.get("eid") {
+            Some(OpResult::Int(eid)) => *eid,
+            _ => epoch_count,
+        };
+        let rows: Vec<Headers> = rows_ref_clone.borrow_mut().drain(..).collect();
+        let mut outc = outc.borrow_mut();
+        writeln!(outc, "== epoch {} ({} rows) ==", eid, rows.len()).unwrap();
+        if !rows.is_empty() {
+            write!(outc, "{}", render_table(&rows, max_rows)).unwrap();
+        }
+        epoch_count += 1;
+    });
+
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label("table")))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Normal,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn of_value(val: f64, threshold: f64) -> Severity {
+        let ratio: f64 = if threshold > 0.0 {
+            val / threshold
+        } else if val >= threshold {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+        match ratio {
+            r if r >= 3.0 => Severity::High,
+            r if r >= 1.5 => Severity::Medium,
+            r if r >= 1.0 => Severity::Low,
+            _ => Severity::Normal,
+        }
+    }
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            Severity::Normal => "",
+            Severity::Low => "\x1b[33m",
+            Severity::Medium => "\x1b[31m",
+            Severity::High => "\x1b[1;41;97m",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Normal => "OK",
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+        }
+    }
+}
+
+pub fn alert_console(
+    key: String,
+    threshold: f64,
+    quiet: bool,
+    mut outc: Box<dyn Write>,
+) -> OperatorRef {
+    let label: String = format!("alert({})", key);
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let severity: Severity = match headers.get(&key) {
+            Some(OpResult::Int(i)) => Severity::of_value(*i as f64, threshold),
+            Some(OpResult::Float(f)) => Severity::of_value(f.0, threshold),
+            _ => Severity::Normal,
+        };
+        if quiet && severity == Severity::Normal {
+            return;
+        }
+        let reset_color: &str = if severity == Severity::Normal {
+            ""
+        } else {
+            "\x1b[0m"
+        };
+        writeln!(
+            outc,
+            "{}[{:<6}] {}{}",
+            severity.color(),
+            severity.label(),
+            string_of_headers(headers),
+            reset_color
+        )
+        .unwrap();
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |_headers: &mut Headers| ());
+
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label(label)))
 }
 
 pub fn get_ip_or_zero(input: String) -> OpResult {
@@ -117,40 +374,177 @@ pub fn get_ip_or_zero(input: String) -> OpResult {
     }
 }
 
+pub type Gauge = Rc<Cell<usize>>;
+
+pub fn record_peak(gauge: &Option<Gauge>, size: usize) {
+    if let Some(gauge) = gauge {
+        gauge.set(gauge.get().max(size));
+    }
+}
+
 pub fn create_meta_meter(
     static_field: Option<String>,
     name: String,
-    mut outc: Box<dyn Write>,
+    drops: Option<Arc<AtomicUsize>>,
+    gauges: Vec<(String, Gauge)>,
+    metrics_op: OperatorRef,
     next_op: OperatorRef,
 ) -> OperatorRef {
+    let label: String = format!("meta_meter({})", name);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op), Rc::clone(&metrics_op)];
     let mut epoch_count: i32 = 0;
-    let mut _headers_count: i32 = 0;
+    let headers_count: Rc<Cell<i32>> = Rc::new(Cell::new(0));
+    let headers_count_ref = Rc::clone(&headers_count);
+    let first_seen: Rc<Cell<Option<Instant>>> = Rc::new(Cell::new(None));
+    let last_seen: Rc<Cell<Option<Instant>>> = Rc::new(Cell::new(None));
+    let first_seen_ref = Rc::clone(&first_seen);
+    let last_seen_ref = Rc::clone(&last_seen);
     let next_op_ref_clone = Rc::clone(&next_op);
 
     let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        _headers_count += 1;
+        let now: Instant = Instant::now();
+        if first_seen.get().is_none() {
+            first_seen.set(Some(now));
+        }
+        last_seen.set(Some(now));
+        headers_count.set(headers_count.get() + 1);
         (next_op.borrow_mut().next)(headers)
     });
 
     let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        writeln!(
-            outc,
-            "{}, {}, {}, {}\n",
-            epoch_count,
-            name,
-            _headers_count,
-            match &static_field {
-                Some(v) => v,
-                None => "",
-            }
-        )
-        .unwrap();
-        _headers_count = 0;
+        let latency: f64 = match (first_seen_ref.take(), last_seen_ref.take()) {
+            (Some(first), Some(last)) => last.duration_since(first).as_secs_f64(),
+            _ => 0.0,
+        };
+        let mut metrics: Headers = tuple! {
+            "meta.epoch" => epoch_count,
+            "meta.name" => name.clone(),
+            "meta.tuples" => headers_count_ref.replace(0),
+            "meta.latency" => latency,
+        };
+        if let Some(drops) = &drops {
+            metrics.insert(
+                "meta.dropped".to_string(),
+                OpResult::Int(drops.swap(0, Ordering::SeqCst) as i32),
+            );
+        }
+        for (gauge_name, gauge) in gauges.iter() {
+            metrics.insert(
+                format!("meta.{}", gauge_name),
+                OpResult::Int(gauge.replace(0) as i32),
+            );
+        }
+        if let Some(v) = &static_field {
+            metrics.insert("meta.static".to_string(), OpResult::Str(v.clone()));
+        }
+        (metrics_op.borrow_mut().next)(&mut metrics);
         epoch_count += 1;
         (next_op_ref_clone.borrow_mut().reset)(headers)
     });
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingTimePolicy {
+    #[default]
+    Fail,
+    SystemTime,
+    LastSeen,
+    ErrorSink,
+}
+
+impl FromStr for MissingTimePolicy {
+    type Err = StreamError;
+
+    fn from_str(s: &str) -> Result<Self, StreamError> {
+        match s {
+            "fail" => Ok(MissingTimePolicy::Fail),
+            "system_time" => Ok(MissingTimePolicy::SystemTime),
+            "last_seen" => Ok(MissingTimePolicy::LastSeen),
+            "error_sink" => Ok(MissingTimePolicy::ErrorSink),
+            other => Err(StreamError::unknown(
+                InputKind::Value,
+                "missing-time policy",
+                other,
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EpochOptions {
+    pub align: bool,
+    pub offset: f64,
+    pub missing_time: MissingTimePolicy,
+}
+
+impl EpochOptions {
+    pub fn aligned(offset: f64) -> Self {
+        EpochOptions {
+            align: true,
+            offset,
+            ..EpochOptions::default()
+        }
+    }
+
+    pub fn with_missing_time(mut self, missing_time: MissingTimePolicy) -> Self {
+        self.missing_time = missing_time;
+        self
+    }
+
+    pub fn first_boundary(&self, epoch_width: f64, time: f64) -> f64 {
+        if self.align {
+            ((time - self.offset) / epoch_width).floor() * epoch_width + self.offset + epoch_width
+        } else {
+            time + epoch_width
+        }
+    }
+
+    pub fn resolve_time(
+        &self,
+        headers: &mut Headers,
+        last_seen: &mut Option<f64>,
+        clock: &dyn Clock,
+    ) -> Result<Option<f64>, SchemaError> {
+        let found: Option<FieldType> = match WellKnownKey::Time.lookup(headers) {
+            Some(OpResult::Float(time)) => {
+                *last_seen = Some(time.0);
+                return Ok(Some(time.0));
+            }
+            Some(other) => FieldType::of_op_result(other),
+            None => None,
+        };
+        let time: f64 = match self.missing_time {
+            MissingTimePolicy::Fail => {
+                return Err(match found {
+                    Some(found) => SchemaError::TypeMismatch {
+                        key: WellKnownKey::Time.into(),
+                        expected: FieldType::Float,
+                        found: Some(found),
+                    },
+                    None => SchemaError::Missing(WellKnownKey::Time.into()),
+                });
+            }
+            MissingTimePolicy::SystemTime => clock.now(),
+            MissingTimePolicy::LastSeen => match *last_seen {
+                Some(time) => time,
+                None => return Ok(None),
+            },
+            MissingTimePolicy::ErrorSink => return Ok(None),
+        };
+        headers.insert(
+            WellKnownKey::Time.into(),
+            OpResult::Float(OrderedFloat(time)),
+        );
+        *last_seen = Some(time);
+        Ok(Some(time))
+    }
 }
 
 pub fn create_epoch_operator(
@@ -158,47 +552,248 @@ pub fn create_epoch_operator(
     key_out: String,
     next_op: OperatorRef,
 ) -> OperatorRef {
-    let mut _epoch_boundary: f64 = 0.0;
-    let mut eid: i32 = 0;
+    create_epoch_operator_with_options(epoch_width, key_out, EpochOptions::default(), next_op)
+}
+
+pub fn create_epoch_operator_with_options(
+    epoch_width: f64,
+    key_out: String,
+    options: EpochOptions,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_epoch_operator_with_error_sink(
+        epoch_width,
+        key_out,
+        options,
+        create_noop_operator(),
+        next_op,
+    )
+}
+
+pub fn create_epoch_operator_with_error_sink(
+    epoch_width: f64,
+    key_out: String,
+    options: EpochOptions,
+    error_op: OperatorRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_epoch_operator_with_clock(
+        epoch_width,
+        key_out,
+        options,
+        system_clock(),
+        error_op,
+        next_op,
+    )
+}
+
+pub fn create_epoch_operator_with_clock(
+    epoch_width: f64,
+    key_out: String,
+    options: EpochOptions,
+    clock: ClockRef,
+    error_op: OperatorRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("epoch({}, {})", epoch_width, key_out);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op), Rc::clone(&error_op)];
+    let state: Rc<Cell<(f64, i32)>> = Rc::new(Cell::new((0.0, 0)));
+    let reset_state: Rc<Cell<(f64, i32)>> = Rc::clone(&state);
+    let epoch_state: EpochState = Rc::clone(&state);
+    let mut last_seen: Option<f64> = None;
+    let mut span: EpochSpan = EpochSpan::new(&key_out, 0);
     let key_out_cp: String = (*key_out).to_string();
     let next_op_ref = Rc::clone(&next_op);
+    let error_op_ref = Rc::clone(&error_op);
+    let fault: OperatorFault = OperatorFault::new();
+    let next_fault: OperatorFault = fault.clone();
 
     let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        let time: f64 = float_of_op_result(&headers.get("time").unwrap_or(&OpResult::Empty))
-            .unwrap()
-            .0;
+        let time: f64 = match options.resolve_time(headers, &mut last_seen, clock.as_ref()) {
+            Ok(Some(time)) => time,
+            Err(e) => return next_fault.record(e),
+            Ok(None) => {
+                trace_event!(key = %key_out, "tuple without time routed to error sink");
+                return (error_op.borrow_mut().next)(headers);
+            }
+        };
+        let (mut _epoch_boundary, mut eid) = state.get();
         if _epoch_boundary == 0.0 {
-            _epoch_boundary = time + epoch_width;
+            _epoch_boundary = options.first_boundary(epoch_width, time);
+            span = EpochSpan::new(&key_out, eid);
         }
         while time >= _epoch_boundary {
-            let new_headers: &mut Headers = headers;
-            new_headers
-                .insert(key_out.clone(), OpResult::Int(eid))
-                .unwrap();
-            (next_op.borrow_mut().reset)(new_headers);
+            span.in_scope(|| {
+                trace_event!(
+                    boundary = _epoch_boundary,
+                    "epoch closed, resetting downstream"
+                );
+                (next_op.borrow_mut().reset)(&mut singleton(key_out.clone(), OpResult::Int(eid)))
+            });
             _epoch_boundary += epoch_width;
             eid += 1;
+            span = EpochSpan::new(&key_out, eid);
         }
-        headers
-            .insert(key_out.clone(), OpResult::Int(eid))
-            .unwrap();
-        (next_op.borrow_mut().next)(headers)
+        state.set((_epoch_boundary, eid));
+        headers.insert(key_out.clone(), OpResult::Int(eid));
+        span.in_scope(|| (next_op.borrow_mut().next)(headers))
     });
 
-    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |_headers: &mut Headers| {
-        let mut new_hmap: BTreeMap<String, OpResult> = BTreeMap::new();
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let (_, eid) = reset_state.get();
+        let mut new_hmap: Headers = Headers::new();
         new_hmap.insert(key_out_cp.clone(), OpResult::Int(eid));
+        trace_event!(key = %key_out_cp, eid, "end of stream, resetting downstream");
         (next_op_ref.borrow_mut().reset)(&mut new_hmap);
-        _epoch_boundary = 0.0;
-        eid = 0;
+        (error_op_ref.borrow_mut().reset)(headers);
+        reset_state.set((0.0, 0));
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_epoch(epoch_state)
+            .with_fault(fault),
+    ))
+}
+
+pub fn create_count_epoch_operator(n: usize, key_out: String, next_op: OperatorRef) -> OperatorRef {
+    let label: String = format!("epoch({} tuples, {})", n, key_out);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let n: usize = n.max(1);
+    let state: Rc<Cell<(usize, i32)>> = Rc::new(Cell::new((0, 0)));
+    let reset_state: Rc<Cell<(usize, i32)>> = Rc::clone(&state);
+    let key_out_cp: String = key_out.clone();
+    let next_op_ref = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let (count, eid) = state.get();
+        headers.insert(key_out.clone(), OpResult::Int(eid));
+        (next_op.borrow_mut().next)(headers);
+        if count + 1 == n {
+            trace_event!(key = %key_out, eid, tuples = n, "count epoch closed");
+            (next_op.borrow_mut().reset)(&mut singleton(key_out.clone(), OpResult::Int(eid)));
+            state.set((0, eid + 1));
+        } else {
+            state.set((count + 1, eid));
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |_headers: &mut Headers| {
+        let (count, eid) = reset_state.replace((0, 0));
+        if count > 0 {
+            (next_op_ref.borrow_mut().reset)(&mut singleton(
+                key_out_cp.clone(),
+                OpResult::Int(eid),
+            ));
+        }
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn multi_epoch_key(epoch_width: f64) -> String {
+    format!("eid_{}s", epoch_width)
+}
+
+pub fn create_multi_epoch_operator(
+    widths: &[f64],
+    options: EpochOptions,
+    next_ops: Vec<OperatorRef>,
+) -> Result<OperatorRef, StreamError> {
+    create_multi_epoch_operator_with_clock(widths, options, system_clock(), next_ops)
+}
+
+pub fn create_multi_epoch_operator_with_clock(
+    widths: &[f64],
+    options: EpochOptions,
+    clock: ClockRef,
+    next_ops: Vec<OperatorRef>,
+) -> Result<OperatorRef, StreamError> {
+    let label: String = format!("multi_epoch({:?})", widths);
+    let downstream: Vec<OperatorRef> = next_ops.iter().map(Rc::clone).collect();
+    if widths.len() != next_ops.len() {
+        return Err(StreamError::value(format!(
+            "multi-epoch needs one downstream operator per width ({} widths, {} operators)",
+            widths.len(),
+            next_ops.len()
+        )));
+    }
+    if let Some(width) = widths.iter().find(|width: &&f64| **width <= 0.0) {
+        return Err(StreamError::value(format!(
+            "epoch width must be positive, found {}",
+            width
+        )));
+    }
+    let widths: Vec<f64> = widths.to_vec();
+    let keys: Vec<String> = widths
+        .iter()
+        .map(|width: &f64| multi_epoch_key(*width))
+        .collect();
+    let reset_keys: Vec<String> = keys.clone();
+    let next_ops_ref: Vec<OperatorRef> = next_ops.iter().map(Rc::clone).collect();
+    let epochs: Rc<RefCell<Vec<(f64, i32)>>> = Rc::new(RefCell::new(vec![(0.0, 0); widths.len()]));
+    let reset_epochs: Rc<RefCell<Vec<(f64, i32)>>> = Rc::clone(&epochs);
+
+    let mut last_seen: Option<f64> = None;
+    let fault: OperatorFault = OperatorFault::new();
+    let next_fault: OperatorFault = fault.clone();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = match options.resolve_time(headers, &mut last_seen, clock.as_ref()) {
+            Ok(Some(time)) => time,
+            Ok(None) => return,
+            Err(e) => return next_fault.record(e),
+        };
+        let mut epochs = epochs.borrow_mut();
+        for (i, width) in widths.iter().enumerate() {
+            let (boundary, eid) = &mut epochs[i];
+            if *boundary == 0.0 {
+                *boundary = options.first_boundary(*width, time);
+            }
+            while time >= *boundary {
+                (next_ops[i].borrow_mut().reset)(&mut singleton(
+                    keys[i].clone(),
+                    OpResult::Int(*eid),
+                ));
+                *boundary += width;
+                *eid += 1;
+            }
+            headers.insert(keys[i].clone(), OpResult::Int(*eid));
+        }
+        for next_op in next_ops.iter() {
+            (next_op.borrow_mut().next)(headers)
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |_headers: &mut Headers| {
+        let mut epochs = reset_epochs.borrow_mut();
+        for (i, next_op) in next_ops_ref.iter().enumerate() {
+            (next_op.borrow_mut().reset)(&mut singleton(
+                reset_keys[i].clone(),
+                OpResult::Int(epochs[i].1),
+            ));
+            epochs[i] = (0.0, 0);
+        }
     });
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+    Ok(Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_fault(fault),
+    )))
 }
 
 pub type FilterFunc = Box<dyn Fn(&Headers) -> bool>;
 
 pub fn create_filter_operator(f: FilterFunc, next_op: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
     let next_op_ref_clone = Rc::clone(&next_op);
 
     let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
@@ -210,25 +805,181 @@ pub fn create_filter_operator(f: FilterFunc, next_op: OperatorRef) -> OperatorRe
     let reset: Box<dyn FnMut(&mut Headers) + 'static> =
         Box::new(move |headers: &mut Headers| (next_op.borrow_mut().reset)(headers));
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("filter")
+            .with_downstream(downstream),
+    ))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    pub fn of_str(op: &str) -> Option<Cmp> {
+        match op {
+            "==" => Some(Cmp::Eq),
+            "!=" => Some(Cmp::Ne),
+            "<" => Some(Cmp::Lt),
+            "<=" => Some(Cmp::Le),
+            ">" => Some(Cmp::Gt),
+            ">=" => Some(Cmp::Ge),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cmp::Eq => "==",
+            Cmp::Ne => "!=",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        }
+    }
+
+    pub fn holds(&self, ord: Option<std::cmp::Ordering>) -> bool {
+        match ord {
+            Some(ord) => match self {
+                Cmp::Eq => ord.is_eq(),
+                Cmp::Ne => ord.is_ne(),
+                Cmp::Lt => ord.is_lt(),
+                Cmp::Le => ord.is_le(),
+                Cmp::Gt => ord.is_gt(),
+                Cmp::Ge => ord.is_ge(),
+            },
+            None => *self == Cmp::Ne,
+        }
+    }
+}
+
+pub fn key_cmp(key: impl HeaderKey, op: Cmp, threshold: &OpResult, headers: &Headers) -> bool {
+    key.lookup(headers)
+        .is_some_and(|val: &OpResult| op.holds(compare_op_results(val, threshold)))
+}
+
+pub fn cmp(key: impl HeaderKey + 'static, op: Cmp, threshold: impl Into<OpResult>) -> FilterFunc {
+    let threshold: OpResult = threshold.into();
+    Box::new(move |headers: &Headers| key_cmp(&key, op, &threshold, headers))
+}
+
+pub fn between(
+    key: impl HeaderKey + 'static,
+    lo: impl Into<OpResult>,
+    hi: impl Into<OpResult>,
+) -> FilterFunc {
+    let lo: OpResult = lo.into();
+    let hi: OpResult = hi.into();
+    Box::new(move |headers: &Headers| {
+        key_cmp(&key, Cmp::Ge, &lo, headers) && key_cmp(&key, Cmp::Le, &hi, headers)
+    })
+}
+
+pub fn flags_set(flags: i32, headers: &Headers) -> bool {
+    match headers.get_known(WellKnownKey::L4Flags) {
+        Some(OpResult::Int(found)) => found & flags == flags,
+        _ => false,
+    }
+}
+
+pub fn flags_equal(flags: i32, headers: &Headers) -> bool {
+    headers.get_known(WellKnownKey::L4Flags) == Some(&OpResult::Int(flags))
+}
+
+pub fn has_flags(flags: i32) -> FilterFunc {
+    Box::new(move |headers: &Headers| flags_set(flags, headers))
+}
+
+pub fn flags_exactly(flags: i32) -> FilterFunc {
+    Box::new(move |headers: &Headers| flags_equal(flags, headers))
+}
+
+pub fn flags_named(names: &str) -> Result<FilterFunc, StreamError> {
+    let names: String = tcp_flags_to_strings(tcp_flags_of_string(names)?);
+    Ok(Box::new(move |headers: &Headers| {
+        match headers.get_known(WellKnownKey::L4Flags) {
+            Some(OpResult::Int(found)) => tcp_flags_to_strings(*found) == names,
+            _ => false,
+        }
+    }))
+}
+
+pub fn key_geq_int(key: impl HeaderKey, threshold: i32, headers: &Headers) -> bool {
+    int_of_op_result(key.lookup(headers).unwrap_or(&OpResult::Empty)).unwrap() >= threshold
+}
+
+pub fn prefix_mask(prefix_len: u8) -> u32 {
+    match prefix_len {
+        0 => 0,
+        len => u32::MAX << (32 - u32::from(len.min(32))),
+    }
+}
+
+pub fn truncate_ipv4(addr: Ipv4Addr, prefix_len: u8) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(addr) & prefix_mask(prefix_len))
 }
 
-pub fn key_geq_int(key: String, threshold: i32, headers: &Headers) -> bool {
-    int_of_op_result(headers.get(&key).unwrap_or(&OpResult::Empty)).unwrap() >= threshold
+pub fn parse_cidr(cidr: &str) -> Result<(Ipv4Addr, u8), StreamError> {
+    let (addr, prefix_len) = cidr.split_once('/').unwrap_or((cidr, "32"));
+    let addr: Ipv4Addr = Ipv4Addr::from_str(addr.trim())
+        .map_err(|_| StreamError::invalid(InputKind::Value, "IPv4 address", addr.trim()))?;
+    let prefix_len: u8 = prefix_len
+        .trim()
+        .parse::<u8>()
+        .ok()
+        .filter(|len: &u8| *len <= 32)
+        .ok_or_else(|| {
+            StreamError::invalid(InputKind::Value, "CIDR prefix length", prefix_len.trim())
+        })?;
+    Ok((truncate_ipv4(addr, prefix_len), prefix_len))
 }
 
-pub fn get_mapped_int(key: String, headers: &Headers) -> i32 {
-    int_of_op_result(headers.get(&key).unwrap_or(&OpResult::Empty)).unwrap()
+pub fn ipv4_in_cidr(addr: Ipv4Addr, network: Ipv4Addr, prefix_len: u8) -> bool {
+    truncate_ipv4(addr, prefix_len) == network
 }
 
-pub fn get_mapped_float(key: String, headers: &Headers) -> OrderedFloat<f64> {
-    float_of_op_result(headers.get(&key).unwrap_or(&OpResult::Empty)).unwrap()
+pub fn ip_in_cidr(key: String, cidr: &str) -> Result<FilterFunc, StreamError> {
+    let (network, prefix_len) = parse_cidr(cidr)?;
+    Ok(Box::new(move |headers: &Headers| match headers.get(&key) {
+        Some(OpResult::IPv4(addr)) => ipv4_in_cidr(*addr, network, prefix_len),
+        _ => false,
+    }))
+}
+
+pub fn truncate_ip_to_prefix(
+    key: String,
+    prefix_len: u8,
+) -> Box<dyn Fn(Headers) -> Headers + 'static> {
+    Box::new(move |mut headers: Headers| {
+        if let Some(OpResult::IPv4(addr)) = headers.get(&key) {
+            let truncated: Ipv4Addr = truncate_ipv4(*addr, prefix_len);
+            headers.insert(key.clone(), OpResult::IPv4(truncated));
+        }
+        headers
+    })
+}
+
+pub fn get_mapped_int(key: impl HeaderKey, headers: &Headers) -> i32 {
+    int_of_op_result(key.lookup(headers).unwrap_or(&OpResult::Empty)).unwrap()
+}
+
+pub fn get_mapped_float(key: impl HeaderKey, headers: &Headers) -> OrderedFloat<f64> {
+    float_of_op_result(key.lookup(headers).unwrap_or(&OpResult::Empty)).unwrap()
 }
 
 pub fn create_map_operator(
     f: Box<dyn Fn(Headers) -> Headers + 'static>,
     next_op: OperatorRef,
 ) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
     let f = Rc::new(RefCell::new(f));
 
     let mapping_func_ref1: Rc<RefCell<Box<dyn Fn(Headers) -> Headers + 'static>>> = Rc::clone(&f);
@@ -245,165 +996,2318 @@ pub fn create_map_operator(
         )
     });
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("map")
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_map_expr_operator(
+    src: &str,
+    input: &Schema,
+    next_op: OperatorRef,
+) -> Result<OperatorRef, StreamError> {
+    let map_expr: MapExpr = parse_map_expr(src)?;
+    map_expr.check_schema(input, src)?;
+    Ok(create_map_operator(
+        Box::new(move |headers: Headers| map_expr.apply(headers)),
+        next_op,
+    ))
+}
+
+pub type MapFunc = Box<dyn Fn(Headers) -> Headers + 'static>;
+
+pub enum StatelessStep {
+    Filter(FilterFunc),
+    Map(MapFunc),
+}
+
+fn run_stateless_steps(
+    steps: &[StatelessStep],
+    headers: &Headers,
+    skip_filters: bool,
+) -> Option<Option<Headers>> {
+    let mut mapped: Option<Headers> = None;
+    for step in steps.iter() {
+        match step {
+            StatelessStep::Filter(f) => {
+                if !skip_filters && !f(mapped.as_ref().unwrap_or(headers)) {
+                    return None;
+                }
+            }
+            StatelessStep::Map(m) => {
+                mapped = Some(m(mapped.take().unwrap_or_else(|| headers.clone())));
+            }
+        }
+    }
+    Some(mapped)
+}
+
+pub fn create_fused_operator(steps: Vec<StatelessStep>, next_op: OperatorRef) -> OperatorRef {
+    let label: String = format!("fused({} steps)", steps.len());
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let steps: Rc<Vec<StatelessStep>> = Rc::new(steps);
+    let steps_ref_clone = Rc::clone(&steps);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(
+            move |headers: &mut Headers| match run_stateless_steps(&steps, headers, false) {
+                Some(Some(mut mapped)) => (next_op.borrow_mut().next)(&mut mapped),
+                Some(None) => (next_op.borrow_mut().next)(headers),
+                None => {}
+            },
+        );
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        match run_stateless_steps(&steps_ref_clone, headers, true) {
+            Some(Some(mut mapped)) => (next_op_ref_clone.borrow_mut().reset)(&mut mapped),
+            _ => (next_op_ref_clone.borrow_mut().reset)(headers),
+        }
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
 }
 
 pub type GroupingFunc = Box<dyn Fn(Headers) -> Headers>;
 pub type ReductionFunc = Box<dyn Fn(OpResult, &mut Headers) -> OpResult>;
 
-pub fn union_headers(headers1: &mut Headers, headers2: &mut Headers) -> Headers {
-    let mut new_headers: Headers = BTreeMap::new();
+pub fn union_headers(headers1: &Headers, headers2: &Headers) -> Headers {
+    let mut new_headers: Headers = Headers::new();
 
-    for (key, val) in headers1.iter_mut() {
+    for (key, val) in headers1.iter() {
         new_headers.insert(key.clone(), val.clone());
     }
 
-    for (key, val) in headers2.iter_mut() {
+    for (key, val) in headers2.iter() {
         new_headers.insert(key.clone(), val.clone());
     }
 
     new_headers
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct FlushOptions {
+    pub parallel_threshold: usize,
+    pub max_rate: Option<f64>,
+}
+
+impl Default for FlushOptions {
+    fn default() -> Self {
+        FlushOptions {
+            parallel_threshold: 4096,
+            max_rate: None,
+        }
+    }
+}
+
+pub fn flush_table<T: Send>(
+    table: Vec<(Headers, T)>,
+    headers: &Headers,
+    options: &FlushOptions,
+    finish: impl Fn(Headers, T) -> Headers + Sync,
+) -> Vec<Headers> {
+    let build = |(grouping_key, val): (Headers, T)| {
+        let mut unioned_headers: Headers = headers.clone();
+        unioned_headers.extend(finish(grouping_key, val));
+        unioned_headers
+    };
+    if table.len() >= options.parallel_threshold {
+        table.into_par_iter().map(build).collect()
+    } else {
+        table.into_iter().map(build).collect()
+    }
+}
+
+pub fn emit_flushed(rows: Vec<Headers>, options: &FlushOptions, next_op: &OperatorRef) {
+    trace_event!(groups = rows.len(), "flushing grouped rows");
+    let start: Instant = Instant::now();
+    for (i, mut row) in rows.into_iter().enumerate() {
+        if let Some(rate) = options.max_rate
+            && rate > 0.0
+        {
+            let due: Duration = Duration::from_secs_f64(i as f64 / rate);
+            let elapsed: Duration = start.elapsed();
+            if due > elapsed {
+                thread::sleep(due - elapsed);
+            }
+        }
+        (next_op.borrow_mut().next)(&mut row)
+    }
+}
+
 pub fn create_groupby_operator(
     groupby: GroupingFunc,
     reduce: ReductionFunc,
     out_key: String,
     next_op: OperatorRef,
 ) -> OperatorRef {
-    let mut _h_tbl: Box<HashMap<Headers, OpResult>> = Box::new(HashMap::new());
-    let h_tbl_ref = Rc::new(RefCell::new(_h_tbl));
-
-    let next_htbl_ref: Rc<RefCell<Box<HashMap<Headers, OpResult>>>> = Rc::clone(&h_tbl_ref);
-    let reset_htbl_ref: Rc<RefCell<Box<HashMap<Headers, OpResult>>>> = Rc::clone(&h_tbl_ref);
+    create_gauged_groupby_operator(
+        groupby,
+        reduce,
+        out_key,
+        None,
+        FlushOptions::default(),
+        next_op,
+    )
+}
 
-    let mut _reset_counter: i32 = 0;
+pub fn create_gauged_groupby_operator(
+    groupby: GroupingFunc,
+    reduce: ReductionFunc,
+    out_key: String,
+    table_size: Option<Gauge>,
+    flush: FlushOptions,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("groupby({})", out_key);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let h_tbl_ref: Rc<RefCell<GroupTable<OpResult>>> = Rc::new(RefCell::new(GroupTable::new()));
+
+    let next_htbl_ref: Rc<RefCell<GroupTable<OpResult>>> = Rc::clone(&h_tbl_ref);
+    let reset_htbl_ref: Rc<RefCell<GroupTable<OpResult>>> = Rc::clone(&h_tbl_ref);
+
+    let mut _reset_counter: i32 = 0;
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut h_tbl = next_htbl_ref.borrow_mut();
+        let grouping_key: GroupKey = h_tbl.key(&groupby(headers.clone()));
+        h_tbl
+            .entries
+            .entry(grouping_key)
+            .and_modify(|val: &mut OpResult| {
+                *val = reduce(mem::replace(val, OpResult::Empty), headers)
+            })
+            .or_insert_with(|| reduce(OpResult::Empty, headers));
+        record_peak(&table_size, h_tbl.len());
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        _reset_counter += 1;
+        let table: Vec<(Headers, OpResult)> = reset_htbl_ref.borrow_mut().drain();
+        let rows: Vec<Headers> = flush_table(
+            table,
+            headers,
+            &flush,
+            |mut grouping_key: Headers, val: OpResult| {
+                grouping_key.insert(out_key.clone(), finalize_op_result(val));
+                grouping_key
+            },
+        );
+        emit_flushed(rows, &flush, &next_op);
+        (next_op.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+fn partial_flush_headers(headers: &Headers) -> Headers {
+    match headers.get_known(WellKnownKey::Eid) {
+        Some(eid) => singleton(WellKnownKey::Eid.into(), eid.clone()),
+        None => Headers::new(),
+    }
+}
+
+pub fn create_groupby_operator_with_budget(
+    groupby: GroupingFunc,
+    reduce: ReductionFunc,
+    out_key: String,
+    budget: MemoryBudget,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("groupby({})", out_key);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let h_tbl_ref: Rc<RefCell<GroupTable<OpResult>>> = Rc::new(RefCell::new(GroupTable::new()));
+    let reset_htbl_ref: Rc<RefCell<GroupTable<OpResult>>> = Rc::clone(&h_tbl_ref);
+    let reset_budget: MemoryBudget = budget.clone();
+    let fault: OperatorFault = budget.fault().clone();
+    let label_cp: String = label.clone();
+    let next_op_ref = Rc::clone(&next_op);
+    let flush: FlushOptions = FlushOptions::default();
+
+    let finish = move |mut grouping_key: Headers, val: OpResult| {
+        grouping_key.insert(out_key.clone(), finalize_op_result(val));
+        grouping_key
+    };
+    let finish_cp = finish.clone();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if budget.fault().failed() {
+            return;
+        }
+        let flushed: Vec<(Headers, OpResult)> = {
+            let mut h_tbl = h_tbl_ref.borrow_mut();
+            let grouping_key: GroupKey = h_tbl.key(&groupby(headers.clone()));
+            match h_tbl.entries.entry(grouping_key) {
+                Entry::Occupied(mut entry) => {
+                    budget.release(approx_op_result_bytes(entry.get()));
+                    let val: OpResult =
+                        reduce(mem::replace(entry.get_mut(), OpResult::Empty), headers);
+                    budget.charge(approx_op_result_bytes(&val));
+                    entry.insert(val);
+                }
+                Entry::Vacant(entry) => {
+                    let val: OpResult = reduce(OpResult::Empty, headers);
+                    budget.charge(entry.key().approx_bytes() + approx_op_result_bytes(&val));
+                    entry.insert(val);
+                }
+            }
+            let flushed: Vec<(GroupKey, OpResult)> =
+                match budget.enforce(&label_cp, &mut h_tbl.entries, approx_op_result_bytes) {
+                    Ok(flushed) => flushed,
+                    Err(e) => {
+                        budget.fault().record(e);
+                        Vec::new()
+                    }
+                };
+            h_tbl.decode_all(flushed)
+        };
+        if !flushed.is_empty() {
+            trace_event!(
+                groups = flushed.len(),
+                "memory budget exceeded, flushing early"
+            );
+            let rows: Vec<Headers> =
+                flush_table(flushed, &partial_flush_headers(headers), &flush, &finish);
+            emit_flushed(rows, &flush, &next_op);
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut h_tbl = reset_htbl_ref.borrow_mut();
+        for (grouping_key, val) in h_tbl.entries.iter() {
+            reset_budget.release(grouping_key.approx_bytes() + approx_op_result_bytes(val));
+        }
+        let table: Vec<(Headers, OpResult)> = h_tbl.drain();
+        drop(h_tbl);
+        let rows: Vec<Headers> = flush_table(table, headers, &flush, &finish_cp);
+        emit_flushed(rows, &flush, &next_op_ref);
+        (next_op_ref.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+fn partition_of(grouping_key: &Headers, partitions: usize) -> usize {
+    let mut hasher: DefaultHasher = DefaultHasher::new();
+    grouping_key.hash(&mut hasher);
+    (hasher.finish() % partitions as u64) as usize
+}
+
+struct DeferredEpoch {
+    headers: Headers,
+    partitions: Vec<Vec<(Headers, OpResult)>>,
+}
+
+fn emit_deferred_partition(
+    deferred: &mut Option<DeferredEpoch>,
+    out_key: &str,
+    next_op: &OperatorRef,
+) {
+    let Some(epoch) = deferred.as_mut() else {
+        return;
+    };
+    while let Some(partition) = epoch.partitions.pop() {
+        if partition.is_empty() {
+            continue;
+        }
+        for (mut grouping_key, val) in partition {
+            let mut unioned_headers: Headers = epoch.headers.clone();
+            grouping_key.insert(out_key.to_string(), finalize_op_result(val));
+            unioned_headers.extend(grouping_key);
+            (next_op.borrow_mut().next)(&mut unioned_headers)
+        }
+        break;
+    }
+    if epoch.partitions.is_empty()
+        && let Some(mut epoch) = deferred.take()
+    {
+        (next_op.borrow_mut().reset)(&mut epoch.headers);
+    }
+}
+
+fn emit_deferred_epoch(deferred: &mut Option<DeferredEpoch>, out_key: &str, next_op: &OperatorRef) {
+    while deferred.is_some() {
+        emit_deferred_partition(deferred, out_key, next_op);
+    }
+}
+
+pub fn create_incremental_groupby_operator(
+    groupby: GroupingFunc,
+    reduce: ReductionFunc,
+    out_key: String,
+    partitions: usize,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("groupby({}, incremental {})", out_key, partitions);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let partitions: usize = partitions.max(1);
+    let h_tbl_ref: Rc<RefCell<HashMap<Headers, OpResult>>> = Rc::new(RefCell::new(HashMap::new()));
+    let reset_htbl_ref = Rc::clone(&h_tbl_ref);
+    let deferred: Rc<RefCell<Option<DeferredEpoch>>> = Rc::new(RefCell::new(None));
+    let (reset_deferred, finish_deferred) = (Rc::clone(&deferred), Rc::clone(&deferred));
+    let (reset_out_key, finish_out_key) = (out_key.clone(), out_key.clone());
+    let (next_op_ref, finish_next_op) = (Rc::clone(&next_op), Rc::clone(&next_op));
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        emit_deferred_partition(&mut deferred.borrow_mut(), &out_key, &next_op);
+        h_tbl_ref
+            .borrow_mut()
+            .entry(groupby(headers.clone()))
+            .and_modify(|val: &mut OpResult| {
+                *val = reduce(mem::replace(val, OpResult::Empty), headers)
+            })
+            .or_insert_with(|| reduce(OpResult::Empty, headers));
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut deferred = reset_deferred.borrow_mut();
+        emit_deferred_epoch(&mut deferred, &reset_out_key, &next_op_ref);
+        let mut split: Vec<Vec<(Headers, OpResult)>> = vec![Vec::new(); partitions];
+        for (grouping_key, val) in reset_htbl_ref.borrow_mut().drain() {
+            split[partition_of(&grouping_key, partitions)].push((grouping_key, val));
+        }
+        trace_event!(partitions, "epoch closed, deferring grouped rows");
+        *deferred = Some(DeferredEpoch {
+            headers: headers.clone(),
+            partitions: split,
+        });
+    });
+
+    let finish: Box<dyn FnMut() + 'static> = Box::new(move || {
+        emit_deferred_epoch(
+            &mut finish_deferred.borrow_mut(),
+            &finish_out_key,
+            &finish_next_op,
+        )
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_finish(finish),
+    ))
+}
+
+pub fn create_groupby_operator_with_backend(
+    groupby: GroupingFunc,
+    reduce: ReductionFunc,
+    out_key: String,
+    backend: Box<dyn StateBackend<OpResult>>,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("groupby({})", out_key);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let backend: Rc<RefCell<Box<dyn StateBackend<OpResult>>>> = Rc::new(RefCell::new(backend));
+    let next_backend_ref = Rc::clone(&backend);
+    let fault: OperatorFault = OperatorFault::new();
+    let (next_fault, reset_fault) = (fault.clone(), fault.clone());
+    let flush: FlushOptions = FlushOptions::default();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if next_fault.failed() {
+            return;
+        }
+        let grouping_key: Headers = groupby(headers.clone());
+        let mut backend = next_backend_ref.borrow_mut();
+        let updated: Result<(), StateError> =
+            backend
+                .get(&grouping_key)
+                .and_then(|val: Option<OpResult>| {
+                    backend.insert(
+                        grouping_key,
+                        reduce(val.unwrap_or(OpResult::Empty), headers),
+                    )
+                });
+        if let Err(e) = updated {
+            next_fault.record(e);
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let finish = |mut grouping_key: Headers, val: OpResult| {
+            grouping_key.insert(out_key.clone(), finalize_op_result(val));
+            grouping_key
+        };
+        let mut table: Vec<(Headers, OpResult)> = Vec::new();
+        let drained: Result<(), StateError> =
+            backend
+                .borrow_mut()
+                .drain_each(&mut |grouping_key: Headers, val: OpResult| {
+                    table.push((grouping_key, val));
+                    if table.len() >= DRAIN_CHUNK {
+                        let rows: Vec<Headers> =
+                            flush_table(mem::take(&mut table), headers, &flush, finish);
+                        emit_flushed(rows, &flush, &next_op);
+                    }
+                });
+        if let Err(e) = drained {
+            reset_fault.record(e);
+        }
+        let rows: Vec<Headers> = flush_table(table, headers, &flush, finish);
+        emit_flushed(rows, &flush, &next_op);
+        (next_op.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+pub fn create_groupby_multi_operator(
+    groupby: GroupingFunc,
+    reductions: Vec<(ReductionFunc, String)>,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_groupby_multi_operator_with_options(
+        groupby,
+        reductions,
+        FlushOptions::default(),
+        next_op,
+    )
+}
+
+pub fn create_groupby_multi_operator_with_options(
+    groupby: GroupingFunc,
+    reductions: Vec<(ReductionFunc, String)>,
+    flush: FlushOptions,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let reduce: MultiReductionFunc = multi_reduce(reductions);
+    let h_tbl_ref: Rc<RefCell<GroupTable<Headers>>> = Rc::new(RefCell::new(GroupTable::new()));
+    let next_htbl_ref: Rc<RefCell<GroupTable<Headers>>> = Rc::clone(&h_tbl_ref);
+    let reset_htbl_ref: Rc<RefCell<GroupTable<Headers>>> = Rc::clone(&h_tbl_ref);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut h_tbl = next_htbl_ref.borrow_mut();
+        let grouping_key: GroupKey = h_tbl.key(&groupby(headers.clone()));
+        let init_vals: Headers = h_tbl.entries.remove(&grouping_key).unwrap_or_default();
+        h_tbl
+            .entries
+            .insert(grouping_key, reduce(init_vals, headers));
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let table: Vec<(Headers, Headers)> = reset_htbl_ref.borrow_mut().drain();
+        let rows: Vec<Headers> = flush_table(
+            table,
+            headers,
+            &flush,
+            |mut grouping_key: Headers, vals: Headers| {
+                grouping_key.extend(finalize_headers(vals));
+                grouping_key
+            },
+        );
+        emit_flushed(rows, &flush, &next_op);
+        (next_op.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("groupby_multi")
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_ewma_operator(
+    groupby: GroupingFunc,
+    key: String,
+    alpha: f64,
+    out_key: String,
+    idle_epochs: usize,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("ewma({})", out_key);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let averages: Rc<RefCell<HashMap<Headers, (f64, usize)>>> =
+        Rc::new(RefCell::new(HashMap::new()));
+    let reset_averages_ref = Rc::clone(&averages);
+    let epoch: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+    let reset_epoch_ref = Rc::clone(&epoch);
+    let fault: OperatorFault = OperatorFault::new();
+    let next_fault: OperatorFault = fault.clone();
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let x: f64 = match get_mapped_number(&key, headers) {
+            Ok(x) => x,
+            Err(e) => return next_fault.record(e),
+        };
+        let now: usize = epoch.get();
+        let (avg, _) = *averages
+            .borrow_mut()
+            .entry(groupby(headers.clone()))
+            .and_modify(|(avg, seen): &mut (f64, usize)| {
+                *avg = alpha * x + (1.0 - alpha) * *avg;
+                *seen = now;
+            })
+            .or_insert((x, now));
+        headers.insert(out_key.clone(), OpResult::Float(OrderedFloat(avg)));
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let now: usize = reset_epoch_ref.get() + 1;
+        reset_epoch_ref.set(now);
+        reset_averages_ref
+            .borrow_mut()
+            .retain(|_, (_, seen): &mut (f64, usize)| now - *seen <= idle_epochs);
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+pub fn create_delta_operator(
+    groupby: GroupingFunc,
+    key: String,
+    out_key: String,
+    idle_epochs: usize,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("delta({})", out_key);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let previous: Rc<RefCell<HashMap<Headers, (f64, usize)>>> =
+        Rc::new(RefCell::new(HashMap::new()));
+    let reset_previous_ref = Rc::clone(&previous);
+    let epoch: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+    let reset_epoch_ref = Rc::clone(&epoch);
+    let fault: OperatorFault = OperatorFault::new();
+    let next_fault: OperatorFault = fault.clone();
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let x: f64 = match get_mapped_number(&key, headers) {
+            Ok(x) => x,
+            Err(e) => return next_fault.record(e),
+        };
+        let prev: Option<(f64, usize)> = previous
+            .borrow_mut()
+            .insert(groupby(headers.clone()), (x, epoch.get()));
+        headers.insert(
+            out_key.clone(),
+            OpResult::Float(OrderedFloat(x - prev.map_or(x, |(prev, _)| prev))),
+        );
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let now: usize = reset_epoch_ref.get() + 1;
+        reset_epoch_ref.set(now);
+        reset_previous_ref
+            .borrow_mut()
+            .retain(|_, (_, seen): &mut (f64, usize)| now - *seen <= idle_epochs);
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+pub type ScoringFunc = Box<dyn Fn(&[f64], f64) -> Option<f64>>;
+
+pub fn zscore(window: &[f64], x: f64) -> Option<f64> {
+    if window.len() < 2 {
+        return None;
+    }
+    let n: f64 = window.len() as f64;
+    let mean: f64 = window.iter().sum::<f64>() / n;
+    let var: f64 = window.iter().map(|v: &f64| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if var == 0.0 {
+        None
+    } else {
+        Some((x - mean) / var.sqrt())
+    }
+}
+
+fn median(vals: &mut [f64]) -> f64 {
+    vals.sort_by(|a: &f64, b: &f64| a.total_cmp(b));
+    let mid: usize = vals.len() / 2;
+    if vals.len().is_multiple_of(2) {
+        (vals[mid - 1] + vals[mid]) / 2.0
+    } else {
+        vals[mid]
+    }
+}
+
+pub fn mad_score(window: &[f64], x: f64) -> Option<f64> {
+    if window.len() < 2 {
+        return None;
+    }
+    let med: f64 = median(&mut window.to_vec());
+    let mad: f64 = median(
+        &mut window
+            .iter()
+            .map(|v: &f64| (v - med).abs())
+            .collect::<Vec<f64>>(),
+    );
+    if mad == 0.0 {
+        None
+    } else {
+        Some(0.6745 * (x - med) / mad)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AnomalyMode {
+    #[default]
+    Outliers,
+    Tag(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnomalyOptions {
+    pub out_key: String,
+    pub window_epochs: usize,
+    pub threshold: f64,
+    pub mode: AnomalyMode,
+}
+
+impl AnomalyOptions {
+    pub fn new(window_epochs: usize, threshold: f64) -> Self {
+        AnomalyOptions {
+            out_key: "score".to_string(),
+            window_epochs,
+            threshold,
+            mode: AnomalyMode::Outliers,
+        }
+    }
+
+    pub fn with_out_key(mut self, out_key: &str) -> Self {
+        self.out_key = out_key.to_string();
+        self
+    }
+
+    pub fn with_mode(mut self, mode: AnomalyMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+pub fn create_scored_anomaly_operator(
+    groupby: GroupingFunc,
+    key: String,
+    options: AnomalyOptions,
+    score: ScoringFunc,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("anomaly({}, {})", key, options.out_key);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let windows: Rc<RefCell<HashMap<Headers, VecDeque<f64>>>> =
+        Rc::new(RefCell::new(HashMap::new()));
+    let fault: OperatorFault = OperatorFault::new();
+    let next_fault: OperatorFault = fault.clone();
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let x: f64 = match get_mapped_number(&key, headers) {
+            Ok(x) => x,
+            Err(e) => return next_fault.record(e),
+        };
+        let x_score: Option<f64> = {
+            let mut windows = windows.borrow_mut();
+            let window: &mut VecDeque<f64> = windows.entry(groupby(headers.clone())).or_default();
+            let x_score: Option<f64> = score(window.make_contiguous(), x);
+            window.push_back(x);
+            if window.len() > options.window_epochs {
+                window.pop_front();
+            }
+            x_score
+        };
+        let outlier: bool = x_score.is_some_and(|s: f64| s.abs() >= options.threshold);
+        match &options.mode {
+            AnomalyMode::Outliers if !outlier => return,
+            AnomalyMode::Outliers => (),
+            AnomalyMode::Tag(flag_key) => {
+                headers.insert(flag_key.clone(), OpResult::Int(outlier as i32));
+            }
+        }
+        headers.insert(
+            options.out_key.clone(),
+            x_score.map_or(OpResult::Empty, |s: f64| OpResult::Float(OrderedFloat(s))),
+        );
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+pub fn create_anomaly_operator(
+    groupby: GroupingFunc,
+    key: String,
+    options: AnomalyOptions,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_scored_anomaly_operator(groupby, key, options, Box::new(zscore), next_op)
+}
+
+pub fn create_mad_anomaly_operator(
+    groupby: GroupingFunc,
+    key: String,
+    options: AnomalyOptions,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_scored_anomaly_operator(groupby, key, options, Box::new(mad_score), next_op)
+}
+
+pub struct GapWindow {
+    pub last_seen: f64,
+    pub gaps: VecDeque<f64>,
+}
+
+impl GapWindow {
+    pub fn mean_and_cv(&self) -> Option<(f64, f64)> {
+        if self.gaps.is_empty() {
+            return None;
+        }
+        let n: f64 = self.gaps.len() as f64;
+        let mean: f64 = self.gaps.iter().sum::<f64>() / n;
+        if mean <= 0.0 {
+            return None;
+        }
+        let var: f64 = self
+            .gaps
+            .iter()
+            .map(|gap: &f64| (gap - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        Some((mean, var.sqrt() / mean))
+    }
+}
+
+pub fn create_periodicity_operator(
+    groupby: GroupingFunc,
+    window_gaps: usize,
+    max_cv: f64,
+    max_gap_secs: f64,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_periodicity_operator_with_clock(
+        groupby,
+        window_gaps,
+        max_cv,
+        max_gap_secs,
+        elapsed_clock(),
+        next_op,
+    )
+}
+
+pub fn create_periodicity_operator_with_clock(
+    groupby: GroupingFunc,
+    window_gaps: usize,
+    max_cv: f64,
+    max_gap_secs: f64,
+    clock: ClockRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let windows: Rc<RefCell<HashMap<Headers, GapWindow>>> = Rc::new(RefCell::new(HashMap::new()));
+    let reset_windows_ref = Rc::clone(&windows);
+    let last_time: Rc<Cell<f64>> = Rc::new(Cell::new(0.0));
+    let reset_last_time_ref = Rc::clone(&last_time);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = tuple_time(headers, clock.as_ref());
+        last_time.set(time);
+        let grouping_key: Headers = groupby(headers.clone());
+        let mut windows = windows.borrow_mut();
+        match windows.get_mut(&grouping_key) {
+            Some(window) => {
+                let gap: f64 = time - window.last_seen;
+                window.last_seen = time;
+                if gap > max_gap_secs {
+                    window.gaps.clear();
+                } else if gap > 0.0 {
+                    window.gaps.push_back(gap);
+                    if window.gaps.len() > window_gaps {
+                        window.gaps.pop_front();
+                    }
+                }
+            }
+            None => {
+                windows.insert(
+                    grouping_key,
+                    GapWindow {
+                        last_seen: time,
+                        gaps: VecDeque::new(),
+                    },
+                );
+            }
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let now: f64 = reset_last_time_ref.get();
+        let mut rows: Vec<Headers> = Vec::new();
+        reset_windows_ref
+            .borrow_mut()
+            .retain(|grouping_key: &Headers, window: &mut GapWindow| {
+                if now - window.last_seen > max_gap_secs {
+                    return false;
+                }
+                if window.gaps.len() >= window_gaps
+                    && let Some((mean, cv)) = window.mean_and_cv()
+                    && cv <= max_cv
+                {
+                    let mut row: Headers = headers.clone();
+                    row.extend(grouping_key.clone());
+                    row.insert("gaps".to_string(), OpResult::Int(window.gaps.len() as i32));
+                    row.insert("mean_gap".to_string(), OpResult::Float(OrderedFloat(mean)));
+                    row.insert("gap_cv".to_string(), OpResult::Float(OrderedFloat(cv)));
+                    rows.push(row);
+                }
+                true
+            });
+        for mut row in rows {
+            (next_op.borrow_mut().next)(&mut row)
+        }
+        (next_op.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("periodicity")
+            .with_downstream(downstream),
+    ))
+}
+
+pub enum SequenceStep {
+    Expect(FilterFunc),
+    Absent(FilterFunc),
+}
+
+struct PartialMatch {
+    step: usize,
+    start: f64,
+    last: f64,
+}
+
+fn sequence_match_headers(grouping_key: &Headers, partial: &PartialMatch) -> Headers {
+    let mut headers: Headers = grouping_key.clone();
+    headers.insert(
+        "seq.start".to_string(),
+        OpResult::Float(OrderedFloat(partial.start)),
+    );
+    headers.insert(
+        "seq.end".to_string(),
+        OpResult::Float(OrderedFloat(partial.last)),
+    );
+    headers
+}
+
+fn only_absent_steps_remain(steps: &[SequenceStep], idx: usize) -> bool {
+    steps[idx..]
+        .iter()
+        .all(|step: &SequenceStep| matches!(step, SequenceStep::Absent(_)))
+}
+
+pub fn create_sequence_operator(
+    groupby: GroupingFunc,
+    steps: Vec<SequenceStep>,
+    within_secs: f64,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_sequence_operator_with_clock(groupby, steps, within_secs, elapsed_clock(), next_op)
+}
+
+pub fn create_sequence_operator_with_clock(
+    groupby: GroupingFunc,
+    steps: Vec<SequenceStep>,
+    within_secs: f64,
+    clock: ClockRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("sequence({} steps)", steps.len());
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let steps = Rc::new(steps);
+    let reset_steps = Rc::clone(&steps);
+    let partials: Rc<RefCell<HashMap<Headers, PartialMatch>>> =
+        Rc::new(RefCell::new(HashMap::new()));
+    let reset_partials = Rc::clone(&partials);
+    let latest: Rc<RefCell<f64>> = Rc::new(RefCell::new(0.0));
+    let reset_latest = Rc::clone(&latest);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = tuple_time(headers, clock.as_ref());
+        let latest_time: f64 = latest.borrow().max(time);
+        *latest.borrow_mut() = latest_time;
+        let grouping_key: Headers = groupby(headers.clone());
+
+        let mut partials = partials.borrow_mut();
+        if let Some(partial) = partials.get(&grouping_key)
+            && time - partial.start > within_secs
+        {
+            let partial: PartialMatch = partials.remove(&grouping_key).unwrap();
+            if only_absent_steps_remain(&steps, partial.step) {
+                (next_op.borrow_mut().next)(&mut sequence_match_headers(&grouping_key, &partial));
+            }
+        }
+
+        let mut step: usize = partials
+            .get(&grouping_key)
+            .map_or(0, |p: &PartialMatch| p.step);
+        while step < steps.len() {
+            match &steps[step] {
+                SequenceStep::Absent(pred) if pred(headers) => {
+                    partials.remove(&grouping_key);
+                    return;
+                }
+                SequenceStep::Absent(_) => step += 1,
+                SequenceStep::Expect(pred) if pred(headers) => {
+                    let partial: &mut PartialMatch = partials
+                        .entry(grouping_key.clone())
+                        .or_insert(PartialMatch {
+                            step: 0,
+                            start: time,
+                            last: time,
+                        });
+                    partial.step = step + 1;
+                    partial.last = time;
+                    break;
+                }
+                SequenceStep::Expect(_) => return,
+            }
+        }
+
+        if partials
+            .get(&grouping_key)
+            .is_some_and(|p: &PartialMatch| p.step == steps.len())
+        {
+            let partial: PartialMatch = partials.remove(&grouping_key).unwrap();
+            (next_op.borrow_mut().next)(&mut sequence_match_headers(&grouping_key, &partial));
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let now: f64 = *reset_latest.borrow();
+        let expired: Vec<Headers> = reset_partials
+            .borrow()
+            .iter()
+            .filter(|(_, partial)| now - partial.start > within_secs)
+            .map(|(grouping_key, _)| grouping_key.clone())
+            .collect();
+        for grouping_key in expired {
+            let partial: PartialMatch = reset_partials.borrow_mut().remove(&grouping_key).unwrap();
+            if only_absent_steps_remain(&reset_steps, partial.step) {
+                let matched: Headers = sequence_match_headers(&grouping_key, &partial);
+                (next_op_ref_clone.borrow_mut().next)(&mut union_headers(headers, &matched));
+            }
+        }
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn filter_groups<K: HeaderKey>(incl_keys: &[K], headers: &mut Headers) -> Headers {
+    let mut new_headers: Headers = Headers::new();
+    for key in incl_keys.iter() {
+        if let Some(val) = key.lookup(headers) {
+            new_headers.insert(key.key_str().to_string(), val.clone());
+        }
+    }
+    new_headers
+}
+
+pub fn single_group(_headers: Headers) -> Headers {
+    Headers::new()
+}
+
+pub fn counter(val: OpResult, _headers: &mut Headers) -> OpResult {
+    match val {
+        OpResult::Empty => OpResult::Int(1),
+        OpResult::Int(i) => OpResult::Int(i + 1),
+        _ => val,
+    }
+}
+
+pub fn sum_ints(
+    search_key: impl HeaderKey,
+    init_val: OpResult,
+    headers: &mut Headers,
+) -> Result<OpResult, StreamError> {
+    match init_val {
+        OpResult::Empty => Ok(OpResult::Int(1)),
+        OpResult::Int(i) => match search_key.lookup(headers) {
+            Some(OpResult::Int(n)) => Ok(OpResult::Int(*n + i)),
+            _ => Err(StreamError::value(
+                "'sum_vals' function failed to find integer 
+                        value mapped to the incorrect type",
+            )),
+        },
+        _ => Ok(init_val),
+    }
+}
+
+pub fn create_distinct_operator(groupby: GroupingFunc, next_op: OperatorRef) -> OperatorRef {
+    create_distinct_operator_with_options(groupby, FlushOptions::default(), next_op)
+}
+
+pub fn create_distinct_operator_with_options(
+    groupby: GroupingFunc,
+    flush: FlushOptions,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let h_tbl_ref: Rc<RefCell<GroupTable<bool>>> = Rc::new(RefCell::new(GroupTable::new()));
+
+    let next_htbl_ref: Rc<RefCell<GroupTable<bool>>> = Rc::clone(&h_tbl_ref);
+    let reset_htbl_ref: Rc<RefCell<GroupTable<bool>>> = Rc::clone(&h_tbl_ref);
+
+    let mut _reset_counter: i32 = 0;
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut h_tbl = next_htbl_ref.borrow_mut();
+        let grouping_key: GroupKey = h_tbl.key(&groupby(headers.clone()));
+        h_tbl.entries.insert(grouping_key, true);
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        _reset_counter += 1;
+        let table: Vec<(Headers, bool)> = reset_htbl_ref.borrow_mut().drain();
+        let rows: Vec<Headers> = flush_table(table, headers, &flush, |key: Headers, _: bool| key);
+        emit_flushed(rows, &flush, &next_op);
+        (next_op.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("distinct")
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_distinct_operator_with_budget(
+    groupby: GroupingFunc,
+    budget: MemoryBudget,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let h_tbl_ref: Rc<RefCell<GroupTable<bool>>> = Rc::new(RefCell::new(GroupTable::new()));
+    let reset_htbl_ref: Rc<RefCell<GroupTable<bool>>> = Rc::clone(&h_tbl_ref);
+    let reset_budget: MemoryBudget = budget.clone();
+    let fault: OperatorFault = budget.fault().clone();
+    let next_op_ref = Rc::clone(&next_op);
+    let flush: FlushOptions = FlushOptions::default();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if budget.fault().failed() {
+            return;
+        }
+        let flushed: Vec<(Headers, bool)> = {
+            let mut h_tbl = h_tbl_ref.borrow_mut();
+            let grouping_key: GroupKey = h_tbl.key(&groupby(headers.clone()));
+            if let Entry::Vacant(entry) = h_tbl.entries.entry(grouping_key) {
+                budget.charge(entry.key().approx_bytes());
+                entry.insert(true);
+            }
+            let flushed: Vec<(GroupKey, bool)> =
+                match budget.enforce("distinct", &mut h_tbl.entries, |_: &bool| 0) {
+                    Ok(flushed) => flushed,
+                    Err(e) => {
+                        budget.fault().record(e);
+                        Vec::new()
+                    }
+                };
+            h_tbl.decode_all(flushed)
+        };
+        if !flushed.is_empty() {
+            trace_event!(
+                keys = flushed.len(),
+                "memory budget exceeded, flushing early"
+            );
+            let rows: Vec<Headers> = flush_table(
+                flushed,
+                &partial_flush_headers(headers),
+                &flush,
+                |key: Headers, _: bool| key,
+            );
+            emit_flushed(rows, &flush, &next_op);
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut h_tbl = reset_htbl_ref.borrow_mut();
+        for grouping_key in h_tbl.entries.keys() {
+            reset_budget.release(grouping_key.approx_bytes());
+        }
+        let table: Vec<(Headers, bool)> = h_tbl.drain();
+        drop(h_tbl);
+        let rows: Vec<Headers> = flush_table(table, headers, &flush, |key: Headers, _: bool| key);
+        emit_flushed(rows, &flush, &next_op_ref);
+        (next_op_ref.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("distinct")
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+pub fn create_distinct_operator_with_backend(
+    groupby: GroupingFunc,
+    backend: Box<dyn StateBackend<bool>>,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let backend: Rc<RefCell<Box<dyn StateBackend<bool>>>> = Rc::new(RefCell::new(backend));
+    let next_backend_ref = Rc::clone(&backend);
+    let fault: OperatorFault = OperatorFault::new();
+    let (next_fault, reset_fault) = (fault.clone(), fault.clone());
+    let flush: FlushOptions = FlushOptions::default();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if next_fault.failed() {
+            return;
+        }
+        let grouping_key: Headers = groupby(headers.clone());
+        if let Err(e) = next_backend_ref.borrow_mut().insert(grouping_key, true) {
+            next_fault.record(e);
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut keys: Vec<(Headers, bool)> = Vec::new();
+        let drained: Result<(), StateError> =
+            backend
+                .borrow_mut()
+                .drain_each(&mut |key: Headers, seen: bool| {
+                    keys.push((key, seen));
+                    if keys.len() >= DRAIN_CHUNK {
+                        let rows: Vec<Headers> =
+                            flush_table(mem::take(&mut keys), headers, &flush, |key, _| key);
+                        emit_flushed(rows, &flush, &next_op);
+                    }
+                });
+        if let Err(e) = drained {
+            reset_fault.record(e);
+        }
+        let rows: Vec<Headers> = flush_table(keys, headers, &flush, |key: Headers, _: bool| key);
+        emit_flushed(rows, &flush, &next_op);
+        (next_op.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("distinct")
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+pub fn tuple_time(headers: &Headers, clock: &dyn Clock) -> f64 {
+    match WellKnownKey::Time.lookup(headers) {
+        Some(OpResult::Float(time)) => time.0,
+        _ => clock.now(),
+    }
+}
+
+pub fn create_distinct_ttl_operator(
+    groupby: GroupingFunc,
+    ttl_secs: f64,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_distinct_ttl_operator_with_clock(groupby, ttl_secs, elapsed_clock(), next_op)
+}
+
+pub fn create_distinct_ttl_operator_with_clock(
+    groupby: GroupingFunc,
+    ttl_secs: f64,
+    clock: ClockRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("distinct(ttl {}s)", ttl_secs);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let seen: Rc<RefCell<HashMap<Headers, f64>>> = Rc::new(RefCell::new(HashMap::new()));
+    let reset_seen_ref = Rc::clone(&seen);
+    let last_time: Rc<Cell<f64>> = Rc::new(Cell::new(0.0));
+    let reset_last_time_ref = Rc::clone(&last_time);
+    let next_op_ref_clone = Rc::clone(&next_op);
 
     let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = tuple_time(headers, clock.as_ref());
+        last_time.set(time);
         let grouping_key: Headers = groupby(headers.clone());
-        next_htbl_ref
+        let mut seen = seen.borrow_mut();
+        match seen.get(&grouping_key) {
+            Some(first_seen) if time - first_seen < ttl_secs => {}
+            _ => {
+                seen.insert(grouping_key, time);
+                (next_op.borrow_mut().next)(headers)
+            }
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let now: f64 = reset_last_time_ref.get();
+        reset_seen_ref
             .borrow_mut()
-            .entry(grouping_key)
-            .and_modify(|val: &mut OpResult| *val = reduce(val.clone(), headers))
-            .or_insert_with(|| reduce(OpResult::Empty, headers));
+            .retain(|_, first_seen: &mut f64| now - *first_seen < ttl_secs);
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub struct ThrottleWindow {
+    pub start: f64,
+    pub emitted: usize,
+    pub suppressed: usize,
+}
+
+pub fn create_throttle_operator(
+    groupby: GroupingFunc,
+    max_per_interval: usize,
+    interval_secs: f64,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_throttle_operator_with_clock(
+        groupby,
+        max_per_interval,
+        interval_secs,
+        elapsed_clock(),
+        next_op,
+    )
+}
+
+pub fn create_throttle_operator_with_clock(
+    groupby: GroupingFunc,
+    max_per_interval: usize,
+    interval_secs: f64,
+    clock: ClockRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("throttle({})", max_per_interval);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let windows: Rc<RefCell<HashMap<Headers, ThrottleWindow>>> =
+        Rc::new(RefCell::new(HashMap::new()));
+    let reset_windows_ref = Rc::clone(&windows);
+    let last_time: Rc<Cell<f64>> = Rc::new(Cell::new(0.0));
+    let reset_last_time_ref = Rc::clone(&last_time);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = tuple_time(headers, clock.as_ref());
+        last_time.set(time);
+        let grouping_key: Headers = groupby(headers.clone());
+        let mut windows = windows.borrow_mut();
+        let window: &mut ThrottleWindow = windows.entry(grouping_key).or_insert(ThrottleWindow {
+            start: time,
+            emitted: 0,
+            suppressed: 0,
+        });
+        if time - window.start >= interval_secs {
+            window.start = time;
+            window.emitted = 0;
+        }
+        if window.emitted >= max_per_interval {
+            window.suppressed += 1;
+            return;
+        }
+        window.emitted += 1;
+        if window.suppressed > 0 {
+            headers.insert(
+                "suppressed".to_string(),
+                OpResult::Int(window.suppressed as i32),
+            );
+            window.suppressed = 0;
+        }
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let now: f64 = reset_last_time_ref.get();
+        reset_windows_ref
+            .borrow_mut()
+            .retain(|_, window: &mut ThrottleWindow| {
+                window.suppressed > 0 || now - window.start < interval_secs
+            });
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn order_op_results(a: &OpResult, b: &OpResult) -> std::cmp::Ordering {
+    compare_op_results(a, b).unwrap_or_else(|| string_of_op_result(a).cmp(&string_of_op_result(b)))
+}
+
+pub fn create_sort_operator(
+    key: String,
+    ascending: bool,
+    limit: Option<usize>,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("sort({})", key);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let buffer: Rc<RefCell<Vec<Headers>>> = Rc::new(RefCell::new(Vec::new()));
+    let reset_buffer_ref = Rc::clone(&buffer);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| buffer.borrow_mut().push(headers.clone()));
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut rows: Vec<Headers> = std::mem::take(&mut *reset_buffer_ref.borrow_mut());
+        rows.sort_by(
+            |a: &Headers, b: &Headers| match (a.get(&key), b.get(&key)) {
+                (Some(a), Some(b)) if ascending => order_op_results(a, b),
+                (Some(a), Some(b)) => order_op_results(b, a),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            },
+        );
+        rows.truncate(limit.unwrap_or(usize::MAX));
+        for mut row in rows {
+            (next_op_ref_clone.borrow_mut().next)(&mut row)
+        }
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn order_headers(a: &Headers, b: &Headers) -> std::cmp::Ordering {
+    a.iter()
+        .zip(b.iter())
+        .map(|((a_key, a_val), (b_key, b_val))| {
+            a_key
+                .cmp(b_key)
+                .then_with(|| order_op_results(a_val, b_val))
+        })
+        .find(|ord: &std::cmp::Ordering| ord.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+pub fn create_ordered_operator(next_op: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let buffer: Rc<RefCell<Vec<Headers>>> = Rc::new(RefCell::new(Vec::new()));
+    let reset_buffer_ref = Rc::clone(&buffer);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| buffer.borrow_mut().push(headers.clone()));
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut rows: Vec<Headers> = std::mem::take(&mut *reset_buffer_ref.borrow_mut());
+        rows.sort_by(order_headers);
+        for mut row in rows {
+            (next_op.borrow_mut().next)(&mut row)
+        }
+        (next_op.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("ordered")
+            .with_downstream(downstream),
+    ))
+}
+
+pub const SAMPLE_RATE_KEY: &str = "sample.rate";
+
+fn record_sample_rate(headers: &mut Headers, rate: f64) {
+    let prior: f64 = match headers.get(SAMPLE_RATE_KEY) {
+        Some(OpResult::Float(prior)) => prior.0,
+        _ => 1.0,
+    };
+    headers.insert(
+        SAMPLE_RATE_KEY.to_string(),
+        OpResult::Float(OrderedFloat(prior * rate)),
+    );
+}
+
+pub fn create_sample_operator(prob: f64, seed: u64, next_op: OperatorRef) -> OperatorRef {
+    let label: String = format!("sample({})", prob);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let prob: f64 = prob.clamp(0.0, 1.0);
+    let mut rng: TrafficRng = TrafficRng::new(seed);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if rng.next_f64() < prob {
+            record_sample_rate(headers, prob);
+            (next_op.borrow_mut().next)(headers)
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_every_nth_operator(n: usize, next_op: OperatorRef) -> OperatorRef {
+    let label: String = format!("every_nth({})", n);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let n: usize = n.max(1);
+    let mut count: usize = 0;
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        count += 1;
+        if count == n {
+            count = 0;
+            record_sample_rate(headers, 1.0 / n as f64);
+            (next_op.borrow_mut().next)(headers)
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_split_operator(l: OperatorRef, r: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&l), Rc::clone(&r)];
+    let l_ref_clone = Rc::clone(&l);
+    let r_ref_clone = Rc::clone(&r);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        (Rc::clone(&l).borrow_mut().next)(&mut headers.clone());
+        (Rc::clone(&r).borrow_mut().next)(headers);
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        (l_ref_clone.borrow_mut().reset)(&mut headers.clone());
+        (r_ref_clone.borrow_mut().reset)(headers);
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("split")
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_route_operator(
+    routes: Vec<(FilterFunc, OperatorRef)>,
+    default_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = routes
+        .iter()
+        .map(|(_, op)| Rc::clone(op))
+        .chain([Rc::clone(&default_op)])
+        .collect();
+    let routes = Rc::new(routes);
+    let routes_ref_clone = Rc::clone(&routes);
+    let default_op_ref_clone = Rc::clone(&default_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        match routes.iter().find(|(pred, _)| pred(headers)) {
+            Some((_, op)) => (op.borrow_mut().next)(headers),
+            None => (default_op.borrow_mut().next)(headers),
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        for (_, op) in routes_ref_clone.iter() {
+            (op.borrow_mut().reset)(&mut headers.clone());
+        }
+        (default_op_ref_clone.borrow_mut().reset)(headers);
     });
 
-    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        _reset_counter += 1;
-        for (grouping_key, val) in reset_htbl_ref.borrow_mut().iter_mut() {
-            let mut unioned_headers: Headers = union_headers(headers, &mut grouping_key.clone());
-            unioned_headers.insert(out_key.clone(), val.clone());
-            (Rc::clone(&next_op).borrow_mut().next)(&mut unioned_headers)
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("route")
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_union_operator(n: usize, next_op: OperatorRef) -> Vec<OperatorRef> {
+    let pending_resets: Rc<RefCell<Vec<i32>>> = Rc::new(RefCell::new(vec![0; n]));
+
+    (0..n)
+        .map(|branch: usize| {
+            let next_op_ref = Rc::clone(&next_op);
+            let reset_next_op_ref = Rc::clone(&next_op);
+            let pending_resets_ref = Rc::clone(&pending_resets);
+            let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+
+            let next: Box<dyn FnMut(&mut Headers) + 'static> =
+                Box::new(move |headers: &mut Headers| (next_op_ref.borrow_mut().next)(headers));
+
+            let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+                Box::new(move |headers: &mut Headers| {
+                    let all_reset: bool = {
+                        let mut counts = pending_resets_ref.borrow_mut();
+                        counts[branch] += 1;
+                        if counts.iter().all(|count: &i32| *count > 0) {
+                            counts.iter_mut().for_each(|count: &mut i32| *count -= 1);
+                            true
+                        } else {
+                            false
+                        }
+                    };
+                    if all_reset {
+                        (reset_next_op_ref.borrow_mut().reset)(headers);
+                    }
+                });
+
+            Rc::new(RefCell::new(
+                Operator::new(next, reset)
+                    .with_label(format!("union[{}]", branch))
+                    .with_downstream(downstream),
+            ))
+        })
+        .collect()
+}
+
+pub type KeyExtractor = Box<dyn FnMut(Headers) -> (Headers, Headers)>;
+
+pub fn singleton(key: String, val: OpResult) -> Headers {
+    Headers::from([(key, val)])
+}
+
+pub type EvictionFunc = Box<dyn FnMut(&Headers, &Headers)>;
+
+#[derive(Default)]
+pub struct JoinBounds {
+    pub max_entries: Option<usize>,
+    pub ttl: Option<i32>,
+    pub on_evict: Option<EvictionFunc>,
+    pub occupancy: Option<Gauge>,
+    pub state: Option<BackendFactory<Headers>>,
+    pub budget: Option<MemoryBudget>,
+    pub dropped: Arc<AtomicUsize>,
+}
+
+impl JoinBounds {
+    pub fn register_stats(&self, stats: &mut PipelineStats, name: &str) -> StatsRef {
+        stats.register(name.to_string(), Some(Arc::clone(&self.dropped)))
+    }
+}
+
+pub type JoinTable = Rc<RefCell<Box<dyn StateBackend<Headers>>>>;
+
+#[derive(Default)]
+pub struct JoinIndex {
+    by_age: BTreeMap<(i32, u64), Headers>,
+    slots: HashMap<Headers, (i32, u64)>,
+    seq: u64,
+}
+
+pub type JoinIndexRef = Rc<RefCell<JoinIndex>>;
+
+impl JoinIndex {
+    pub fn of_table(table: &dyn StateBackend<Headers>, eid_key: &str) -> Result<Self, StateError> {
+        let mut index: JoinIndex = JoinIndex::default();
+        for key in table.keys()? {
+            if let Some(OpResult::Int(eid)) = key.get(eid_key) {
+                index.insert(&key, *eid);
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn insert(&mut self, key: &Headers, eid: i32) {
+        if !self.slots.contains_key(key) {
+            self.seq += 1;
+            self.by_age.insert((eid, self.seq), key.clone());
+            self.slots.insert(key.clone(), (eid, self.seq));
         }
-        (next_op.borrow_mut().reset)(headers);
-        reset_htbl_ref.borrow_mut().clear();
-    });
+    }
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
-}
+    pub fn pop_oldest(&mut self) -> Option<Headers> {
+        let (_, key) = self.by_age.pop_first()?;
+        self.slots.remove(&key);
+        Some(key)
+    }
 
-pub fn filter_groups(incl_keys: Vec<String>, headers: &mut Headers) -> Headers {
-    let mut new_headers: Headers = BTreeMap::new();
-    for (key, val) in headers.iter_mut() {
-        if incl_keys.contains(key) {
-            new_headers.insert(key.clone(), val.clone());
+    pub fn pop_older_than(&mut self, eid: i32) -> Option<Headers> {
+        match self.by_age.first_key_value() {
+            Some(((oldest, _), _)) if *oldest < eid => self.pop_oldest(),
+            _ => None,
         }
     }
-    new_headers
 }
 
-pub fn single_group(_headers: Headers) -> Headers {
-    BTreeMap::new()
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JoinInput {
+    pub name: String,
+    pub rename: Vec<(String, String)>,
+    pub values: Vec<String>,
 }
 
-pub fn counter(val: OpResult, _headers: &mut Headers) -> OpResult {
-    match val {
-        OpResult::Empty => OpResult::Int(1),
-        OpResult::Int(i) => OpResult::Int(i + 1),
-        _ => val,
+impl JoinInput {
+    pub fn new(name: &str) -> Self {
+        JoinInput {
+            name: name.to_string(),
+            ..JoinInput::default()
+        }
+    }
+
+    pub fn with_rename(mut self, key: &str, field: &str) -> Self {
+        self.rename.push((key.to_string(), field.to_string()));
+        self
+    }
+
+    pub fn with_values(mut self, values: &[&str]) -> Self {
+        self.values = values.iter().map(|val: &&str| val.to_string()).collect();
+        self
+    }
+
+    pub fn fields(&self, key_fields: &[String]) -> Vec<(String, String)> {
+        key_fields
+            .iter()
+            .map(|key: &String| {
+                let field: &String = self
+                    .rename
+                    .iter()
+                    .find(|(renamed, _)| renamed == key)
+                    .map_or(key, |(_, field)| field);
+                (key.clone(), field.clone())
+            })
+            .collect()
+    }
+
+    fn extractor(&self, key_fields: &[String]) -> KeyExtractor {
+        let fields: Vec<(String, String)> = self.fields(key_fields);
+        let values: Vec<String> = self.values.clone();
+        Box::new(move |mut headers: Headers| {
+            (
+                rename_filtered_keys(fields.clone(), &mut headers),
+                filter_groups(&values, &mut headers),
+            )
+        })
     }
 }
 
-pub fn sum_ints(
-    search_key: String,
-    init_val: OpResult,
-    headers: &mut Headers,
-) -> Result<OpResult, Error> {
-    match init_val {
-        OpResult::Empty => Ok(OpResult::Int(1)),
-        OpResult::Int(i) => match headers.get_mut(&search_key) {
-            Some(OpResult::Int(n)) => Ok(OpResult::Int(*n + i)),
-            _ => Err(Error::new(
-                ErrorKind::InvalidInput,
-                "'sum_vals' function failed to find integer 
-                        value mapped to the incorrect type",
-            )),
-        },
-        _ => Ok(init_val),
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MultiJoinSpec {
+    pub key_fields: Vec<String>,
+    pub inputs: Vec<JoinInput>,
+}
+
+impl MultiJoinSpec {
+    pub fn new(key_fields: &[&str]) -> Self {
+        MultiJoinSpec {
+            key_fields: key_fields
+                .iter()
+                .map(|key: &&str| key.to_string())
+                .collect(),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn with_input(mut self, input: JoinInput) -> Self {
+        self.inputs.push(input);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), StreamError> {
+        if self.key_fields.is_empty() {
+            return Err(StreamError::value(
+                "join spec needs at least one key field".to_string(),
+            ));
+        }
+        if self.inputs.len() < 2 {
+            return Err(StreamError::value(format!(
+                "join needs at least two inputs, found {}",
+                self.inputs.len()
+            )));
+        }
+        for (i, key) in self.key_fields.iter().enumerate() {
+            if self.key_fields[..i].contains(key) {
+                return Err(StreamError::value(format!(
+                    "join key field '{}' is listed twice",
+                    key
+                )));
+            }
+        }
+        for input in self.inputs.iter() {
+            for (i, (key, _)) in input.rename.iter().enumerate() {
+                if !self.key_fields.contains(key) {
+                    return Err(StreamError::value(format!(
+                        "{} rename targets '{}', which is not a join key field",
+                        input.name, key
+                    )));
+                }
+                if input.rename[..i].iter().any(|(other, _)| other == key) {
+                    return Err(StreamError::value(format!(
+                        "{} side renames join key field '{}' twice",
+                        input.name, key
+                    )));
+                }
+            }
+        }
+        let values: Vec<&String> = self
+            .inputs
+            .iter()
+            .flat_map(|input: &JoinInput| input.values.iter())
+            .collect();
+        for (i, val) in values.iter().enumerate() {
+            if self.key_fields.contains(val) {
+                return Err(StreamError::value(format!(
+                    "join value field '{}' is also a key field",
+                    val
+                )));
+            }
+            if values[..i].contains(val) {
+                return Err(StreamError::value(format!(
+                    "join value field '{}' would be produced twice",
+                    val
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_schemas(&self, eid_key: &str, inputs: &[Schema]) -> Result<Schema, SchemaError> {
+        let mut output: Schema = Schema::new().with(eid_key, FieldType::Int);
+        let mut key_types: Vec<Option<FieldType>> = vec![None; self.key_fields.len()];
+        for (input, schema) in self.inputs.iter().zip(inputs.iter()) {
+            let stage: String = format!("join ({})", input.name);
+            schema.require(eid_key, FieldType::Int, &stage)?;
+            for (i, (key, field)) in input.fields(&self.key_fields).into_iter().enumerate() {
+                let ty: FieldType = schema.require(&field, FieldType::Any, &stage)?;
+                match key_types[i] {
+                    Some(expected) if !expected.accepts(ty) => {
+                        return Err(SchemaError::TypeMismatch {
+                            key,
+                            expected,
+                            found: Some(ty),
+                        });
+                    }
+                    Some(expected) if expected != FieldType::Any => {}
+                    _ => key_types[i] = Some(ty),
+                }
+            }
+            for val in input.values.iter() {
+                output = output.with(val, schema.require(val, FieldType::Any, &stage)?);
+            }
+        }
+        for (key, ty) in self.key_fields.iter().zip(key_types) {
+            output = output.with(key, ty.unwrap_or(FieldType::Any));
+        }
+        Ok(output)
+    }
+
+    pub fn extractors(&self) -> Vec<KeyExtractor> {
+        self.inputs
+            .iter()
+            .map(|input: &JoinInput| input.extractor(&self.key_fields))
+            .collect()
     }
 }
 
-pub fn create_distinct_operator(groupby: GroupingFunc, next_op: OperatorRef) -> OperatorRef {
-    let mut _h_tbl: Box<HashMap<Headers, bool>> = Box::new(HashMap::new());
-    let h_tbl_ref = Rc::new(RefCell::new(_h_tbl));
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JoinSpec {
+    pub key_fields: Vec<String>,
+    pub left_rename: Vec<(String, String)>,
+    pub right_rename: Vec<(String, String)>,
+    pub left_values: Vec<String>,
+    pub right_values: Vec<String>,
+}
 
-    let next_htbl_ref: Rc<RefCell<Box<HashMap<Headers, bool>>>> = Rc::clone(&h_tbl_ref);
-    let reset_htbl_ref: Rc<RefCell<Box<HashMap<Headers, bool>>>> = Rc::clone(&h_tbl_ref);
+impl JoinSpec {
+    pub fn new(key_fields: &[&str]) -> Self {
+        JoinSpec {
+            key_fields: key_fields
+                .iter()
+                .map(|key: &&str| key.to_string())
+                .collect(),
+            ..JoinSpec::default()
+        }
+    }
 
-    let mut _reset_counter: i32 = 0;
+    pub fn with_left_rename(mut self, key: &str, field: &str) -> Self {
+        self.left_rename.push((key.to_string(), field.to_string()));
+        self
+    }
 
-    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        let mut _grouping_key: BTreeMap<String, OpResult> = groupby(headers.clone());
-        next_htbl_ref.borrow_mut().insert(_grouping_key, true);
-    });
+    pub fn with_right_rename(mut self, key: &str, field: &str) -> Self {
+        self.right_rename.push((key.to_string(), field.to_string()));
+        self
+    }
 
-    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        _reset_counter += 1;
-        for (key, _) in reset_htbl_ref.borrow_mut().iter_mut() {
-            let mut unioned_headers: Headers = union_headers(headers, &mut key.clone());
-            (Rc::clone(&next_op).borrow_mut().next)(&mut unioned_headers);
+    pub fn with_left_values(mut self, values: &[&str]) -> Self {
+        self.left_values = values.iter().map(|val: &&str| val.to_string()).collect();
+        self
+    }
+
+    pub fn with_right_values(mut self, values: &[&str]) -> Self {
+        self.right_values = values.iter().map(|val: &&str| val.to_string()).collect();
+        self
+    }
+
+    pub fn to_multi(&self) -> MultiJoinSpec {
+        let side = |name: &str, rename: &Vec<(String, String)>, values: &Vec<String>| JoinInput {
+            name: name.to_string(),
+            rename: rename.clone(),
+            values: values.clone(),
+        };
+        MultiJoinSpec {
+            key_fields: self.key_fields.clone(),
+            inputs: vec![
+                side("left", &self.left_rename, &self.left_values),
+                side("right", &self.right_rename, &self.right_values),
+            ],
         }
-        (next_op.borrow_mut().reset)(headers);
-        reset_htbl_ref.borrow_mut().clear();
-    });
+    }
+
+    pub fn left_fields(&self) -> Vec<(String, String)> {
+        self.to_multi().inputs[0].fields(&self.key_fields)
+    }
+
+    pub fn right_fields(&self) -> Vec<(String, String)> {
+        self.to_multi().inputs[1].fields(&self.key_fields)
+    }
+
+    pub fn validate(&self) -> Result<(), StreamError> {
+        self.to_multi().validate()
+    }
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+    pub fn check_schemas(
+        &self,
+        eid_key: &str,
+        left: &Schema,
+        right: &Schema,
+    ) -> Result<Schema, SchemaError> {
+        self.to_multi()
+            .check_schemas(eid_key, &[left.clone(), right.clone()])
+    }
+
+    pub fn extractors(&self) -> (KeyExtractor, KeyExtractor) {
+        let mut extractors: Vec<KeyExtractor> = self.to_multi().extractors();
+        let right: KeyExtractor = extractors.pop().unwrap();
+        (extractors.pop().unwrap(), right)
+    }
 }
 
-pub fn create_split_operator(l: OperatorRef, r: OperatorRef) -> OperatorRef {
-    let l_ref_clone = Rc::clone(&l);
-    let r_ref_clone = Rc::clone(&r);
+pub fn create_join_operator(
+    eid_key: Option<String>,
+    left_extractor: KeyExtractor,
+    right_extractor: KeyExtractor,
+    next_op: OperatorRef,
+) -> (OperatorRef, OperatorRef) {
+    create_bounded_join_operator(
+        eid_key,
+        JoinBounds::default(),
+        left_extractor,
+        right_extractor,
+        next_op,
+    )
+}
 
-    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        (Rc::clone(&l).borrow_mut().next)(headers);
-        (Rc::clone(&r).borrow_mut().next)(headers);
-    });
+pub fn create_join_operator_with_spec(
+    eid_key: Option<String>,
+    spec: &JoinSpec,
+    bounds: JoinBounds,
+    next_op: OperatorRef,
+) -> Result<(OperatorRef, OperatorRef), StreamError> {
+    spec.validate()?;
+    let (left_extractor, right_extractor) = spec.extractors();
+    Ok(create_bounded_join_operator(
+        eid_key,
+        bounds,
+        left_extractor,
+        right_extractor,
+        next_op,
+    ))
+}
 
-    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
-        (l_ref_clone.borrow_mut().reset)(headers);
-        (r_ref_clone.borrow_mut().reset)(headers);
-    });
+struct MultiJoinState {
+    table: HashMap<Headers, Vec<Option<Headers>>>,
+    open_epochs: Vec<i32>,
+    closed: i32,
+}
+
+pub fn create_multi_join_operator(
+    eid_key: Option<String>,
+    spec: &MultiJoinSpec,
+    next_op: OperatorRef,
+) -> Result<Vec<OperatorRef>, StreamError> {
+    spec.validate()?;
+    let n: usize = spec.inputs.len();
+    let eid_key: Rc<String> = Rc::new(eid_key.unwrap_or_else(|| "eid".to_string()));
+    let state: Rc<RefCell<MultiJoinState>> = Rc::new(RefCell::new(MultiJoinState {
+        table: HashMap::new(),
+        open_epochs: vec![0; n],
+        closed: 0,
+    }));
+    let ops: Vec<OperatorRef> = spec
+        .extractors()
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut f): (usize, KeyExtractor)| {
+            let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+            let next_op_ref1 = Rc::clone(&next_op);
+            let next_op_ref2 = Rc::clone(&next_op);
+            let eid_key_ref1 = Rc::clone(&eid_key);
+            let eid_key_ref2 = Rc::clone(&eid_key);
+            let state_ref1 = Rc::clone(&state);
+            let state_ref2 = Rc::clone(&state);
+
+            let next: Box<dyn FnMut(&mut Headers) + 'static> =
+                Box::new(move |headers: &mut Headers| {
+                    let (mut key, vals) = f(headers.clone());
+                    let eid: i32 = get_mapped_int(eid_key_ref1.as_str(), headers);
+                    key.insert(eid_key_ref1.to_string(), OpResult::Int(eid));
+                    let mut state = state_ref1.borrow_mut();
+                    if eid < state.closed {
+                        return;
+                    }
+                    let slots: &mut Vec<Option<Headers>> = state
+                        .table
+                        .entry(key.clone())
+                        .or_insert_with(|| vec![None; n]);
+                    slots[i] = Some(vals);
+                    if slots.iter().all(Option::is_some) {
+                        let slots: Vec<Option<Headers>> = state.table.remove(&key).unwrap();
+                        drop(state);
+                        trace_event!(eid, inputs = n, "multi-join match");
+                        let mut merged: Headers = slots
+                            .into_iter()
+                            .flatten()
+                            .fold(key, |merged: Headers, vals: Headers| {
+                                union_headers(&merged, &vals)
+                            });
+                        (next_op_ref1.borrow_mut().next)(&mut merged)
+                    }
+                });
+
+            let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+                Box::new(move |headers: &mut Headers| {
+                    let eid: i32 = get_mapped_int(eid_key_ref2.as_str(), headers);
+                    let mut state = state_ref2.borrow_mut();
+                    state.open_epochs[i] = state.open_epochs[i].max(eid + 1);
+                    let open: i32 = state.open_epochs.iter().copied().min().unwrap_or(0);
+                    while state.closed < open {
+                        let closed: i32 = state.closed;
+                        state.table.retain(|key: &Headers, _| {
+                            get_mapped_int(eid_key_ref2.as_str(), key) != closed
+                        });
+                        state.closed += 1;
+                        (next_op_ref2.borrow_mut().reset)(&mut singleton(
+                            eid_key_ref2.to_string(),
+                            OpResult::Int(closed),
+                        ));
+                    }
+                });
 
-    Rc::new(RefCell::new(Operator::new(next, reset)))
+            Rc::new(RefCell::new(
+                Operator::new(next, reset)
+                    .with_label(format!("join_n({})", spec.inputs[i].name))
+                    .with_downstream(downstream),
+            ))
+        })
+        .collect();
+    Ok(ops)
 }
 
-pub type KeyExtractor = Box<dyn FnMut(Headers) -> (Headers, Headers)>;
+pub const TEMPORAL_JOIN_DELTA_KEY: &str = "join.dt";
 
-pub fn singleton(key: String, val: OpResult) -> Headers {
-    BTreeMap::from([(key, val)])
+#[derive(Default)]
+struct TemporalJoinSide {
+    buffered: HashMap<Headers, VecDeque<(f64, Headers)>>,
+    arrivals: VecDeque<(f64, Headers)>,
+    resets: usize,
 }
 
-pub fn create_join_operator(
+impl TemporalJoinSide {
+    fn take_nearest(&mut self, key: &Headers, time: f64, tolerance: f64) -> Option<(f64, Headers)> {
+        let bucket: &mut VecDeque<(f64, Headers)> = self.buffered.get_mut(key)?;
+        let (i, _) = bucket
+            .iter()
+            .enumerate()
+            .filter(|(_, (other, _))| (other - time).abs() <= tolerance)
+            .min_by(|(_, (a, _)), (_, (b, _))| (a - time).abs().total_cmp(&(b - time).abs()))?;
+        let matched: Option<(f64, Headers)> = bucket.remove(i);
+        if bucket.is_empty() {
+            self.buffered.remove(key);
+        }
+        matched
+    }
+
+    fn buffer(&mut self, key: Headers, time: f64, vals: Headers) {
+        self.arrivals.push_back((time, key.clone()));
+        self.buffered
+            .entry(key)
+            .or_default()
+            .push_back((time, vals));
+    }
+
+    fn expire(&mut self, cutoff: f64) {
+        while let Some((time, _)) = self.arrivals.front()
+            && *time < cutoff
+        {
+            let (_, key) = self.arrivals.pop_front().unwrap();
+            if let Some(bucket) = self.buffered.get_mut(&key) {
+                bucket.retain(|(time, _)| *time >= cutoff);
+                if bucket.is_empty() {
+                    self.buffered.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+pub fn create_temporal_join_operator(
+    tolerance: f64,
+    left_extractor: KeyExtractor,
+    right_extractor: KeyExtractor,
+    next_op: OperatorRef,
+) -> (OperatorRef, OperatorRef) {
+    create_temporal_join_operator_with_clock(
+        tolerance,
+        elapsed_clock(),
+        left_extractor,
+        right_extractor,
+        next_op,
+    )
+}
+
+pub fn create_temporal_join_operator_with_clock(
+    tolerance: f64,
+    clock: ClockRef,
+    left_extractor: KeyExtractor,
+    right_extractor: KeyExtractor,
+    next_op: OperatorRef,
+) -> (OperatorRef, OperatorRef) {
+    let sides: Rc<RefCell<[TemporalJoinSide; 2]>> = Rc::new(RefCell::new(Default::default()));
+    let watermark: Rc<Cell<f64>> = Rc::new(Cell::new(f64::NEG_INFINITY));
+    let forwarded: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+    let build_side = |side: usize, mut f: KeyExtractor| -> OperatorRef {
+        let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+        let next_op_ref1 = Rc::clone(&next_op);
+        let next_op_ref2 = Rc::clone(&next_op);
+        let sides_ref1 = Rc::clone(&sides);
+        let sides_ref2 = Rc::clone(&sides);
+        let watermark = Rc::clone(&watermark);
+        let forwarded = Rc::clone(&forwarded);
+        let clock: ClockRef = Rc::clone(&clock);
+
+        let next: Box<dyn FnMut(&mut Headers) + 'static> =
+            Box::new(move |headers: &mut Headers| {
+                let time: f64 = tuple_time(headers, clock.as_ref());
+                let (key, vals) = f(headers.clone());
+                watermark.set(watermark.get().max(time));
+                let mut sides = sides_ref1.borrow_mut();
+                for other in sides.iter_mut() {
+                    other.expire(watermark.get() - tolerance);
+                }
+                match sides[1 - side].take_nearest(&key, time, tolerance) {
+                    Some((other_time, other_vals)) => {
+                        drop(sides);
+                        trace_event!(side, "temporal join match");
+                        let dt: f64 = if side == 0 {
+                            other_time - time
+                        } else {
+                            time - other_time
+                        };
+                        let mut merged: Headers =
+                            union_headers(&union_headers(&key, &vals), &other_vals);
+                        merged.insert(
+                            TEMPORAL_JOIN_DELTA_KEY.to_string(),
+                            OpResult::Float(OrderedFloat(dt)),
+                        );
+                        (next_op_ref1.borrow_mut().next)(&mut merged)
+                    }
+                    None => sides[side].buffer(key, time, vals),
+                }
+            });
+
+        let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+            Box::new(move |headers: &mut Headers| {
+                let mut sides = sides_ref2.borrow_mut();
+                sides[side].resets += 1;
+                let ready: usize = sides[0].resets.min(sides[1].resets);
+                drop(sides);
+                while forwarded.get() < ready {
+                    forwarded.set(forwarded.get() + 1);
+                    (next_op_ref2.borrow_mut().reset)(headers)
+                }
+            });
+
+        Rc::new(RefCell::new(
+            Operator::new(next, reset)
+                .with_label(format!("temporal_join({}s)", tolerance))
+                .with_downstream(downstream),
+        ))
+    };
+    let left_op: OperatorRef = build_side(0, left_extractor);
+    let right_op: OperatorRef = build_side(1, right_extractor);
+    (left_op, right_op)
+}
+
+fn evict_join_entry(
+    h_tbl: &mut dyn StateBackend<Headers>,
+    key: &Headers,
+    on_evict: &mut Option<EvictionFunc>,
+) -> Result<(), StateError> {
+    if let Some(vals) = h_tbl.remove(key)?
+        && let Some(f) = on_evict.as_mut()
+    {
+        f(key, &vals);
+    }
+    Ok(())
+}
+
+pub fn evict_join_entries(
+    h_tbl: &mut dyn StateBackend<Headers>,
+    index: &mut JoinIndex,
+    curr_epoch: i32,
+    max_entries: Option<usize>,
+    ttl: Option<i32>,
+    on_evict: &mut Option<EvictionFunc>,
+) -> Result<(), StateError> {
+    if let Some(ttl) = ttl {
+        while let Some(key) = index.pop_older_than(curr_epoch.saturating_sub(ttl) + 1) {
+            evict_join_entry(h_tbl, &key, on_evict)?;
+        }
+    }
+    if let Some(max_entries) = max_entries {
+        while index.len() >= max_entries {
+            match index.pop_oldest() {
+                Some(key) => evict_join_entry(h_tbl, &key, on_evict)?,
+                None => break,
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn evict_join_over_budget(
+    h_tbl: &mut dyn StateBackend<Headers>,
+    index: &mut JoinIndex,
+    budget: &MemoryBudget,
+    on_evict: &mut Option<EvictionFunc>,
+) -> Result<(), StateError> {
+    if !budget.exceeded() {
+        return Ok(());
+    }
+    if budget.action == BudgetAction::Error {
+        return Err(budget.exceeded_error("join"));
+    }
+    let mut evicted: usize = 0;
+    while budget.used() > budget.low_water() {
+        match index.pop_oldest() {
+            Some(key) => evict_join_entry(h_tbl, &key, on_evict)?,
+            None => break,
+        }
+        evicted += 1;
+    }
+    budget.record_evictions(evicted);
+    Ok(())
+}
+
+pub fn create_bounded_join_operator(
     eid_key: Option<String>,
+    bounds: JoinBounds,
     left_extractor: KeyExtractor,
     right_extractor: KeyExtractor,
     next_op: OperatorRef,
 ) -> (OperatorRef, OperatorRef) {
-    let mut _h_tbl1: Rc<RefCell<HashMap<Headers, Headers>>> = Rc::new(RefCell::new(HashMap::new()));
+    let JoinBounds {
+        max_entries,
+        ttl,
+        on_evict,
+        occupancy,
+        state,
+        budget,
+        dropped,
+    } = bounds;
+    let on_evict: Option<EvictionFunc> = {
+        let mut inner: Option<EvictionFunc> = on_evict;
+        let budget: Option<MemoryBudget> = budget.clone();
+        Some(Box::new(move |key: &Headers, vals: &Headers| {
+            dropped.fetch_add(1, Ordering::SeqCst);
+            if let Some(budget) = &budget {
+                budget.release(approx_headers_bytes(key) + approx_headers_bytes(vals));
+            }
+            if let Some(f) = inner.as_mut() {
+                f(key, vals);
+            }
+        }))
+    };
+    let on_evict: Rc<RefCell<Option<EvictionFunc>>> = Rc::new(RefCell::new(on_evict));
+    let fault: OperatorFault = match &budget {
+        Some(budget) => budget.fault().clone(),
+        None => OperatorFault::new(),
+    };
+    let open_table = |side: &str| -> JoinTable {
+        Rc::new(RefCell::new(match &state {
+            Some(factory) => factory(side),
+            None => memory_backend(),
+        }))
+    };
+
+    let mut _h_tbl1: JoinTable = open_table("left");
     let h_tbl1_ref_1 = Rc::clone(&_h_tbl1);
     let h_tbl1_ref_2 = Rc::clone(&_h_tbl1);
 
-    let mut _h_tbl2: Rc<RefCell<HashMap<Headers, Headers>>> = Rc::new(RefCell::new(HashMap::new()));
+    let mut _h_tbl2: JoinTable = open_table("right");
     let h_tbl2_ref_1 = Rc::clone(&_h_tbl2);
     let h_tbl2_ref_2 = Rc::clone(&_h_tbl2);
 
@@ -413,13 +3317,24 @@ pub fn create_join_operator(
     let mut _eid_key: Rc<RefCell<String>> = Rc::new(RefCell::new(
         eid_key.clone().unwrap_or_else(|| "eid".to_string()),
     ));
+    let open_index = |table: &JoinTable| -> JoinIndexRef {
+        let index: JoinIndex = JoinIndex::of_table(table.borrow().as_ref(), &_eid_key.borrow())
+            .unwrap_or_else(|e: StateError| {
+                fault.record(e);
+                JoinIndex::default()
+            });
+        Rc::new(RefCell::new(index))
+    };
+    let left_index: JoinIndexRef = open_index(&_h_tbl1);
+    let right_index: JoinIndexRef = open_index(&_h_tbl2);
 
     let handle_join_side: Rc<
         RefCell<
             Box<
                 dyn FnMut(
-                        Rc<RefCell<HashMap<Headers, Headers>>>,
-                        Rc<RefCell<HashMap<Headers, Headers>>>,
+                        JoinTable,
+                        JoinIndexRef,
+                        JoinTable,
                         Rc<RefCell<i32>>,
                         Rc<RefCell<i32>>,
                         KeyExtractor,
@@ -429,8 +3344,9 @@ pub fn create_join_operator(
             >,
         >,
     > = Rc::new(RefCell::new(Box::new(
-        move |mut _curr_h_tbl: Rc<RefCell<HashMap<Headers, Headers>>>,
-              mut _other_hash_tbl: Rc<RefCell<HashMap<Headers, Headers>>>,
+        move |mut _curr_h_tbl: JoinTable,
+              curr_index: JoinIndexRef,
+              mut _other_hash_tbl: JoinTable,
               curr_epoch_ref: Rc<RefCell<i32>>,
               other_epoch_ref: Rc<RefCell<i32>>,
               mut f: KeyExtractor,
@@ -442,13 +3358,23 @@ pub fn create_join_operator(
             let other_epoch_ref2 = Rc::clone(&other_epoch_ref);
             let eid_key_ref1 = Rc::clone(&eid_key);
             let eid_key_ref2 = Rc::clone(&eid_key);
+            let curr_h_tbl_ref = Rc::clone(&_curr_h_tbl);
+            let curr_index_ref = Rc::clone(&curr_index);
+            let on_evict_ref1 = Rc::clone(&on_evict);
+            let on_evict_ref2 = Rc::clone(&on_evict);
+            let occupancy: Option<Gauge> = occupancy.clone();
+            let budget: Option<MemoryBudget> = budget.clone();
+            let (next_fault, reset_fault) = (fault.clone(), fault.clone());
+            let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
 
             let next: Box<dyn FnMut(&mut Headers) + 'static> =
                 Box::new(move |mut headers: &mut Headers| {
+                    if next_fault.failed() {
+                        return;
+                    }
                     let mut _headers_cp = &mut headers;
                     let (key, vals) = f(_headers_cp.clone());
-                    let mut _curr_epoch: i32 =
-                        get_mapped_int(eid_key.borrow_mut().clone(), headers);
+                    let mut _curr_epoch: i32 = get_mapped_int(eid_key.borrow().as_str(), headers);
 
                     while _curr_epoch > *curr_epoch_ref.borrow() {
                         if *other_epoch_ref1.borrow() > *curr_epoch_ref.borrow() {
@@ -463,31 +3389,66 @@ pub fn create_join_operator(
 
                     let mut new_headers: Headers = key.clone();
                     new_headers.insert(eid_key_ref1.borrow().clone(), OpResult::Int(_curr_epoch));
-                    match _other_hash_tbl
-                        .borrow_mut()
-                        .iter_mut()
-                        .find(|(key, _)| **key == new_headers)
+                    let matched: Option<Headers> = match _other_hash_tbl.borrow().get(&new_headers)
                     {
-                        Some((_, val)) => (next_op_ref1.borrow_mut().next)(
-                            &mut (union_headers(
-                                &mut union_headers(&mut new_headers, &mut vals.clone()),
-                                val,
-                            )),
-                        ),
+                        Ok(matched) => matched,
+                        Err(e) => return next_fault.record(e),
+                    };
+                    match matched {
+                        Some(val) => {
+                            trace_event!(eid = _curr_epoch, "join match");
+                            (next_op_ref1.borrow_mut().next)(&mut union_headers(
+                                &union_headers(&new_headers, &vals),
+                                &val,
+                            ))
+                        }
                         None => {
-                            _curr_h_tbl
-                                .borrow_mut()
-                                .insert(new_headers, vals.clone())
-                                .unwrap();
+                            trace_event!(eid = _curr_epoch, "join miss, buffering");
+                            let mut curr_h_tbl = _curr_h_tbl.borrow_mut();
+                            let mut index = curr_index.borrow_mut();
+                            let mut buffered = || -> Result<(), StateError> {
+                                evict_join_entries(
+                                    curr_h_tbl.as_mut(),
+                                    &mut index,
+                                    _curr_epoch,
+                                    max_entries,
+                                    ttl,
+                                    &mut on_evict_ref1.borrow_mut(),
+                                )?;
+                                if let Some(budget) = &budget {
+                                    budget.charge(
+                                        approx_headers_bytes(&new_headers)
+                                            + approx_headers_bytes(&vals),
+                                    );
+                                }
+                                curr_h_tbl.insert(new_headers.clone(), vals.clone())?;
+                                index.insert(&new_headers, _curr_epoch);
+                                if let Some(budget) = &budget {
+                                    evict_join_over_budget(
+                                        curr_h_tbl.as_mut(),
+                                        &mut index,
+                                        budget,
+                                        &mut on_evict_ref1.borrow_mut(),
+                                    )?;
+                                }
+                                Ok(())
+                            };
+                            if let Err(e) = buffered() {
+                                next_fault.record(e);
+                            }
                         }
                     }
+                    record_peak(
+                        &occupancy,
+                        _curr_h_tbl.borrow().len() + _other_hash_tbl.borrow().len(),
+                    );
                 });
 
             let reset: Box<dyn FnMut(&mut Headers) + 'static> =
                 Box::new(move |headers: &mut Headers| {
                     let mut _curr_epoch: i32 =
-                        get_mapped_int(eid_key_ref2.borrow().clone(), headers);
-                    while _curr_epoch > curr_epoch_ref1.borrow().clone() {
+                        get_mapped_int(eid_key_ref2.borrow().as_str(), headers);
+                    while _curr_epoch >= curr_epoch_ref1.borrow().clone() {
                         if *other_epoch_ref2.borrow() > *curr_epoch_ref1.borrow() {
                             (next_op_ref2.borrow_mut().reset)(&mut singleton(
                                 eid_key_ref2.borrow().clone(),
@@ -497,39 +3458,551 @@ pub fn create_join_operator(
                         let mut count = curr_epoch_ref1.borrow_mut();
                         *count += 1;
                     }
+                    if let Err(e) = evict_join_entries(
+                        curr_h_tbl_ref.borrow_mut().as_mut(),
+                        &mut curr_index_ref.borrow_mut(),
+                        _curr_epoch,
+                        None,
+                        ttl,
+                        &mut on_evict_ref2.borrow_mut(),
+                    ) {
+                        reset_fault.record(e);
+                    }
                 });
-            Rc::new(RefCell::new(Operator::new(next, reset)))
+            Rc::new(RefCell::new(
+                Operator::new(next, reset)
+                    .with_label("join")
+                    .with_downstream(downstream)
+                    .with_fault(fault.clone()),
+            ))
         },
     )));
-    (
-        (*handle_join_side.borrow_mut())(
-            h_tbl1_ref_1,
-            h_tbl2_ref_1,
-            Rc::clone(&_left_curr_epoch),
-            Rc::clone(&_right_curr_epoch),
-            left_extractor,
-            Rc::clone(&_eid_key),
-        ),
-        (*handle_join_side.borrow_mut())(
-            h_tbl2_ref_2,
-            h_tbl1_ref_2,
-            Rc::clone(&_right_curr_epoch),
-            Rc::clone(&_left_curr_epoch),
-            right_extractor,
-            _eid_key,
-        ),
-    )
+    let left_op: OperatorRef = (*handle_join_side.borrow_mut())(
+        h_tbl1_ref_1,
+        left_index,
+        h_tbl2_ref_1,
+        Rc::clone(&_left_curr_epoch),
+        Rc::clone(&_right_curr_epoch),
+        left_extractor,
+        Rc::clone(&_eid_key),
+    );
+    let right_op: OperatorRef = (*handle_join_side.borrow_mut())(
+        h_tbl2_ref_2,
+        right_index,
+        h_tbl1_ref_2,
+        Rc::clone(&_right_curr_epoch),
+        Rc::clone(&_left_curr_epoch),
+        right_extractor,
+        _eid_key,
+    );
+    (left_op, right_op)
 }
 
 pub fn rename_filtered_keys(
     renaming_pairs: Vec<(String, String)>,
     headers: &mut Headers,
 ) -> Headers {
-    let mut new_headers: BTreeMap<String, OpResult> = BTreeMap::new();
+    let mut new_headers: Headers = Headers::new();
     for (new_key, old_key) in renaming_pairs {
         if let Some(val) = headers.get(&old_key) {
-            new_headers.insert(new_key, val.clone()).unwrap();
+            new_headers.insert(new_key, val.clone());
         }
     }
     new_headers
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::state::{collect_faults, first_fault};
+    use crate::testing::{TestSink, run_trace, tuple};
+
+    fn at(time: f64) -> Headers {
+        tuple(&[("time", OpResult::Float(OrderedFloat(time)))])
+    }
+
+    fn eids(rows: &[Headers]) -> Vec<i32> {
+        rows.iter()
+            .map(|headers: &Headers| get_mapped_int("eid", headers))
+            .collect()
+    }
+
+    #[test]
+    fn epoch_resets_downstream_once_per_boundary() {
+        let sink: TestSink = run_trace(
+            |next_op: OperatorRef| create_epoch_operator(1.0, "eid".to_string(), next_op),
+            vec![at(100.0), at(100.5), at(101.2), at(103.5)],
+        );
+        assert_eq!(eids(&sink.emitted()), vec![0, 0, 1, 3]);
+        assert_eq!(eids(&sink.resets()), vec![0, 1, 2, 3]);
+        assert_eq!(
+            sink.resets(),
+            (0..4)
+                .map(|eid: i32| singleton("eid".to_string(), OpResult::Int(eid)))
+                .collect::<Vec<Headers>>()
+        );
+    }
+
+    #[test]
+    fn epoch_final_reset_carries_last_eid() {
+        let sink: TestSink = run_trace(
+            |next_op: OperatorRef| create_epoch_operator(10.0, "eid".to_string(), next_op),
+            vec![at(5.0), at(30.0)],
+        );
+        sink.assert_epoch_count(3);
+        assert_eq!(eids(&sink.resets()), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn count_epoch_final_reset_closes_only_a_partial_epoch() {
+        let count_epoch =
+            |next_op: OperatorRef| create_count_epoch_operator(2, "eid".to_string(), next_op);
+        let sink: TestSink = run_trace(count_epoch, (0..4).map(|i| at(i as f64)));
+        assert_eq!(eids(&sink.resets()), vec![0, 1]);
+        let sink: TestSink = run_trace(count_epoch, (0..3).map(|i| at(i as f64)));
+        assert_eq!(eids(&sink.resets()), vec![0, 1]);
+        assert_eq!(eids(&sink.emitted()), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn count_epoch_without_input_does_not_reset_downstream() {
+        let sink: TestSink = run_trace(
+            |next_op: OperatorRef| create_count_epoch_operator(2, "eid".to_string(), next_op),
+            Vec::new(),
+        );
+        sink.assert_emitted_count(0);
+        assert_eq!(sink.epochs(), 0);
+    }
+
+    fn keyed(time: f64, key: i32) -> Headers {
+        tuple(&[
+            ("time", OpResult::Float(OrderedFloat(time))),
+            ("k", OpResult::Int(key)),
+        ])
+    }
+
+    fn incremental_query(next_op: OperatorRef) -> OperatorRef {
+        create_epoch_operator(
+            1.0,
+            "eid".to_string(),
+            create_incremental_groupby_operator(
+                Box::new(|mut headers: Headers| filter_groups(&["k"], &mut headers)),
+                Box::new(counter),
+                "n".to_string(),
+                4,
+                next_op,
+            ),
+        )
+    }
+
+    #[test]
+    fn incremental_groupby_spreads_closed_epoch_over_next_tuples() {
+        let sink: TestSink = TestSink::new();
+        let query: OperatorRef = incremental_query(sink.operator());
+        for key in 0..32 {
+            (query.borrow_mut().next)(&mut keyed(100.0, key));
+        }
+        sink.assert_emitted_count(0);
+
+        (query.borrow_mut().next)(&mut keyed(101.5, 0));
+        let first: usize = sink.emitted().len();
+        assert!(first > 0 && first < 32, "emitted {} of 32 groups", first);
+        sink.assert_epoch_count(0);
+
+        for _ in 0..3 {
+            (query.borrow_mut().next)(&mut keyed(101.5, 1));
+        }
+        sink.assert_emitted_count(32).assert_epoch_count(1);
+        assert!(sink.emitted().iter().all(|headers: &Headers| {
+            get_mapped_int("eid", headers) == 0 && get_mapped_int("n", headers) == 1
+        }));
+        assert_eq!(eids(&sink.resets()), vec![0]);
+    }
+
+    #[test]
+    fn incremental_groupby_flushes_last_epoch_at_finish() {
+        let sink: TestSink = run_trace(
+            incremental_query,
+            vec![
+                keyed(100.0, 1),
+                keyed(100.2, 2),
+                keyed(101.1, 1),
+                keyed(101.3, 1),
+            ],
+        );
+        assert_eq!(eids(&sink.resets()), vec![0, 1]);
+        sink.assert_emitted_count(3)
+            .assert_emitted_where(|headers: &Headers| {
+                get_mapped_int("eid", headers) == 1 && get_mapped_int("n", headers) == 2
+            });
+        let emitted: Vec<Headers> = sink.emitted();
+        assert_eq!(eids(&emitted), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn split_gives_each_branch_its_own_headers() {
+        let (left, right): (TestSink, TestSink) = (TestSink::new(), TestSink::new());
+        let split: OperatorRef = create_split_operator(
+            create_map_operator(
+                Box::new(|mut headers: Headers| {
+                    headers.insert("time".to_string(), OpResult::Int(0));
+                    headers
+                }),
+                left.operator(),
+            ),
+            right.operator(),
+        );
+        (split.borrow_mut().next)(&mut at(1.0));
+        left.assert_emitted_where(|headers: &Headers| {
+            headers.get("time") == Some(&OpResult::Int(0))
+        });
+        assert_eq!(right.emitted(), vec![at(1.0)]);
+    }
+
+    #[test]
+    fn route_gives_each_branch_its_own_reset_headers() {
+        let (tagged, other, default): (TestSink, TestSink, TestSink) =
+            (TestSink::new(), TestSink::new(), TestSink::new());
+        let tagged_op: OperatorRef = tagged.operator();
+        let tagging: OperatorRef = Rc::new(RefCell::new(Operator::new(
+            Box::new(|_: &mut Headers| {}),
+            Box::new(move |headers: &mut Headers| {
+                headers.insert("tag".to_string(), OpResult::Int(1));
+                (tagged_op.borrow_mut().reset)(headers)
+            }),
+        )));
+        let route: OperatorRef = create_route_operator(
+            vec![
+                (Box::new(|_: &Headers| false), tagging),
+                (Box::new(|_: &Headers| true), other.operator()),
+            ],
+            default.operator(),
+        );
+        (route.borrow_mut().next)(&mut at(1.0));
+        (route.borrow_mut().reset)(&mut at(2.0));
+        assert_eq!(other.emitted(), vec![at(1.0)]);
+        assert_eq!(tagged.resets()[0].get("tag"), Some(&OpResult::Int(1)));
+        assert_eq!(other.resets(), vec![at(2.0)]);
+        assert_eq!(default.resets(), vec![at(2.0)]);
+    }
+
+    fn join_side(field: &'static str) -> KeyExtractor {
+        Box::new(move |mut headers: Headers| {
+            let key: Headers = filter_groups(&["k"], &mut headers);
+            (key, filter_groups(&[field], &mut headers))
+        })
+    }
+
+    #[test]
+    fn join_closes_each_epoch_once_on_reset() {
+        let sink: TestSink = TestSink::new();
+        let (left, right): (OperatorRef, OperatorRef) =
+            create_join_operator(None, join_side("a"), join_side("b"), sink.operator());
+        let side = |field: &str, eid: i32| {
+            tuple(&[
+                ("eid", OpResult::Int(eid)),
+                ("k", OpResult::Int(1)),
+                (field, OpResult::Int(eid)),
+            ])
+        };
+        (left.borrow_mut().next)(&mut side("a", 0));
+        (right.borrow_mut().next)(&mut side("b", 0));
+        for op in [&left, &right] {
+            (op.borrow_mut().reset)(&mut singleton("eid".to_string(), OpResult::Int(0)));
+        }
+        sink.assert_emitted_count(1);
+        assert_eq!(eids(&sink.resets()), vec![0]);
+
+        (left.borrow_mut().next)(&mut side("a", 1));
+        (right.borrow_mut().next)(&mut side("b", 1));
+        for op in [&left, &right] {
+            (op.borrow_mut().reset)(&mut singleton("eid".to_string(), OpResult::Int(1)));
+        }
+        assert_eq!(eids(&sink.emitted()), vec![0, 1]);
+        assert_eq!(eids(&sink.resets()), vec![0, 1]);
+    }
+
+    fn join_row(field: &str, eid: i32, key: i32) -> Headers {
+        tuple(&[
+            ("eid", OpResult::Int(eid)),
+            ("k", OpResult::Int(key)),
+            (field, OpResult::Int(key)),
+        ])
+    }
+
+    #[test]
+    fn bounded_join_evicts_oldest_unmatched_entries_and_counts_drops() {
+        let sink: TestSink = TestSink::new();
+        let bounds: JoinBounds = JoinBounds {
+            max_entries: Some(2),
+            ..JoinBounds::default()
+        };
+        let dropped: Arc<AtomicUsize> = Arc::clone(&bounds.dropped);
+        let (left, right): (OperatorRef, OperatorRef) = create_bounded_join_operator(
+            None,
+            bounds,
+            join_side("a"),
+            join_side("b"),
+            sink.operator(),
+        );
+        for key in 0..3 {
+            (left.borrow_mut().next)(&mut join_row("a", 0, key));
+        }
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+
+        (right.borrow_mut().next)(&mut join_row("b", 0, 0));
+        (right.borrow_mut().next)(&mut join_row("b", 0, 2));
+        sink.assert_emitted_count(1)
+            .assert_emitted_where(|headers: &Headers| headers.get("k") == Some(&OpResult::Int(2)));
+    }
+
+    #[test]
+    fn bounded_join_expires_entries_after_ttl_epochs() {
+        let sink: TestSink = TestSink::new();
+        let bounds: JoinBounds = JoinBounds {
+            ttl: Some(1),
+            ..JoinBounds::default()
+        };
+        let dropped: Arc<AtomicUsize> = Arc::clone(&bounds.dropped);
+        let (left, _right): (OperatorRef, OperatorRef) = create_bounded_join_operator(
+            None,
+            bounds,
+            join_side("a"),
+            join_side("b"),
+            sink.operator(),
+        );
+        (left.borrow_mut().next)(&mut join_row("a", 0, 0));
+        (left.borrow_mut().next)(&mut join_row("a", 1, 1));
+        (left.borrow_mut().reset)(&mut singleton("eid".to_string(), OpResult::Int(1)));
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn epoch_faults_instead_of_panicking_on_tuples_without_time() {
+        let sink: TestSink = TestSink::new();
+        let op: OperatorRef = create_epoch_operator(1.0, "eid".to_string(), sink.operator());
+        (op.borrow_mut().next)(&mut tuple(&[("x", OpResult::Int(1))]));
+        sink.assert_emitted_count(0);
+        match first_fault(&collect_faults(&op)) {
+            Some(StreamError::Schema(SchemaError::Missing(key))) => assert_eq!(key, "time"),
+            other => panic!("expected a missing time fault, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn anomaly_tag_mode_forwards_normal_tuples_with_their_score() {
+        let count = |n: i32| tuple(&[("host", OpResult::Int(1)), ("n", OpResult::Int(n))]);
+        let options: AnomalyOptions = AnomalyOptions::new(4, 3.0)
+            .with_out_key("z")
+            .with_mode(AnomalyMode::Tag("outlier".to_string()));
+        let sink: TestSink = run_trace(
+            |next_op: OperatorRef| {
+                create_anomaly_operator(
+                    Box::new(|headers: Headers| filter_groups(&["host"], &mut headers.clone())),
+                    "n".to_string(),
+                    options,
+                    next_op,
+                )
+            },
+            vec![count(10), count(12), count(11), count(50)],
+        );
+        let flags: Vec<i32> = sink
+            .emitted()
+            .iter()
+            .map(|headers: &Headers| get_mapped_int("outlier", headers))
+            .collect();
+        assert_eq!(flags, vec![0, 0, 0, 1]);
+        assert_eq!(sink.emitted()[0].get("z"), Some(&OpResult::Empty));
+    }
+
+    #[test]
+    fn delta_forgets_groups_idle_for_longer_than_the_limit() {
+        let bytes = |n: i32| tuple(&[("host", OpResult::Int(1)), ("bytes", OpResult::Int(n))]);
+        let sink: TestSink = TestSink::new();
+        let op: OperatorRef = create_delta_operator(
+            Box::new(|headers: Headers| filter_groups(&["host"], &mut headers.clone())),
+            "bytes".to_string(),
+            "change".to_string(),
+            1,
+            sink.operator(),
+        );
+        (op.borrow_mut().next)(&mut bytes(100));
+        (op.borrow_mut().reset)(&mut Headers::new());
+        (op.borrow_mut().next)(&mut bytes(150));
+        (op.borrow_mut().reset)(&mut Headers::new());
+        (op.borrow_mut().reset)(&mut Headers::new());
+        (op.borrow_mut().reset)(&mut Headers::new());
+        (op.borrow_mut().next)(&mut bytes(400));
+        let changes: Vec<Option<OpResult>> = sink
+            .emitted()
+            .iter()
+            .map(|headers: &Headers| headers.get("change").cloned())
+            .collect();
+        assert_eq!(
+            changes,
+            [0.0, 50.0, 0.0]
+                .map(|change: f64| Some(OpResult::Float(OrderedFloat(change))))
+                .to_vec()
+        );
+    }
+
+    fn host_kind(kind: i32) -> Headers {
+        tuple(&[("host", OpResult::Int(1)), ("kind", OpResult::Int(kind))])
+    }
+
+    fn by_host() -> GroupingFunc {
+        Box::new(|headers: Headers| filter_groups(&["host"], &mut headers.clone()))
+    }
+
+    #[test]
+    fn sequence_takes_the_clock_time_for_tuples_without_one() {
+        let is_kind = |kind: i32| -> FilterFunc {
+            Box::new(move |headers: &Headers| get_mapped_int("kind", headers) == kind)
+        };
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new(100.0));
+        let sink: TestSink = TestSink::new();
+        let op: OperatorRef = create_sequence_operator_with_clock(
+            by_host(),
+            vec![
+                SequenceStep::Expect(is_kind(1)),
+                SequenceStep::Expect(is_kind(2)),
+            ],
+            5.0,
+            Rc::clone(&clock) as ClockRef,
+            sink.operator(),
+        );
+        (op.borrow_mut().next)(&mut host_kind(1));
+        clock.advance(2.0);
+        (op.borrow_mut().next)(&mut host_kind(2));
+        clock.advance(10.0);
+        (op.borrow_mut().next)(&mut host_kind(1));
+        clock.advance(10.0);
+        (op.borrow_mut().next)(&mut host_kind(2));
+        sink.assert_emitted_count(1);
+        let matched: &Headers = &sink.emitted()[0];
+        assert_eq!(
+            matched.get("seq.start"),
+            Some(&OpResult::Float(OrderedFloat(100.0)))
+        );
+        assert_eq!(
+            matched.get("seq.end"),
+            Some(&OpResult::Float(OrderedFloat(102.0)))
+        );
+    }
+
+    #[test]
+    fn periodicity_reports_keys_beaconing_on_the_clock() {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new(0.0));
+        let sink: TestSink = TestSink::new();
+        let op: OperatorRef = create_periodicity_operator_with_clock(
+            by_host(),
+            3,
+            0.1,
+            60.0,
+            Rc::clone(&clock) as ClockRef,
+            sink.operator(),
+        );
+        let beacon = |host: i32| tuple(&[("host", OpResult::Int(host))]);
+        for gap in [10.0, 10.0, 10.0, 10.0] {
+            (op.borrow_mut().next)(&mut beacon(1));
+            (op.borrow_mut().next)(&mut beacon(2));
+            clock.advance(gap);
+        }
+        clock.advance(-5.0);
+        (op.borrow_mut().next)(&mut beacon(2));
+        (op.borrow_mut().reset)(&mut Headers::new());
+        sink.assert_emitted_count(1);
+        let row: &Headers = &sink.emitted()[0];
+        assert_eq!(get_mapped_int("host", row), 1);
+        assert_eq!(get_mapped_int("gaps", row), 3);
+        assert_eq!(
+            row.get("mean_gap"),
+            Some(&OpResult::Float(OrderedFloat(10.0)))
+        );
+        assert_eq!(sink.epochs(), 1);
+    }
+
+    #[test]
+    fn throttle_windows_follow_the_clock() {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new(0.0));
+        let sink: TestSink = TestSink::new();
+        let op: OperatorRef = create_throttle_operator_with_clock(
+            by_host(),
+            2,
+            10.0,
+            Rc::clone(&clock) as ClockRef,
+            sink.operator(),
+        );
+        for _ in 0..4 {
+            (op.borrow_mut().next)(&mut host_kind(1));
+            clock.advance(1.0);
+        }
+        sink.assert_emitted_count(2);
+        clock.set(10.0);
+        (op.borrow_mut().next)(&mut host_kind(1));
+        sink.assert_emitted_count(3);
+        assert_eq!(get_mapped_int("suppressed", &sink.emitted()[2]), 2);
+    }
+
+    fn host_at(host: i32, time: f64) -> Headers {
+        tuple(&[
+            ("host", OpResult::Int(host)),
+            ("time", OpResult::Float(OrderedFloat(time))),
+        ])
+    }
+
+    #[test]
+    fn throttle_caps_each_key_and_reports_what_it_suppressed() {
+        let sink: TestSink = run_trace(
+            |next_op: OperatorRef| create_throttle_operator(by_host(), 1, 10.0, next_op),
+            vec![
+                host_at(1, 0.0),
+                host_at(2, 0.5),
+                host_at(1, 1.0),
+                host_at(1, 2.0),
+                host_at(1, 12.0),
+            ],
+        );
+        let emitted: Vec<(i32, Option<OpResult>)> = sink
+            .emitted()
+            .iter()
+            .map(|headers: &Headers| {
+                (
+                    get_mapped_int("host", headers),
+                    headers.get("suppressed").cloned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            emitted,
+            vec![(1, None), (2, None), (1, Some(OpResult::Int(2)))]
+        );
+        assert_eq!(sink.epochs(), 1);
+    }
+
+    #[test]
+    fn periodicity_flags_only_regular_keys_with_enough_gaps() {
+        let sink: TestSink = run_trace(
+            |next_op: OperatorRef| create_periodicity_operator(by_host(), 3, 0.2, 30.0, next_op),
+            vec![
+                host_at(1, 0.0),
+                host_at(2, 0.0),
+                host_at(3, 0.0),
+                host_at(2, 2.0),
+                host_at(1, 10.0),
+                host_at(3, 10.0),
+                host_at(1, 20.0),
+                host_at(2, 20.0),
+                host_at(2, 25.0),
+                host_at(1, 30.0),
+            ],
+        );
+        sink.assert_emitted_count(1);
+        let row: &Headers = &sink.emitted()[0];
+        assert_eq!(get_mapped_int("host", row), 1);
+        assert_eq!(get_mapped_int("gaps", row), 3);
+        assert_eq!(row.get("gap_cv"), Some(&OpResult::Float(OrderedFloat(0.0))));
+        assert_eq!(sink.epochs(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+#![allow(dead_code)]
+
+use crate::error::{StateError, StreamError};
+use crate::state::StateCodec;
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+
+pub const CAPTURE_MAGIC: &[u8; 6] = b"WTCAP1";
+
+const TAG_NEXT: u8 = 0;
+const TAG_RESET: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CaptureEvent {
+    Next(Headers),
+    Reset(Headers),
+}
+
+impl CaptureEvent {
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let (tag, headers): (u8, &Headers) = match self {
+            CaptureEvent::Next(headers) => (TAG_NEXT, headers),
+            CaptureEvent::Reset(headers) => (TAG_RESET, headers),
+        };
+        out.push(tag);
+        headers.encode(out);
+    }
+
+    pub fn apply(&self, next_op: &OperatorRef) {
+        match self {
+            CaptureEvent::Next(headers) => (next_op.borrow_mut().next)(&mut headers.clone()),
+            CaptureEvent::Reset(headers) => (next_op.borrow_mut().reset)(&mut headers.clone()),
+        }
+    }
+}
+
+fn write_event(outc: &mut dyn Write, event: &CaptureEvent) {
+    let mut buf: Vec<u8> = Vec::new();
+    event.encode(&mut buf);
+    outc.write_all(&buf).unwrap();
+}
+
+pub fn create_tee_operator(mut outc: Box<dyn Write>, next_op: OperatorRef) -> OperatorRef {
+    outc.write_all(CAPTURE_MAGIC).unwrap();
+    let outc: Rc<RefCell<Box<dyn Write>>> = Rc::new(RefCell::new(outc));
+    let outc_ref = Rc::clone(&outc);
+    let next_op_ref = Rc::clone(&next_op);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        write_event(
+            outc.borrow_mut().as_mut(),
+            &CaptureEvent::Next(headers.clone()),
+        );
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut outc = outc_ref.borrow_mut();
+        write_event(outc.as_mut(), &CaptureEvent::Reset(headers.clone()));
+        outc.flush().unwrap();
+        (next_op_ref.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("tee")
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn tee_to_file(path: &str, next_op: OperatorRef) -> Result<OperatorRef, StreamError> {
+    let outc: Box<dyn Write> = Box::new(BufWriter::new(File::create(path)?));
+    Ok(create_tee_operator(outc, next_op))
+}
+
+pub fn decode_capture(bytes: &[u8]) -> Result<Vec<CaptureEvent>, StreamError> {
+    let mut input: &[u8] = bytes
+        .strip_prefix(CAPTURE_MAGIC.as_slice())
+        .ok_or_else(|| StateError::Codec("missing capture header".to_string()))?;
+    let mut events: Vec<CaptureEvent> = Vec::new();
+    while let Some((tag, rest)) = input.split_first() {
+        input = rest;
+        let headers: Headers = Headers::decode(&mut input)?;
+        events.push(match *tag {
+            TAG_NEXT => CaptureEvent::Next(headers),
+            TAG_RESET => CaptureEvent::Reset(headers),
+            _ => return Err(StateError::Codec(format!("unknown capture tag {}", tag)).into()),
+        });
+    }
+    Ok(events)
+}
+
+pub fn load_capture(path: &str) -> Result<Vec<CaptureEvent>, StreamError> {
+    decode_capture(&fs::read(path)?)
+}
+
+pub fn replay_capture(events: &[CaptureEvent], next_op: &OperatorRef) {
+    for event in events {
+        event.apply(next_op);
+    }
+}
@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+
+use crate::error::{InputKind, SchemaError, StreamError};
+use crate::params::QueryParams;
+use crate::plan::fan_out;
+use crate::schema::Schema;
+use crate::utils::OperatorRef;
+use std::collections::BTreeMap;
+
+pub type QueryConstructor = fn(&QueryParams, OperatorRef) -> Vec<OperatorRef>;
+
+pub struct QueryEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub inputs: Vec<&'static str>,
+    pub defaults: QueryParams,
+    pub constructor: QueryConstructor,
+}
+
+impl QueryEntry {
+    pub fn new(
+        name: &'static str,
+        description: &'static str,
+        inputs: &[&'static str],
+        constructor: QueryConstructor,
+    ) -> Self {
+        QueryEntry {
+            name,
+            description,
+            inputs: inputs.to_vec(),
+            defaults: QueryParams::default(),
+            constructor,
+        }
+    }
+
+    pub fn with_defaults(mut self, defaults: QueryParams) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    pub fn check_inputs(&self, schema: &Schema) -> Result<(), StreamError> {
+        match self
+            .inputs
+            .iter()
+            .find(|field: &&&str| schema.field_type(field).is_none())
+        {
+            Some(field) => {
+                Err(SchemaError::NotProduced(field.to_string(), self.name.to_string()).into())
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn instantiate(&self, params: Option<&QueryParams>, next_op: OperatorRef) -> OperatorRef {
+        fan_out((self.constructor)(
+            params.unwrap_or(&self.defaults),
+            next_op,
+        ))
+    }
+}
+
+#[derive(Default)]
+pub struct QueryCatalog {
+    entries: BTreeMap<&'static str, QueryEntry>,
+}
+
+impl QueryCatalog {
+    pub fn new() -> Self {
+        QueryCatalog::default()
+    }
+
+    pub fn register(mut self, entry: QueryEntry) -> Self {
+        self.entries.insert(entry.name, entry);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Result<&QueryEntry, StreamError> {
+        self.entries
+            .get(name)
+            .ok_or_else(|| StreamError::unknown(InputKind::Config, "catalog query", name))
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.entries.keys().copied().collect()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &QueryEntry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn instantiate(
+        &self,
+        name: &str,
+        params: Option<&QueryParams>,
+        next_op: OperatorRef,
+    ) -> Result<OperatorRef, StreamError> {
+        let entry: &QueryEntry = self.get(name)?;
+        entry.check_inputs(&Schema::decoded())?;
+        Ok(entry.instantiate(params, next_op))
+    }
+}
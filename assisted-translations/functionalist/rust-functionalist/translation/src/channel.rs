@@ -0,0 +1,235 @@
+#![allow(dead_code)]
+
+use crate::error::StreamError;
+use crate::state::OperatorFault;
+use crate::tenant::panic_message;
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    Block,
+    DropOldest,
+    Sample(usize),
+}
+
+pub enum ChannelMsg {
+    Next(Headers),
+    Reset(Headers),
+    Close,
+}
+
+#[derive(Default)]
+pub struct ChannelStats {
+    pub dropped: AtomicUsize,
+    pub delivered: AtomicUsize,
+}
+
+struct ChannelState {
+    queue: VecDeque<ChannelMsg>,
+    closed: bool,
+    failure: Option<String>,
+    overflow_count: usize,
+}
+
+pub struct BoundedChannel {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<ChannelState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    pub stats: ChannelStats,
+}
+
+impl BoundedChannel {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        BoundedChannel {
+            capacity: capacity.max(1),
+            policy,
+            state: Mutex::new(ChannelState {
+                queue: VecDeque::new(),
+                closed: false,
+                failure: None,
+                overflow_count: 0,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            stats: ChannelStats::default(),
+        }
+    }
+
+    fn drop_oldest_tuple(&self, state: &mut ChannelState) -> bool {
+        match state
+            .queue
+            .iter()
+            .position(|msg| matches!(msg, ChannelMsg::Next(_)))
+        {
+            Some(idx) => {
+                state.queue.remove(idx);
+                self.stats.dropped.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn send(&self, msg: ChannelMsg) -> Result<(), StreamError> {
+        let mut state = self.state.lock().unwrap();
+        if let ChannelMsg::Next(_) = msg {
+            match self.policy {
+                OverflowPolicy::Block => {
+                    while state.queue.len() >= self.capacity && !state.closed {
+                        state = self.not_full.wait(state).unwrap();
+                    }
+                }
+                OverflowPolicy::DropOldest => {
+                    if state.queue.len() >= self.capacity {
+                        self.drop_oldest_tuple(&mut state);
+                    }
+                }
+                OverflowPolicy::Sample(n) => {
+                    if state.queue.len() >= self.capacity {
+                        state.overflow_count += 1;
+                        if !state.overflow_count.is_multiple_of(n.max(1))
+                            || !self.drop_oldest_tuple(&mut state)
+                        {
+                            self.stats.dropped.fetch_add(1, Ordering::SeqCst);
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(failure) = &state.failure {
+            return Err(StreamError::WorkerPanicked(failure.clone()));
+        }
+        if !state.closed {
+            state.queue.push_back(msg);
+            self.not_empty.notify_one();
+        }
+        Ok(())
+    }
+
+    pub fn recv(&self) -> ChannelMsg {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(msg) = state.queue.pop_front() {
+                self.not_full.notify_one();
+                return msg;
+            }
+            if state.closed {
+                return ChannelMsg::Close;
+            }
+            state = self.not_empty.wait(state).unwrap();
+        }
+    }
+
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+
+    pub fn fail(&self, failure: String) {
+        self.state.lock().unwrap().failure = Some(failure);
+        self.close();
+    }
+}
+
+pub fn spawn_channel_worker(
+    channel: Arc<BoundedChannel>,
+    build: impl FnOnce() -> OperatorRef + Send + 'static,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let next_op: OperatorRef = build();
+            loop {
+                match channel.recv() {
+                    ChannelMsg::Next(mut headers) => {
+                        (next_op.borrow_mut().next)(&mut headers);
+                        channel.stats.delivered.fetch_add(1, Ordering::SeqCst);
+                    }
+                    ChannelMsg::Reset(mut headers) => (next_op.borrow_mut().reset)(&mut headers),
+                    ChannelMsg::Close => return,
+                }
+            }
+        }));
+        if let Err(payload) = result {
+            channel.fail(panic_message(payload.as_ref()));
+        }
+    })
+}
+
+pub fn create_channel_operator(
+    capacity: usize,
+    policy: OverflowPolicy,
+    build: impl FnOnce() -> OperatorRef + Send + 'static,
+) -> (OperatorRef, Arc<BoundedChannel>, JoinHandle<()>) {
+    let channel: Arc<BoundedChannel> = Arc::new(BoundedChannel::new(capacity, policy));
+    let worker: JoinHandle<()> = spawn_channel_worker(Arc::clone(&channel), build);
+    let next_channel: Arc<BoundedChannel> = Arc::clone(&channel);
+    let reset_channel: Arc<BoundedChannel> = Arc::clone(&channel);
+    let fault: OperatorFault = OperatorFault::new();
+    let (next_fault, reset_fault) = (fault.clone(), fault.clone());
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if let Err(e) = next_channel.send(ChannelMsg::Next(headers.clone())) {
+            next_fault.record(e);
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if let Err(e) = reset_channel.send(ChannelMsg::Reset(headers.clone())) {
+            reset_fault.record(e);
+        }
+    });
+
+    (
+        Rc::new(RefCell::new(
+            Operator::new(next, reset)
+                .with_label("channel")
+                .with_fault(fault),
+        )),
+        channel,
+        worker,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{collect_faults, first_fault};
+    use crate::testing::tuple;
+    use crate::utils::OpResult;
+    use ordered_float::OrderedFloat;
+
+    fn at(time: f64) -> Headers {
+        tuple(&[("time", OpResult::Float(OrderedFloat(time)))])
+    }
+
+    #[test]
+    fn channel_reports_a_panicking_worker_instead_of_blocking() {
+        let (op, channel, worker) = create_channel_operator(1, OverflowPolicy::Block, || {
+            let next: Box<dyn FnMut(&mut Headers) + 'static> =
+                Box::new(|_: &mut Headers| panic!("sink failed"));
+            let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(|_: &mut Headers| ());
+            Rc::new(RefCell::new(Operator::new(next, reset)))
+        });
+        (op.borrow_mut().next)(&mut at(100.0));
+        worker.join().unwrap();
+        for time in [100.5, 101.0, 101.5] {
+            (op.borrow_mut().next)(&mut at(time));
+        }
+        assert_eq!(channel.stats.delivered.load(Ordering::SeqCst), 0);
+        match first_fault(&collect_faults(&op)) {
+            Some(StreamError::WorkerPanicked(msg)) => assert_eq!(msg, "sink failed"),
+            other => panic!("expected a worker panic fault, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+#![allow(dead_code)]
+
+use crate::error::StreamError;
+use crate::json::json_of_headers;
+use crate::schema::FieldType;
+use crate::trace_event;
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::rc::Rc;
+
+pub const CLICKHOUSE_DEFAULT_URL: &str = "http://127.0.0.1:8123";
+pub const CLICKHOUSE_BATCH_ROWS: usize = 10_000;
+pub const CLICKHOUSE_BUFFER_SUFFIX: &str = "_buffer";
+
+#[derive(Clone, Debug)]
+pub struct ClickHouseClient {
+    pub url: String,
+    pub database: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+impl ClickHouseClient {
+    pub fn new(url: &str, database: &str) -> Self {
+        ClickHouseClient {
+            url: url.trim_end_matches('/').to_string(),
+            database: database.to_string(),
+            user: None,
+            password: None,
+        }
+    }
+
+    pub fn execute(&self, sql: &str, body: &str) -> Result<(), StreamError> {
+        let mut request: ureq::Request = ureq::post(&self.url)
+            .query("database", &self.database)
+            .query("query", sql);
+        if let Some(user) = &self.user {
+            request = request.set("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.password {
+            request = request.set("X-ClickHouse-Key", password);
+        }
+        request
+            .send_string(body)
+            .map_err(|e| Error::other(format!("clickhouse: {}", e)))?;
+        Ok(())
+    }
+}
+
+pub fn clickhouse_type(field_type: FieldType) -> &'static str {
+    match field_type {
+        FieldType::Int => "Int32",
+        FieldType::Float => "Float64",
+        FieldType::IPv4 => "IPv4",
+        FieldType::MAC | FieldType::Str | FieldType::Any => "String",
+    }
+}
+
+pub fn quote_ident(name: &str) -> String {
+    format!("`{}`", name.replace('`', "\\`"))
+}
+
+pub fn infer_columns(rows: &[Headers]) -> BTreeMap<String, FieldType> {
+    let mut columns: BTreeMap<String, FieldType> = BTreeMap::new();
+    for row in rows {
+        for (key, val) in row.iter() {
+            if let Some(field_type) = FieldType::of_op_result(val) {
+                columns
+                    .entry(key.clone())
+                    .and_modify(|seen: &mut FieldType| {
+                        if *seen != field_type {
+                            *seen = FieldType::Any
+                        }
+                    })
+                    .or_insert(field_type);
+            }
+        }
+    }
+    columns
+}
+
+pub fn create_table_ddl(table: &str, columns: &BTreeMap<String, FieldType>) -> String {
+    let defs: Vec<String> = columns
+        .iter()
+        .map(|(name, field_type)| {
+            format!(
+                "{} Nullable({})",
+                quote_ident(name),
+                clickhouse_type(*field_type)
+            )
+        })
+        .collect();
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({}) ENGINE = MergeTree ORDER BY tuple()",
+        quote_ident(table),
+        defs.join(", ")
+    )
+}
+
+pub fn create_buffer_ddl(database: &str, table: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} AS {} ENGINE = Buffer({}, {}, 16, 10, 100, 10000, 1000000, 10000000, 100000000)",
+        quote_ident(&format!("{}{}", table, CLICKHOUSE_BUFFER_SUFFIX)),
+        quote_ident(table),
+        quote_ident(database),
+        quote_ident(table)
+    )
+}
+
+pub fn add_column_ddl(table: &str, name: &str, field_type: FieldType) -> String {
+    format!(
+        "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} Nullable({})",
+        quote_ident(table),
+        quote_ident(name),
+        clickhouse_type(field_type)
+    )
+}
+
+pub struct ClickHouseSink {
+    pub client: ClickHouseClient,
+    pub table: String,
+    pub buffer: bool,
+    pub batch_rows: usize,
+    columns: Option<BTreeMap<String, FieldType>>,
+    rows: Vec<Headers>,
+}
+
+impl ClickHouseSink {
+    pub fn new(client: ClickHouseClient, table: &str, buffer: bool) -> Self {
+        ClickHouseSink {
+            client,
+            table: table.to_string(),
+            buffer,
+            batch_rows: CLICKHOUSE_BATCH_ROWS,
+            columns: None,
+            rows: Vec::new(),
+        }
+    }
+
+    fn insert_table(&self) -> String {
+        if self.buffer {
+            format!("{}{}", self.table, CLICKHOUSE_BUFFER_SUFFIX)
+        } else {
+            self.table.clone()
+        }
+    }
+
+    fn sync_schema(&mut self) -> Result<(), StreamError> {
+        let inferred: BTreeMap<String, FieldType> = infer_columns(&self.rows);
+        match &mut self.columns {
+            None => {
+                self.client
+                    .execute(&create_table_ddl(&self.table, &inferred), "")?;
+                if self.buffer {
+                    self.client
+                        .execute(&create_buffer_ddl(&self.client.database, &self.table), "")?;
+                }
+                self.columns = Some(inferred);
+            }
+            Some(columns) => {
+                for (name, field_type) in inferred {
+                    if columns.contains_key(&name) {
+                        continue;
+                    }
+                    self.client
+                        .execute(&add_column_ddl(&self.table, &name, field_type), "")?;
+                    if self.buffer {
+                        let buffer_table: String =
+                            format!("{}{}", self.table, CLICKHOUSE_BUFFER_SUFFIX);
+                        self.client
+                            .execute(&add_column_ddl(&buffer_table, &name, field_type), "")?;
+                    }
+                    columns.insert(name, field_type);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn push(&mut self, headers: &Headers) {
+        self.rows.push(headers.clone());
+        if self.rows.len() >= self.batch_rows {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let result: Result<(), StreamError> = self.sync_schema().and_then(|()| {
+            let body: String = self
+                .rows
+                .iter()
+                .map(|row: &Headers| json_of_headers(row).to_string() + "\n")
+                .collect();
+            self.client.execute(
+                &format!(
+                    "INSERT INTO {} FORMAT JSONEachRow",
+                    quote_ident(&self.insert_table())
+                ),
+                &body,
+            )
+        });
+        if let Err(_e) = result {
+            trace_event!(
+                rows = self.rows.len(),
+                error = %_e,
+                "clickhouse: dropped an insert batch"
+            );
+        }
+        self.rows.clear();
+    }
+}
+
+pub fn dump_clickhouse(sink: ClickHouseSink) -> OperatorRef {
+    let label: String = format!("clickhouse({})", sink.table);
+    let sink: Rc<RefCell<ClickHouseSink>> = Rc::new(RefCell::new(sink));
+    let sink_ref_clone = Rc::clone(&sink);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| sink.borrow_mut().push(headers));
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |_headers: &mut Headers| sink_ref_clone.borrow_mut().flush());
+
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label(label)))
+}
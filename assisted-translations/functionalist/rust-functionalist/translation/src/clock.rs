@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+
+use crate::keys::{HeaderKey, WellKnownKey};
+use crate::utils::{Headers, OpResult, Operator, OperatorRef};
+use ordered_float::OrderedFloat;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub trait Clock {
+    fn now(&self) -> f64;
+}
+
+pub type ClockRef = Rc<dyn Clock>;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d: Duration| d.as_secs_f64())
+    }
+}
+
+impl Clock for Instant {
+    fn now(&self) -> f64 {
+        self.elapsed().as_secs_f64()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Cell<f64>,
+}
+
+impl ManualClock {
+    pub fn new(start: f64) -> Self {
+        ManualClock {
+            now: Cell::new(start),
+        }
+    }
+
+    pub fn set(&self, time: f64) {
+        self.now.set(time);
+    }
+
+    pub fn advance(&self, secs: f64) -> f64 {
+        self.now.set(self.now.get() + secs);
+        self.now.get()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.now.get()
+    }
+}
+
+pub fn system_clock() -> ClockRef {
+    Rc::new(SystemClock)
+}
+
+pub fn elapsed_clock() -> ClockRef {
+    Rc::new(Instant::now())
+}
+
+pub fn create_timestamp_operator(clock: ClockRef, next_op: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if !matches!(WellKnownKey::Time.lookup(headers), Some(OpResult::Float(_))) {
+            headers.insert(
+                WellKnownKey::Time.into(),
+                OpResult::Float(OrderedFloat(clock.now())),
+            );
+        }
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("timestamp")
+            .with_downstream(downstream),
+    ))
+}
@@ -0,0 +1,847 @@
+#![allow(dead_code)]
+
+use serde::Deserialize;
+
+use crate::audit::{AuditLog, audit_operators};
+use crate::bpf::{BpfInsn, KernelFilter};
+use crate::budget::{BudgetAction, MemoryBudget, parse_bytes};
+use crate::builtins::{
+    CsvOptions, alert_console, create_dump_operator, create_ordered_operator,
+    dump_as_csv_with_options, dump_table,
+};
+use crate::capture::{CaptureEvent, load_capture};
+use crate::catalog::QueryCatalog;
+use crate::clickhouse::{
+    CLICKHOUSE_BATCH_ROWS, CLICKHOUSE_DEFAULT_URL, ClickHouseClient, ClickHouseSink,
+    dump_clickhouse,
+};
+use crate::dsl::{Predicate, Token, leading_filters_of_tokens, parse_tokens_with_budget, tokenize};
+use crate::email::{
+    DEFAULT_DIGEST_SUBJECT, EmailDigest, SMTP_DEFAULT_SERVER, SmtpConfig, alert_email,
+};
+use crate::error::{InputKind, StreamError};
+use crate::eve::load_eve;
+use crate::field_map::{FieldMapper, create_field_mapper_operator};
+use crate::json::load_json_lines;
+use crate::live::{BackendKind, CaptureOptions, LiveSource, open_backend, stop_live_capture};
+use crate::mqtt::{MQTT_DEFAULT_BROKER, MqttClient, MqttOptions, dump_mqtt};
+use crate::otel::{OTLP_DEFAULT_ENDPOINT, OtlpExporter, OtlpOptions, create_otlp_operator};
+use crate::packet::DecodeOptions;
+use crate::params::QueryParams;
+use crate::pcap::load_pcap;
+use crate::plan::{PlanBuilder, PlanStage, check_stages, fan_out};
+use crate::reassembly::DEFAULT_FRAGMENT_TIMEOUT;
+use crate::redis::{REDIS_DEFAULT_ADDR, RedisPool, dump_redis};
+use crate::replay::Pacer;
+use crate::schema::Schema;
+use crate::state::{OperatorFault, collect_faults, first_fault};
+use crate::stats::{PipelineStats, StatsRef};
+use crate::tenant::{Tenant, TenantQuota, create_tenant_operator};
+use crate::trace_event;
+use crate::traffic_gen::{Scenario, generate, synthetic_headers};
+use crate::utils::{EpochState, Headers, OpResult, OperatorRef, collect_epochs, finish_operators};
+#[cfg(target_os = "linux")]
+use crate::xdp::{XdpOptions, XdpSource};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Error, Write, stdout};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceConfig {
+    Synthetic {
+        count: i32,
+    },
+    Generator {
+        #[serde(default)]
+        seed: u64,
+        duration: f64,
+        scenarios: Vec<Scenario>,
+    },
+    Pcap {
+        path: String,
+        #[serde(default)]
+        decapsulate: bool,
+        #[serde(default)]
+        reassemble: bool,
+        fragment_timeout: Option<f64>,
+    },
+    Eve {
+        path: String,
+        #[serde(default)]
+        event_types: Vec<String>,
+    },
+    Json {
+        path: String,
+    },
+    Capture {
+        path: String,
+    },
+    Live {
+        #[serde(default)]
+        interface: String,
+        backend: Option<String>,
+        #[serde(default)]
+        promiscuous: bool,
+        #[serde(default)]
+        decapsulate: bool,
+        count: Option<usize>,
+        queues: Option<Vec<u32>>,
+        #[serde(default)]
+        zero_copy: bool,
+        #[serde(default)]
+        native: bool,
+        kernel_filter: Option<bool>,
+    },
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SinkConfig {
+    #[default]
+    Stdout,
+    Dump {
+        #[serde(default)]
+        show_reset: bool,
+        path: Option<String>,
+    },
+    Csv {
+        path: Option<String>,
+        header: Option<bool>,
+        columns: Option<Vec<String>>,
+        delimiter: Option<char>,
+        #[serde(default)]
+        trailing_delimiter: bool,
+        null: Option<String>,
+    },
+    Table {
+        path: Option<String>,
+        max_rows: Option<usize>,
+    },
+    Alert {
+        key: String,
+        threshold: f64,
+        #[serde(default)]
+        quiet: bool,
+    },
+    Otlp {
+        endpoint: Option<String>,
+        service_name: Option<String>,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        key: Option<String>,
+        #[serde(default)]
+        threshold: f64,
+        #[serde(default)]
+        metrics: Vec<String>,
+        logs: Option<bool>,
+    },
+    Redis {
+        addr: Option<String>,
+        key: String,
+        ttl: Option<u64>,
+    },
+    Clickhouse {
+        url: Option<String>,
+        database: Option<String>,
+        table: String,
+        user: Option<String>,
+        password: Option<String>,
+        #[serde(default)]
+        buffer: bool,
+        batch_rows: Option<usize>,
+    },
+    Mqtt {
+        broker: Option<String>,
+        topic: String,
+        client_id: Option<String>,
+        username: Option<String>,
+        password: Option<String>,
+        #[serde(default)]
+        qos: u8,
+        #[serde(default)]
+        retain: bool,
+    },
+    Email {
+        server: Option<String>,
+        from: String,
+        to: Vec<String>,
+        username: Option<String>,
+        password: Option<String>,
+        key: String,
+        threshold: f64,
+        digest_secs: f64,
+        subject: Option<String>,
+        line: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct QueryConfig {
+    pub name: String,
+    #[serde(default)]
+    pub query: String,
+    pub catalog: Option<String>,
+    #[serde(default)]
+    pub params: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    pub sink: SinkConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TenantConfig {
+    pub name: String,
+    pub max_tuples_per_sec: Option<f64>,
+    pub max_state_bytes: Option<String>,
+    #[serde(default)]
+    pub state_action: BudgetAction,
+    #[serde(default)]
+    pub queries: Vec<QueryConfig>,
+}
+
+impl TenantConfig {
+    pub fn quota(&self) -> Result<TenantQuota, StreamError> {
+        Ok(TenantQuota {
+            max_tuples_per_sec: self.max_tuples_per_sec,
+            max_state_bytes: self
+                .max_state_bytes
+                .as_deref()
+                .map(parse_bytes)
+                .transpose()?,
+            state_action: self.state_action,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PipelineConfig {
+    pub source: SourceConfig,
+    #[serde(default)]
+    pub params: QueryParams,
+    #[serde(default)]
+    pub queries: Vec<QueryConfig>,
+    #[serde(default)]
+    pub tenants: Vec<TenantConfig>,
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+    #[serde(default)]
+    pub replay_speed: Option<f64>,
+    #[serde(default)]
+    pub deterministic: bool,
+    #[serde(default)]
+    pub audit: Option<String>,
+}
+
+impl SourceConfig {
+    pub fn headers(&self) -> Result<Box<dyn Iterator<Item = Headers>>, StreamError> {
+        self.headers_with_filter(&KernelFilter::Accept)
+    }
+
+    pub fn headers_with_filter(
+        &self,
+        filter: &KernelFilter,
+    ) -> Result<Box<dyn Iterator<Item = Headers>>, StreamError> {
+        Ok(match self {
+            SourceConfig::Synthetic { count } => Box::new((0..*count).map(synthetic_headers)),
+            SourceConfig::Generator {
+                seed,
+                duration,
+                scenarios,
+            } => Box::new(generate(*seed, *duration, scenarios).into_iter()),
+            SourceConfig::Pcap {
+                path,
+                decapsulate,
+                reassemble,
+                fragment_timeout,
+            } => {
+                let options: DecodeOptions = DecodeOptions {
+                    decapsulate: *decapsulate,
+                    fragment_timeout: match reassemble {
+                        true => Some(fragment_timeout.unwrap_or(DEFAULT_FRAGMENT_TIMEOUT)),
+                        false => None,
+                    },
+                };
+                Box::new(load_pcap(path, &options)?.into_iter())
+            }
+            SourceConfig::Eve { path, event_types } => {
+                Box::new(load_eve(path, event_types)?.into_iter())
+            }
+            SourceConfig::Json { path } => Box::new(load_json_lines(path)?.into_iter()),
+            SourceConfig::Capture { path } => Box::new(load_capture(path)?.into_iter().filter_map(
+                |event: CaptureEvent| match event {
+                    CaptureEvent::Next(headers) => Some(headers),
+                    CaptureEvent::Reset(_) => None,
+                },
+            )),
+            SourceConfig::Live {
+                interface,
+                backend,
+                promiscuous,
+                decapsulate,
+                count,
+                queues,
+                zero_copy,
+                native,
+                kernel_filter,
+            } => {
+                let kind: BackendKind = backend.as_deref().unwrap_or("auto").parse()?;
+                let options: DecodeOptions = DecodeOptions {
+                    decapsulate: *decapsulate,
+                    fragment_timeout: None,
+                };
+                match kind {
+                    #[cfg(target_os = "linux")]
+                    BackendKind::Xdp => {
+                        let mut xdp: XdpOptions = XdpOptions::new(interface, options);
+                        if let Some(queues) = queues {
+                            xdp.queues = queues.clone();
+                        }
+                        xdp.zero_copy = *zero_copy;
+                        xdp.native = *native;
+                        if !*decapsulate && kernel_filter.unwrap_or(true) {
+                            xdp.filter = filter.clone();
+                        }
+                        let mut source: XdpSource = XdpSource::open(&xdp)?;
+                        source.limit = *count;
+                        Box::new(source)
+                    }
+                    kind => {
+                        let program: Option<Vec<BpfInsn>> = match filter {
+                            KernelFilter::Accept => None,
+                            _ if *decapsulate || !kernel_filter.unwrap_or(true) => None,
+                            filter => filter
+                                .compile()
+                                .inspect_err(|_e| {
+                                    trace_event!(error = %_e, "kernel filter disabled");
+                                })
+                                .ok(),
+                        };
+                        let capture: CaptureOptions = CaptureOptions {
+                            interface: interface.clone(),
+                            promiscuous: *promiscuous,
+                            filter: program,
+                        };
+                        let mut source: LiveSource =
+                            LiveSource::new(open_backend(kind, &capture)?, options);
+                        source.limit = *count;
+                        Box::new(source)
+                    }
+                }
+            }
+        })
+    }
+
+    pub fn schema(&self) -> Schema {
+        match self {
+            SourceConfig::Eve { .. } => Schema::eve(),
+            _ => Schema::decoded(),
+        }
+    }
+}
+
+impl PipelineConfig {
+    pub fn from_toml(src: &str) -> Result<Self, StreamError> {
+        toml::from_str(src).map_err(|e| StreamError::syntax(InputKind::Config, "TOML config", e))
+    }
+
+    pub fn from_yaml(src: &str) -> Result<Self, StreamError> {
+        serde_yaml::from_str(src)
+            .map_err(|e| StreamError::syntax(InputKind::Config, "YAML config", e))
+    }
+
+    pub fn field_mapper(&self) -> Result<FieldMapper, StreamError> {
+        let mapper: FieldMapper = self.rename.clone().into_iter().collect();
+        mapper.validate()?;
+        Ok(mapper)
+    }
+
+    pub fn schema(&self) -> Schema {
+        match self.field_mapper() {
+            Ok(mapper) => mapper.map_schema(&self.source.schema()),
+            Err(_) => self.source.schema(),
+        }
+    }
+
+    pub fn set_quiet(&mut self) {
+        let tenant_queries = self
+            .tenants
+            .iter_mut()
+            .flat_map(|tenant: &mut TenantConfig| tenant.queries.iter_mut());
+        for query in self.queries.iter_mut().chain(tenant_queries) {
+            if let SinkConfig::Alert { quiet, .. } = &mut query.sink {
+                *quiet = true;
+            }
+        }
+    }
+
+    pub fn set_param(&mut self, assignment: &str) -> Result<(), StreamError> {
+        self.params.set_assignment(assignment)
+    }
+
+    pub fn from_path(path: &str) -> Result<Self, StreamError> {
+        let src: String = fs::read_to_string(path)?;
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => PipelineConfig::from_toml(&src),
+            Some("yaml") | Some("yml") => PipelineConfig::from_yaml(&src),
+            _ => Err(StreamError::unexpected(
+                InputKind::Config,
+                "a .toml, .yaml or .yml config",
+                format!("'{}'", path),
+            )),
+        }
+    }
+}
+
+pub fn output_of_path(path: &Option<String>) -> Result<Box<dyn Write>, StreamError> {
+    Ok(match path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(stdout()),
+    })
+}
+
+pub fn create_sink(sink: &SinkConfig) -> Result<OperatorRef, StreamError> {
+    Ok(match sink {
+        SinkConfig::Stdout => create_dump_operator(false, Box::new(stdout())),
+        SinkConfig::Dump { show_reset, path } => {
+            create_dump_operator(*show_reset, output_of_path(path)?)
+        }
+        SinkConfig::Csv {
+            path,
+            header,
+            columns,
+            delimiter,
+            trailing_delimiter,
+            null,
+        } => {
+            let options: CsvOptions = CsvOptions {
+                delimiter: delimiter.unwrap_or(','),
+                trailing_delimiter: *trailing_delimiter,
+                null: null.clone().unwrap_or_default(),
+                header: header.unwrap_or(true),
+            };
+            Rc::new(RefCell::new(dump_as_csv_with_options(
+                None,
+                columns.clone(),
+                options,
+                output_of_path(path)?,
+            )))
+        }
+        SinkConfig::Table { path, max_rows } => {
+            dump_table(output_of_path(path)?, max_rows.unwrap_or(20))
+        }
+        SinkConfig::Alert {
+            key,
+            threshold,
+            quiet,
+        } => alert_console(key.clone(), *threshold, *quiet, Box::new(stdout())),
+        SinkConfig::Otlp {
+            endpoint,
+            service_name,
+            headers,
+            key,
+            threshold,
+            metrics,
+            logs,
+        } => {
+            let mut exporter: OtlpExporter = OtlpExporter::new(
+                endpoint.as_deref().unwrap_or(OTLP_DEFAULT_ENDPOINT),
+                service_name.as_deref().unwrap_or("sonata"),
+            );
+            exporter.headers = headers.clone();
+            let options: OtlpOptions = OtlpOptions {
+                severity_key: key.clone(),
+                threshold: *threshold,
+                metrics: metrics.clone(),
+                logs: logs.unwrap_or(true),
+            };
+            create_otlp_operator(exporter, options)
+        }
+        SinkConfig::Redis { addr, key, ttl } => dump_redis(
+            RedisPool::new(addr.as_deref().unwrap_or(REDIS_DEFAULT_ADDR)),
+            key.clone(),
+            *ttl,
+        ),
+        SinkConfig::Clickhouse {
+            url,
+            database,
+            table,
+            user,
+            password,
+            buffer,
+            batch_rows,
+        } => {
+            let mut client: ClickHouseClient = ClickHouseClient::new(
+                url.as_deref().unwrap_or(CLICKHOUSE_DEFAULT_URL),
+                database.as_deref().unwrap_or("default"),
+            );
+            client.user = user.clone();
+            client.password = password.clone();
+            let mut sink: ClickHouseSink = ClickHouseSink::new(client, table, *buffer);
+            sink.batch_rows = batch_rows.unwrap_or(CLICKHOUSE_BATCH_ROWS);
+            dump_clickhouse(sink)
+        }
+        SinkConfig::Mqtt {
+            broker,
+            topic,
+            client_id,
+            username,
+            password,
+            qos,
+            retain,
+        } => {
+            let options: MqttOptions = MqttOptions {
+                broker: broker.clone().unwrap_or(MQTT_DEFAULT_BROKER.to_string()),
+                client_id: client_id.clone().unwrap_or("sonata".to_string()),
+                username: username.clone(),
+                password: password.clone(),
+                qos: *qos,
+                retain: *retain,
+            };
+            dump_mqtt(MqttClient::new(options)?, topic.clone())
+        }
+        SinkConfig::Email {
+            server,
+            from,
+            to,
+            username,
+            password,
+            key,
+            threshold,
+            digest_secs,
+            subject,
+            line,
+        } => {
+            let smtp: SmtpConfig = SmtpConfig {
+                server: server.clone().unwrap_or(SMTP_DEFAULT_SERVER.to_string()),
+                hello: "localhost".to_string(),
+                from: from.clone(),
+                to: to.clone(),
+                username: username.clone(),
+                password: password.clone(),
+            };
+            let mut digest: EmailDigest =
+                EmailDigest::new(smtp, key, *threshold, Duration::from_secs_f64(*digest_secs));
+            digest.subject = subject
+                .clone()
+                .unwrap_or(DEFAULT_DIGEST_SUBJECT.to_string());
+            digest.line = line.clone();
+            alert_email(digest)
+        }
+    })
+}
+
+pub fn create_pipeline_sink(
+    sink: &SinkConfig,
+    deterministic: bool,
+) -> Result<OperatorRef, StreamError> {
+    let sink: OperatorRef = create_sink(sink)?;
+    Ok(if deterministic {
+        create_ordered_operator(sink)
+    } else {
+        sink
+    })
+}
+
+pub fn tokens_of_param(name: &str, val: &toml::Value) -> Result<Vec<Token>, StreamError> {
+    let tokens: Vec<Token> = match val {
+        toml::Value::String(s) => tokenize(s)?,
+        toml::Value::Array(items) => {
+            let mut tokens: Vec<Token> = Vec::new();
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    tokens.push(Token::Comma);
+                }
+                tokens.extend(tokens_of_param(name, item)?);
+            }
+            tokens
+        }
+        toml::Value::Table(_) => {
+            return Err(StreamError::unexpected(
+                InputKind::Param,
+                "a scalar or array",
+                format!("a table for '{}'", name),
+            ));
+        }
+        other => vec![Token::Word(other.to_string())],
+    };
+    match tokens.contains(&Token::Pipe) {
+        true => Err(
+            StreamError::invalid(InputKind::Param, "query fragment", val.to_string())
+                .within("parameter", name),
+        ),
+        false => Ok(tokens),
+    }
+}
+
+pub fn substitute_params(
+    tokens: Vec<Token>,
+    params: &BTreeMap<String, toml::Value>,
+) -> Result<Vec<Token>, StreamError> {
+    let mut substituted: Vec<Token> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let param = match &token {
+            Token::Word(word) => word
+                .strip_prefix("${")
+                .and_then(|rest: &str| rest.strip_suffix('}'))
+                .and_then(|name: &str| params.get_key_value(name)),
+            _ => None,
+        };
+        match param {
+            Some((name, val)) => substituted.extend(tokens_of_param(name, val)?),
+            None => substituted.push(token),
+        }
+    }
+    Ok(substituted)
+}
+
+type SigintHook = Box<dyn Fn() + Send + 'static>;
+
+static SIGINT_HANDLER: OnceLock<Result<(), String>> = OnceLock::new();
+static SIGINT_COUNT: AtomicUsize = AtomicUsize::new(0);
+static SIGINT_NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+static SIGINT_HOOKS: Mutex<Vec<(usize, SigintHook)>> = Mutex::new(Vec::new());
+
+pub struct SigintSubscription {
+    id: usize,
+    seen: usize,
+}
+
+impl SigintSubscription {
+    pub fn interrupted(&self) -> bool {
+        SIGINT_COUNT.load(Ordering::SeqCst) > self.seen
+    }
+}
+
+impl Drop for SigintSubscription {
+    fn drop(&mut self) {
+        if let Ok(mut hooks) = SIGINT_HOOKS.lock() {
+            hooks.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+pub fn subscribe_sigint(
+    on_interrupt: impl Fn() + Send + 'static,
+) -> Result<SigintSubscription, StreamError> {
+    SIGINT_HANDLER
+        .get_or_init(|| {
+            ctrlc::set_handler(|| {
+                SIGINT_COUNT.fetch_add(1, Ordering::SeqCst);
+                if let Ok(hooks) = SIGINT_HOOKS.lock() {
+                    hooks.iter().for_each(|(_, hook)| hook());
+                }
+            })
+            .map_err(|e| e.to_string())
+        })
+        .clone()
+        .map_err(|e| StreamError::Io(Error::other(e)))?;
+    let id: usize = SIGINT_NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut hooks) = SIGINT_HOOKS.lock() {
+        hooks.push((id, Box::new(on_interrupt)));
+    }
+    Ok(SigintSubscription {
+        id,
+        seen: SIGINT_COUNT.load(Ordering::SeqCst),
+    })
+}
+
+fn build_queries(
+    config: &PipelineConfig,
+    queries: &[QueryConfig],
+    catalog: &QueryCatalog,
+    global_params: &BTreeMap<String, toml::Value>,
+    budget: Option<&MemoryBudget>,
+    filters: &mut Vec<Vec<Predicate>>,
+) -> Result<PlanBuilder, StreamError> {
+    let mut plan: PlanBuilder = PlanBuilder::new();
+    for query in queries.iter() {
+        if let Some(name) = &query.catalog {
+            let params: QueryParams = QueryParams::from_map(
+                global_params
+                    .clone()
+                    .into_iter()
+                    .chain(query.params.clone())
+                    .collect(),
+            )
+            .map_err(|e: StreamError| e.within("query", &query.name))?;
+            let op: OperatorRef = catalog
+                .instantiate(
+                    name,
+                    Some(&params),
+                    create_pipeline_sink(&query.sink, config.deterministic)?,
+                )
+                .map_err(|e: StreamError| e.within("query", &query.name))?;
+            plan = plan.add_query(Vec::new(), op);
+            filters.push(Vec::new());
+            continue;
+        }
+        let tokens: Vec<Token> = tokenize(&query.query)
+            .and_then(|tokens: Vec<Token>| substitute_params(tokens, &query.params))
+            .and_then(|tokens: Vec<Token>| substitute_params(tokens, global_params))
+            .map_err(|e: StreamError| e.within("query", &query.name))?;
+        let stages: Vec<PlanStage> = parse_tokens_with_budget(&tokens, budget)
+            .map_err(|e: StreamError| e.within("query", &query.name))?;
+        check_stages(&stages, &config.schema())
+            .map_err(|e| StreamError::from(e).within("query", &query.name))?;
+        filters.push(leading_filters_of_tokens(&tokens)?);
+        plan = plan.add_query(
+            stages,
+            create_pipeline_sink(&query.sink, config.deterministic)?,
+        );
+    }
+    Ok(plan)
+}
+
+pub struct Pipeline {
+    pub source: SourceConfig,
+    pub kernel_filter: KernelFilter,
+    pub query: OperatorRef,
+    pub stats: PipelineStats,
+    pub audit: Option<AuditLog>,
+    pub replay_speed: Option<f64>,
+    pub faults: Vec<OperatorFault>,
+    pub epochs: Vec<EpochState>,
+}
+
+impl Pipeline {
+    pub fn from_config(path: &str) -> Result<Pipeline, StreamError> {
+        Pipeline::from_pipeline_config(PipelineConfig::from_path(path)?)
+    }
+
+    pub fn from_pipeline_config(config: PipelineConfig) -> Result<Pipeline, StreamError> {
+        Pipeline::from_pipeline_config_with_catalog(config, &QueryCatalog::new())
+    }
+
+    pub fn from_pipeline_config_with_catalog(
+        config: PipelineConfig,
+        catalog: &QueryCatalog,
+    ) -> Result<Pipeline, StreamError> {
+        let global_params: BTreeMap<String, toml::Value> = config.params.to_map();
+        let mapper: FieldMapper = config.field_mapper()?;
+        let mut filters: Vec<Vec<Predicate>> = Vec::new();
+        let mut stats: PipelineStats = PipelineStats::new();
+        let plan: PlanBuilder = build_queries(
+            &config,
+            &config.queries,
+            catalog,
+            &global_params,
+            None,
+            &mut filters,
+        )?;
+        let mut roots: Vec<OperatorRef> = vec![plan.optimize().compile_with_stats(&mut stats)];
+        for tenant_config in config.tenants.iter() {
+            let tenant: Tenant = Tenant::new(&tenant_config.name, tenant_config.quota()?);
+            let plan: PlanBuilder = build_queries(
+                &config,
+                &tenant_config.queries,
+                catalog,
+                &global_params,
+                tenant.budget.as_ref(),
+                &mut filters,
+            )
+            .map_err(|e: StreamError| e.within("tenant", &tenant.name))?;
+            let tenant_stats: StatsRef = stats.register(format!("tenant({})", tenant.name), None);
+            let root: OperatorRef = plan.optimize().compile_with_stats(&mut stats);
+            roots.push(create_tenant_operator(&tenant, tenant_stats, root));
+        }
+        let kernel_filter: KernelFilter = match mapper.is_empty() {
+            true => KernelFilter::of_queries(&filters),
+            false => KernelFilter::Accept,
+        };
+        let query: OperatorRef = match mapper.is_empty() {
+            true => fan_out(roots),
+            false => create_field_mapper_operator(mapper, fan_out(roots)),
+        };
+        let audit: Option<AuditLog> = config.audit.as_deref().map(AuditLog::new);
+        if let Some(log) = &audit {
+            audit_operators(&query, log);
+        }
+        let faults: Vec<OperatorFault> = collect_faults(&query);
+        let epochs: Vec<EpochState> = collect_epochs(&query);
+        Ok(Pipeline {
+            source: config.source,
+            kernel_filter,
+            query,
+            stats,
+            audit,
+            replay_speed: config.replay_speed,
+            faults,
+            epochs,
+        })
+    }
+
+    pub fn run(&mut self) -> Result<(), StreamError> {
+        let sigint: SigintSubscription = subscribe_sigint(stop_live_capture)?;
+        let mut pacer: Pacer = Pacer::new(self.replay_speed.unwrap_or(0.0));
+        for mut headers in self.source.headers_with_filter(&self.kernel_filter)? {
+            if sigint.interrupted() {
+                break;
+            }
+            pacer.pace(&headers);
+            self.push(&mut headers)?;
+        }
+        self.finish();
+        first_fault(&self.faults).map_or(Ok(()), Err)
+    }
+
+    pub fn push(&mut self, headers: &mut Headers) -> Result<(), StreamError> {
+        (self.query.borrow_mut().next)(headers);
+        match first_fault(&self.faults) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    pub fn stats(&self) -> &PipelineStats {
+        &self.stats
+    }
+
+    pub fn at_epoch_boundary(&self, headers: &Headers) -> bool {
+        let started: Vec<f64> = self
+            .epochs
+            .iter()
+            .map(|epoch: &EpochState| epoch.get().0)
+            .filter(|boundary: &f64| *boundary != 0.0)
+            .collect();
+        match headers.get("time") {
+            _ if started.is_empty() => true,
+            Some(OpResult::Float(time)) => started.iter().any(|boundary: &f64| time.0 >= *boundary),
+            _ => false,
+        }
+    }
+
+    pub fn finish(&mut self) {
+        (self.query.borrow_mut().reset)(&mut Headers::new());
+        finish_operators(&self.query);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_params_substitute_whole_tokens_only() {
+        let params: BTreeMap<String, toml::Value> = BTreeMap::from([
+            ("port".to_string(), toml::Value::Integer(22)),
+            (
+                "keys".to_string(),
+                toml::Value::String("ipv4.src | dump".to_string()),
+            ),
+        ]);
+        let tokens: Vec<Token> =
+            tokenize("filter l4.dport == ${port} && dns.qname == \"${port}\"").unwrap();
+        assert_eq!(
+            substitute_params(tokens, &params).unwrap(),
+            tokenize("filter l4.dport == 22 && dns.qname == \"${port}\"").unwrap()
+        );
+        let tokens: Vec<Token> = tokenize("epoch 1s | groupby ${keys} count as n").unwrap();
+        assert!(substitute_params(tokens, &params).is_err());
+    }
+}
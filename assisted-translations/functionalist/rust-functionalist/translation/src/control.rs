@@ -0,0 +1,286 @@
+#![allow(dead_code)]
+
+use serde_json::{Map, Value, json};
+
+use crate::catalog::QueryCatalog;
+use crate::config::{Pipeline, PipelineConfig, SigintSubscription, subscribe_sigint};
+use crate::error::StreamError;
+use crate::http::HttpRequest;
+use crate::live::stop_live_capture;
+use crate::params::QueryParams;
+use crate::replay::Pacer;
+use crate::stats::OperatorStats;
+use crate::trace_event;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_CONTROL_ADDR: &str = "127.0.0.1:8081";
+pub const STATS_PUBLISH_INTERVAL: usize = 1024;
+pub const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+pub const CONTROL_IO_TIMEOUT: Duration = Duration::from_secs(5);
+pub const MAX_CONTROL_BODY: usize = 64 * 1024;
+
+#[derive(Default)]
+pub struct ControlState {
+    pub paused: AtomicBool,
+    pub tuples: AtomicUsize,
+    pub rebuilds: AtomicUsize,
+    pub params_changed: AtomicBool,
+    pub params: Mutex<QueryParams>,
+    pub queries: Mutex<Vec<(String, String)>>,
+    pub stats: Mutex<Vec<(String, OperatorStats)>>,
+}
+
+pub type ControlRef = Arc<ControlState>;
+
+impl ControlState {
+    pub fn new(config: &PipelineConfig) -> ControlRef {
+        let tenant_queries = config.tenants.iter().flat_map(|tenant| {
+            tenant
+                .queries
+                .iter()
+                .map(move |query| (format!("{}/{}", tenant.name, query.name), query))
+        });
+        let queries: Vec<(String, String)> = config
+            .queries
+            .iter()
+            .map(|query| (query.name.clone(), query))
+            .chain(tenant_queries)
+            .map(|(name, query)| {
+                let src: String = match &query.catalog {
+                    Some(name) => format!("catalog:{}", name),
+                    None => query.query.clone(),
+                };
+                (name, src)
+            })
+            .collect();
+        Arc::new(ControlState {
+            params: Mutex::new(config.params.clone()),
+            queries: Mutex::new(queries),
+            ..ControlState::default()
+        })
+    }
+
+    pub fn publish_stats(&self, pipeline: &Pipeline) {
+        *self.stats.lock().unwrap() = pipeline.stats().snapshot();
+    }
+
+    pub fn set_params(&self, updates: &Map<String, Value>) -> Result<(), StreamError> {
+        let mut params: QueryParams = self.params.lock().unwrap().clone();
+        for (name, value) in updates.iter() {
+            let value: String = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            params.set(name, &value)?;
+        }
+        *self.params.lock().unwrap() = params;
+        self.params_changed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn queries_json(&self) -> Value {
+        Value::Array(
+            self.queries
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, query)| json!({ "name": name, "query": query }))
+                .collect(),
+        )
+    }
+
+    pub fn stats_json(&self) -> Value {
+        let operators: Vec<Value> = self
+            .stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, stats)| {
+                json!({
+                    "operator": name,
+                    "in": stats.tuples_in,
+                    "out": stats.tuples_out,
+                    "resets": stats.resets,
+                    "errors": stats.errors,
+                    "drops": stats.drops,
+                })
+            })
+            .collect();
+        json!({
+            "paused": self.paused.load(Ordering::SeqCst),
+            "tuples": self.tuples.load(Ordering::SeqCst),
+            "rebuilds": self.rebuilds.load(Ordering::SeqCst),
+            "operators": operators,
+        })
+    }
+
+    pub fn params_json(&self) -> Value {
+        serde_json::to_value(&*self.params.lock().unwrap()).unwrap_or(Value::Null)
+    }
+}
+
+pub struct ControlResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ControlResponse {
+    pub fn ok(body: Value) -> Self {
+        ControlResponse { status: 200, body }
+    }
+
+    pub fn error(status: u16, msg: impl Into<String>) -> Self {
+        ControlResponse {
+            status,
+            body: json!({ "error": msg.into() }),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let body: String = self.body.to_string();
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            body.len(),
+            body
+        )
+        .into_bytes()
+    }
+}
+
+pub fn handle_request(
+    control: &ControlState,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> ControlResponse {
+    match (method, path) {
+        ("GET", "/queries") => ControlResponse::ok(control.queries_json()),
+        ("GET", "/stats") => ControlResponse::ok(control.stats_json()),
+        ("GET", "/params") => ControlResponse::ok(control.params_json()),
+        ("PUT", "/params") | ("POST", "/params") => match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Object(updates)) => match control.set_params(&updates) {
+                Ok(()) => ControlResponse::ok(control.params_json()),
+                Err(e) => ControlResponse::error(400, e.to_string()),
+            },
+            _ => ControlResponse::error(400, "expected a JSON object of parameters"),
+        },
+        ("POST", "/pause") => {
+            control.paused.store(true, Ordering::SeqCst);
+            ControlResponse::ok(control.stats_json())
+        }
+        ("POST", "/resume") => {
+            control.paused.store(false, Ordering::SeqCst);
+            ControlResponse::ok(control.stats_json())
+        }
+        (_, "/queries" | "/stats" | "/params" | "/pause" | "/resume") => {
+            ControlResponse::error(405, format!("{} is not allowed on {}", method, path))
+        }
+        _ => ControlResponse::error(404, format!("no route for {}", path)),
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<(HttpRequest, Vec<u8>), StreamError> {
+    let mut reader: BufReader<&TcpStream> = BufReader::new(stream);
+    let mut head: String = String::new();
+    let mut content_length: usize = 0;
+    loop {
+        let mut line: String = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse::<usize>().unwrap_or(0);
+        }
+        head.push_str(&line);
+    }
+    if content_length > MAX_CONTROL_BODY {
+        return Err(StreamError::value(format!(
+            "request body of {} bytes exceeds the {} byte limit",
+            content_length, MAX_CONTROL_BODY
+        )));
+    }
+    let mut body: Vec<u8> = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok((HttpRequest::decode(head.as_bytes())?, body))
+}
+
+fn serve_connection(control: &ControlState, mut stream: TcpStream) -> Result<(), StreamError> {
+    stream.set_read_timeout(Some(CONTROL_IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONTROL_IO_TIMEOUT))?;
+    let response: ControlResponse = match read_request(&stream) {
+        Ok((request, body)) => handle_request(control, &request.method, &request.path, &body),
+        Err(e) => ControlResponse::error(400, e.to_string()),
+    };
+    stream.write_all(&response.encode())?;
+    Ok(())
+}
+
+pub fn spawn_control_server(addr: &str, control: ControlRef) -> Result<(), StreamError> {
+    let listener: TcpListener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(_e) = serve_connection(&control, stream) {
+                trace_event!(error = %_e, "control: connection failed");
+            }
+        }
+    });
+    Ok(())
+}
+
+pub fn run_controlled(
+    mut config: PipelineConfig,
+    catalog: &QueryCatalog,
+    control: ControlRef,
+) -> Result<Pipeline, StreamError> {
+    let sigint: SigintSubscription = subscribe_sigint(stop_live_capture)?;
+    let mut pipeline: Pipeline =
+        Pipeline::from_pipeline_config_with_catalog(config.clone(), catalog)?;
+    let mut pacer: Pacer = Pacer::new(pipeline.replay_speed.unwrap_or(0.0));
+    for mut headers in pipeline.source.headers()? {
+        while control.paused.load(Ordering::SeqCst) && !sigint.interrupted() {
+            control.publish_stats(&pipeline);
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+        if sigint.interrupted() {
+            break;
+        }
+        if control.params_changed.load(Ordering::SeqCst) && pipeline.at_epoch_boundary(&headers) {
+            control.params_changed.store(false, Ordering::SeqCst);
+            config.params = control.params.lock().unwrap().clone();
+            pipeline.finish();
+            pipeline = Pipeline::from_pipeline_config_with_catalog(config.clone(), catalog)?;
+            control.rebuilds.fetch_add(1, Ordering::SeqCst);
+        }
+        pacer.pace(&headers);
+        pipeline.push(&mut headers)?;
+        if control
+            .tuples
+            .fetch_add(1, Ordering::SeqCst)
+            .is_multiple_of(STATS_PUBLISH_INTERVAL)
+        {
+            control.publish_stats(&pipeline);
+        }
+    }
+    pipeline.finish();
+    control.publish_stats(&pipeline);
+    Ok(pipeline)
+}
@@ -0,0 +1,95 @@
+#![allow(dead_code)]
+
+use crate::dsl::compile_query;
+use crate::error::StreamError;
+use crate::json::headers_of_json;
+use crate::packet::DecodeOptions;
+use crate::pcap::decode_pcap;
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub const DEMO_QUERIES: [(&str, &str); 6] = [
+    (
+        "count_pkts",
+        "epoch 1s | groupby ipv4.src, ipv4.dst count as pkts",
+    ),
+    (
+        "distinct_srcs",
+        "epoch 1s | distinct ipv4.src | groupby * count as srcs",
+    ),
+    (
+        "tcp_new_cons",
+        "epoch 1s | filter ipv4.proto == 6 && l4.flags == 2 | groupby ipv4.dst count as cons | filter cons >= 40",
+    ),
+    (
+        "ssh_brute_force",
+        "epoch 1s | filter ipv4.proto == 6 && l4.dport == 22 | distinct ipv4.src, ipv4.dst, ipv4.len | groupby ipv4.dst, ipv4.len count as srcs | filter srcs >= 40",
+    ),
+    (
+        "super_spreader",
+        "epoch 1s | distinct ipv4.src, ipv4.dst | groupby ipv4.src count as dsts | filter dsts >= 40 | sort dsts desc",
+    ),
+    (
+        "port_scan",
+        "epoch 1s | distinct ipv4.src, l4.dport | groupby ipv4.src count as ports | filter ports >= 40",
+    ),
+];
+
+pub fn demo_query(name_or_query: &str) -> &str {
+    DEMO_QUERIES
+        .iter()
+        .find(|(name, _)| *name == name_or_query)
+        .map_or(name_or_query, |(_, query)| query)
+}
+
+pub fn create_collect_operator(output: Rc<RefCell<Vec<Headers>>>) -> OperatorRef {
+    Rc::new(RefCell::new(
+        Operator::new(
+            Box::new(move |headers: &mut Headers| output.borrow_mut().push(headers.clone())),
+            Box::new(|_: &mut Headers| {}),
+        )
+        .with_label("collect"),
+    ))
+}
+
+pub struct DemoPipeline {
+    query: OperatorRef,
+    output: Rc<RefCell<Vec<Headers>>>,
+}
+
+impl DemoPipeline {
+    pub fn new(name_or_query: &str) -> Result<Self, StreamError> {
+        let output: Rc<RefCell<Vec<Headers>>> = Rc::new(RefCell::new(Vec::new()));
+        let query: OperatorRef = compile_query(
+            demo_query(name_or_query),
+            create_collect_operator(Rc::clone(&output)),
+        )?;
+        Ok(DemoPipeline { query, output })
+    }
+
+    fn take_output(&self) -> Vec<Headers> {
+        std::mem::take(&mut *self.output.borrow_mut())
+    }
+
+    pub fn push(&mut self, mut headers: Headers) -> Vec<Headers> {
+        (self.query.borrow_mut().next)(&mut headers);
+        self.take_output()
+    }
+
+    pub fn process_packet(&mut self, json: &str) -> Result<Vec<Headers>, StreamError> {
+        Ok(self.push(headers_of_json(json)?))
+    }
+
+    pub fn process_pcap(&mut self, bytes: &[u8]) -> Result<Vec<Headers>, StreamError> {
+        for mut headers in decode_pcap(bytes, &DecodeOptions::default())? {
+            (self.query.borrow_mut().next)(&mut headers);
+        }
+        Ok(self.take_output())
+    }
+
+    pub fn finish(&mut self) -> Vec<Headers> {
+        (self.query.borrow_mut().reset)(&mut Headers::new());
+        self.take_output()
+    }
+}
@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+
+use crate::error::{ParseError, StreamError};
+use crate::keys::WellKnownKey;
+use crate::packet::IPPROTO_UDP;
+use crate::utils::{Headers, OpResult};
+use std::collections::HashMap;
+
+pub const DNS_PORT: i32 = 53;
+
+pub const DNS_QTYPE_A: i32 = 1;
+pub const DNS_QTYPE_CNAME: i32 = 5;
+pub const DNS_QTYPE_MX: i32 = 15;
+pub const DNS_QTYPE_TXT: i32 = 16;
+pub const DNS_QTYPE_AAAA: i32 = 28;
+
+pub const DNS_RCODE_NOERROR: i32 = 0;
+pub const DNS_RCODE_NXDOMAIN: i32 = 3;
+
+const HEADER_LEN: usize = 12;
+const MAX_LABEL_LEN: usize = 63;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub response: bool,
+    pub rcode: i32,
+    pub qname: String,
+    pub qtype: i32,
+}
+
+fn dns_error(msg: &str) -> StreamError {
+    StreamError::Parse(ParseError::Packet {
+        protocol: "DNS payload",
+        msg: msg.to_string(),
+    })
+}
+
+fn read_u16(payload: &[u8], offset: usize) -> Result<u16, StreamError> {
+    payload
+        .get(offset..offset + 2)
+        .map(|bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| dns_error("truncated"))
+}
+
+impl DnsMessage {
+    pub fn query(id: u16, qname: &str, qtype: i32) -> Self {
+        DnsMessage {
+            id,
+            response: false,
+            rcode: DNS_RCODE_NOERROR,
+            qname: qname.to_string(),
+            qtype,
+        }
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<DnsMessage, StreamError> {
+        if payload.len() < HEADER_LEN {
+            return Err(dns_error("shorter than header"));
+        }
+        let flags: u16 = read_u16(payload, 2)?;
+        if read_u16(payload, 4)? == 0 {
+            return Err(dns_error("no question"));
+        }
+        let mut labels: Vec<String> = Vec::new();
+        let mut offset: usize = HEADER_LEN;
+        loop {
+            let len: usize = *payload.get(offset).ok_or_else(|| dns_error("truncated"))? as usize;
+            offset += 1;
+            if len == 0 {
+                break;
+            }
+            if len > MAX_LABEL_LEN {
+                return Err(dns_error("compressed or oversized label in question"));
+            }
+            let label: &[u8] = payload
+                .get(offset..offset + len)
+                .ok_or_else(|| dns_error("truncated"))?;
+            labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+            offset += len;
+        }
+        Ok(DnsMessage {
+            id: read_u16(payload, 0)?,
+            response: flags & 0x8000 != 0,
+            rcode: (flags & 0x000F) as i32,
+            qname: labels.join("."),
+            qtype: read_u16(payload, offset)? as i32,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let flags: u16 = if self.response { 0x8180 } else { 0x0100 } | (self.rcode as u16 & 0x000F);
+        let mut payload: Vec<u8> = Vec::with_capacity(HEADER_LEN + self.qname.len() + 6);
+        payload.extend(self.id.to_be_bytes());
+        payload.extend(flags.to_be_bytes());
+        payload.extend([0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in self
+            .qname
+            .split('.')
+            .filter(|label: &&str| !label.is_empty())
+        {
+            let label: &[u8] = &label.as_bytes()[..label.len().min(MAX_LABEL_LEN)];
+            payload.push(label.len() as u8);
+            payload.extend(label);
+        }
+        payload.push(0);
+        payload.extend((self.qtype as u16).to_be_bytes());
+        payload.extend(1u16.to_be_bytes());
+        payload
+    }
+
+    pub fn insert_into(&self, size: usize, headers: &mut Headers) {
+        headers.insert(
+            WellKnownKey::DnsQname.into(),
+            OpResult::Str(self.qname.clone()),
+        );
+        headers.insert(WellKnownKey::DnsQtype.into(), OpResult::Int(self.qtype));
+        headers.insert(WellKnownKey::DnsRcode.into(), OpResult::Int(self.rcode));
+        headers.insert(
+            WellKnownKey::DnsSize.into(),
+            OpResult::Int(i32::try_from(size).unwrap_or(i32::MAX)),
+        );
+    }
+}
+
+pub fn is_dns(proto: i32, sport: i32, dport: i32) -> bool {
+    proto == IPPROTO_UDP && (sport == DNS_PORT || dport == DNS_PORT)
+}
+
+pub fn name_entropy(name: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in name.chars().filter(|c: &char| *c != '.') {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    let total: f64 = counts.values().sum::<usize>() as f64;
+    counts
+        .values()
+        .map(|count: &usize| {
+            let p: f64 = *count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // query for "www.Example.com" type A, class IN
+    const QUERY: [u8; 33] = [
+        0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // header
+        0x03, b'w', b'w', b'w', 0x07, b'E', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o',
+        b'm', 0x00, // qname
+        0x00, 0x01, 0x00, 0x01, // qtype, qclass
+    ];
+
+    #[test]
+    fn decodes_a_query_fixture() {
+        assert_eq!(
+            DnsMessage::decode(&QUERY).unwrap(),
+            DnsMessage::query(0x1234, "www.example.com", DNS_QTYPE_A)
+        );
+        let mut nxdomain: [u8; 33] = QUERY;
+        nxdomain[2..4].copy_from_slice(&[0x81, 0x83]);
+        let response: DnsMessage = DnsMessage::decode(&nxdomain).unwrap();
+        assert!(response.response);
+        assert_eq!(response.rcode, DNS_RCODE_NXDOMAIN);
+    }
+
+    #[test]
+    fn truncated_queries_are_errors() {
+        // the class is never read, so only cuts before it must fail
+        for len in 0..QUERY.len() - 2 {
+            assert!(DnsMessage::decode(&QUERY[..len]).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn malformed_queries_are_errors() {
+        let mut no_question: [u8; 33] = QUERY;
+        no_question[5] = 0;
+        let mut compressed: [u8; 33] = QUERY;
+        compressed[16] = 0xc0;
+        let mut overrunning_label: [u8; 33] = QUERY;
+        overrunning_label[24] = 0x3f;
+        for payload in [no_question, compressed, overrunning_label] {
+            assert!(matches!(
+                DnsMessage::decode(&payload),
+                Err(StreamError::Parse(ParseError::Packet { .. }))
+            ));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+#![allow(dead_code)]
+
+use crate::utils::OperatorRef;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+fn op_id(op: &OperatorRef) -> *const () {
+    Rc::as_ptr(op) as *const ()
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn visible(op: &OperatorRef) -> Vec<OperatorRef> {
+    let node = op.borrow();
+    if !node.label.is_empty() {
+        return vec![Rc::clone(op)];
+    }
+    node.downstream.iter().flat_map(visible).collect()
+}
+
+struct DotWriter {
+    ids: HashMap<*const (), usize>,
+    nodes: Vec<String>,
+    edges: Vec<String>,
+}
+
+impl DotWriter {
+    fn visit(&mut self, op: &OperatorRef) -> usize {
+        if let Some(id) = self.ids.get(&op_id(op)) {
+            return *id;
+        }
+        let id: usize = self.nodes.len() + 1;
+        self.ids.insert(op_id(op), id);
+        let (label, downstream): (String, Vec<OperatorRef>) = {
+            let node = op.borrow();
+            (node.label.clone(), node.downstream.clone())
+        };
+        let shape: &str = if downstream.is_empty() {
+            "ellipse"
+        } else {
+            "box"
+        };
+        self.nodes.push(format!(
+            "    n{} [label=\"{}\", shape={}];",
+            id,
+            escape(&label),
+            shape
+        ));
+        for child in downstream.iter().flat_map(visible) {
+            let child_id: usize = self.visit(&child);
+            self.edges.push(format!("    n{} -> n{};", id, child_id));
+        }
+        id
+    }
+}
+
+pub fn to_dot(name: &str, roots: &[OperatorRef]) -> String {
+    let mut writer: DotWriter = DotWriter {
+        ids: HashMap::new(),
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+    for root in roots.iter().flat_map(visible) {
+        let id: usize = writer.visit(&root);
+        writer.edges.push(format!("    n0 -> n{};", id));
+    }
+    let mut out: String = format!("digraph \"{}\" {{\n", escape(name));
+    out.push_str("    rankdir=TB;\n");
+    out.push_str("    n0 [label=\"source\", shape=invhouse];\n");
+    for line in writer.nodes.iter().chain(writer.edges.iter()) {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str("}\n");
+    out
+}
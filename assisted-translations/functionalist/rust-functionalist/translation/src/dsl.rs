@@ -0,0 +1,1137 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::biflow::DEFAULT_BIFLOW_TIMEOUT;
+use crate::budget::{BudgetAction, MemoryBudget, parse_bytes};
+use crate::builtins::{
+    Cmp, EpochOptions, FilterFunc, GroupingFunc, MapFunc, MissingTimePolicy, ReductionFunc,
+    counter, filter_groups, ipv4_in_cidr, parse_cidr, single_group,
+};
+use crate::error::{InputKind, StreamError};
+use crate::first_seen::{FIRST_SEEN_KEY, LAST_SEEN_KEY, SeenTable};
+use crate::keys::WellKnownKey;
+use crate::plan::{PlanStage, check_stages, fuse_stages};
+use crate::reducers::{max_int, mean_float, min_int, percentile, stddev, variance};
+use crate::schema::{FieldType, Schema, SchemaError};
+use crate::services::{SERVICE_KEY, service_map};
+use crate::state::spill_backend;
+use crate::tcp_stream::TcpStreamOptions;
+use crate::utils::{Headers, OpResult, OperatorRef, compare_op_results};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Word(String),
+    Op(String),
+    Pipe,
+    Comma,
+    LParen,
+    RParen,
+}
+
+pub fn dsl_error(msg: String) -> StreamError {
+    StreamError::query(msg)
+}
+
+fn expected_error(expected: impl Into<String>, found: impl fmt::Debug) -> StreamError {
+    StreamError::unexpected(InputKind::Query, expected, format!("{:?}", found))
+}
+
+fn invalid_error(what: &'static str, word: &str) -> StreamError {
+    StreamError::invalid(InputKind::Query, what, word)
+}
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>, StreamError> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i: usize = 0;
+    while i < chars.len() {
+        let c: char = chars[i];
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '"' => {
+                let start: usize = i;
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(dsl_error(format!(
+                        "unterminated string at offset {}",
+                        start
+                    )));
+                }
+                i += 1;
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+            _ if ["==", "!=", ">=", "<=", "&&", "||"].contains(&two.as_str()) => {
+                tokens.push(Token::Op(two));
+                i += 2;
+            }
+            '|' => {
+                tokens.push(Token::Pipe);
+                i += 1;
+            }
+            '<' | '>' | '!' | '=' | '+' | '-' | '*' | '/' => {
+                tokens.push(Token::Op(c.to_string()));
+                i += 1;
+            }
+            '&' => return Err(dsl_error(format!("unexpected '&' at offset {}", i))),
+            _ => {
+                let start: usize = i;
+                while i < chars.len() && !is_word_break(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_word_break(c: char) -> bool {
+    c.is_whitespace() || "()<>=!&|,+-*/\"".contains(c)
+}
+
+fn unquote(word: String) -> String {
+    match word.len() >= 2 && word.starts_with('"') && word.ends_with('"') {
+        true => word[1..word.len() - 1].to_string(),
+        false => word,
+    }
+}
+
+pub fn parse_literal(word: &str) -> Option<OpResult> {
+    if let Ok(i) = word.parse::<i32>() {
+        Some(OpResult::Int(i))
+    } else if let Ok(f) = word.parse::<f64>() {
+        Some(OpResult::Float(OrderedFloat(f)))
+    } else if let Ok(addr) = Ipv4Addr::from_str(word) {
+        Some(OpResult::IPv4(addr))
+    } else if word.len() >= 2 && word.starts_with('"') && word.ends_with('"') {
+        Some(OpResult::Str(word[1..word.len() - 1].to_string()))
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Field(String),
+    Literal(OpResult),
+}
+
+impl Operand {
+    fn of_word(word: &str) -> Operand {
+        match parse_literal(word) {
+            Some(lit) => Operand::Literal(lit),
+            None => Operand::Field(word.to_string()),
+        }
+    }
+
+    fn resolve<'a>(&'a self, headers: &'a Headers) -> Option<&'a OpResult> {
+        match self {
+            Operand::Field(key) => headers.get(key),
+            Operand::Literal(lit) => Some(lit),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    Compare(Operand, Cmp, Operand),
+    InCidr(String, Ipv4Addr, u8),
+    Not(Box<Predicate>),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+impl Predicate {
+    pub fn fields(&self) -> Vec<(String, FieldType)> {
+        match self {
+            Predicate::Compare(lhs, _, rhs) => [lhs, rhs]
+                .iter()
+                .filter_map(|operand: &&Operand| match operand {
+                    Operand::Field(key) => Some((key.clone(), FieldType::Any)),
+                    Operand::Literal(_) => None,
+                })
+                .collect(),
+            Predicate::InCidr(key, _, _) => vec![(key.clone(), FieldType::IPv4)],
+            Predicate::Not(p) => p.fields(),
+            Predicate::And(a, b) | Predicate::Or(a, b) => {
+                let mut fields: Vec<(String, FieldType)> = a.fields();
+                fields.extend(b.fields());
+                fields
+            }
+        }
+    }
+
+    pub fn eval(&self, headers: &Headers) -> bool {
+        match self {
+            Predicate::Compare(lhs, op, rhs) => {
+                match (lhs.resolve(headers), rhs.resolve(headers)) {
+                    (Some(a), Some(b)) => op.holds(compare_op_results(a, b)),
+                    _ => false,
+                }
+            }
+            Predicate::InCidr(key, network, prefix_len) => match headers.get(key) {
+                Some(OpResult::IPv4(addr)) => ipv4_in_cidr(*addr, *network, *prefix_len),
+                _ => false,
+            },
+            Predicate::Not(p) => !p.eval(headers),
+            Predicate::And(a, b) => a.eval(headers) && b.eval(headers),
+            Predicate::Or(a, b) => a.eval(headers) || b.eval(headers),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl ArithOp {
+    fn of_token(token: Option<&Token>, ops: &[ArithOp]) -> Option<ArithOp> {
+        let op: ArithOp = match token {
+            Some(Token::Op(op)) if op == "+" => ArithOp::Add,
+            Some(Token::Op(op)) if op == "-" => ArithOp::Sub,
+            Some(Token::Op(op)) if op == "*" => ArithOp::Mul,
+            Some(Token::Op(op)) if op == "/" => ArithOp::Div,
+            _ => return None,
+        };
+        ops.contains(&op).then_some(op)
+    }
+
+    pub fn apply(&self, lhs: &OpResult, rhs: &OpResult) -> Option<OpResult> {
+        match (lhs, rhs) {
+            (OpResult::Int(a), OpResult::Int(b)) => match self {
+                ArithOp::Add => a.checked_add(*b),
+                ArithOp::Sub => a.checked_sub(*b),
+                ArithOp::Mul => a.checked_mul(*b),
+                ArithOp::Div => a.checked_div(*b),
+            }
+            .map(OpResult::Int),
+            _ => {
+                let (a, b): (f64, f64) = (number_of(lhs)?, number_of(rhs)?);
+                Some(OpResult::Float(OrderedFloat(match self {
+                    ArithOp::Add => a + b,
+                    ArithOp::Sub => a - b,
+                    ArithOp::Mul => a * b,
+                    ArithOp::Div => a / b,
+                })))
+            }
+        }
+    }
+}
+
+fn number_of(val: &OpResult) -> Option<f64> {
+    match val {
+        OpResult::Int(i) => Some(*i as f64),
+        OpResult::Float(f) => Some(f.0),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Operand(Operand),
+    Binary(Box<Expr>, ArithOp, Box<Expr>),
+}
+
+impl Expr {
+    pub fn fields(&self) -> Vec<String> {
+        match self {
+            Expr::Operand(Operand::Field(key)) => vec![key.clone()],
+            Expr::Operand(Operand::Literal(_)) => Vec::new(),
+            Expr::Binary(lhs, _, rhs) => {
+                let mut fields: Vec<String> = lhs.fields();
+                fields.extend(rhs.fields());
+                fields
+            }
+        }
+    }
+
+    pub fn field_type(&self, input: &Schema) -> FieldType {
+        match self {
+            Expr::Operand(Operand::Field(key)) => input.field_type(key).unwrap_or(FieldType::Any),
+            Expr::Operand(Operand::Literal(lit)) => {
+                FieldType::of_op_result(lit).unwrap_or(FieldType::Any)
+            }
+            Expr::Binary(lhs, _, rhs) => match (lhs.field_type(input), rhs.field_type(input)) {
+                (FieldType::Int, FieldType::Int) => FieldType::Int,
+                (FieldType::Float, _) | (_, FieldType::Float) => FieldType::Float,
+                _ => FieldType::Any,
+            },
+        }
+    }
+
+    pub fn eval(&self, headers: &Headers) -> Option<OpResult> {
+        match self {
+            Expr::Operand(operand) => operand.resolve(headers).cloned(),
+            Expr::Binary(lhs, op, rhs) => op.apply(&lhs.eval(headers)?, &rhs.eval(headers)?),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapExpr {
+    pub out_key: String,
+    pub expr: Expr,
+}
+
+impl MapExpr {
+    pub fn apply(&self, mut headers: Headers) -> Headers {
+        if let Some(val) = self.expr.eval(&headers) {
+            headers.insert(self.out_key.clone(), val);
+        }
+        headers
+    }
+
+    pub fn check_schema(&self, input: &Schema, stage: &str) -> Result<Schema, SchemaError> {
+        for key in self.expr.fields() {
+            input.require(&key, FieldType::Any, stage)?;
+        }
+        Ok(input
+            .clone()
+            .with(&self.out_key, self.expr.field_type(input)))
+    }
+}
+
+pub fn parse_map_expr(src: &str) -> Result<MapExpr, StreamError> {
+    let mut parser: Parser = Parser::new(tokenize(src)?);
+    let map_expr: MapExpr = parser.parse_map_expr()?;
+    match parser.peek() {
+        None => Ok(map_expr),
+        Some(token) => Err(expected_error("the end of the expression", token)),
+    }
+}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser { tokens, pos: 0 }
+    }
+
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        let token: Option<Token> = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    pub fn expect_word(&mut self) -> Result<String, StreamError> {
+        match self.next_token() {
+            Some(Token::Word(w)) => Ok(w),
+            other => Err(expected_error("a name", other)),
+        }
+    }
+
+    pub fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(&Token::Word(keyword.to_string())) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn expect_keyword(&mut self, keyword: &str) -> Result<(), StreamError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(expected_error(format!("'{}'", keyword), self.peek()))
+        }
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if self.peek() == Some(&Token::Op(op.to_string())) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn parse_predicate(&mut self) -> Result<Predicate, StreamError> {
+        let mut lhs: Predicate = self.parse_conjunction()?;
+        while self.eat_op("||") {
+            lhs = Predicate::Or(Box::new(lhs), Box::new(self.parse_conjunction()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_conjunction(&mut self) -> Result<Predicate, StreamError> {
+        let mut lhs: Predicate = self.parse_atom()?;
+        while self.eat_op("&&") {
+            lhs = Predicate::And(Box::new(lhs), Box::new(self.parse_atom()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_atom(&mut self) -> Result<Predicate, StreamError> {
+        if self.eat_op("!") {
+            return Ok(Predicate::Not(Box::new(self.parse_atom()?)));
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let inner: Predicate = self.parse_predicate()?;
+            return match self.next_token() {
+                Some(Token::RParen) => Ok(inner),
+                other => Err(expected_error("')'", other)),
+            };
+        }
+        let lhs: String = self.expect_word()?;
+        if self.eat_keyword("in") {
+            let (network, prefix_len) = parse_cidr(&self.expect_cidr()?)?;
+            return Ok(Predicate::InCidr(lhs, network, prefix_len));
+        }
+        if self.eat_keyword("between") {
+            let lo: Operand = Operand::of_word(&self.expect_operand()?);
+            self.expect_keyword("and")?;
+            let hi: Operand = Operand::of_word(&self.expect_operand()?);
+            return Ok(Predicate::And(
+                Box::new(Predicate::Compare(Operand::of_word(&lhs), Cmp::Ge, lo)),
+                Box::new(Predicate::Compare(Operand::of_word(&lhs), Cmp::Le, hi)),
+            ));
+        }
+        let token: Option<Token> = self.next_token();
+        let op: Cmp = match &token {
+            Some(Token::Op(op)) => Cmp::of_str(op),
+            _ => None,
+        }
+        .ok_or_else(|| expected_error("a comparison", &token))?;
+        let rhs: String = self.expect_operand()?;
+        Ok(Predicate::Compare(
+            Operand::of_word(&lhs),
+            op,
+            Operand::of_word(&rhs),
+        ))
+    }
+
+    pub fn parse_map_expr(&mut self) -> Result<MapExpr, StreamError> {
+        let out_key: String = unquote(self.expect_word()?);
+        if !self.eat_op("=") {
+            return Err(expected_error(
+                format!("'=' after '{}'", out_key),
+                self.peek(),
+            ));
+        }
+        Ok(MapExpr {
+            out_key,
+            expr: self.parse_expr()?,
+        })
+    }
+
+    pub fn parse_expr(&mut self) -> Result<Expr, StreamError> {
+        let mut lhs: Expr = self.parse_term()?;
+        while let Some(op) = ArithOp::of_token(self.peek(), &[ArithOp::Add, ArithOp::Sub]) {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.parse_term()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, StreamError> {
+        let mut lhs: Expr = self.parse_factor()?;
+        while let Some(op) = ArithOp::of_token(self.peek(), &[ArithOp::Mul, ArithOp::Div]) {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.parse_factor()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expr, StreamError> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let inner: Expr = self.parse_expr()?;
+            return match self.next_token() {
+                Some(Token::RParen) => Ok(inner),
+                other => Err(expected_error("')'", other)),
+            };
+        }
+        Ok(Expr::Operand(Operand::of_word(&self.expect_operand()?)))
+    }
+
+    fn expect_operand(&mut self) -> Result<String, StreamError> {
+        let negated: bool = self.eat_op("-");
+        match self.next_token() {
+            Some(Token::Word(word)) if negated => Ok(format!("-{}", word)),
+            Some(Token::Word(word)) => Ok(word),
+            other => Err(expected_error("an operand", other)),
+        }
+    }
+
+    fn expect_key(&mut self) -> Result<String, StreamError> {
+        if self.eat_op("*") {
+            return Ok("*".to_string());
+        }
+        self.expect_word()
+    }
+
+    pub fn parse_keys(&mut self) -> Result<Vec<String>, StreamError> {
+        let mut keys: Vec<String> = vec![self.expect_key()?];
+        while self.peek() == Some(&Token::Comma) {
+            self.pos += 1;
+            keys.push(self.expect_key()?);
+        }
+        Ok(keys)
+    }
+
+    pub fn expect_path(&mut self) -> Result<String, StreamError> {
+        match self.next_token() {
+            Some(Token::Word(w)) => Ok(unquote(w)),
+            Some(Token::Op(op)) if op == "/" || op == "-" => Err(dsl_error(
+                "paths containing '/' or '-' must be quoted".to_string(),
+            )),
+            other => Err(expected_error("a path", other)),
+        }
+    }
+
+    fn expect_cidr(&mut self) -> Result<String, StreamError> {
+        let addr: String = self.expect_word()?;
+        if !self.eat_op("/") {
+            return Ok(addr);
+        }
+        Ok(format!("{}/{}", addr, self.expect_word()?))
+    }
+}
+
+pub fn parse_duration(word: &str) -> Result<f64, StreamError> {
+    let (num, scale) = if let Some(n) = word.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = word.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = word.strip_suffix('m') {
+        (n, 60.0)
+    } else if let Some(n) = word.strip_suffix('h') {
+        (n, 3600.0)
+    } else {
+        (word, 1.0)
+    };
+    num.parse::<f64>()
+        .map(|n: f64| n * scale)
+        .map_err(|_| invalid_error("duration", word))
+}
+
+fn parse_budget(parser: &mut Parser) -> Result<Option<MemoryBudget>, StreamError> {
+    if !parser.eat_keyword("budget") {
+        return Ok(None);
+    }
+    let limit_bytes: usize = parse_bytes(&parser.expect_word()?)?;
+    let action: BudgetAction = match parser.peek() {
+        Some(Token::Word(_)) => parser.expect_word()?.parse()?,
+        _ => BudgetAction::default(),
+    };
+    Ok(Some(MemoryBudget::new(limit_bytes, action)))
+}
+
+fn parse_spill(parser: &mut Parser) -> Result<Option<String>, StreamError> {
+    if !parser.eat_keyword("spill") {
+        return Ok(None);
+    }
+    Ok(Some(parser.expect_path()?))
+}
+
+fn parse_incremental(parser: &mut Parser) -> Result<Option<usize>, StreamError> {
+    if !parser.eat_keyword("incremental") {
+        return Ok(None);
+    }
+    let word: String = parser.expect_word()?;
+    word.parse::<usize>()
+        .ok()
+        .filter(|n: &usize| *n > 0)
+        .map(Some)
+        .ok_or_else(|| invalid_error("partition count", &word))
+}
+
+fn schema_keys(keys: &[String]) -> Vec<String> {
+    keys.iter().filter(|key| *key != "*").cloned().collect()
+}
+
+pub fn grouping_of_keys(keys: Vec<String>) -> GroupingFunc {
+    if keys == ["*"] {
+        Box::new(single_group)
+    } else {
+        Box::new(move |mut headers: Headers| filter_groups(&keys, &mut headers))
+    }
+}
+
+fn parse_reduction(
+    parser: &mut Parser,
+) -> Result<(ReductionFunc, Option<String>, FieldType), StreamError> {
+    let name: String = parser.expect_word()?;
+    let mut input_key: Option<String> = None;
+    let mut key = |parser: &mut Parser| -> Result<String, StreamError> {
+        let key: String = parser.expect_word()?;
+        input_key = Some(key.clone());
+        Ok(key)
+    };
+    let reduce: ReductionFunc = match name.as_str() {
+        "count" => Box::new(counter),
+        "sum" => {
+            let key: String = key(parser)?;
+            Box::new(move |init_val: OpResult, headers: &mut Headers| {
+                let base: i32 = match init_val {
+                    OpResult::Int(i) => i,
+                    _ => 0,
+                };
+                match headers.get(&key) {
+                    Some(OpResult::Int(n)) => OpResult::Int(base.saturating_add(*n)),
+                    _ => OpResult::Int(base),
+                }
+            })
+        }
+        "min" => min_int(key(parser)?),
+        "max" => max_int(key(parser)?),
+        "mean" => mean_float(key(parser)?),
+        "variance" => variance(key(parser)?),
+        "stddev" => stddev(key(parser)?),
+        "percentile" => {
+            let word: String = parser.expect_word()?;
+            let p: f64 = word
+                .parse::<f64>()
+                .map_err(|_| invalid_error("percentile", &word))?;
+            percentile(key(parser)?, p)
+        }
+        other => return Err(StreamError::unknown(InputKind::Query, "reduction", other)),
+    };
+    let out_type: FieldType = match name.as_str() {
+        "count" | "sum" | "min" | "max" => FieldType::Int,
+        _ => FieldType::Float,
+    };
+    Ok((reduce, input_key, out_type))
+}
+
+fn stage_label(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token: &Token| match token {
+            Token::Word(w) | Token::Op(w) => w.clone(),
+            Token::Pipe => "|".to_string(),
+            Token::Comma => ",".to_string(),
+            Token::LParen => "(".to_string(),
+            Token::RParen => ")".to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn parse_epoch_key(parser: &mut Parser) -> Result<String, StreamError> {
+    if parser.eat_keyword("as") {
+        parser.expect_word()
+    } else {
+        Ok("eid".to_string())
+    }
+}
+
+pub fn parse_stage(tokens: Vec<Token>) -> Result<PlanStage, StreamError> {
+    parse_stage_with_budget(tokens, None)
+}
+
+pub fn parse_stage_with_budget(
+    tokens: Vec<Token>,
+    default_budget: Option<&MemoryBudget>,
+) -> Result<PlanStage, StreamError> {
+    let label: String = stage_label(&tokens);
+    let identity: String = label.clone();
+    let mut parser: Parser = Parser::new(tokens);
+    let stage: PlanStage = match parser.expect_word()?.as_str() {
+        "epoch" => {
+            let word: String = parser.expect_word()?;
+            if parser.eat_keyword("tuples") {
+                let n: usize = word
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n: &usize| *n > 0)
+                    .ok_or_else(|| invalid_error("epoch tuple count", &word))?;
+                PlanStage::count_epoch(n, parse_epoch_key(&mut parser)?)
+            } else {
+                let width: f64 = parse_duration(&word)?;
+                let options: EpochOptions = if parser.eat_keyword("aligned") {
+                    EpochOptions::aligned(if parser.eat_keyword("offset") {
+                        parse_duration(&parser.expect_word()?)?
+                    } else {
+                        0.0
+                    })
+                } else {
+                    EpochOptions::default()
+                };
+                let options: EpochOptions = if parser.eat_keyword("missing") {
+                    options.with_missing_time(parser.expect_word()?.parse::<MissingTimePolicy>()?)
+                } else {
+                    options
+                };
+                PlanStage::epoch_with_options(width, parse_epoch_key(&mut parser)?, options)
+            }
+        }
+        "filter" => {
+            let pred: Predicate = parser.parse_predicate()?;
+            let fields: Vec<(String, FieldType)> = pred.fields();
+            let f: FilterFunc = Box::new(move |headers: &Headers| pred.eval(headers));
+            PlanStage::filter(label, f).with_check(Box::new(move |input: &Schema, stage: &str| {
+                for (key, ty) in fields.iter() {
+                    input.require(key, *ty, stage)?;
+                }
+                Ok(input.clone())
+            }))
+        }
+        "map" => {
+            let map_expr: MapExpr = parser.parse_map_expr()?;
+            let check_expr: MapExpr = map_expr.clone();
+            let f: MapFunc = Box::new(move |headers: Headers| map_expr.apply(headers));
+            PlanStage::map(label, f).with_check(Box::new(move |input: &Schema, stage: &str| {
+                check_expr.check_schema(input, stage)
+            }))
+        }
+        "groupby" => {
+            let keys: Vec<String> = parser.parse_keys()?;
+            let (reduce, input_key, out_type) = parse_reduction(&mut parser)?;
+            parser.expect_keyword("as")?;
+            let out_key: String = parser.expect_word()?;
+            let check_keys: Vec<String> = schema_keys(&keys);
+            let check_out_key: String = out_key.clone();
+            let grouping: GroupingFunc = grouping_of_keys(keys);
+            let budget: Option<MemoryBudget> = parse_budget(&mut parser)?;
+            let spill: Option<String> = parse_spill(&mut parser)?;
+            let incremental: Option<usize> = parse_incremental(&mut parser)?;
+            match (budget, spill, incremental) {
+                (Some(_), Some(_), _) => {
+                    return Err(dsl_error(
+                        "'budget' and 'spill' cannot be combined".to_string(),
+                    ));
+                }
+                (Some(_), _, Some(_)) | (_, Some(_), Some(_)) => {
+                    return Err(dsl_error(
+                        "'incremental' cannot be combined with 'budget' or 'spill'".to_string(),
+                    ));
+                }
+                (None, None, Some(_)) if default_budget.is_some() => {
+                    return Err(dsl_error(
+                        "'incremental' groupby cannot run under a state budget".to_string(),
+                    ));
+                }
+                (None, None, Some(partitions)) => {
+                    PlanStage::groupby_incremental(label, grouping, reduce, out_key, partitions)
+                }
+                (None, Some(dir), None) => PlanStage::groupby_with_backend(
+                    label,
+                    grouping,
+                    reduce,
+                    out_key,
+                    spill_backend(&dir)?,
+                ),
+                (budget, None, None) => match budget.or_else(|| default_budget.cloned()) {
+                    Some(budget) => {
+                        PlanStage::groupby_with_budget(label, grouping, reduce, out_key, budget)
+                    }
+                    None => PlanStage::groupby(label, grouping, reduce, out_key),
+                },
+            }
+            .with_check(Box::new(move |input: &Schema, stage: &str| {
+                if let Some(key) = &input_key {
+                    input.require(key, FieldType::Any, stage)?;
+                }
+                Ok(input
+                    .project(&check_keys, stage)?
+                    .with(&check_out_key, out_type))
+            }))
+        }
+        "distinct" => {
+            let keys: Vec<String> = parser.parse_keys()?;
+            let check_keys: Vec<String> = schema_keys(&keys);
+            if parser.eat_keyword("within") {
+                let ttl: f64 = parse_duration(&parser.expect_word()?)?;
+                PlanStage::distinct_ttl(label, grouping_of_keys(keys), ttl).with_check(Box::new(
+                    move |input: &Schema, stage: &str| {
+                        input.project(&check_keys, stage)?;
+                        Ok(input.clone())
+                    },
+                ))
+            } else {
+                let grouping: GroupingFunc = grouping_of_keys(keys);
+                match (parse_budget(&mut parser)?, parse_spill(&mut parser)?) {
+                    (Some(_), Some(_)) => {
+                        return Err(dsl_error(
+                            "'budget' and 'spill' cannot be combined".to_string(),
+                        ));
+                    }
+                    (None, Some(dir)) => {
+                        PlanStage::distinct_with_backend(label, grouping, spill_backend(&dir)?)
+                    }
+                    (budget, None) => match budget.or_else(|| default_budget.cloned()) {
+                        Some(budget) => PlanStage::distinct_with_budget(label, grouping, budget),
+                        None => PlanStage::distinct(label, grouping),
+                    },
+                }
+                .with_check(Box::new(move |input: &Schema, stage: &str| {
+                    input.project(&check_keys, stage)
+                }))
+            }
+        }
+        "first_seen" => {
+            let keys: Vec<String> = parser.parse_keys()?;
+            let check_keys: Vec<String> = schema_keys(&keys);
+            let forget_after: Option<f64> = if parser.eat_keyword("forget") {
+                Some(parse_duration(&parser.expect_word()?)?)
+            } else {
+                None
+            };
+            let table: SeenTable = if parser.eat_keyword("persist") {
+                SeenTable::open(parser.expect_path()?, forget_after)?
+            } else {
+                SeenTable::new(forget_after)
+            };
+            PlanStage::first_seen(label, grouping_of_keys(keys), table).with_check(Box::new(
+                move |input: &Schema, stage: &str| {
+                    input.project(&check_keys, stage)?;
+                    let output: Schema = input.clone().with(FIRST_SEEN_KEY, FieldType::Float);
+                    Ok(match forget_after {
+                        Some(_) => output.with(LAST_SEEN_KEY, FieldType::Float),
+                        None => output,
+                    })
+                },
+            ))
+        }
+        "service" => {
+            let port_key: String = match parser.peek() {
+                Some(Token::Word(w)) if w != "as" => parser.expect_word()?,
+                _ => WellKnownKey::L4Dport.into(),
+            };
+            let out_key: String = if parser.eat_keyword("as") {
+                parser.expect_word()?
+            } else {
+                SERVICE_KEY.to_string()
+            };
+            let check_port_key: String = port_key.clone();
+            let check_out_key: String = out_key.clone();
+            PlanStage::map(label, service_map(port_key, out_key)).with_check(Box::new(
+                move |input: &Schema, stage: &str| {
+                    input.require(&check_port_key, FieldType::Int, stage)?;
+                    Ok(input.clone().with(&check_out_key, FieldType::Str))
+                },
+            ))
+        }
+        "sort" => {
+            let key: String = parser.expect_word()?;
+            let ascending: bool = !parser.eat_keyword("desc");
+            if ascending {
+                parser.eat_keyword("asc");
+            }
+            let limit: Option<usize> = if parser.eat_keyword("limit") {
+                let word: String = parser.expect_word()?;
+                Some(
+                    word.parse::<usize>()
+                        .map_err(|_| invalid_error("sort limit", &word))?,
+                )
+            } else {
+                None
+            };
+            PlanStage::sort(key, ascending, limit)
+        }
+        "sample" => {
+            let word: String = parser.expect_word()?;
+            let prob: f64 = word
+                .parse::<f64>()
+                .ok()
+                .filter(|prob: &f64| (0.0..=1.0).contains(prob))
+                .ok_or_else(|| invalid_error("sampling probability", &word))?;
+            let seed: u64 = if parser.eat_keyword("seed") {
+                let word: String = parser.expect_word()?;
+                word.parse::<u64>()
+                    .map_err(|_| invalid_error("seed", &word))?
+            } else {
+                0
+            };
+            PlanStage::sample(prob, seed)
+        }
+        "every" => {
+            let word: String = parser.expect_word()?;
+            let n: usize = word
+                .parse::<usize>()
+                .ok()
+                .filter(|n: &usize| *n > 0)
+                .ok_or_else(|| invalid_error("sampling interval", &word))?;
+            PlanStage::every_nth(n)
+        }
+        "throttle" => {
+            let keys: Vec<String> = parser.parse_keys()?;
+            let word: String = parser.expect_word()?;
+            let max_per_interval: usize = word
+                .parse::<usize>()
+                .map_err(|_| invalid_error("throttle limit", &word))?;
+            parser.expect_keyword("per")?;
+            let interval: f64 = parse_duration(&parser.expect_word()?)?;
+            let check_keys: Vec<String> = schema_keys(&keys);
+            PlanStage::throttle(label, grouping_of_keys(keys), max_per_interval, interval)
+                .with_check(Box::new(move |input: &Schema, stage: &str| {
+                    input.project(&check_keys, stage)?;
+                    Ok(input.clone())
+                }))
+        }
+        "biflow" => {
+            let keys: Vec<String> = parser.parse_keys()?;
+            let timeout: f64 = if parser.eat_keyword("timeout") {
+                parse_duration(&parser.expect_word()?)?
+            } else {
+                DEFAULT_BIFLOW_TIMEOUT
+            };
+            PlanStage::biflow(keys, timeout)
+        }
+        "streams" => {
+            let mut options: TcpStreamOptions = TcpStreamOptions::default();
+            if parser.eat_keyword("idle") {
+                options.idle_timeout = parse_duration(&parser.expect_word()?)?;
+            }
+            PlanStage::tcp_streams(options)
+        }
+        other => return Err(StreamError::unknown(InputKind::Query, "stage", other)),
+    };
+    if !parser.at_end() {
+        return Err(expected_error("the end of the stage", parser.peek()));
+    }
+    Ok(stage.with_identity(identity))
+}
+
+pub fn parse_query(src: &str) -> Result<Vec<PlanStage>, StreamError> {
+    parse_query_with_budget(src, None)
+}
+
+pub fn parse_query_with_budget(
+    src: &str,
+    default_budget: Option<&MemoryBudget>,
+) -> Result<Vec<PlanStage>, StreamError> {
+    parse_tokens_with_budget(&tokenize(src)?, default_budget)
+}
+
+pub fn parse_tokens_with_budget(
+    tokens: &[Token],
+    default_budget: Option<&MemoryBudget>,
+) -> Result<Vec<PlanStage>, StreamError> {
+    tokens
+        .split(|token: &Token| *token == Token::Pipe)
+        .map(|stage_tokens: &[Token]| {
+            if stage_tokens.is_empty() {
+                Err(dsl_error("empty stage in query".to_string()))
+            } else {
+                parse_stage_with_budget(stage_tokens.to_vec(), default_budget)
+            }
+        })
+        .collect()
+}
+
+pub fn leading_filters(src: &str) -> Result<Vec<Predicate>, StreamError> {
+    leading_filters_of_tokens(&tokenize(src)?)
+}
+
+pub fn leading_filters_of_tokens(tokens: &[Token]) -> Result<Vec<Predicate>, StreamError> {
+    let mut filters: Vec<Predicate> = Vec::new();
+    for stage_tokens in tokens.split(|token: &Token| *token == Token::Pipe) {
+        let mut parser: Parser = Parser::new(stage_tokens.to_vec());
+        if !parser.eat_keyword("filter") {
+            break;
+        }
+        filters.push(parser.parse_predicate()?);
+    }
+    Ok(filters)
+}
+
+pub fn check_query(src: &str, input: &Schema) -> Result<Schema, StreamError> {
+    Ok(check_stages(&parse_query(src)?, input)?)
+}
+
+pub fn compile_query(src: &str, next_op: OperatorRef) -> Result<OperatorRef, StreamError> {
+    Ok(fuse_stages(parse_query(src)?)
+        .into_iter()
+        .rev()
+        .fold(next_op, |acc: OperatorRef, stage: PlanStage| {
+            stage.build(acc)
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::{create_map_expr_operator, get_mapped_int};
+    use crate::error::ParseError;
+    use crate::testing::{TestSink, run_trace, tuple};
+
+    fn labels(src: &str) -> Vec<String> {
+        parse_query(src)
+            .unwrap()
+            .into_iter()
+            .map(|stage: PlanStage| stage.label)
+            .collect()
+    }
+
+    fn word(w: &str) -> Token {
+        Token::Word(w.to_string())
+    }
+
+    fn op(o: &str) -> Token {
+        Token::Op(o.to_string())
+    }
+
+    #[test]
+    fn every_stage_keyword_parses_into_its_stage() {
+        let cases: [(&str, &str); 16] = [
+            ("epoch 1s", "epoch(1, eid)"),
+            ("epoch 5m aligned offset 30s", "epoch(300, eid, aligned 30)"),
+            ("epoch 100 tuples as n", "count_epoch(100, n)"),
+            ("filter a == 1", "filter(filter a == 1)"),
+            ("map x = a + 1", "map(map x = a + 1)"),
+            (
+                "groupby a, b count as n",
+                "groupby(groupby a , b count as n, n)",
+            ),
+            ("distinct *", "distinct(distinct *)"),
+            (
+                "distinct a within 10s",
+                "distinct_ttl(distinct a within 10s, 10)",
+            ),
+            (
+                "first_seen a forget 1h",
+                "first_seen(first_seen a forget 1h)",
+            ),
+            ("service port as svc", "map(service port as svc)"),
+            ("sort n desc limit 3", "sort(n, desc, 3)"),
+            ("sample 0.5 seed 7", "sample(0.5, 7)"),
+            ("every 10", "every_nth(10)"),
+            ("throttle a 5 per 1s", "throttle(throttle a 5 per 1s, 5, 1)"),
+            ("biflow a, b timeout 30s", "biflow(a, b, 30)"),
+            ("streams idle 5s", "tcp_streams(5)"),
+        ];
+        for (src, label) in cases {
+            assert_eq!(labels(src), vec![label.to_string()], "{}", src);
+        }
+        assert!(matches!(
+            parse_query("bogus a"),
+            Err(StreamError::Parse(ParseError::Unknown { .. }))
+        ));
+    }
+
+    #[test]
+    fn between_is_an_inclusive_range() {
+        let pred: Predicate = Parser::new(tokenize("a between 1 and 5").unwrap())
+            .parse_predicate()
+            .unwrap();
+        let at = |a: i32| pred.eval(&tuple(&[("a", OpResult::Int(a))]));
+        assert_eq!([0, 1, 3, 5, 6].map(at), [false, true, true, true, false]);
+        assert!(parse_query("filter a between 1 5").is_err());
+    }
+
+    #[test]
+    fn unquoted_hyphens_tokenize_as_arithmetic() {
+        assert_eq!(
+            tokenize("syns+synacks-acks").unwrap(),
+            vec![
+                word("syns"),
+                op("+"),
+                word("synacks"),
+                op("-"),
+                word("acks")
+            ]
+        );
+        assert_eq!(
+            tokenize("\"syns+synacks-acks\" >= 3").unwrap(),
+            vec![word("\"syns+synacks-acks\""), op(">="), word("3")]
+        );
+        let map_expr: MapExpr =
+            parse_map_expr("\"syns+synacks-acks\" = syns+synacks-acks").unwrap();
+        assert_eq!(map_expr.out_key, "syns+synacks-acks");
+        assert_eq!(map_expr.expr.fields(), vec!["syns", "synacks", "acks"]);
+    }
+
+    #[test]
+    fn literals_are_classified_before_field_names() {
+        assert_eq!(parse_literal("7"), Some(OpResult::Int(7)));
+        assert_eq!(
+            parse_literal("1e3"),
+            Some(OpResult::Float(OrderedFloat(1000.0)))
+        );
+        assert!(matches!(parse_literal("nan"), Some(OpResult::Float(f)) if f.is_nan()));
+        assert_eq!(
+            parse_literal("inf"),
+            Some(OpResult::Float(OrderedFloat(f64::INFINITY)))
+        );
+        assert_eq!(
+            parse_literal("10.0.0.1"),
+            Some(OpResult::IPv4(Ipv4Addr::new(10, 0, 0, 1)))
+        );
+        assert_eq!(
+            parse_literal("\"ssh\""),
+            Some(OpResult::Str("ssh".to_string()))
+        );
+        assert_eq!(parse_literal("host"), None);
+        assert_eq!(
+            Operand::of_word("inf"),
+            Operand::Literal(OpResult::Float(OrderedFloat(f64::INFINITY)))
+        );
+        assert_eq!(Operand::of_word("host"), Operand::Field("host".to_string()));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_offending_input() {
+        let message = |src: &str| parse_query(src).err().unwrap().to_string();
+        assert_eq!(message("filter a & b"), "unexpected '&' at offset 9");
+        assert_eq!(
+            message("filter a == \"x"),
+            "unterminated string at offset 12"
+        );
+        assert_eq!(
+            message("epoch 1s extra"),
+            "expected the end of the stage, found Some(Word(\"extra\"))"
+        );
+        assert_eq!(message("map x = a +"), "expected an operand, found None");
+        assert_eq!(message("filter a == 1 | | every 2"), "empty stage in query");
+    }
+
+    #[test]
+    fn map_expr_operator_evaluates_unspaced_arithmetic() {
+        let input: Schema = Schema::new()
+            .with("a", FieldType::Int)
+            .with("b", FieldType::Int);
+        let sink: TestSink = run_trace(
+            |next_op: OperatorRef| {
+                create_map_expr_operator("\"a-b\" = a-b*-2", &input, next_op).unwrap()
+            },
+            vec![tuple(&[("a", OpResult::Int(10)), ("b", OpResult::Int(3))])],
+        );
+        assert_eq!(get_mapped_int("a-b", &sink.emitted()[0]), 16);
+    }
+
+    #[test]
+    fn map_expr_operator_rejects_unknown_fields() {
+        let input: Schema = Schema::new().with("a", FieldType::Int);
+        let sink: TestSink = TestSink::new();
+        assert!(create_map_expr_operator("c = a + b", &input, sink.operator()).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+#![allow(dead_code)]
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::builtins::Severity;
+use crate::error::StreamError;
+use crate::trace_event;
+use crate::utils::{Headers, OpResult, Operator, OperatorRef, render_template, string_of_headers};
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Error, Write};
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub const SMTP_DEFAULT_SERVER: &str = "127.0.0.1:25";
+pub const DEFAULT_DIGEST_SUBJECT: &str = "[sonata] {count} alerts";
+
+#[derive(Clone, Debug, Default)]
+pub struct SmtpConfig {
+    pub server: String,
+    pub hello: String,
+    pub from: String,
+    pub to: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+fn smtp_error(msg: String) -> StreamError {
+    StreamError::Io(Error::other(msg))
+}
+
+struct SmtpSession {
+    reader: BufReader<TcpStream>,
+}
+
+impl SmtpSession {
+    fn expect(&mut self, code: &str) -> Result<(), StreamError> {
+        loop {
+            let mut line: String = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(smtp_error("smtp: connection closed".to_string()));
+            }
+            if !line.starts_with(code) {
+                return Err(smtp_error(format!("smtp: {}", line.trim_end())));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn command(&mut self, line: &str, code: &str) -> Result<(), StreamError> {
+        write!(self.reader.get_mut(), "{}\r\n", line)?;
+        self.expect(code)
+    }
+}
+
+pub fn dot_stuff(body: &str) -> String {
+    body.lines()
+        .map(|line: &str| match line.strip_prefix('.') {
+            Some(_) => format!(".{}\r\n", line),
+            None => format!("{}\r\n", line),
+        })
+        .collect()
+}
+
+pub fn send_email(cfg: &SmtpConfig, subject: &str, body: &str) -> Result<(), StreamError> {
+    let mut session: SmtpSession = SmtpSession {
+        reader: BufReader::new(TcpStream::connect(&cfg.server)?),
+    };
+    session.expect("220")?;
+    session.command(&format!("EHLO {}", cfg.hello), "250")?;
+    if let (Some(username), Some(password)) = (&cfg.username, &cfg.password) {
+        let token: String = STANDARD.encode(format!("\0{}\0{}", username, password));
+        session.command(&format!("AUTH PLAIN {}", token), "235")?;
+    }
+    session.command(&format!("MAIL FROM:<{}>", cfg.from), "250")?;
+    for rcpt in cfg.to.iter() {
+        session.command(&format!("RCPT TO:<{}>", rcpt), "250")?;
+    }
+    session.command("DATA", "354")?;
+    let message: String = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}.",
+        cfg.from,
+        cfg.to.join(", "),
+        subject,
+        dot_stuff(body)
+    );
+    session.command(&message, "250")?;
+    session.command("QUIT", "221")
+}
+
+pub struct EmailDigest {
+    pub smtp: SmtpConfig,
+    pub key: String,
+    pub threshold: f64,
+    pub interval: Duration,
+    pub subject: String,
+    pub line: Option<String>,
+    rows: Vec<Headers>,
+    last_sent: Instant,
+}
+
+impl EmailDigest {
+    pub fn new(smtp: SmtpConfig, key: &str, threshold: f64, interval: Duration) -> Self {
+        EmailDigest {
+            smtp,
+            key: key.to_string(),
+            threshold,
+            interval,
+            subject: DEFAULT_DIGEST_SUBJECT.to_string(),
+            line: None,
+            rows: Vec::new(),
+            last_sent: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, headers: &Headers) {
+        let severity: Severity = match headers.get(&self.key) {
+            Some(OpResult::Int(i)) => Severity::of_value(*i as f64, self.threshold),
+            Some(OpResult::Float(f)) => Severity::of_value(f.0, self.threshold),
+            _ => Severity::Normal,
+        };
+        if severity != Severity::Normal {
+            self.rows.push(headers.clone());
+        }
+    }
+
+    fn render_line(&self, row: &Headers) -> String {
+        match &self.line {
+            Some(template) => render_template(template, row),
+            None => string_of_headers(row),
+        }
+    }
+
+    pub fn render(&self) -> (String, String) {
+        let mut summary: Headers = self.rows.first().cloned().unwrap_or_default();
+        summary.insert("count".to_string(), OpResult::Int(self.rows.len() as i32));
+        let subject: String = render_template(&self.subject, &summary);
+        let body: String = self
+            .rows
+            .iter()
+            .map(|row: &Headers| self.render_line(row) + "\n")
+            .collect();
+        (subject, body)
+    }
+
+    pub fn flush(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let (subject, body) = self.render();
+        match send_email(&self.smtp, &subject, &body) {
+            Ok(()) => self.rows.clear(),
+            Err(_e) => {
+                trace_event!(
+                    rows = self.rows.len(),
+                    error = %_e,
+                    "email: digest not sent, keeping it for the next flush"
+                );
+            }
+        }
+        self.last_sent = Instant::now();
+    }
+
+    pub fn flush_if_due(&mut self) {
+        if self.last_sent.elapsed() >= self.interval {
+            self.flush();
+        }
+    }
+}
+
+impl Drop for EmailDigest {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+pub fn alert_email(digest: EmailDigest) -> OperatorRef {
+    let label: String = format!("email({})", digest.key);
+    let digest: Rc<RefCell<EmailDigest>> = Rc::new(RefCell::new(digest));
+    let digest_ref_clone = Rc::clone(&digest);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| digest.borrow_mut().push(headers));
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |_headers: &mut Headers| digest_ref_clone.borrow_mut().flush_if_due());
+
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label(label)))
+}
@@ -0,0 +1,517 @@
+#![allow(dead_code)]
+
+use maxminddb::{Reader, geoip2};
+
+use crate::builtins::{ipv4_in_cidr, parse_cidr};
+use crate::error::{SchemaError, StreamError};
+use crate::json::headers_of_json;
+use crate::keys::WellKnownKey;
+use crate::trace_event;
+use crate::utils::{Headers, OpResult, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const SOURCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn open_mmdb(path: &str) -> Result<Reader<Vec<u8>>, StreamError> {
+    Reader::open_readfile(path)
+        .map_err(|e| StreamError::Io(Error::new(ErrorKind::InvalidData, e.to_string())))
+}
+
+pub fn geoip_headers(readers: &[Reader<Vec<u8>>], addr: IpAddr) -> Headers {
+    let mut geo_headers: Headers = Headers::new();
+    for reader in readers {
+        if let Ok(record) = reader.lookup::<geoip2::Country>(addr)
+            && let Some(iso_code) = record.country.and_then(|c| c.iso_code)
+        {
+            geo_headers.insert(
+                "geo.country".to_string(),
+                OpResult::Str(iso_code.to_string()),
+            );
+        }
+        if let Ok(record) = reader.lookup::<geoip2::Asn>(addr)
+            && let Some(asn) = record
+                .autonomous_system_number
+                .and_then(|n| i32::try_from(n).ok())
+        {
+            geo_headers.insert("geo.asn".to_string(), OpResult::Int(asn));
+        }
+    }
+    geo_headers
+}
+
+pub fn create_geoip_operator(
+    mmdb_paths: Vec<String>,
+    ip_key: String,
+    next_op: OperatorRef,
+) -> Result<OperatorRef, StreamError> {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let readers: Vec<Reader<Vec<u8>>> = mmdb_paths
+        .iter()
+        .map(|path: &String| open_mmdb(path))
+        .collect::<Result<_, _>>()?;
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if let Some(OpResult::IPv4(addr)) = headers.get(&ip_key) {
+            let geo_headers: Headers = geoip_headers(&readers, IpAddr::V4(*addr));
+            headers.extend(geo_headers);
+        }
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Ok(Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("geoip")
+            .with_downstream(downstream),
+    )))
+}
+
+pub struct Blocklist {
+    pub hosts: HashSet<Ipv4Addr>,
+    pub networks: Vec<(Ipv4Addr, u8)>,
+    pub skipped: usize,
+}
+
+impl Blocklist {
+    pub fn contains(&self, addr: Ipv4Addr) -> bool {
+        self.hosts.contains(&addr)
+            || self
+                .networks
+                .iter()
+                .any(|(network, prefix_len)| ipv4_in_cidr(addr, *network, *prefix_len))
+    }
+}
+
+pub fn read_source(path_or_url: &str) -> Result<String, StreamError> {
+    if path_or_url.starts_with("http://") || path_or_url.starts_with("https://") {
+        ureq::get(path_or_url)
+            .timeout(SOURCE_TIMEOUT)
+            .call()
+            .map_err(|e| Error::other(e.to_string()))?
+            .into_string()
+            .map_err(StreamError::Io)
+    } else {
+        Ok(fs::read_to_string(path_or_url)?)
+    }
+}
+
+pub fn load_blocklist(path_or_url: &str) -> Result<Blocklist, StreamError> {
+    let mut blocklist: Blocklist = Blocklist {
+        hosts: HashSet::new(),
+        networks: Vec::new(),
+        skipped: 0,
+    };
+    for line in read_source(path_or_url)?.lines() {
+        let entry: &str = line.split(['#', ';']).next().unwrap_or("").trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match parse_cidr(entry) {
+            Ok((addr, 32)) => {
+                blocklist.hosts.insert(addr);
+            }
+            Ok(network) => blocklist.networks.push(network),
+            Err(_) => blocklist.skipped += 1,
+        }
+    }
+    if blocklist.skipped > 0 {
+        trace_event!(
+            skipped = blocklist.skipped,
+            source = path_or_url,
+            "blocklist: skipped malformed entries"
+        );
+    }
+    Ok(blocklist)
+}
+
+fn spawn_blocklist_reloader(
+    path_or_url: String,
+    reload_secs: f64,
+    current: Weak<Mutex<Arc<Blocklist>>>,
+) {
+    thread::spawn(move || {
+        let mut _failures: usize = 0;
+        loop {
+            thread::sleep(Duration::from_secs_f64(reload_secs));
+            let reloaded: Result<Blocklist, StreamError> = load_blocklist(&path_or_url);
+            let Some(current) = current.upgrade() else {
+                return;
+            };
+            match reloaded {
+                Ok(blocklist) => *current.lock().unwrap() = Arc::new(blocklist),
+                Err(_e) => {
+                    _failures += 1;
+                    trace_event!(
+                        source = %path_or_url,
+                        failures = _failures,
+                        error = %_e,
+                        "blocklist: reload failed, keeping the previous list"
+                    );
+                }
+            }
+        }
+    });
+}
+
+pub fn create_blocklist_operator(
+    path_or_url: String,
+    ip_keys: Vec<String>,
+    out_key: String,
+    reload_secs: Option<f64>,
+    drop_unmatched: bool,
+    next_op: OperatorRef,
+) -> Result<OperatorRef, StreamError> {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let current: Arc<Mutex<Arc<Blocklist>>> =
+        Arc::new(Mutex::new(Arc::new(load_blocklist(&path_or_url)?)));
+    match reload_secs {
+        Some(secs) if secs > 0.0 => {
+            spawn_blocklist_reloader(path_or_url, secs, Arc::downgrade(&current))
+        }
+        Some(secs) => {
+            return Err(StreamError::config(format!(
+                "blocklist reload interval must be positive, got {}",
+                secs
+            )));
+        }
+        None => {}
+    }
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let blocklist: Arc<Blocklist> = Arc::clone(&current.lock().unwrap());
+        let matched: bool = ip_keys.iter().any(|key: &String| match headers.get(key) {
+            Some(OpResult::IPv4(addr)) => blocklist.contains(*addr),
+            _ => false,
+        });
+        if matched || !drop_unmatched {
+            headers.insert(out_key.clone(), OpResult::Int(matched as i32));
+            (next_op.borrow_mut().next)(headers)
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Ok(Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("blocklist")
+            .with_downstream(downstream),
+    )))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OuiDb {
+    pub vendors: HashMap<[u8; 3], String>,
+}
+
+pub fn parse_oui(prefix: &str) -> Option<[u8; 3]> {
+    let hex: String = prefix
+        .chars()
+        .filter(|c: &char| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    if hex.len() != 6 {
+        return None;
+    }
+    let val: u32 = u32::from_str_radix(&hex, 16).ok()?;
+    let [_, a, b, c] = val.to_be_bytes();
+    Some([a, b, c])
+}
+
+pub fn string_of_oui(oui: &[u8; 3]) -> String {
+    format!("{:02X}:{:02X}:{:02X}", oui[0], oui[1], oui[2])
+}
+
+impl OuiDb {
+    pub fn parse(src: &str) -> OuiDb {
+        let mut vendors: HashMap<[u8; 3], String> = HashMap::new();
+        for line in src.lines() {
+            let line: &str = line.split('#').next().unwrap_or("").trim();
+            let Some((prefix, rest)) = line.split_once(|c: char| c.is_whitespace() || c == ',')
+            else {
+                continue;
+            };
+            let Some(oui) = parse_oui(prefix) else {
+                continue;
+            };
+            let rest: &str = rest
+                .trim()
+                .trim_start_matches("(hex)")
+                .trim_start_matches("(base 16)");
+            if let Some(vendor) = rest
+                .split('\t')
+                .map(str::trim)
+                .rfind(|field: &&str| !field.is_empty())
+            {
+                vendors.insert(oui, vendor.to_string());
+            }
+        }
+        OuiDb { vendors }
+    }
+
+    pub fn lookup(&self, mac: &[u8; 6]) -> Option<&str> {
+        self.vendors
+            .get(&[mac[0], mac[1], mac[2]])
+            .map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vendors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vendors.is_empty()
+    }
+}
+
+pub fn load_oui_db(path_or_url: &str) -> Result<OuiDb, StreamError> {
+    Ok(OuiDb::parse(&read_source(path_or_url)?))
+}
+
+pub fn oui_headers(oui_db: &OuiDb, mac_key: &str, mac: &[u8; 6]) -> Headers {
+    let mut oui_headers: Headers = Headers::new();
+    oui_headers.insert(
+        format!("{}.oui", mac_key),
+        OpResult::Str(string_of_oui(&[mac[0], mac[1], mac[2]])),
+    );
+    if let Some(vendor) = oui_db.lookup(mac) {
+        oui_headers.insert(
+            format!("{}.vendor", mac_key),
+            OpResult::Str(vendor.to_string()),
+        );
+    }
+    oui_headers
+}
+
+pub fn create_oui_lookup_operator(oui_db: OuiDb, next_op: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let mac_keys: [WellKnownKey; 2] = [WellKnownKey::EthSrc, WellKnownKey::EthDst];
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        for key in mac_keys {
+            if let Some(OpResult::MAC(mac)) = headers.get_known(key) {
+                let oui_headers: Headers = oui_headers(&oui_db, key.as_str(), mac);
+                headers.extend(oui_headers);
+            }
+        }
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("oui")
+            .with_downstream(downstream),
+    ))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LookupKey {
+    Exact(OpResult),
+    Network(Ipv4Addr, u8),
+}
+
+impl LookupKey {
+    fn of_op_result(val: &OpResult) -> LookupKey {
+        match val {
+            OpResult::Str(s) if s.contains('/') => match parse_cidr(s) {
+                Ok((network, prefix_len)) => LookupKey::Network(network, prefix_len),
+                Err(_) => LookupKey::Exact(val.clone()),
+            },
+            _ => LookupKey::Exact(val.clone()),
+        }
+    }
+
+    fn matches(&self, val: &OpResult) -> bool {
+        match (self, val) {
+            (LookupKey::Exact(expected), val) => expected == val,
+            (LookupKey::Network(network, prefix_len), OpResult::IPv4(addr)) => {
+                ipv4_in_cidr(*addr, *network, *prefix_len)
+            }
+            _ => false,
+        }
+    }
+
+    fn specificity(&self) -> u32 {
+        match self {
+            LookupKey::Exact(_) => 33,
+            LookupKey::Network(_, prefix_len) => *prefix_len as u32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LookupTable {
+    pub key_fields: Vec<String>,
+    exact: HashMap<Headers, Headers>,
+    networks: Vec<(Vec<LookupKey>, Headers)>,
+}
+
+pub type LookupTableRef = Rc<RefCell<LookupTable>>;
+
+impl LookupTable {
+    pub fn new(key_fields: Vec<String>, rows: Vec<Headers>) -> Result<Self, StreamError> {
+        let mut table: LookupTable = LookupTable {
+            key_fields,
+            ..LookupTable::default()
+        };
+        table.refresh(rows)?;
+        Ok(table)
+    }
+
+    pub fn refresh(&mut self, rows: Vec<Headers>) -> Result<(), StreamError> {
+        let mut exact: HashMap<Headers, Headers> = HashMap::new();
+        let mut networks: Vec<(Vec<LookupKey>, Headers)> = Vec::new();
+        for mut row in rows {
+            let mut keys: Vec<LookupKey> = Vec::new();
+            for field in self.key_fields.iter() {
+                let val: OpResult = row
+                    .remove(field)
+                    .ok_or_else(|| SchemaError::Missing(field.clone()))?;
+                keys.push(LookupKey::of_op_result(&val));
+            }
+            let exact_vals: Option<Vec<OpResult>> = keys
+                .iter()
+                .map(|key: &LookupKey| match key {
+                    LookupKey::Exact(val) => Some(val.clone()),
+                    LookupKey::Network(..) => None,
+                })
+                .collect();
+            match exact_vals {
+                Some(vals) => {
+                    exact.insert(self.key_fields.iter().cloned().zip(vals).collect(), row);
+                }
+                None => networks.push((keys, row)),
+            }
+        }
+        networks.sort_by_key(|(keys, _)| {
+            std::cmp::Reverse(keys.iter().map(LookupKey::specificity).sum::<u32>())
+        });
+        self.exact = exact;
+        self.networks = networks;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.exact.len() + self.networks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn lookup(&self, headers: &Headers) -> Option<&Headers> {
+        let vals: Vec<&OpResult> = self
+            .key_fields
+            .iter()
+            .map(|field: &String| headers.get(field))
+            .collect::<Option<_>>()?;
+        let key: Headers = self
+            .key_fields
+            .iter()
+            .cloned()
+            .zip(vals.iter().map(|val: &&OpResult| (*val).clone()))
+            .collect();
+        self.exact.get(&key).or_else(|| {
+            self.networks
+                .iter()
+                .find(|(keys, _)| {
+                    keys.iter()
+                        .zip(vals.iter())
+                        .all(|(key, val)| key.matches(val))
+                })
+                .map(|(_, row)| row)
+        })
+    }
+}
+
+pub fn load_lookup_rows(path_or_url: &str) -> Result<Vec<Headers>, StreamError> {
+    read_source(path_or_url)?
+        .lines()
+        .map(str::trim)
+        .filter(|line: &&str| !line.is_empty() && !line.starts_with('#'))
+        .map(headers_of_json)
+        .collect()
+}
+
+pub fn create_lookup_join_operator(
+    table: LookupTableRef,
+    drop_unmatched: bool,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = format!("lookup_join({})", table.borrow().key_fields.join(", "));
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let row: Option<Headers> = table.borrow().lookup(headers).cloned();
+        match row {
+            Some(row) => headers.extend(row),
+            None if drop_unmatched => return,
+            None => {}
+        }
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
+
+pub fn create_reloading_lookup_join_operator(
+    path_or_url: String,
+    key_fields: Vec<String>,
+    reload_secs: f64,
+    drop_unmatched: bool,
+    next_op: OperatorRef,
+) -> Result<OperatorRef, StreamError> {
+    let table: LookupTableRef = Rc::new(RefCell::new(LookupTable::new(
+        key_fields,
+        load_lookup_rows(&path_or_url)?,
+    )?));
+    let table_ref_clone = Rc::clone(&table);
+    let mut loaded_at: Instant = Instant::now();
+    let join_op: OperatorRef = create_lookup_join_operator(table, drop_unmatched, next_op);
+    let join_op_ref_clone = Rc::clone(&join_op);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&join_op)];
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if loaded_at.elapsed().as_secs_f64() >= reload_secs {
+            if let Err(_e) = load_lookup_rows(&path_or_url)
+                .and_then(|rows: Vec<Headers>| table_ref_clone.borrow_mut().refresh(rows))
+            {
+                trace_event!(error = %_e, "lookup_join: keeping the previous table");
+            }
+            loaded_at = Instant::now();
+        }
+        (join_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (join_op_ref_clone.borrow_mut().reset)(headers));
+
+    Ok(Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("lookup_reload")
+            .with_downstream(downstream),
+    )))
+}
@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+
+use thiserror::Error;
+
+pub use crate::schema::SchemaError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputKind {
+    Query,
+    Config,
+    Param,
+    Value,
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("This query is empty, cannot collect on an empty query")]
+    EmptyQuery,
+    #[error("This query has no end operator to collect into")]
+    MissingEndOp,
+    #[error("unknown {what} '{name}'")]
+    Unknown {
+        kind: InputKind,
+        what: &'static str,
+        name: String,
+    },
+    #[error("invalid {what} '{found}'")]
+    Invalid {
+        kind: InputKind,
+        what: &'static str,
+        found: String,
+    },
+    #[error("expected {expected}, found {found}")]
+    Unexpected {
+        kind: InputKind,
+        expected: String,
+        found: String,
+    },
+    #[error("invalid {format}: {msg}")]
+    Syntax {
+        kind: InputKind,
+        format: &'static str,
+        msg: String,
+    },
+    #[error("{msg}")]
+    Rejected { kind: InputKind, msg: String },
+    #[error("malformed {protocol}: {msg}")]
+    Packet { protocol: &'static str, msg: String },
+}
+
+impl ParseError {
+    pub fn kind(&self) -> Option<InputKind> {
+        match self {
+            ParseError::EmptyQuery | ParseError::MissingEndOp => Some(InputKind::Query),
+            ParseError::Unknown { kind, .. }
+            | ParseError::Invalid { kind, .. }
+            | ParseError::Unexpected { kind, .. }
+            | ParseError::Syntax { kind, .. }
+            | ParseError::Rejected { kind, .. } => Some(*kind),
+            ParseError::Packet { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("corrupt state entry: {0}")]
+    Codec(String),
+    #[error("state backend: {0}")]
+    Backend(String),
+    #[error("{label}: memory budget of {limit} bytes exceeded")]
+    BudgetExceeded { label: String, limit: usize },
+}
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    State(#[from] StateError),
+    #[error("channel worker panicked: {0}")]
+    WorkerPanicked(String),
+    #[error("{scope} '{name}': {source}")]
+    Within {
+        scope: &'static str,
+        name: String,
+        #[source]
+        source: Box<StreamError>,
+    },
+}
+
+impl StreamError {
+    pub fn query(msg: impl Into<String>) -> Self {
+        StreamError::rejected(InputKind::Query, msg)
+    }
+
+    pub fn config(msg: impl Into<String>) -> Self {
+        StreamError::rejected(InputKind::Config, msg)
+    }
+
+    pub fn param(msg: impl Into<String>) -> Self {
+        StreamError::rejected(InputKind::Param, msg)
+    }
+
+    pub fn value(msg: impl Into<String>) -> Self {
+        StreamError::rejected(InputKind::Value, msg)
+    }
+
+    pub fn rejected(kind: InputKind, msg: impl Into<String>) -> Self {
+        StreamError::Parse(ParseError::Rejected {
+            kind,
+            msg: msg.into(),
+        })
+    }
+
+    pub fn unknown(kind: InputKind, what: &'static str, name: impl Into<String>) -> Self {
+        StreamError::Parse(ParseError::Unknown {
+            kind,
+            what,
+            name: name.into(),
+        })
+    }
+
+    pub fn invalid(kind: InputKind, what: &'static str, found: impl Into<String>) -> Self {
+        StreamError::Parse(ParseError::Invalid {
+            kind,
+            what,
+            found: found.into(),
+        })
+    }
+
+    pub fn unexpected(
+        kind: InputKind,
+        expected: impl Into<String>,
+        found: impl Into<String>,
+    ) -> Self {
+        StreamError::Parse(ParseError::Unexpected {
+            kind,
+            expected: expected.into(),
+            found: found.into(),
+        })
+    }
+
+    pub fn syntax(kind: InputKind, format: &'static str, msg: impl ToString) -> Self {
+        StreamError::Parse(ParseError::Syntax {
+            kind,
+            format,
+            msg: msg.to_string(),
+        })
+    }
+
+    pub fn within(self, scope: &'static str, name: impl Into<String>) -> Self {
+        StreamError::Within {
+            scope,
+            name: name.into(),
+            source: Box::new(self),
+        }
+    }
+}
@@ -0,0 +1,291 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+use serde_json::Value;
+
+use crate::error::{ParseError, StreamError};
+use crate::packet::{IPPROTO_ICMP, IPPROTO_TCP, IPPROTO_UDP};
+use crate::schema::{FieldType, Schema};
+use crate::utils::{Headers, OpResult};
+use std::fs;
+use std::net::Ipv4Addr;
+
+pub const EVE_INT_FIELDS: [(&str, &str); 11] = [
+    ("alert", "signature_id"),
+    ("alert", "gid"),
+    ("alert", "rev"),
+    ("alert", "severity"),
+    ("flow", "pkts_toserver"),
+    ("flow", "pkts_toclient"),
+    ("flow", "bytes_toserver"),
+    ("flow", "bytes_toclient"),
+    ("flow", "age"),
+    ("", "src_port"),
+    ("", "dest_port"),
+];
+
+pub const EVE_STR_FIELDS: [(&str, &str); 8] = [
+    ("alert", "signature"),
+    ("alert", "category"),
+    ("alert", "action"),
+    ("flow", "state"),
+    ("flow", "reason"),
+    ("", "event_type"),
+    ("", "app_proto"),
+    ("", "flow_id"),
+];
+
+fn eve_error(msg: String) -> StreamError {
+    StreamError::Parse(ParseError::Packet {
+        protocol: "eve.json",
+        msg,
+    })
+}
+
+pub fn eve_key(section: &str, field: &str) -> String {
+    match (section, field) {
+        ("", "src_port") => "l4.sport".to_string(),
+        ("", "dest_port") => "l4.dport".to_string(),
+        ("", field) => format!("eve.{}", field),
+        ("alert", "signature_id") => "alert.sid".to_string(),
+        (section, field) => format!("{}.{}", section, field),
+    }
+}
+
+impl Schema {
+    pub fn eve() -> Self {
+        let schema: Schema = Schema::new()
+            .with("time", FieldType::Float)
+            .with("ipv4.src", FieldType::IPv4)
+            .with("ipv4.dst", FieldType::IPv4)
+            .with("ipv4.proto", FieldType::Int);
+        let schema: Schema =
+            EVE_INT_FIELDS
+                .iter()
+                .fold(schema, |schema: Schema, (section, field)| {
+                    schema.with(&eve_key(section, field), FieldType::Int)
+                });
+        EVE_STR_FIELDS
+            .iter()
+            .fold(schema, |schema: Schema, (section, field)| {
+                schema.with(&eve_key(section, field), FieldType::Str)
+            })
+    }
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year: i64 = if month <= 2 { year - 1 } else { year };
+    let era: i64 = year.div_euclid(400);
+    let yoe: i64 = year - era * 400;
+    let doy: i64 = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe: i64 = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub fn parse_eve_timestamp(ts: &str) -> Option<f64> {
+    let (date, time) = ts.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part: &str| part.parse::<i64>());
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    if !(0..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let split: usize = time.rfind(['+', '-', 'Z']).unwrap_or(time.len());
+    let (clock, zone) = time.split_at(split);
+    let mut clock = clock.splitn(3, ':');
+    let hours: i64 = clock
+        .next()?
+        .parse()
+        .ok()
+        .filter(|h: &i64| (0..24).contains(h))?;
+    let minutes: i64 = clock
+        .next()?
+        .parse()
+        .ok()
+        .filter(|m: &i64| (0..60).contains(m))?;
+    let seconds: f64 = clock
+        .next()?
+        .parse()
+        .ok()
+        .filter(|s: &f64| (0.0..61.0).contains(s))?;
+    let offset: i64 = match zone {
+        "" | "Z" => 0,
+        _ => {
+            let digits: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            if digits.len() != 4 {
+                return None;
+            }
+            let minutes: i64 =
+                digits[..2].parse::<i64>().ok()? * 60 + digits[2..].parse::<i64>().ok()?;
+            if zone.starts_with('-') {
+                -minutes
+            } else {
+                minutes
+            }
+        }
+    };
+    let days: i64 = days_from_civil(year, month, day);
+    Some((days * 86400 + hours * 3600 + (minutes - offset) * 60) as f64 + seconds)
+}
+
+pub fn eve_proto(proto: &str) -> Option<i32> {
+    match proto.to_ascii_uppercase().as_str() {
+        "TCP" => Some(IPPROTO_TCP),
+        "UDP" => Some(IPPROTO_UDP),
+        "ICMP" => Some(IPPROTO_ICMP),
+        other => other.parse::<i32>().ok(),
+    }
+}
+
+fn eve_field<'a>(record: &'a Value, section: &str, field: &str) -> Option<&'a Value> {
+    match section {
+        "" => record.get(field),
+        section => record.get(section)?.get(field),
+    }
+}
+
+pub fn headers_of_eve(record: &Value) -> Result<Option<Headers>, StreamError> {
+    eve_headers(record).map_err(eve_error)
+}
+
+fn eve_headers(record: &Value) -> Result<Option<Headers>, String> {
+    let ip = |field: &str| -> Option<Ipv4Addr> { record.get(field)?.as_str()?.parse().ok() };
+    let (Some(src), Some(dst)) = (ip("src_ip"), ip("dest_ip")) else {
+        return Ok(None);
+    };
+    let time: f64 = record
+        .get("timestamp")
+        .and_then(Value::as_str)
+        .and_then(parse_eve_timestamp)
+        .ok_or_else(|| "record without a valid timestamp".to_string())?;
+    let mut headers: Headers = Headers::new();
+    headers.insert("time".to_string(), OpResult::Float(OrderedFloat(time)));
+    headers.insert("ipv4.src".to_string(), OpResult::IPv4(src));
+    headers.insert("ipv4.dst".to_string(), OpResult::IPv4(dst));
+    if let Some(proto) = record
+        .get("proto")
+        .and_then(Value::as_str)
+        .and_then(eve_proto)
+    {
+        headers.insert("ipv4.proto".to_string(), OpResult::Int(proto));
+    }
+    for (section, field) in EVE_INT_FIELDS.iter() {
+        if let Some(n) = eve_field(record, section, field).and_then(Value::as_i64) {
+            let n: i32 = n.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+            headers.insert(eve_key(section, field), OpResult::Int(n));
+        }
+    }
+    for (section, field) in EVE_STR_FIELDS.iter() {
+        match eve_field(record, section, field) {
+            Some(Value::String(s)) => {
+                headers.insert(eve_key(section, field), OpResult::Str(s.clone()));
+            }
+            Some(Value::Number(n)) => {
+                headers.insert(eve_key(section, field), OpResult::Str(n.to_string()));
+            }
+            _ => {}
+        }
+    }
+    Ok(Some(headers))
+}
+
+pub fn decode_eve(src: &str, event_types: &[String]) -> Result<Vec<Headers>, StreamError> {
+    let mut rows: Vec<Headers> = Vec::new();
+    for (lineno, line) in src.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(line)
+            .map_err(|e| eve_error(format!("line {}: {}", lineno + 1, e)))?;
+        let event_type: &str = record
+            .get("event_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if !event_types.is_empty() && !event_types.iter().any(|t: &String| t == event_type) {
+            continue;
+        }
+        if let Some(headers) = eve_headers(&record)
+            .map_err(|e: String| eve_error(format!("line {}: {}", lineno + 1, e)))?
+        {
+            rows.push(headers);
+        }
+    }
+    rows.sort_by(|a: &Headers, b: &Headers| {
+        let time = |h: &Headers| match h.get("time") {
+            Some(OpResult::Float(t)) => *t,
+            _ => OrderedFloat(0.0),
+        };
+        time(a).cmp(&time(b))
+    });
+    Ok(rows)
+}
+
+pub fn load_eve(path: &str, event_types: &[String]) -> Result<Vec<Headers>, StreamError> {
+    decode_eve(&fs::read_to_string(path)?, event_types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALERT: &str = r#"{"timestamp":"2024-03-01T12:00:05.250000+0100","flow_id":1234,"event_type":"alert","src_ip":"10.0.0.1","src_port":51000,"dest_ip":"10.0.0.2","dest_port":22,"proto":"TCP","alert":{"action":"allowed","gid":1,"signature_id":2001219,"rev":20,"signature":"ET SCAN Potential SSH Scan","category":"Attempted Information Leak","severity":2}}"#;
+
+    fn time_of(headers: &Headers) -> f64 {
+        match headers.get("time") {
+            Some(OpResult::Float(t)) => t.0,
+            other => panic!("no time in {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_an_alert_fixture() {
+        let rows: Vec<Headers> = decode_eve(ALERT, &[]).unwrap();
+        assert_eq!(rows.len(), 1);
+        let row: &Headers = &rows[0];
+        assert_eq!(time_of(row), 1709290805.25);
+        assert_eq!(
+            row.get("ipv4.src"),
+            Some(&OpResult::IPv4(Ipv4Addr::new(10, 0, 0, 1)))
+        );
+        assert_eq!(row.get("ipv4.proto"), Some(&OpResult::Int(IPPROTO_TCP)));
+        assert_eq!(row.get("l4.dport"), Some(&OpResult::Int(22)));
+        assert_eq!(row.get("alert.sid"), Some(&OpResult::Int(2001219)));
+        assert_eq!(
+            row.get("eve.flow_id"),
+            Some(&OpResult::Str("1234".to_string()))
+        );
+        assert!(decode_eve(ALERT, &["flow".to_string()]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated_records_are_errors() {
+        for len in 1..ALERT.len() {
+            let line: &str = &ALERT[..len];
+            let err: StreamError = decode_eve(line, &[]).unwrap_err();
+            assert!(err.to_string().contains("line 1"), "{}", line);
+        }
+    }
+
+    #[test]
+    fn malformed_records_are_errors_or_skipped() {
+        let with_timestamp = |ts: &str| ALERT.replace("2024-03-01T12:00:05.250000+0100", ts);
+        for ts in [
+            "99999999999999999-01-01T00:00:00Z",
+            "2024-01-01T99999999999999999:00:00Z",
+            "2024-13-01T00:00:00Z",
+            "2024-01-01T00:61:00Z",
+            "2024-01-01T00:00:inf",
+            "2024-01-01T00:00:00+99999",
+            "2024-01-01",
+        ] {
+            assert_eq!(parse_eve_timestamp(ts), None, "{}", ts);
+            assert!(decode_eve(&with_timestamp(ts), &[]).is_err(), "{}", ts);
+        }
+        let ipv6: String = ALERT.replace("10.0.0.1", "fe80::1");
+        assert!(decode_eve(&ipv6, &[]).unwrap().is_empty());
+        assert!(
+            decode_eve("[1, 2]\nnull\n\"alert\"", &[])
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
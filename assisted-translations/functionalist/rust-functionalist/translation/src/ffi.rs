@@ -0,0 +1,119 @@
+#![allow(dead_code)]
+
+use crate::demo::demo_query;
+use crate::dsl::compile_query;
+use crate::json::json_of_headers;
+use crate::packet::{DecodeOptions, PacketRecord};
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::ffi::{CStr, c_char, c_int, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+use std::rc::Rc;
+use std::slice;
+
+pub const SONATA_OK: c_int = 0;
+pub const SONATA_ERR_NULL: c_int = -1;
+pub const SONATA_ERR_DECODE: c_int = -2;
+pub const SONATA_ERR_PANIC: c_int = -3;
+
+pub type SonataSinkFn =
+    extern "C" fn(user_data: *mut c_void, tuple_json: *const c_char, len: usize);
+
+pub struct SonataPipeline {
+    query: OperatorRef,
+    options: DecodeOptions,
+}
+
+fn create_callback_operator(sink: SonataSinkFn, user_data: *mut c_void) -> OperatorRef {
+    Rc::new(RefCell::new(
+        Operator::new(
+            Box::new(move |headers: &mut Headers| {
+                let mut json: Vec<u8> = json_of_headers(headers).to_string().into_bytes();
+                let len: usize = json.len();
+                json.push(0);
+                sink(user_data, json.as_ptr() as *const c_char, len)
+            }),
+            Box::new(|_: &mut Headers| {}),
+        )
+        .with_label("ffi_callback"),
+    ))
+}
+
+fn guarded(f: impl FnOnce() -> c_int) -> c_int {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(SONATA_ERR_PANIC)
+}
+
+/// # Safety
+/// `query` must be a NUL-terminated string. `user_data` is passed back to `sink` unchanged.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sonata_pipeline_new(
+    query: *const c_char,
+    decapsulate: bool,
+    sink: SonataSinkFn,
+    user_data: *mut c_void,
+) -> *mut SonataPipeline {
+    if query.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(query) = unsafe { CStr::from_ptr(query) }.to_str() else {
+        return ptr::null_mut();
+    };
+    match compile_query(demo_query(query), create_callback_operator(sink, user_data)) {
+        Ok(query) => Box::into_raw(Box::new(SonataPipeline {
+            query,
+            options: DecodeOptions {
+                decapsulate,
+                fragment_timeout: None,
+            },
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `pipeline` must come from `sonata_pipeline_new` and `data` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sonata_pipeline_feed_packet(
+    pipeline: *mut SonataPipeline,
+    data: *const u8,
+    len: usize,
+    ts: f64,
+) -> c_int {
+    let Some(pipeline) = (unsafe { pipeline.as_mut() }) else {
+        return SONATA_ERR_NULL;
+    };
+    if data.is_null() {
+        return SONATA_ERR_NULL;
+    }
+    let frame: &[u8] = unsafe { slice::from_raw_parts(data, len) };
+    let Ok(record) = PacketRecord::decode(ts, frame, &pipeline.options) else {
+        return SONATA_ERR_DECODE;
+    };
+    guarded(|| {
+        (pipeline.query.borrow_mut().next)(&mut Headers::from(record));
+        SONATA_OK
+    })
+}
+
+/// # Safety
+/// `pipeline` must come from `sonata_pipeline_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sonata_pipeline_flush(pipeline: *mut SonataPipeline) -> c_int {
+    let Some(pipeline) = (unsafe { pipeline.as_mut() }) else {
+        return SONATA_ERR_NULL;
+    };
+    guarded(|| {
+        (pipeline.query.borrow_mut().reset)(&mut Headers::new());
+        SONATA_OK
+    })
+}
+
+/// # Safety
+/// `pipeline` must come from `sonata_pipeline_new` and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sonata_pipeline_free(pipeline: *mut SonataPipeline) {
+    if !pipeline.is_null() {
+        drop(unsafe { Box::from_raw(pipeline) });
+    }
+}
@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+
+use crate::error::StreamError;
+use crate::schema::{FieldType, Schema};
+use crate::utils::{Headers, OpResult, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FieldMapper {
+    rules: Vec<(String, String)>,
+}
+
+impl FieldMapper {
+    pub fn new() -> Self {
+        FieldMapper::default()
+    }
+
+    pub fn with_rule(mut self, from: &str, to: &str) -> Self {
+        self.rules.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    pub fn rules(&self) -> &[(String, String)] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), StreamError> {
+        let mut sources: BTreeSet<&str> = BTreeSet::new();
+        let mut targets: BTreeSet<&str> = BTreeSet::new();
+        for (from, to) in self.rules.iter() {
+            if from.is_empty() || to.is_empty() {
+                return Err(StreamError::config(
+                    "field rename rules need non-empty names",
+                ));
+            }
+            if !sources.insert(from) {
+                return Err(StreamError::config(format!(
+                    "field '{}' is renamed more than once",
+                    from
+                )));
+            }
+            if !targets.insert(to) {
+                return Err(StreamError::config(format!(
+                    "more than one field is renamed to '{}'",
+                    to
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, headers: &mut Headers) {
+        let moved: Vec<(&String, OpResult)> = self
+            .rules
+            .iter()
+            .filter_map(|(from, to)| headers.remove(from).map(|val: OpResult| (to, val)))
+            .collect();
+        for (to, val) in moved {
+            headers.insert(to.clone(), val);
+        }
+    }
+
+    pub fn map_schema(&self, schema: &Schema) -> Schema {
+        let mut mapped: Schema = schema.clone();
+        let moved: Vec<(&String, FieldType)> = self
+            .rules
+            .iter()
+            .filter_map(|(from, to)| mapped.fields.remove(from).map(|ty: FieldType| (to, ty)))
+            .collect();
+        for (to, ty) in moved {
+            mapped.fields.insert(to.clone(), ty);
+        }
+        mapped
+    }
+}
+
+impl FromIterator<(String, String)> for FieldMapper {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(rules: I) -> Self {
+        FieldMapper {
+            rules: rules.into_iter().collect(),
+        }
+    }
+}
+
+pub fn create_field_mapper_operator(mapper: FieldMapper, next_op: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let label: String = format!("field_map({} rules)", mapper.rules.len());
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        mapper.apply(headers);
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
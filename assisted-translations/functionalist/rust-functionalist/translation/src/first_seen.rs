@@ -0,0 +1,196 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::builtins::{GroupingFunc, tuple_time};
+use crate::clock::{ClockRef, elapsed_clock};
+use crate::error::StreamError;
+use crate::state::{OperatorFault, decode_state, encode_state};
+use crate::utils::{Headers, OpResult, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub const FIRST_SEEN_KEY: &str = "first_seen";
+pub const LAST_SEEN_KEY: &str = "last_seen";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sighting {
+    New,
+    Returned { first: f64, last: f64 },
+    Known,
+}
+
+#[derive(Debug, Default)]
+pub struct SeenTable {
+    seen: HashMap<Headers, (f64, f64)>,
+    forget_after: Option<f64>,
+    path: Option<PathBuf>,
+}
+
+impl SeenTable {
+    pub fn new(forget_after: Option<f64>) -> Self {
+        SeenTable {
+            forget_after,
+            ..SeenTable::default()
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>, forget_after: Option<f64>) -> Result<Self, StreamError> {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let entries: Vec<(Headers, (f64, f64))> = match fs::read(&path) {
+            Ok(bytes) => decode_state(&bytes)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(SeenTable {
+            seen: entries.into_iter().collect(),
+            forget_after,
+            path: Some(path),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    pub fn get(&self, key: &Headers) -> Option<(f64, f64)> {
+        self.seen.get(key).copied()
+    }
+
+    pub fn observe(&mut self, key: Headers, time: f64) -> Sighting {
+        match self.seen.get_mut(&key) {
+            None => {
+                self.seen.insert(key, (time, time));
+                Sighting::New
+            }
+            Some((first, last)) => {
+                let previous: f64 = *last;
+                *last = last.max(time);
+                match self.forget_after {
+                    Some(gap) if time - previous >= gap => Sighting::Returned {
+                        first: *first,
+                        last: previous,
+                    },
+                    _ => Sighting::Known,
+                }
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), StreamError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let entries: Vec<(Headers, (f64, f64))> = self
+            .seen
+            .iter()
+            .map(|(key, times)| (key.clone(), *times))
+            .collect();
+        let tmp: PathBuf = path.with_extension("tmp");
+        fs::write(&tmp, encode_state(&entries))
+            .and_then(|()| fs::rename(&tmp, path))
+            .map_err(|e| {
+                StreamError::from(e).within("first_seen table", path.display().to_string())
+            })
+    }
+}
+
+pub fn create_first_seen_operator(
+    groupby: GroupingFunc,
+    table: SeenTable,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_first_seen_operator_with_clock(groupby, table, elapsed_clock(), next_op)
+}
+
+pub fn create_first_seen_operator_with_clock(
+    groupby: GroupingFunc,
+    table: SeenTable,
+    clock: ClockRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let label: String = match table.forget_after {
+        Some(gap) => format!("first_seen(forget {}s)", gap),
+        None => "first_seen".to_string(),
+    };
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let table: Rc<RefCell<SeenTable>> = Rc::new(RefCell::new(table));
+    let reset_table_ref = Rc::clone(&table);
+    let next_op_ref_clone = Rc::clone(&next_op);
+    let fault: OperatorFault = OperatorFault::new();
+    let reset_fault: OperatorFault = fault.clone();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = tuple_time(headers, clock.as_ref());
+        let grouping_key: Headers = groupby(headers.clone());
+        let (first, last) = match table.borrow_mut().observe(grouping_key, time) {
+            Sighting::Known => return,
+            Sighting::New => (time, None),
+            Sighting::Returned { first, last } => (first, Some(last)),
+        };
+        headers.insert(
+            FIRST_SEEN_KEY.to_string(),
+            OpResult::Float(OrderedFloat(first)),
+        );
+        if let Some(last) = last {
+            headers.insert(
+                LAST_SEEN_KEY.to_string(),
+                OpResult::Float(OrderedFloat(last)),
+            );
+        }
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if let Err(e) = reset_table_ref.borrow().save() {
+            reset_fault.record(e);
+        }
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream)
+            .with_fault(fault),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::filter_groups;
+    use crate::clock::ManualClock;
+    use crate::state::{collect_faults, first_fault};
+    use crate::testing::{TestSink, tuple};
+
+    #[test]
+    fn failing_to_persist_seen_keys_faults_the_operator() {
+        let path: PathBuf = std::env::temp_dir()
+            .join("first_seen_missing_dir")
+            .join("seen.bin");
+        let sink: TestSink = TestSink::new();
+        let op: OperatorRef = create_first_seen_operator_with_clock(
+            Box::new(|headers: Headers| filter_groups(&["host"], &mut headers.clone())),
+            SeenTable::open(&path, None).unwrap(),
+            Rc::new(ManualClock::new(1.0)),
+            sink.operator(),
+        );
+        (op.borrow_mut().next)(&mut tuple(&[("host", OpResult::Int(1))]));
+        (op.borrow_mut().reset)(&mut Headers::new());
+        sink.assert_emitted_count(1);
+        assert_eq!(sink.epochs(), 1);
+        assert!(matches!(
+            first_fault(&collect_faults(&op)),
+            Some(StreamError::Within { .. })
+        ));
+    }
+}
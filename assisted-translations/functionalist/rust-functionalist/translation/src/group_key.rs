@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+
+use smallvec::SmallVec;
+
+use crate::budget::{BudgetKey, approx_op_result_bytes};
+use crate::builtins::{filter_groups, order_op_results};
+use crate::traffic_gen::{Scenario, generate};
+use crate::utils::{Headers, OpResult};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+pub const GROUP_KEY_INLINE: usize = 4;
+
+pub type FieldId = u16;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GroupKey(SmallVec<[(FieldId, OpResult); GROUP_KEY_INLINE]>);
+
+impl GroupKey {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl BudgetKey for GroupKey {
+    fn approx_bytes(&self) -> usize {
+        self.0
+            .iter()
+            .map(|(_, val)| size_of::<FieldId>() + approx_op_result_bytes(val))
+            .sum()
+    }
+
+    fn order(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|((a_id, a_val), (b_id, b_val))| {
+                a_id.cmp(b_id).then_with(|| order_op_results(a_val, b_val))
+            })
+            .find(|ord: &Ordering| ord.is_ne())
+            .unwrap_or_else(|| self.len().cmp(&other.len()))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct KeyCodec {
+    fields: Vec<String>,
+}
+
+impl KeyCodec {
+    pub fn new() -> Self {
+        KeyCodec::default()
+    }
+
+    fn field_id(&mut self, key: &str) -> FieldId {
+        match self.fields.iter().position(|field: &String| field == key) {
+            Some(pos) => pos as FieldId,
+            None => {
+                self.fields.push(key.to_string());
+                (self.fields.len() - 1) as FieldId
+            }
+        }
+    }
+
+    pub fn encode(&mut self, headers: &Headers) -> GroupKey {
+        GroupKey(
+            headers
+                .iter()
+                .map(|(key, val)| (self.field_id(key), val.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn decode(&self, key: GroupKey) -> Headers {
+        key.0
+            .into_iter()
+            .map(|(id, val)| (self.fields[id as usize].clone(), val))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupTable<V> {
+    pub codec: KeyCodec,
+    pub entries: HashMap<GroupKey, V>,
+}
+
+impl<V> Default for GroupTable<V> {
+    fn default() -> Self {
+        GroupTable {
+            codec: KeyCodec::new(),
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<V> GroupTable<V> {
+    pub fn new() -> Self {
+        GroupTable::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn key(&mut self, grouping_key: &Headers) -> GroupKey {
+        self.codec.encode(grouping_key)
+    }
+
+    pub fn decode_all(&self, entries: Vec<(GroupKey, V)>) -> Vec<(Headers, V)> {
+        entries
+            .into_iter()
+            .map(|(key, val)| (self.codec.decode(key), val))
+            .collect()
+    }
+
+    pub fn drain(&mut self) -> Vec<(Headers, V)> {
+        let entries: Vec<(GroupKey, V)> = self.entries.drain().collect();
+        self.decode_all(entries)
+    }
+}
+
+pub fn benchmark_groupby(packets: usize) -> (usize, Duration, Duration) {
+    let rows: Vec<Headers> = generate(7, 10.0, &[Scenario::Background { packets }]);
+    let group = |headers: &Headers| filter_groups(&["ipv4.src", "ipv4.dst"], &mut headers.clone());
+    let count = |val: Option<&i32>| val.map_or(1, |n: &i32| n + 1);
+
+    let start: Instant = Instant::now();
+    let mut by_tuple: HashMap<Headers, i32> = HashMap::new();
+    for headers in rows.iter() {
+        let grouping_key: Headers = group(headers);
+        let n: i32 = count(by_tuple.get(&grouping_key));
+        by_tuple.insert(grouping_key, n);
+    }
+    let tuple_time: Duration = start.elapsed();
+
+    let start: Instant = Instant::now();
+    let mut codec: KeyCodec = KeyCodec::new();
+    let mut by_key: HashMap<GroupKey, i32> = HashMap::new();
+    for headers in rows.iter() {
+        let grouping_key: GroupKey = codec.encode(&group(headers));
+        let n: i32 = count(by_key.get(&grouping_key));
+        by_key.insert(grouping_key, n);
+    }
+    let decoded: Vec<Headers> = by_key
+        .into_keys()
+        .map(|key: GroupKey| codec.decode(key))
+        .collect();
+    let key_time: Duration = start.elapsed();
+
+    assert_eq!(by_tuple.len(), decoded.len());
+    (decoded.len(), tuple_time, key_time)
+}
@@ -0,0 +1,136 @@
+#![allow(dead_code)]
+
+use crate::error::{ParseError, StreamError};
+use crate::keys::WellKnownKey;
+use crate::packet::IPPROTO_TCP;
+use crate::utils::{Headers, OpResult};
+
+pub const HTTP_PORTS: [i32; 2] = [80, 8080];
+
+const METHODS: [&str; 9] = [
+    "GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub host: String,
+    pub path: String,
+}
+
+fn http_error(msg: &str) -> StreamError {
+    StreamError::Parse(ParseError::Packet {
+        protocol: "HTTP request",
+        msg: msg.to_string(),
+    })
+}
+
+impl HttpRequest {
+    pub fn new(method: &str, host: &str, path: &str) -> Self {
+        HttpRequest {
+            method: method.to_string(),
+            host: host.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<HttpRequest, StreamError> {
+        let head: &[u8] = match payload.windows(4).position(|w: &[u8]| w == b"\r\n\r\n") {
+            Some(end) => &payload[..end],
+            None => payload,
+        };
+        let head: &str = std::str::from_utf8(head).map_err(|_| http_error("not UTF-8"))?;
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let (method, path, version) = match (
+            request_line.next(),
+            request_line.next(),
+            request_line.next(),
+        ) {
+            (Some(method), Some(path), Some(version)) => (method, path, version),
+            _ => return Err(http_error("incomplete request line")),
+        };
+        if !METHODS.contains(&method) || !version.starts_with("HTTP/") {
+            return Err(http_error("not a request line"));
+        }
+        let host: &str = lines
+            .filter_map(|line: &str| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("host"))
+            .map(|(_, value)| value.trim())
+            .unwrap_or_default();
+        Ok(HttpRequest::new(method, &host.to_ascii_lowercase(), path))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: traffic-gen\r\n\r\n",
+            self.method, self.path, self.host
+        )
+        .into_bytes()
+    }
+
+    pub fn insert_into(&self, headers: &mut Headers) {
+        headers.insert(
+            WellKnownKey::HttpMethod.into(),
+            OpResult::Str(self.method.clone()),
+        );
+        headers.insert(
+            WellKnownKey::HttpHost.into(),
+            OpResult::Str(self.host.clone()),
+        );
+        headers.insert(
+            WellKnownKey::HttpPath.into(),
+            OpResult::Str(self.path.clone()),
+        );
+    }
+}
+
+pub fn is_http(proto: i32, dport: i32) -> bool {
+    proto == IPPROTO_TCP && HTTP_PORTS.contains(&dport)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &[u8] =
+        b"GET /index.html HTTP/1.1\r\nHost: Example.COM\r\nAccept: */*\r\n\r\nbody";
+
+    #[test]
+    fn decodes_a_request_fixture() {
+        assert_eq!(
+            HttpRequest::decode(REQUEST).unwrap(),
+            HttpRequest::new("GET", "example.com", "/index.html")
+        );
+    }
+
+    #[test]
+    fn truncated_requests_are_errors_until_the_request_line_is_complete() {
+        let line_end: usize = b"GET /index.html HTTP/".len();
+        for len in 0..REQUEST.len() {
+            match HttpRequest::decode(&REQUEST[..len]) {
+                Ok(request) => {
+                    assert!(len >= line_end, "{}", len);
+                    assert_eq!(request.path, "/index.html");
+                }
+                Err(_) => assert!(len < line_end, "{}", len),
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_requests_are_errors() {
+        for payload in [
+            &b"\xff\xfe / HTTP/1.1\r\n\r\n"[..],
+            b"BREW /pot HTTP/1.1\r\n\r\n",
+            b"GET / SPDY/3\r\n\r\n",
+            b"GET /\r\nHost: a\r\n\r\n",
+            b"\r\n\r\nGET / HTTP/1.1",
+        ] {
+            assert!(matches!(
+                HttpRequest::decode(payload),
+                Err(StreamError::Parse(ParseError::Packet { .. }))
+            ));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+use serde_json::{Map, Number, Value};
+
+use crate::error::{InputKind, StreamError};
+use crate::reducers::finalize_op_result;
+use crate::schema::{FieldType, Schema};
+use crate::utils::{Headers, OpResult, mac_of_string, string_of_mac};
+use std::fs;
+use std::net::Ipv4Addr;
+
+pub fn op_result_of_json(
+    key: &str,
+    val: &Value,
+    expected: Option<FieldType>,
+) -> Result<OpResult, StreamError> {
+    Ok(match val {
+        Value::Null => OpResult::Empty,
+        Value::Bool(b) => OpResult::Int(*b as i32),
+        Value::Number(n) => match n.as_i64().and_then(|i: i64| i32::try_from(i).ok()) {
+            Some(i) if expected != Some(FieldType::Float) => OpResult::Int(i),
+            _ => OpResult::Float(OrderedFloat(n.as_f64().unwrap_or(f64::NAN))),
+        },
+        Value::String(s) if expected == Some(FieldType::Str) => OpResult::Str(s.clone()),
+        Value::String(s) => match (s.parse::<Ipv4Addr>(), mac_of_string(s)) {
+            (Ok(addr), _) => OpResult::IPv4(addr),
+            (_, Some(mac)) => OpResult::MAC(mac),
+            _ => OpResult::Str(s.clone()),
+        },
+        Value::Array(_) | Value::Object(_) => {
+            return Err(StreamError::value(format!(
+                "field '{}' must be a scalar JSON value",
+                key
+            )));
+        }
+    })
+}
+
+pub fn json_of_op_result(val: &OpResult) -> Value {
+    match val {
+        OpResult::Int(i) => Value::from(*i),
+        OpResult::Float(f) => Number::from_f64(f.0).map_or(Value::Null, Value::Number),
+        OpResult::IPv4(addr) => Value::String(addr.to_string()),
+        OpResult::MAC(mac) => Value::String(string_of_mac(mac)),
+        OpResult::Str(s) => Value::String(s.clone()),
+        OpResult::Summary(_) => json_of_op_result(&finalize_op_result(val.clone())),
+        OpResult::Empty => Value::Null,
+    }
+}
+
+pub fn headers_of_json(src: &str) -> Result<Headers, StreamError> {
+    let value: Value = serde_json::from_str(src)
+        .map_err(|e: serde_json::Error| StreamError::syntax(InputKind::Value, "JSON", e))?;
+    let Value::Object(fields) = value else {
+        return Err(StreamError::value(
+            "expected a JSON object of header fields",
+        ));
+    };
+    let schema: Schema = Schema::decoded();
+    fields
+        .iter()
+        .map(|(key, val)| {
+            let val: OpResult = op_result_of_json(key, val, schema.field_type(key))?;
+            Ok((key.clone(), val))
+        })
+        .collect()
+}
+
+pub fn load_json_lines(path: &str) -> Result<Vec<Headers>, StreamError> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line: &&str| !line.trim().is_empty())
+        .map(headers_of_json)
+        .collect()
+}
+
+pub fn json_of_headers(headers: &Headers) -> Value {
+    Value::Object(
+        headers
+            .iter()
+            .map(|(key, val)| (key.clone(), json_of_op_result(val)))
+            .collect::<Map<String, Value>>(),
+    )
+}
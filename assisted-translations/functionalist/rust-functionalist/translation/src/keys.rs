@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+
+use crate::utils::{Headers, OpResult};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WellKnownKey {
+    EthDst,
+    EthEthertype,
+    EthSrc,
+    Ipv4Dst,
+    Ipv4Hlen,
+    Ipv4Len,
+    Ipv4Proto,
+    Ipv4Src,
+    L4Dport,
+    L4Flags,
+    L4Sport,
+    Time,
+    Eid,
+    IcmpType,
+    IcmpCode,
+    ArpOp,
+    ArpSpa,
+    DnsQname,
+    DnsQtype,
+    DnsRcode,
+    DnsSize,
+    HttpMethod,
+    HttpHost,
+    HttpPath,
+    TlsJa3,
+    TlsJa3s,
+    EthVlan,
+    Ipv4Frags,
+    TcpSeq,
+    TcpAck,
+    TcpLen,
+}
+
+impl WellKnownKey {
+    pub const PACKET: [WellKnownKey; 12] = [
+        WellKnownKey::EthDst,
+        WellKnownKey::EthEthertype,
+        WellKnownKey::EthSrc,
+        WellKnownKey::Ipv4Dst,
+        WellKnownKey::Ipv4Hlen,
+        WellKnownKey::Ipv4Len,
+        WellKnownKey::Ipv4Proto,
+        WellKnownKey::Ipv4Src,
+        WellKnownKey::L4Dport,
+        WellKnownKey::L4Flags,
+        WellKnownKey::L4Sport,
+        WellKnownKey::Time,
+    ];
+
+    pub const OPTIONAL: [WellKnownKey; 18] = [
+        WellKnownKey::IcmpType,
+        WellKnownKey::IcmpCode,
+        WellKnownKey::ArpOp,
+        WellKnownKey::ArpSpa,
+        WellKnownKey::DnsQname,
+        WellKnownKey::DnsQtype,
+        WellKnownKey::DnsRcode,
+        WellKnownKey::DnsSize,
+        WellKnownKey::HttpMethod,
+        WellKnownKey::HttpHost,
+        WellKnownKey::HttpPath,
+        WellKnownKey::TlsJa3,
+        WellKnownKey::TlsJa3s,
+        WellKnownKey::EthVlan,
+        WellKnownKey::Ipv4Frags,
+        WellKnownKey::TcpSeq,
+        WellKnownKey::TcpAck,
+        WellKnownKey::TcpLen,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WellKnownKey::EthDst => "eth.dst",
+            WellKnownKey::EthEthertype => "eth.ethertype",
+            WellKnownKey::EthSrc => "eth.src",
+            WellKnownKey::Ipv4Dst => "ipv4.dst",
+            WellKnownKey::Ipv4Hlen => "ipv4.hlen",
+            WellKnownKey::Ipv4Len => "ipv4.len",
+            WellKnownKey::Ipv4Proto => "ipv4.proto",
+            WellKnownKey::Ipv4Src => "ipv4.src",
+            WellKnownKey::L4Dport => "l4.dport",
+            WellKnownKey::L4Flags => "l4.flags",
+            WellKnownKey::L4Sport => "l4.sport",
+            WellKnownKey::Time => "time",
+            WellKnownKey::Eid => "eid",
+            WellKnownKey::IcmpType => "icmp.type",
+            WellKnownKey::IcmpCode => "icmp.code",
+            WellKnownKey::ArpOp => "arp.op",
+            WellKnownKey::ArpSpa => "arp.spa",
+            WellKnownKey::DnsQname => "dns.qname",
+            WellKnownKey::DnsQtype => "dns.qtype",
+            WellKnownKey::DnsRcode => "dns.rcode",
+            WellKnownKey::DnsSize => "dns.size",
+            WellKnownKey::HttpMethod => "http.method",
+            WellKnownKey::HttpHost => "http.host",
+            WellKnownKey::HttpPath => "http.path",
+            WellKnownKey::TlsJa3 => "tls.ja3",
+            WellKnownKey::TlsJa3s => "tls.ja3s",
+            WellKnownKey::EthVlan => "eth.vlan",
+            WellKnownKey::Ipv4Frags => "ipv4.frags",
+            WellKnownKey::TcpSeq => "tcp.seq",
+            WellKnownKey::TcpAck => "tcp.ack",
+            WellKnownKey::TcpLen => "tcp.len",
+        }
+    }
+
+    pub fn of_str(key: &str) -> Option<WellKnownKey> {
+        WellKnownKey::PACKET
+            .into_iter()
+            .chain([WellKnownKey::Eid])
+            .chain(WellKnownKey::OPTIONAL)
+            .find(|known: &WellKnownKey| known.as_str() == key)
+    }
+
+    pub fn packet_index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl AsRef<str> for WellKnownKey {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for WellKnownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<WellKnownKey> for String {
+    fn from(key: WellKnownKey) -> String {
+        key.as_str().to_string()
+    }
+}
+
+pub trait HeaderKey {
+    fn key_str(&self) -> &str;
+
+    fn lookup<'a>(&self, headers: &'a Headers) -> Option<&'a OpResult> {
+        headers.get(self.key_str())
+    }
+}
+
+impl HeaderKey for str {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl HeaderKey for String {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl HeaderKey for WellKnownKey {
+    fn key_str(&self) -> &str {
+        self.as_str()
+    }
+
+    fn lookup<'a>(&self, headers: &'a Headers) -> Option<&'a OpResult> {
+        headers.get_known(*self)
+    }
+}
+
+impl<T: HeaderKey + ?Sized> HeaderKey for &T {
+    fn key_str(&self) -> &str {
+        (**self).key_str()
+    }
+
+    fn lookup<'a>(&self, headers: &'a Headers) -> Option<&'a OpResult> {
+        (**self).lookup(headers)
+    }
+}
+
+impl Headers {
+    pub fn get_known(&self, key: WellKnownKey) -> Option<&OpResult> {
+        self.get_hinted(key.packet_index(), key.as_str())
+    }
+
+    pub fn contains_known(&self, key: WellKnownKey) -> bool {
+        self.get_known(key).is_some()
+    }
+}
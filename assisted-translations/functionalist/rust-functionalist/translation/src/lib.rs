@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+
+pub mod audit;
+pub mod batch;
+pub mod biflow;
+pub mod bpf;
+pub mod budget;
+pub mod builtins;
+pub mod capture;
+pub mod catalog;
+pub mod channel;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod clickhouse;
+pub mod clock;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod control;
+pub mod demo;
+pub mod dns;
+pub mod dot;
+pub mod dsl;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod email;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod enrichment;
+pub mod error;
+pub mod eve;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
+pub mod field_map;
+pub mod first_seen;
+pub mod group_key;
+pub mod http;
+pub mod json;
+pub mod keys;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod live;
+pub mod mqtt;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod otel;
+pub mod packet;
+pub mod params;
+pub mod pcap;
+pub mod plan;
+pub mod reassembly;
+pub mod record;
+pub mod redis;
+pub mod reducers;
+pub mod registry;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod repl;
+pub mod replay;
+pub mod schema;
+#[cfg(feature = "grpc")]
+pub mod server;
+pub mod services;
+pub mod small_map;
+pub mod state;
+pub mod stats;
+pub mod tcp_stream;
+pub mod tenant;
+pub mod testing;
+pub mod tls;
+pub mod trace;
+pub mod traffic_gen;
+pub mod utils;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+#[cfg(target_os = "linux")]
+pub mod xdp;
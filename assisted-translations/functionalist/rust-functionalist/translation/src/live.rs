@@ -0,0 +1,473 @@
+#![allow(dead_code)]
+
+use crate::bpf::BpfInsn;
+use crate::error::{InputKind, StreamError};
+use crate::packet::{DecodeOptions, ETHERTYPE_IPV4, PacketRecord};
+use crate::trace_event;
+use crate::utils::Headers;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const CAPTURE_SNAPLEN: usize = 65536;
+pub const CAPTURE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+static CAPTURE_STOP: AtomicBool = AtomicBool::new(false);
+
+pub fn stop_live_capture() {
+    CAPTURE_STOP.store(true, Ordering::SeqCst);
+}
+
+pub fn live_capture_stopped() -> bool {
+    CAPTURE_STOP.load(Ordering::SeqCst)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkType {
+    Ethernet,
+    RawIpv4,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawFrame {
+    pub time: f64,
+    pub link: LinkType,
+    pub data: Vec<u8>,
+}
+
+impl RawFrame {
+    pub fn ethernet(&self) -> Vec<u8> {
+        match self.link {
+            LinkType::Ethernet => self.data.clone(),
+            LinkType::RawIpv4 => {
+                let mut frame: Vec<u8> = vec![0; 12];
+                frame.extend_from_slice(&(ETHERTYPE_IPV4 as u16).to_be_bytes());
+                frame.extend_from_slice(&self.data);
+                frame
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    pub received: u64,
+    pub dropped: u64,
+}
+
+pub trait CaptureBackend {
+    fn name(&self) -> &'static str;
+    fn next_frame(&mut self) -> Result<Option<RawFrame>, StreamError>;
+    fn stats(&self) -> CaptureStats;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    #[default]
+    Auto,
+    PacketSocket,
+    RawSocket,
+    Xdp,
+}
+
+impl FromStr for BackendKind {
+    type Err = StreamError;
+
+    fn from_str(s: &str) -> Result<Self, StreamError> {
+        match s {
+            "auto" => Ok(BackendKind::Auto),
+            "packet" | "af_packet" => Ok(BackendKind::PacketSocket),
+            "raw" | "rawsock" => Ok(BackendKind::RawSocket),
+            "xdp" | "af_xdp" => Ok(BackendKind::Xdp),
+            other => Err(StreamError::unknown(
+                InputKind::Config,
+                "capture backend",
+                other,
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CaptureOptions {
+    pub interface: String,
+    pub promiscuous: bool,
+    pub filter: Option<Vec<BpfInsn>>,
+}
+
+pub fn capture_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d: Duration| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+fn unsupported(kind: BackendKind) -> StreamError {
+    StreamError::Io(Error::new(
+        ErrorKind::Unsupported,
+        format!("{:?} capture is not available on this platform", kind),
+    ))
+}
+
+pub fn open_backend(
+    kind: BackendKind,
+    options: &CaptureOptions,
+) -> Result<Box<dyn CaptureBackend>, StreamError> {
+    match kind {
+        #[cfg(target_os = "linux")]
+        BackendKind::Auto | BackendKind::PacketSocket => {
+            Ok(Box::new(packet_socket::PacketSocketBackend::open(options)?))
+        }
+        #[cfg(windows)]
+        BackendKind::Auto | BackendKind::RawSocket => {
+            Ok(Box::new(raw_socket::RawSocketBackend::open(options)?))
+        }
+        other => Err(unsupported(other)),
+    }
+}
+
+pub struct LiveSource {
+    pub backend: Box<dyn CaptureBackend>,
+    pub options: DecodeOptions,
+    pub limit: Option<usize>,
+    pub decode_errors: usize,
+    delivered: usize,
+}
+
+impl LiveSource {
+    pub fn new(backend: Box<dyn CaptureBackend>, options: DecodeOptions) -> Self {
+        LiveSource {
+            backend,
+            options,
+            limit: None,
+            decode_errors: 0,
+            delivered: 0,
+        }
+    }
+}
+
+impl Iterator for LiveSource {
+    type Item = Headers;
+
+    fn next(&mut self) -> Option<Headers> {
+        if self
+            .limit
+            .is_some_and(|limit: usize| self.delivered >= limit)
+        {
+            return None;
+        }
+        while !live_capture_stopped() {
+            let frame: RawFrame = match self.backend.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => continue,
+                Err(_e) => {
+                    trace_event!(
+                        backend = self.backend.name(),
+                        error = %_e,
+                        "live capture failed, ending the stream"
+                    );
+                    return None;
+                }
+            };
+            match PacketRecord::decode(frame.time, &frame.ethernet(), &self.options) {
+                Ok(record) => {
+                    self.delivered += 1;
+                    return Some(Headers::from(record));
+                }
+                Err(_) => self.decode_errors += 1,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub mod packet_socket {
+    use super::{
+        CAPTURE_POLL_INTERVAL, CAPTURE_SNAPLEN, CaptureBackend, CaptureOptions, CaptureStats,
+        LinkType, RawFrame, capture_time,
+    };
+    use crate::error::StreamError;
+    use std::ffi::CString;
+    use std::io::{Error, ErrorKind};
+    use std::mem::{size_of, zeroed};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    const ETH_P_ALL: u16 = 0x0003;
+
+    fn last_error() -> StreamError {
+        StreamError::Io(Error::last_os_error())
+    }
+
+    fn setsockopt<T>(fd: &OwnedFd, level: i32, name: i32, val: &T) -> Result<(), StreamError> {
+        let rc: i32 = unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                level,
+                name,
+                val as *const T as *const libc::c_void,
+                size_of::<T>() as libc::socklen_t,
+            )
+        };
+        if rc < 0 { Err(last_error()) } else { Ok(()) }
+    }
+
+    pub struct PacketSocketBackend {
+        pub fd: OwnedFd,
+        pub ifindex: i32,
+        buf: Vec<u8>,
+        stats: CaptureStats,
+    }
+
+    impl PacketSocketBackend {
+        pub fn open(options: &CaptureOptions) -> Result<PacketSocketBackend, StreamError> {
+            let raw: i32 =
+                unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, ETH_P_ALL.to_be() as i32) };
+            if raw < 0 {
+                return Err(last_error());
+            }
+            let fd: OwnedFd = unsafe { OwnedFd::from_raw_fd(raw) };
+            if let Some(program) = &options.filter {
+                let fprog: libc::sock_fprog = libc::sock_fprog {
+                    len: program.len() as u16,
+                    filter: program.as_ptr() as *mut libc::sock_filter,
+                };
+                setsockopt(&fd, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &fprog)?;
+            }
+            let ifindex: i32 = match options.interface.as_str() {
+                "" | "any" => 0,
+                name => {
+                    let name: CString = CString::new(name).map_err(|_| {
+                        StreamError::Io(Error::new(ErrorKind::InvalidInput, "bad interface name"))
+                    })?;
+                    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+                        0 => return Err(last_error()),
+                        index => index as i32,
+                    }
+                }
+            };
+            let mut addr: libc::sockaddr_ll = unsafe { zeroed() };
+            addr.sll_family = libc::AF_PACKET as u16;
+            addr.sll_protocol = ETH_P_ALL.to_be();
+            addr.sll_ifindex = ifindex;
+            let rc: i32 = unsafe {
+                libc::bind(
+                    fd.as_raw_fd(),
+                    &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+                )
+            };
+            if rc < 0 {
+                return Err(last_error());
+            }
+            if options.promiscuous && ifindex != 0 {
+                let mut mreq: libc::packet_mreq = unsafe { zeroed() };
+                mreq.mr_ifindex = ifindex;
+                mreq.mr_type = libc::PACKET_MR_PROMISC as u16;
+                setsockopt(&fd, libc::SOL_PACKET, libc::PACKET_ADD_MEMBERSHIP, &mreq)?;
+            }
+            let timeout: libc::timeval = libc::timeval {
+                tv_sec: 0,
+                tv_usec: CAPTURE_POLL_INTERVAL.as_micros() as libc::suseconds_t,
+            };
+            setsockopt(&fd, libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeout)?;
+            Ok(PacketSocketBackend {
+                fd,
+                ifindex,
+                buf: vec![0; CAPTURE_SNAPLEN],
+                stats: CaptureStats::default(),
+            })
+        }
+
+        fn kernel_stats(&self) -> Option<libc::tpacket_stats> {
+            let mut stats: libc::tpacket_stats = unsafe { zeroed() };
+            let mut len: libc::socklen_t = size_of::<libc::tpacket_stats>() as libc::socklen_t;
+            let rc: i32 = unsafe {
+                libc::getsockopt(
+                    self.fd.as_raw_fd(),
+                    libc::SOL_PACKET,
+                    libc::PACKET_STATISTICS,
+                    &mut stats as *mut libc::tpacket_stats as *mut libc::c_void,
+                    &mut len,
+                )
+            };
+            (rc == 0).then_some(stats)
+        }
+    }
+
+    impl CaptureBackend for PacketSocketBackend {
+        fn name(&self) -> &'static str {
+            "af_packet"
+        }
+
+        fn next_frame(&mut self) -> Result<Option<RawFrame>, StreamError> {
+            let n: isize = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    self.buf.as_mut_ptr() as *mut libc::c_void,
+                    self.buf.len(),
+                    0,
+                )
+            };
+            if n < 0 {
+                let err: Error = Error::last_os_error();
+                return match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted => {
+                        Ok(None)
+                    }
+                    _ => Err(StreamError::Io(err)),
+                };
+            }
+            self.stats.received += 1;
+            Ok(Some(RawFrame {
+                time: capture_time(),
+                link: LinkType::Ethernet,
+                data: self.buf[..n as usize].to_vec(),
+            }))
+        }
+
+        fn stats(&self) -> CaptureStats {
+            match self.kernel_stats() {
+                Some(kernel) => CaptureStats {
+                    received: self.stats.received,
+                    dropped: kernel.tp_drops as u64,
+                },
+                None => self.stats,
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+pub mod raw_socket {
+    use super::{
+        CAPTURE_POLL_INTERVAL, CAPTURE_SNAPLEN, CaptureBackend, CaptureOptions, CaptureStats,
+        LinkType, RawFrame, capture_time,
+    };
+    use crate::error::StreamError;
+    use std::io::{Error, ErrorKind};
+    use std::net::{Ipv4Addr, UdpSocket};
+    use std::os::windows::io::{FromRawSocket, RawSocket};
+    use std::ptr;
+
+    const AF_INET: i32 = 2;
+    const SOCK_RAW: i32 = 3;
+    const IPPROTO_IP: i32 = 0;
+    const SIO_RCVALL: u32 = 0x9800_0001;
+    const RCVALL_ON: u32 = 1;
+    const INVALID_SOCKET: usize = !0;
+
+    #[repr(C)]
+    struct SockAddrIn {
+        sin_family: u16,
+        sin_port: u16,
+        sin_addr: [u8; 4],
+        sin_zero: [u8; 8],
+    }
+
+    #[link(name = "ws2_32")]
+    unsafe extern "system" {
+        fn socket(af: i32, kind: i32, protocol: i32) -> usize;
+        fn bind(s: usize, name: *const SockAddrIn, namelen: i32) -> i32;
+        fn closesocket(s: usize) -> i32;
+        fn WSAIoctl(
+            s: usize,
+            code: u32,
+            in_buf: *const u32,
+            in_len: u32,
+            out_buf: *mut u8,
+            out_len: u32,
+            returned: *mut u32,
+            overlapped: *mut u8,
+            completion: *mut u8,
+        ) -> i32;
+    }
+
+    fn init_winsock() -> Result<(), StreamError> {
+        UdpSocket::bind("0.0.0.0:0")?;
+        Ok(())
+    }
+
+    pub struct RawSocketBackend {
+        pub socket: UdpSocket,
+        buf: Vec<u8>,
+        stats: CaptureStats,
+    }
+
+    impl RawSocketBackend {
+        pub fn open(options: &CaptureOptions) -> Result<RawSocketBackend, StreamError> {
+            let addr: Ipv4Addr = options.interface.parse::<Ipv4Addr>().map_err(|_| {
+                StreamError::config(format!(
+                    "raw socket capture binds to a local IPv4 address, found '{}'",
+                    options.interface
+                ))
+            })?;
+            init_winsock()?;
+            let raw: usize = unsafe { socket(AF_INET, SOCK_RAW, IPPROTO_IP) };
+            if raw == INVALID_SOCKET {
+                return Err(StreamError::Io(Error::last_os_error()));
+            }
+            let sockaddr: SockAddrIn = SockAddrIn {
+                sin_family: AF_INET as u16,
+                sin_port: 0,
+                sin_addr: addr.octets(),
+                sin_zero: [0; 8],
+            };
+            let mut returned: u32 = 0;
+            let ok: bool = unsafe {
+                bind(raw, &sockaddr, size_of::<SockAddrIn>() as i32) == 0
+                    && WSAIoctl(
+                        raw,
+                        SIO_RCVALL,
+                        &RCVALL_ON,
+                        size_of::<u32>() as u32,
+                        ptr::null_mut(),
+                        0,
+                        &mut returned,
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                    ) == 0
+            };
+            if !ok {
+                let err: Error = Error::last_os_error();
+                unsafe { closesocket(raw) };
+                return Err(StreamError::Io(err));
+            }
+            let socket: UdpSocket = unsafe { UdpSocket::from_raw_socket(raw as RawSocket) };
+            socket.set_read_timeout(Some(CAPTURE_POLL_INTERVAL))?;
+            Ok(RawSocketBackend {
+                socket,
+                buf: vec![0; CAPTURE_SNAPLEN],
+                stats: CaptureStats::default(),
+            })
+        }
+    }
+
+    impl CaptureBackend for RawSocketBackend {
+        fn name(&self) -> &'static str {
+            "raw_socket"
+        }
+
+        fn next_frame(&mut self) -> Result<Option<RawFrame>, StreamError> {
+            match self.socket.recv(&mut self.buf) {
+                Ok(n) => {
+                    self.stats.received += 1;
+                    Ok(Some(RawFrame {
+                        time: capture_time(),
+                        link: LinkType::RawIpv4,
+                        data: self.buf[..n].to_vec(),
+                    }))
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    Ok(None)
+                }
+                Err(e) => Err(StreamError::Io(e)),
+            }
+        }
+
+        fn stats(&self) -> CaptureStats {
+            self.stats
+        }
+    }
+}
@@ -1,15 +1,32 @@
 #![allow(dead_code)]
 
-use std::{cell::RefCell, collections::BTreeMap, io::stdout, rc::Rc};
+use std::{cell::RefCell, io::stdout, net::Ipv4Addr, rc::Rc, sync::Arc, time::Duration};
 
-use builtins::{
-    counter, create_distinct_operator, create_epoch_operator, create_filter_operator, create_groupby_operator, create_join_operator, create_map_operator, dump_as_csv, filter_groups, get_mapped_int, key_geq_int, rename_filtered_keys, single_group, sum_ints, FilterFunc, GroupingFunc, ReductionFunc
+use translation::{
+    batch, builtins, capture, catalog, config, control, dns, dot, enrichment, group_key, keys,
+    packet, params, plan, reducers, repl, schema, trace, traffic_gen, tuple_record, utils,
 };
-use ordered_float::OrderedFloat;
-use utils::{Headers, OpResult, OperatorRef};
 
-mod builtins;
-mod utils;
+use builtins::{
+    counter, create_distinct_operator, create_distinct_ttl_operator, create_dump_operator, create_epoch_operator, create_epoch_operator_with_options, create_filter_operator, create_groupby_multi_operator, create_groupby_operator, create_join_operator, create_map_expr_operator, create_map_operator, create_multi_join_operator, create_periodicity_operator, create_split_operator, create_union_operator, dump_as_csv, filter_groups, flags_equal, flags_exactly, flags_set, get_mapped_int, cmp, ipv4_in_cidr, parse_cidr, rename_filtered_keys, single_group, sum_ints, Cmp, EpochOptions, FilterFunc, GroupingFunc, JoinInput, MultiJoinSpec, ReductionFunc
+};
+use capture::{load_capture, replay_capture};
+use catalog::{QueryCatalog, QueryEntry};
+use config::{Pipeline, PipelineConfig};
+use control::{DEFAULT_CONTROL_ADDR, ControlRef, ControlState, run_controlled, spawn_control_server};
+use keys::WellKnownKey;
+use dot::to_dot;
+use enrichment::{OuiDb, create_oui_lookup_operator, load_oui_db};
+use dns::{DNS_PORT, DNS_QTYPE_TXT, name_entropy};
+use params::QueryParams;
+use plan::PipelineBuilder;
+use packet::{ARP_REPLY, ETHERTYPE_ARP, ICMP_ECHO_REQUEST, IPPROTO_ICMP, IPPROTO_TCP, IPPROTO_UDP, NTP_PORT};
+use reducers::sum_int;
+use repl::run_repl;
+use schema::{FieldType, Schema};
+use trace::init_tracing;
+use traffic_gen::synthetic_headers;
+use utils::{Headers, OpResult, OperatorRef, TCP_ACK, TCP_FIN, TCP_PSH, TCP_SYN, TCP_SYNACK, TCP_URG};
 
 fn ident(next_op: OperatorRef) -> OperatorRef {
     create_map_operator(
@@ -22,172 +39,278 @@ fn ident(next_op: OperatorRef) -> OperatorRef {
     )
 }
 
-fn count_pkts(next_op: OperatorRef) -> OperatorRef {
-    let incl_keys = Vec::from(["ipv4.src".to_string(), "ipv4.dst".to_string()]);
+fn count_pkts(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst];
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
-    create_epoch_operator(
-        1.0,
-        "eid".to_string(),
-        create_groupby_operator(groupby_func, Box::new(counter), "pkts".to_string(), next_op),
-    )
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .groupby(groupby_func, Box::new(counter), "pkts")
+        .sink(next_op)
 }
 
-fn pkts_per_source_dst(next_op: OperatorRef) -> OperatorRef {
-    let incl_keys = Vec::from(["ipv4.src".to_string(), "ipv4.dst".to_string()]);
+fn pkts_per_source_dst(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst];
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
-    create_epoch_operator(
-        1.0,
-        "eid".to_string(),
-        create_groupby_operator(groupby_func, Box::new(counter), "pkts".to_string(), next_op),
-    )
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .groupby(groupby_func, Box::new(counter), "pkts")
+        .sink(next_op)
 }
 
-fn distinct_srcs(next_op: OperatorRef) -> OperatorRef {
-    let incl_keys = Vec::from(["ipv4.src".to_string()]);
+fn distinct_srcs(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
-    create_epoch_operator(
-        1.0,
-        "eid".to_string(),
-        create_distinct_operator(
-            groupby_func,
-            create_groupby_operator(
-                Box::new(single_group),
-                Box::new(counter),
-                "srcs".to_string(),
-                next_op,
-            ),
-        ),
-    )
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .distinct(groupby_func)
+        .groupby(Box::new(single_group), Box::new(counter), "srcs")
+        .sink(next_op)
 }
 
-fn tcp_new_cons(next_op: OperatorRef) -> OperatorRef {
-    let threshold: i32 = 40;
-    let incl_keys: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+fn tcp_new_cons(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.new_cons_threshold;
+    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
     let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-        get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-            && get_mapped_int("l4.flags".to_string(), &headers) == 2
+        get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+            && flags_equal(TCP_SYN, headers)
     });
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
-    let filter_func2: FilterFunc =
-        Box::new(move |headers: &Headers| key_geq_int("cons".to_string(), threshold, headers));
-    create_epoch_operator(
-        1.0,
-        "eid".to_string(),
-        create_filter_operator(
-            filter_func,
-            create_groupby_operator(
-                groupby_func,
-                Box::new(counter),
-                "cons".to_string(),
-                create_filter_operator(filter_func2, next_op),
-            ),
-        ),
-    )
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let filter_func2: FilterFunc = cmp("cons", Cmp::Ge, threshold);
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .filter(filter_func)
+        .groupby(groupby_func, Box::new(counter), "cons")
+        .filter(filter_func2)
+        .sink(next_op)
 }
 
-fn ssh_brute_force(next_op: OperatorRef) -> OperatorRef {
-    let threshold: i32 = 40;
-    let incl_keys: Vec<String> = Vec::from([
-        "ipv4.src".to_string(),
-        "ipv4.dst".to_string(),
-        "ipv4.len".to_string(),
-    ]);
-    let incl_keys2: Vec<String> = Vec::from(["ipv4.dst".to_string(), "ipv4.len".to_string()]);
+fn ssh_brute_force(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.ssh_brute_force_threshold;
+    let incl_keys: [WellKnownKey; 3] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst, WellKnownKey::Ipv4Len];
+    let incl_keys2: [WellKnownKey; 2] = [WellKnownKey::Ipv4Dst, WellKnownKey::Ipv4Len];
     let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-        get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-            && get_mapped_int("l4.dport".to_string(), &headers) == 22
+        get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+            && get_mapped_int(WellKnownKey::L4Dport, &headers) == 22
     });
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
     let groupby_func2: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys2.clone(), &mut headers));
-    let filter_func2: FilterFunc =
-        Box::new(move |headers: &Headers| key_geq_int("srcs".to_string(), threshold, headers));
-    create_epoch_operator(
-        1.0,
-        "eid".to_string(),
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys2, &mut headers));
+    let filter_func2: FilterFunc = cmp("srcs", Cmp::Ge, threshold);
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .filter(filter_func)
+        .distinct(groupby_func)
+        .groupby(groupby_func2, Box::new(counter), "srcs")
+        .filter(filter_func2)
+        .sink(next_op)
+}
+
+tuple_record! {
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct SshBruteForceAlert {
+        "eid" => pub eid: i32,
+        "ipv4.dst" => pub dst: Ipv4Addr,
+        "ipv4.len" => pub len: i32,
+        "srcs" => pub srcs: i32,
+    }
+}
+
+fn super_spreader(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.super_spreader_threshold;
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst];
+    let incl_keys2: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let groupby_func2: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys2, &mut headers));
+    let filter_func: FilterFunc = cmp("dsts", Cmp::Ge, threshold);
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .distinct(groupby_func)
+        .groupby(groupby_func2, Box::new(counter), "dsts")
+        .filter(filter_func)
+        .sort("dsts", false, None)
+        .sink(next_op)
+}
+
+fn port_scan(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.port_scan_threshold;
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::L4Dport];
+    let incl_keys2: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let groupby_func2: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys2, &mut headers));
+    let filter_func: FilterFunc = cmp("ports", Cmp::Ge, threshold);
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .distinct(groupby_func)
+        .groupby(groupby_func2, Box::new(counter), "ports")
+        .filter(filter_func)
+        .sink(next_op)
+}
+
+fn count_scan_targets(
+    incl_keys: [WellKnownKey; 2],
+    count_key: &'static str,
+    scan_type: &'static str,
+    threshold: i32,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let filter_func: FilterFunc = cmp(count_key, Cmp::Ge, threshold);
+    create_groupby_operator(
+        groupby_func,
+        Box::new(counter),
+        count_key.to_string(),
         create_filter_operator(
             filter_func,
-            create_distinct_operator(
-                groupby_func,
-                create_groupby_operator(
-                    groupby_func2,
-                    Box::new(counter),
-                    "srcs".to_string(),
-                    create_filter_operator(filter_func2, next_op),
-                ),
+            create_map_operator(
+                Box::new(move |mut headers: Headers| {
+                    if headers.contains_key(count_key) {
+                        headers.insert(
+                            "scan_type".to_string(),
+                            OpResult::Str(scan_type.to_string()),
+                        );
+                    }
+                    headers
+                }),
+                next_op,
             ),
         ),
     )
 }
 
-fn super_spreader(next_op: OperatorRef) -> OperatorRef {
-    let threshold: i32 = 40;
-    let incl_keys: Vec<String> = Vec::from(["ipv4.src".to_string(), "ipv4.dst".to_string()]);
-    let incl_keys2: Vec<String> = Vec::from(["ipv4.src".to_string()]);
+fn vertical_scan_branch(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    count_scan_targets(
+        [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst],
+        "ports",
+        "vertical",
+        params.port_scan_threshold,
+        next_op,
+    )
+}
+
+fn horizontal_scan_branch(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    count_scan_targets(
+        [WellKnownKey::Ipv4Src, WellKnownKey::L4Dport],
+        "hosts",
+        "horizontal",
+        params.port_scan_threshold,
+        next_op,
+    )
+}
+
+fn distinct_probes(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let incl_keys: [WellKnownKey; 3] = [
+        WellKnownKey::Ipv4Src,
+        WellKnownKey::Ipv4Dst,
+        WellKnownKey::L4Dport,
+    ];
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
-    let groupby_func2: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys2.clone(), &mut headers));
-    let filter_func: FilterFunc =
-        Box::new(move |headers: &Headers| key_geq_int("dsts".to_string(), threshold, headers));
-    create_epoch_operator(
-        1.0,
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    create_epoch_operator_with_options(
+        params.epoch_dur,
         "eid".to_string(),
-        create_distinct_operator(
-            groupby_func,
-            create_groupby_operator(
-                groupby_func2,
-                Box::new(counter),
-                "dsts".to_string(),
-                create_filter_operator(filter_func, next_op),
-            ),
+        params.epoch_options(),
+        create_distinct_operator(groupby_func, next_op),
+    )
+}
+
+fn vertical_scan(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    distinct_probes(params, vertical_scan_branch(params, next_op))
+}
+
+fn horizontal_scan(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    distinct_probes(params, horizontal_scan_branch(params, next_op))
+}
+
+fn scan_types(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let branches: Vec<OperatorRef> = create_union_operator(2, next_op);
+    distinct_probes(
+        params,
+        create_split_operator(
+            vertical_scan_branch(params, Rc::clone(&branches[0])),
+            horizontal_scan_branch(params, Rc::clone(&branches[1])),
         ),
     )
 }
 
-fn port_scan(next_op: OperatorRef) -> OperatorRef {
-    let threshold: i32 = 40;
-    let incl_keys: Vec<String> = Vec::from(["ipv4.src".to_string(), "l4.dport".to_string()]);
-    let incl_keys2: Vec<String> = Vec::from(["ipv4.src".to_string()]);
+fn stealth_scan(
+    params: &QueryParams,
+    probe: FilterFunc,
+    threshold: i32,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let incl_keys: [WellKnownKey; 3] = [
+        WellKnownKey::Ipv4Src,
+        WellKnownKey::Ipv4Dst,
+        WellKnownKey::L4Dport,
+    ];
+    let incl_keys2: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+    let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+        get_mapped_int(WellKnownKey::Ipv4Proto, headers) == IPPROTO_TCP && probe(headers)
+    });
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
     let groupby_func2: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys2.clone(), &mut headers));
-    let filter_func: FilterFunc =
-        Box::new(move |headers: &Headers| key_geq_int("ports".to_string(), threshold, headers));
-    create_epoch_operator(
-        1.0,
-        "eid".to_string(),
-        create_distinct_operator(
-            groupby_func,
-            create_groupby_operator(
-                groupby_func2,
-                Box::new(counter),
-                "ports".to_string(),
-                create_filter_operator(filter_func, next_op),
-            ),
-        ),
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys2, &mut headers));
+    let filter_func2: FilterFunc = cmp("probes", Cmp::Ge, threshold);
+    PipelineBuilder::new()
+        .epoch_with_options(params.epoch_dur, "eid", params.epoch_options())
+        .filter(filter_func)
+        .distinct(groupby_func)
+        .groupby(groupby_func2, Box::new(counter), "probes")
+        .filter(filter_func2)
+        .sink(next_op)
+}
+
+fn fin_scan(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    stealth_scan(
+        params,
+        flags_exactly(TCP_FIN),
+        params.fin_scan_threshold,
+        next_op,
+    )
+}
+
+fn null_scan(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    stealth_scan(
+        params,
+        flags_exactly(0),
+        params.null_scan_threshold,
+        next_op,
     )
 }
 
-fn ddos(next_op: OperatorRef) -> OperatorRef {
-    let threshold: i32 = 40;
-    let incl_keys: Vec<String> = Vec::from(["ipv4.src".to_string(), "ipv4.dst".to_string()]);
-    let incl_keys2: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+fn xmas_scan(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    stealth_scan(
+        params,
+        flags_exactly(TCP_FIN | TCP_PSH | TCP_URG),
+        params.xmas_scan_threshold,
+        next_op,
+    )
+}
+
+fn ddos(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.ddos_threshold;
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst];
+    let incl_keys2: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
     let groupby_func2: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys2.clone(), &mut headers));
-    let filter_func: FilterFunc =
-        Box::new(move |headers: &Headers| key_geq_int("ports".to_string(), threshold, headers));
-    create_epoch_operator(
-        1.0,
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys2, &mut headers));
+    let filter_func: FilterFunc = cmp("srcs", Cmp::Ge, threshold);
+    create_epoch_operator_with_options(
+        params.epoch_dur,
         "eid".to_string(),
+        params.epoch_options(),
         create_distinct_operator(
             groupby_func,
             create_groupby_operator(
@@ -200,23 +323,25 @@ fn ddos(next_op: OperatorRef) -> OperatorRef {
     )
 }
 
-fn syn_flood_sonata(next_op: OperatorRef) -> [OperatorRef; 3] {
-    let threshold: i32 = 3;
-    let epoch_dur: f64 = 1.0;
+fn syn_flood_sonata(params: &QueryParams, next_op: OperatorRef) -> [OperatorRef; 3] {
+    let threshold: i32 = params.syn_flood_threshold;
+    let epoch_dur: f64 = params.epoch_dur;
+    let epoch_options: EpochOptions = params.epoch_options();
 
     let mut syns: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-                    && get_mapped_int("l4.flags".to_string(), &headers) == 2
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+                    && flags_equal(TCP_SYN, headers)
             });
             let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys.clone(), &mut headers)
+                filter_groups(&incl_keys, &mut headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(
                     filter_func,
                     create_groupby_operator(
@@ -231,17 +356,18 @@ fn syn_flood_sonata(next_op: OperatorRef) -> [OperatorRef; 3] {
 
     let mut acks: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-                    && get_mapped_int("l4.flags".to_string(), &headers) == 16
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+                    && flags_equal(TCP_ACK, headers)
             });
             let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys.clone(), &mut headers)
+                filter_groups(&incl_keys, &mut headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(
                     filter_func,
                     create_groupby_operator(
@@ -256,17 +382,18 @@ fn syn_flood_sonata(next_op: OperatorRef) -> [OperatorRef; 3] {
 
     let mut synacks: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op1: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["ipv4.src".to_string()]);
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-                    && get_mapped_int("l4.flags".to_string(), &headers) == 18
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+                    && flags_equal(TCP_SYNACK, headers)
             });
             let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys.clone(), &mut headers)
+                filter_groups(&incl_keys, &mut headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(
                     filter_func,
                     create_groupby_operator(
@@ -279,116 +406,63 @@ fn syn_flood_sonata(next_op: OperatorRef) -> [OperatorRef; 3] {
             )
         });
 
-    let mut first_join_ops: Box<dyn FnMut(OperatorRef) -> (OperatorRef, OperatorRef) + 'static> =
-        Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["host".to_string()]);
-            let incl_keys2: Vec<String> = Vec::from(["syns+synacks".to_string()]);
-            let incl_keys3: Vec<String> = Vec::from(["acks".to_string()]);
-            let left_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    (
-                        filter_groups(incl_keys.clone(), &mut headers),
-                        filter_groups(incl_keys2.clone(), &mut headers),
-                    )
-                });
-            let right_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    (
-                        rename_filtered_keys(
-                            Vec::from([("ipv4.dst".to_string(), "host".to_string())]),
-                            &mut headers.clone(),
-                        ),
-                        filter_groups(incl_keys3.clone(), &mut headers),
-                    )
-                });
-            let mapping_func: Box<dyn Fn(Headers) -> Headers + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    headers
-                        .insert(
-                            "syns+synacks".to_string(),
-                            utils::OpResult::Int(get_mapped_int("acks".to_string(), &headers)),
-                        )
-                        .unwrap();
-                    headers
-                });
-            let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                key_geq_int("syns+synacks-acks".to_string(), threshold, headers)
-            });
-            create_join_operator(
-                None,
-                left_extractor_func,
-                right_extractor_func,
-                create_map_operator(mapping_func, create_filter_operator(filter_func, next_op)),
-            )
-        });
-
-    let mut second_join_ops: Box<dyn FnMut(OperatorRef) -> (OperatorRef, OperatorRef) + 'static> =
-        Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["syns".to_string()]);
-            let incl_keys2: Vec<String> = Vec::from(["synacks".to_string()]);
-            let left_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    (
-                        rename_filtered_keys(
-                            Vec::from([("ipv4.dst".to_string(), "host".to_string())]),
-                            &mut headers.clone(),
-                        ),
-                        filter_groups(incl_keys.clone(), &mut headers),
-                    )
-                });
-            let right_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    (
-                        rename_filtered_keys(
-                            Vec::from([("ipv4.src".to_string(), "host".to_string())]),
-                            &mut headers.clone(),
-                        ),
-                        filter_groups(incl_keys2.clone(), &mut headers),
-                    )
-                });
-            let mapping_func: Box<dyn Fn(Headers) -> Headers + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    headers
-                        .insert(
-                            "syns+synacks".to_string(),
-                            utils::OpResult::Int(
-                                get_mapped_int("syns".to_string(), &headers)
-                                    + get_mapped_int("synacks".to_string(), &headers),
-                            ),
-                        )
-                        .unwrap();
-                    headers
-                });
-            create_join_operator(
-                None,
-                left_extractor_func,
-                right_extractor_func,
-                create_map_operator(mapping_func, next_op),
-            )
-        });
-
-    let (join_op1, join_op2) = first_join_ops(next_op);
-    let (join_op3, join_op4) = second_join_ops(join_op1);
+    let spec: MultiJoinSpec = MultiJoinSpec::new(&["host"])
+        .with_input(
+            JoinInput::new("syns")
+                .with_rename("host", "ipv4.dst")
+                .with_values(&["syns"]),
+        )
+        .with_input(
+            JoinInput::new("synacks")
+                .with_rename("host", "ipv4.src")
+                .with_values(&["synacks"]),
+        )
+        .with_input(
+            JoinInput::new("acks")
+                .with_rename("host", "ipv4.dst")
+                .with_values(&["acks"]),
+        );
+    let filter_func: FilterFunc = cmp("syns+synacks-acks", Cmp::Ge, threshold);
+    let join_ops: Vec<OperatorRef> = create_multi_join_operator(
+        None,
+        &spec,
+        create_map_expr_operator(
+            "\"syns+synacks-acks\" = syns + synacks - acks",
+            &Schema::new()
+                .with("syns", FieldType::Int)
+                .with("synacks", FieldType::Int)
+                .with("acks", FieldType::Int),
+            create_filter_operator(filter_func, next_op),
+        )
+        .unwrap(),
+    )
+    .unwrap();
 
-    [syns(join_op3), synacks(join_op4), acks(join_op2)]
+    [
+        syns(Rc::clone(&join_ops[0])),
+        synacks(Rc::clone(&join_ops[1])),
+        acks(Rc::clone(&join_ops[2])),
+    ]
 }
 
-fn completed_flows(next_op: OperatorRef) -> [OperatorRef; 2] {
-    let threshold: i32 = 1;
-    let epoch_dur: f64 = 30.0;
+fn completed_flows(params: &QueryParams, next_op: OperatorRef) -> [OperatorRef; 2] {
+    let threshold: i32 = params.completed_flows_threshold;
+    let epoch_dur: f64 = params.completed_flows_epoch_dur;
+    let epoch_options: EpochOptions = params.epoch_options();
     let mut syns: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-                    && get_mapped_int("l4.flags".to_string(), &headers) == 2
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+                    && flags_equal(TCP_SYN, headers)
             });
             let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys.clone(), &mut headers)
+                filter_groups(&incl_keys, &mut headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(
                     filter_func,
                     create_groupby_operator(
@@ -403,17 +477,18 @@ fn completed_flows(next_op: OperatorRef) -> [OperatorRef; 2] {
 
     let mut fins: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["ipv4.src".to_string()]);
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-                    && ((get_mapped_int("l4.flags".to_string(), &headers) & 1) == 1)
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+                    && flags_set(TCP_FIN, headers)
             });
             let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys.clone(), &mut headers)
+                filter_groups(&incl_keys, &mut headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(
                     filter_func,
                     create_groupby_operator(
@@ -436,7 +511,7 @@ fn completed_flows(next_op: OperatorRef) -> [OperatorRef; 2] {
                             Vec::from([("ipv4.dst".to_string(), "host".to_string())]),
                             &mut headers,
                         ),
-                        filter_groups(incl_keys.clone(), &mut headers),
+                        filter_groups(&incl_keys, &mut headers),
                     )
                 });
             let right_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
@@ -447,27 +522,22 @@ fn completed_flows(next_op: OperatorRef) -> [OperatorRef; 2] {
                             Vec::from([("ipv4.src".to_string(), "host".to_string())]),
                             &mut headers,
                         ),
-                        filter_groups(incl_keys2.clone(), &mut headers),
+                        filter_groups(&incl_keys2, &mut headers),
                     )
                 });
-            let mapping_func: Box<dyn Fn(Headers) -> Headers + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    headers
-                        .insert(
-                            "diff".to_string(),
-                            utils::OpResult::Int(get_mapped_int("syns".to_string(), &headers)),
-                        )
-                        .unwrap();
-                    headers
-                });
-            let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                key_geq_int("diff".to_string(), threshold, headers)
-            });
+            let filter_func: FilterFunc = cmp("diff", Cmp::Ge, threshold);
             create_join_operator(
                 None,
                 left_extractor_func,
                 right_extractor_func,
-                create_map_operator(mapping_func, create_filter_operator(filter_func, next_op)),
+                create_map_expr_operator(
+                    "diff = syns - fins",
+                    &Schema::new()
+                        .with("syns", FieldType::Int)
+                        .with("fins", FieldType::Int),
+                    create_filter_operator(filter_func, next_op),
+                )
+                .unwrap(),
             )
         });
     let (join_op1, join_op2) = create_join_ops(next_op);
@@ -475,35 +545,31 @@ fn completed_flows(next_op: OperatorRef) -> [OperatorRef; 2] {
     [syns(join_op1), fins(join_op2)]
 }
 
-fn slowloris(next_op: OperatorRef) -> [OperatorRef; 2] {
-    let t1: i32 = 5;
-    let t2: i32 = 500;
-    let t3: i32 = 90;
-    let epoch_dur: f64 = 1.0;
+fn slowloris(params: &QueryParams, next_op: OperatorRef) -> [OperatorRef; 2] {
+    let t1: i32 = params.slowloris_min_conns;
+    let t2: i32 = params.slowloris_min_bytes;
+    let t3: i32 = params.slowloris_max_bytes_per_conn;
+    let epoch_dur: f64 = params.epoch_dur;
+    let epoch_options: EpochOptions = params.epoch_options();
 
     let mut n_conns: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from([
-                "ipv4.src".to_string(),
-                "ipv4.dst".to_string(),
-                "l4.sport".to_string(),
-            ]);
-            let incl_keys2: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+            let incl_keys: [WellKnownKey; 3] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst, WellKnownKey::L4Sport];
+            let incl_keys2: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-            });
-            let filter_func2: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("n_conns".to_string(), &headers) >= t1
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
             });
+            let filter_func2: FilterFunc = cmp("n_conns", Cmp::Ge, t1);
             let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys.clone(), &mut headers)
+                filter_groups(&incl_keys, &mut headers)
             });
             let groupby_func2: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys2.clone(), &mut headers)
+                filter_groups(&incl_keys2, &mut headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(
                     filter_func,
                     create_distinct_operator(
@@ -521,23 +587,22 @@ fn slowloris(next_op: OperatorRef) -> [OperatorRef; 2] {
 
     let mut n_bytes: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
-            let incl_keys: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-            });
-            let filter_func2: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("n_bytes".to_string(), &headers) >= t2
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
             });
+            let filter_func2: FilterFunc = cmp("n_bytes", Cmp::Ge, t2);
             let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
-                filter_groups(incl_keys.clone(), &mut headers)
+                filter_groups(&incl_keys, &mut headers)
             });
             let reduce_func: ReductionFunc =
                 Box::new(move |init_val: OpResult, headers: &mut Headers| {
-                    sum_ints("ipv4.len".to_string(), init_val, headers).unwrap()
+                    sum_ints(WellKnownKey::Ipv4Len, init_val, headers).unwrap()
                 });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(
                     filter_func,
                     create_groupby_operator(
@@ -554,43 +619,35 @@ fn slowloris(next_op: OperatorRef) -> [OperatorRef; 2] {
         Box::new(move |next_op: OperatorRef| {
             let left_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
                 Box::new(move |mut headers: Headers| {
-                    let incl_keys: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+                    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
                     let incl_keys2: Vec<String> = Vec::from(["n_conns".to_string()]);
                     (
-                        filter_groups(incl_keys.clone(), &mut headers),
-                        filter_groups(incl_keys2.clone(), &mut headers),
+                        filter_groups(&incl_keys, &mut headers),
+                        filter_groups(&incl_keys2, &mut headers),
                     )
                 });
             let right_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
                 Box::new(move |mut headers: Headers| {
-                    let incl_keys: Vec<String> = Vec::from(["ipv4.dst".to_string()]);
+                    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
                     let incl_keys2: Vec<String> = Vec::from(["n_bytes".to_string()]);
                     (
-                        filter_groups(incl_keys.clone(), &mut headers),
-                        filter_groups(incl_keys2.clone(), &mut headers),
+                        filter_groups(&incl_keys, &mut headers),
+                        filter_groups(&incl_keys2, &mut headers),
                     )
                 });
-            let mapping_func: Box<dyn Fn(Headers) -> Headers + 'static> =
-                Box::new(move |mut headers: Headers| {
-                    headers
-                        .insert(
-                            "bytes_per_conn".to_string(),
-                            utils::OpResult::Int(
-                                get_mapped_int("n_bytes".to_string(), &headers)
-                                    / get_mapped_int("n_conns".to_string(), &headers),
-                            ),
-                        )
-                        .unwrap();
-                    headers
-                });
-            let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("bytes_per_conn".to_string(), headers) <= t3
-            });
+            let filter_func: FilterFunc = cmp("bytes_per_conn", Cmp::Le, t3);
             create_join_operator(
                 None,
                 left_extractor_func,
                 right_extractor_func,
-                create_map_operator(mapping_func, create_filter_operator(filter_func, next_op)),
+                create_map_expr_operator(
+                    "bytes_per_conn = n_bytes / n_conns",
+                    &Schema::new()
+                        .with("n_bytes", FieldType::Int)
+                        .with("n_conns", FieldType::Int),
+                    create_filter_operator(filter_func, next_op),
+                )
+                .unwrap(),
             )
         });
     let (join_op1, join_op2) = create_join_ops(next_op);
@@ -598,17 +655,19 @@ fn slowloris(next_op: OperatorRef) -> [OperatorRef; 2] {
     [n_conns(join_op1), n_bytes(join_op2)]
 }
 
-fn create_join_operator_test(next_op: OperatorRef) -> [OperatorRef; 2] {
-    let epoch_dur: f64 = 1.0;
+fn create_join_operator_test(params: &QueryParams, next_op: OperatorRef) -> [OperatorRef; 2] {
+    let epoch_dur: f64 = params.epoch_dur;
+    let epoch_options: EpochOptions = params.epoch_options();
     let mut syns: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-                    && get_mapped_int("l4.flags".to_string(), &headers) == 2
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+                    && flags_equal(TCP_SYN, headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(filter_func, next_op),
             )
         });
@@ -616,12 +675,13 @@ fn create_join_operator_test(next_op: OperatorRef) -> [OperatorRef; 2] {
     let mut synacks: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
         Box::new(move |next_op: OperatorRef| {
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
-                get_mapped_int("ipv4.proto".to_string(), &headers) == 6
-                    && get_mapped_int("l4.flags".to_string(), &headers) == 18
+                get_mapped_int(WellKnownKey::Ipv4Proto, &headers) == 6
+                    && flags_equal(TCP_SYNACK, headers)
             });
-            create_epoch_operator(
+            create_epoch_operator_with_options(
                 epoch_dur,
                 "eid".to_string(),
+                epoch_options,
                 create_filter_operator(filter_func, next_op),
             )
         });
@@ -648,7 +708,7 @@ fn create_join_operator_test(next_op: OperatorRef) -> [OperatorRef; 2] {
                             Vec::from([("ipv4.src".to_string(), "host".to_string())]),
                             &mut headers,
                         ),
-                        filter_groups(Vec::from(["time".to_string()]), &mut headers),
+                        filter_groups(&[WellKnownKey::Time], &mut headers),
                     )
                 });
             create_join_operator(None, left_extractor_func, right_extractor_func, next_op)
@@ -658,10 +718,334 @@ fn create_join_operator_test(next_op: OperatorRef) -> [OperatorRef; 2] {
     [syns(join_op1), synacks(join_op2)]
 }
 
+fn ping_sweep(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.ping_sweep_threshold;
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst];
+    let incl_keys2: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+    let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+        get_mapped_int(WellKnownKey::Ipv4Proto, headers) == IPPROTO_ICMP
+            && get_mapped_int(WellKnownKey::IcmpType, headers) == ICMP_ECHO_REQUEST
+    });
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let groupby_func2: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys2, &mut headers));
+    let filter_func2: FilterFunc = cmp("hosts", Cmp::Ge, threshold);
+    create_epoch_operator_with_options(
+        params.epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_filter_operator(
+            filter_func,
+            create_distinct_operator(
+                groupby_func,
+                create_groupby_operator(
+                    groupby_func2,
+                    Box::new(counter),
+                    "hosts".to_string(),
+                    create_filter_operator(filter_func2, next_op),
+                ),
+            ),
+        ),
+    )
+}
+
+fn arp_spoof(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.arp_spoof_threshold;
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::ArpSpa, WellKnownKey::EthSrc];
+    let incl_keys2: [WellKnownKey; 1] = [WellKnownKey::ArpSpa];
+    let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+        get_mapped_int(WellKnownKey::EthEthertype, headers) == ETHERTYPE_ARP
+            && get_mapped_int(WellKnownKey::ArpOp, headers) == ARP_REPLY
+    });
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let groupby_func2: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys2, &mut headers));
+    let filter_func2: FilterFunc = cmp("macs", Cmp::Ge, threshold);
+    create_epoch_operator_with_options(
+        params.long_epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_filter_operator(
+            filter_func,
+            create_distinct_operator(
+                groupby_func,
+                create_groupby_operator(
+                    groupby_func2,
+                    Box::new(counter),
+                    "macs".to_string(),
+                    create_filter_operator(filter_func2, next_op),
+                ),
+            ),
+        ),
+    )
+}
+
+fn dns_tunneling(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let t1: i32 = params.dns_suspect_names_threshold;
+    let t2: i32 = params.dns_txt_queries_threshold;
+    let max_len: usize = 52;
+    let max_entropy: f64 = 4.0;
+    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+    let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+        get_mapped_int(WellKnownKey::Ipv4Proto, headers) == IPPROTO_UDP
+            && get_mapped_int(WellKnownKey::L4Dport, headers) == DNS_PORT
+            && headers.contains_known(WellKnownKey::DnsQname)
+    });
+    let mapping_func: Box<dyn Fn(Headers) -> Headers + 'static> =
+        Box::new(move |mut headers: Headers| {
+            if let Some(OpResult::Str(qname)) = headers.get_known(WellKnownKey::DnsQname) {
+                let suspect: bool = qname.len() > max_len || name_entropy(qname) > max_entropy;
+                let txt: bool = get_mapped_int(WellKnownKey::DnsQtype, &headers) == DNS_QTYPE_TXT;
+                headers.insert("dns.suspect".to_string(), OpResult::Int(suspect as i32));
+                headers.insert("dns.txt".to_string(), OpResult::Int(txt as i32));
+            }
+            headers
+        });
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let filter_func2: FilterFunc = Box::new(move |headers: &Headers| {
+        get_mapped_int("suspect_names", headers) >= t1
+            || get_mapped_int("txt_queries", headers) >= t2
+    });
+    create_epoch_operator_with_options(
+        params.long_epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_filter_operator(
+            filter_func,
+            create_map_operator(
+                mapping_func,
+                create_groupby_multi_operator(
+                    groupby_func,
+                    vec![
+                        (sum_int("dns.suspect".to_string()), "suspect_names".to_string()),
+                        (sum_int("dns.txt".to_string()), "txt_queries".to_string()),
+                    ],
+                    create_filter_operator(filter_func2, next_op),
+                ),
+            ),
+        ),
+    )
+}
+
+fn http_flood(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.http_flood_threshold;
+    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::HttpHost];
+    let filter_func: FilterFunc =
+        Box::new(move |headers: &Headers| headers.contains_known(WellKnownKey::HttpMethod));
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let filter_func2: FilterFunc = cmp("requests", Cmp::Ge, threshold);
+    create_epoch_operator_with_options(
+        params.epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_filter_operator(
+            filter_func,
+            create_groupby_operator(
+                groupby_func,
+                Box::new(counter),
+                "requests".to_string(),
+                create_filter_operator(filter_func2, next_op),
+            ),
+        ),
+    )
+}
+
+fn ja3_blocklist(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let blocklist: Vec<String> = params.ja3_blocklist.clone();
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::TlsJa3, WellKnownKey::Ipv4Src];
+    let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+        match headers.get_known(WellKnownKey::TlsJa3) {
+            Some(OpResult::Str(ja3)) => blocklist.contains(ja3),
+            _ => false,
+        }
+    });
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    create_epoch_operator_with_options(
+        params.long_epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_filter_operator(
+            filter_func,
+            create_groupby_operator(
+                groupby_func,
+                Box::new(counter),
+                "handshakes".to_string(),
+                next_op,
+            ),
+        ),
+    )
+}
+
+fn beaconing(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst];
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    create_epoch_operator_with_options(
+        params.long_epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_periodicity_operator(
+            groupby_func,
+            params.beacon_window_gaps,
+            params.beacon_max_cv,
+            params.beacon_max_gap_secs,
+            create_filter_operator(
+                cmp("mean_gap", Cmp::Ge, params.beacon_min_mean_gap),
+                next_op,
+            ),
+        ),
+    )
+}
+
+fn amplification(params: &QueryParams, next_op: OperatorRef) -> [OperatorRef; 2] {
+    let reflector_ports: [i32; 2] = [NTP_PORT, DNS_PORT];
+    let max_request_len: i32 = params.amplification_max_request_len;
+    let threshold: f64 = params.amplification_factor;
+    let epoch_dur: f64 = params.amplification_epoch_dur;
+    let epoch_options: EpochOptions = params.epoch_options();
+
+    let mut requests: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
+        Box::new(move |next_op: OperatorRef| {
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+            let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+                get_mapped_int(WellKnownKey::Ipv4Proto, headers) == IPPROTO_UDP
+                    && reflector_ports.contains(&get_mapped_int(WellKnownKey::L4Dport, headers))
+                    && get_mapped_int(WellKnownKey::Ipv4Len, headers) <= max_request_len
+            });
+            let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
+                filter_groups(&incl_keys, &mut headers)
+            });
+            create_epoch_operator_with_options(
+                epoch_dur,
+                "eid".to_string(),
+                epoch_options,
+                create_filter_operator(
+                    filter_func,
+                    create_groupby_operator(
+                        groupby_func,
+                        sum_int(WellKnownKey::Ipv4Len.as_str().to_string()),
+                        "req_bytes".to_string(),
+                        next_op,
+                    ),
+                ),
+            )
+        });
+
+    let mut responses: Box<dyn FnMut(OperatorRef) -> OperatorRef + 'static> =
+        Box::new(move |next_op: OperatorRef| {
+            let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Dst];
+            let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+                get_mapped_int(WellKnownKey::Ipv4Proto, headers) == IPPROTO_UDP
+                    && reflector_ports.contains(&get_mapped_int(WellKnownKey::L4Sport, headers))
+            });
+            let groupby_func: GroupingFunc = Box::new(move |mut headers: Headers| {
+                filter_groups(&incl_keys, &mut headers)
+            });
+            create_epoch_operator_with_options(
+                epoch_dur,
+                "eid".to_string(),
+                epoch_options,
+                create_filter_operator(
+                    filter_func,
+                    create_groupby_operator(
+                        groupby_func,
+                        sum_int(WellKnownKey::Ipv4Len.as_str().to_string()),
+                        "resp_bytes".to_string(),
+                        next_op,
+                    ),
+                ),
+            )
+        });
+
+    let mut create_join_ops: Box<dyn FnMut(OperatorRef) -> (OperatorRef, OperatorRef) + 'static> =
+        Box::new(move |next_op: OperatorRef| {
+            let left_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
+                Box::new(move |mut headers: Headers| {
+                    let incl_keys: Vec<String> = Vec::from(["req_bytes".to_string()]);
+                    (
+                        rename_filtered_keys(
+                            Vec::from([("host".to_string(), "ipv4.src".to_string())]),
+                            &mut headers,
+                        ),
+                        filter_groups(&incl_keys, &mut headers),
+                    )
+                });
+            let right_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
+                Box::new(move |mut headers: Headers| {
+                    let incl_keys: Vec<String> = Vec::from(["resp_bytes".to_string()]);
+                    (
+                        rename_filtered_keys(
+                            Vec::from([("host".to_string(), "ipv4.dst".to_string())]),
+                            &mut headers,
+                        ),
+                        filter_groups(&incl_keys, &mut headers),
+                    )
+                });
+            let filter_func: FilterFunc = cmp("amp_factor", Cmp::Ge, threshold);
+            create_join_operator(
+                None,
+                left_extractor_func,
+                right_extractor_func,
+                create_map_expr_operator(
+                    "amp_factor = resp_bytes / req_bytes",
+                    &Schema::new()
+                        .with("resp_bytes", FieldType::Int)
+                        .with("req_bytes", FieldType::Int),
+                    create_filter_operator(filter_func, next_op),
+                )
+                .unwrap(),
+            )
+        });
+    let (join_op1, join_op2) = create_join_ops(next_op);
+
+    [requests(join_op1), responses(join_op2)]
+}
+
+fn exfiltration(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.exfiltration_bytes;
+    let internal_nets: Vec<(Ipv4Addr, u8)> = params
+        .internal_nets
+        .iter()
+        .map(|cidr: &String| parse_cidr(cidr).unwrap())
+        .collect();
+    let is_internal = move |key: WellKnownKey, headers: &Headers| match headers.get_known(key) {
+        Some(OpResult::IPv4(addr)) => internal_nets
+            .iter()
+            .any(|(network, prefix_len)| ipv4_in_cidr(*addr, *network, *prefix_len)),
+        _ => false,
+    };
+    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+    let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
+        is_internal(WellKnownKey::Ipv4Src, headers) && !is_internal(WellKnownKey::Ipv4Dst, headers)
+    });
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let filter_func2: FilterFunc = cmp("out_bytes", Cmp::Ge, threshold);
+    create_epoch_operator_with_options(
+        params.exfiltration_epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_filter_operator(
+            filter_func,
+            create_groupby_operator(
+                groupby_func,
+                sum_int(WellKnownKey::Ipv4Len.as_str().to_string()),
+                "out_bytes".to_string(),
+                create_filter_operator(filter_func2, next_op),
+            ),
+        ),
+    )
+}
+
 fn q3(next_op: OperatorRef) -> OperatorRef {
-    let incl_keys: Vec<String> = Vec::from(["ipv4.src".to_string(), "ipv4.dst".to_string()]);
+    let incl_keys: [WellKnownKey; 2] = [WellKnownKey::Ipv4Src, WellKnownKey::Ipv4Dst];
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
     create_epoch_operator(
         100.0,
         "eid".to_string(),
@@ -670,9 +1054,9 @@ fn q3(next_op: OperatorRef) -> OperatorRef {
 }
 
 fn q4(next_op: OperatorRef) -> OperatorRef {
-    let incl_keys: Vec<String> = Vec::from(["ipv4.src".to_string()]);
+    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
     let groupby_func: GroupingFunc =
-        Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
     create_epoch_operator(
         10000.0,
         "eid".to_string(),
@@ -680,26 +1064,399 @@ fn q4(next_op: OperatorRef) -> OperatorRef {
     )
 }
 
+fn rogue_devices(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let oui_db: OuiDb = if params.oui_db.is_empty() {
+        OuiDb::default()
+    } else {
+        load_oui_db(&params.oui_db).unwrap()
+    };
+    let host_keys: [&str; 3] = ["eth.src.oui", "eth.src.vendor", "eth.src"];
+    let vendor_keys: [&str; 2] = ["eth.src.oui", "eth.src.vendor"];
+    let oui_keys: [&str; 1] = ["eth.src.oui"];
+    let distinct_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&host_keys, &mut headers));
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&vendor_keys, &mut headers));
+    let novelty_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&oui_keys, &mut headers));
+    create_epoch_operator_with_options(
+        params.long_epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_oui_lookup_operator(
+            oui_db,
+            create_distinct_operator(
+                distinct_func,
+                create_groupby_operator(
+                    groupby_func,
+                    Box::new(counter),
+                    "hosts".to_string(),
+                    create_distinct_ttl_operator(novelty_func, f64::INFINITY, next_op),
+                ),
+            ),
+        ),
+    )
+}
+
+fn fragmentation(params: &QueryParams, next_op: OperatorRef) -> OperatorRef {
+    let threshold: i32 = params.fragment_threshold;
+    let incl_keys: [WellKnownKey; 1] = [WellKnownKey::Ipv4Src];
+    let filter_func: FilterFunc =
+        Box::new(|headers: &Headers| headers.contains_known(WellKnownKey::Ipv4Frags));
+    let groupby_func: GroupingFunc =
+        Box::new(move |mut headers: Headers| filter_groups(&incl_keys, &mut headers));
+    let filter_func2: FilterFunc = cmp("frags", Cmp::Ge, threshold);
+    create_epoch_operator_with_options(
+        params.epoch_dur,
+        "eid".to_string(),
+        params.epoch_options(),
+        create_filter_operator(
+            filter_func,
+            create_groupby_operator(
+                groupby_func,
+                sum_int(WellKnownKey::Ipv4Frags.as_str().to_string()),
+                "frags".to_string(),
+                create_filter_operator(filter_func2, next_op),
+            ),
+        ),
+    )
+}
+
+fn query_catalog() -> QueryCatalog {
+    QueryCatalog::new()
+        .register(QueryEntry::new(
+            "count_pkts",
+            "Packets per source/destination pair per epoch",
+            &["ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| vec![count_pkts(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "pkts_per_source_dst",
+            "Packets per source/destination pair per epoch",
+            &["ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| vec![pkts_per_source_dst(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "distinct_srcs",
+            "Number of distinct sources per epoch",
+            &["ipv4.src"],
+            |params: &QueryParams, next_op: OperatorRef| vec![distinct_srcs(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "tcp_new_cons",
+            "Hosts receiving many new TCP connections",
+            &["ipv4.proto", "l4.flags", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| vec![tcp_new_cons(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "ssh_brute_force",
+            "Hosts receiving many same-length SSH connections",
+            &["ipv4.proto", "l4.dport", "ipv4.src", "ipv4.dst", "ipv4.len"],
+            |params: &QueryParams, next_op: OperatorRef| vec![ssh_brute_force(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "super_spreader",
+            "Sources contacting many distinct destinations",
+            &["ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| vec![super_spreader(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "port_scan",
+            "Sources probing many distinct ports",
+            &["ipv4.src", "l4.dport"],
+            |params: &QueryParams, next_op: OperatorRef| vec![port_scan(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "vertical_scan",
+            "Sources probing many ports on one host",
+            &["ipv4.src", "ipv4.dst", "l4.dport"],
+            |params: &QueryParams, next_op: OperatorRef| vec![vertical_scan(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "horizontal_scan",
+            "Sources probing one port across many hosts",
+            &["ipv4.src", "ipv4.dst", "l4.dport"],
+            |params: &QueryParams, next_op: OperatorRef| vec![horizontal_scan(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "scan_types",
+            "Vertical and horizontal scans tagged with scan_type",
+            &["ipv4.src", "ipv4.dst", "l4.dport"],
+            |params: &QueryParams, next_op: OperatorRef| vec![scan_types(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "fin_scan",
+            "Sources sending FIN-only probes",
+            &["ipv4.proto", "l4.flags", "ipv4.src", "ipv4.dst", "l4.dport"],
+            |params: &QueryParams, next_op: OperatorRef| vec![fin_scan(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "null_scan",
+            "Sources sending probes with no TCP flags",
+            &["ipv4.proto", "l4.flags", "ipv4.src", "ipv4.dst", "l4.dport"],
+            |params: &QueryParams, next_op: OperatorRef| vec![null_scan(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "xmas_scan",
+            "Sources sending FIN|PSH|URG probes",
+            &["ipv4.proto", "l4.flags", "ipv4.src", "ipv4.dst", "l4.dport"],
+            |params: &QueryParams, next_op: OperatorRef| vec![xmas_scan(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "ddos",
+            "Hosts contacted by many distinct sources",
+            &["ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| vec![ddos(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "syn_flood_sonata",
+            "Hosts with many more SYNs than ACKs",
+            &["ipv4.proto", "l4.flags", "ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| syn_flood_sonata(params, next_op).to_vec(),
+        ))
+        .register(QueryEntry::new(
+            "completed_flows",
+            "Hosts with more SYNs than FINs",
+            &["ipv4.proto", "l4.flags", "ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| completed_flows(params, next_op).to_vec(),
+        ))
+        .register(QueryEntry::new(
+            "slowloris",
+            "Hosts with many low-volume connections",
+            &["ipv4.proto", "ipv4.src", "ipv4.dst", "l4.sport", "ipv4.len"],
+            |params: &QueryParams, next_op: OperatorRef| slowloris(params, next_op).to_vec(),
+        ))
+        .register(QueryEntry::new(
+            "ping_sweep",
+            "Sources sending ICMP echo requests to many hosts",
+            &["ipv4.proto", "icmp.type", "ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| vec![ping_sweep(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "arp_spoof",
+            "IP addresses claimed by several MACs in ARP replies",
+            &["eth.ethertype", "arp.op", "arp.spa", "eth.src"],
+            |params: &QueryParams, next_op: OperatorRef| vec![arp_spoof(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "dns_tunneling",
+            "Sources issuing long, high-entropy or TXT DNS queries",
+            &["ipv4.proto", "l4.dport", "dns.qname", "dns.qtype", "ipv4.src"],
+            |params: &QueryParams, next_op: OperatorRef| vec![dns_tunneling(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "http_flood",
+            "HTTP hosts receiving many requests",
+            &["http.method", "http.host"],
+            |params: &QueryParams, next_op: OperatorRef| vec![http_flood(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "ja3_blocklist",
+            "TLS clients whose JA3 fingerprint is blocklisted",
+            &["tls.ja3", "ipv4.src"],
+            |params: &QueryParams, next_op: OperatorRef| vec![ja3_blocklist(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "beaconing",
+            "Periodic low-volume flows across epochs",
+            &["time", "ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| vec![beaconing(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "amplification",
+            "Hosts receiving far more UDP reflector bytes than they request",
+            &["ipv4.proto", "l4.sport", "l4.dport", "ipv4.len", "ipv4.src", "ipv4.dst"],
+            |params: &QueryParams, next_op: OperatorRef| amplification(params, next_op).to_vec(),
+        ))
+        .register(QueryEntry::new(
+            "exfiltration",
+            "Internal sources sending large volumes to external hosts",
+            &["ipv4.src", "ipv4.dst", "ipv4.len"],
+            |params: &QueryParams, next_op: OperatorRef| vec![exfiltration(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "rogue_devices",
+            "MAC vendor prefixes seen for the first time in an epoch",
+            &["eth.src"],
+            |params: &QueryParams, next_op: OperatorRef| vec![rogue_devices(params, next_op)],
+        ))
+        .register(QueryEntry::new(
+            "fragmentation",
+            "Sources sending excessive IPv4 fragments",
+            &["ipv4.src", "ipv4.frags"],
+            |params: &QueryParams, next_op: OperatorRef| vec![fragmentation(params, next_op)],
+        ))
+}
+
 fn create_query() -> OperatorRef { 
     ident(Rc::new(RefCell::new(dump_as_csv(None, Some(false), Box::new(stdout())))))
 }
 
 fn main() {
+    init_tracing();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let quiet: bool = args.iter().any(|arg| arg == "--quiet");
+    match args.iter().find(|arg| !arg.starts_with("--")).map(String::as_str) {
+        Some("repl") => {
+            run_repl(Duration::from_millis(500)).unwrap();
+            return;
+        }
+        Some("queries") => {
+            for entry in query_catalog().entries() {
+                println!(
+                    "{}: {} (inputs: {})",
+                    entry.name,
+                    entry.description,
+                    entry.inputs.join(", ")
+                );
+            }
+            return;
+        }
+        Some("dot") => {
+            let catalog: QueryCatalog = query_catalog();
+            let name: &str = args
+                .iter()
+                .filter(|arg| !arg.starts_with("--"))
+                .nth(1)
+                .map(String::as_str)
+                .unwrap_or("syn_flood_sonata");
+            let entry: &QueryEntry = catalog.get(name).unwrap();
+            let ops: Vec<OperatorRef> = (entry.constructor)(
+                &entry.defaults,
+                create_dump_operator(false, Box::new(stdout())),
+            );
+            print!("{}", to_dot(name, &ops));
+            return;
+        }
+        Some("replay-capture") => {
+            let mut positional = args.iter().filter(|arg| !arg.starts_with("--")).skip(1);
+            let path: &str = positional.next().map(String::as_str).unwrap();
+            let dump: OperatorRef = create_dump_operator(false, Box::new(stdout()));
+            let query: OperatorRef = match positional.next() {
+                Some(name) => query_catalog().instantiate(name, None, dump).unwrap(),
+                None => dump,
+            };
+            replay_capture(&load_capture(path).unwrap(), &query);
+            return;
+        }
+        Some("bench-filter") => {
+            let bench: batch::FilterBenchmark = batch::benchmark_filter(1_000_000, 1024);
+            println!(
+                "selected {} rows: per-tuple {:?}, columnar {:?} incl. {:?} building columns ({:.2}x)",
+                bench.selected,
+                bench.per_tuple,
+                bench.columnar,
+                bench.column_build,
+                bench.per_tuple.as_secs_f64() / bench.columnar.as_secs_f64()
+            );
+            return;
+        }
+        Some("bench-groupby") => {
+            let (groups, by_tuple, by_key) = group_key::benchmark_groupby(1_000_000);
+            println!(
+                "grouped into {} keys: tuple keys {:?}, packed keys {:?} ({:.2}x)",
+                groups,
+                by_tuple,
+                by_key,
+                by_tuple.as_secs_f64() / by_key.as_secs_f64()
+            );
+            return;
+        }
+        Some(path) => {
+            let mut config: PipelineConfig = PipelineConfig::from_path(path).unwrap();
+            if quiet {
+                config.set_quiet();
+            }
+            for assignment in args.iter().filter_map(|arg| arg.strip_prefix("--param=")) {
+                config.set_param(assignment).unwrap();
+            }
+            if let Some(speed) = args.iter().find_map(|arg| arg.strip_prefix("--speed=")) {
+                config.replay_speed = Some(speed.parse::<f64>().unwrap());
+            }
+            if args.iter().any(|arg| arg == "--deterministic") {
+                config.deterministic = true;
+            }
+            if let Some(eid_key) = args.iter().find_map(|arg| match arg.as_str() {
+                "--audit" => Some("eid"),
+                _ => arg.strip_prefix("--audit="),
+            }) {
+                config.audit = Some(eid_key.to_string());
+            }
+            if let Some(addr) = args.iter().find_map(|arg| match arg.as_str() {
+                "--control" => Some(DEFAULT_CONTROL_ADDR),
+                _ => arg.strip_prefix("--control="),
+            }) {
+                let control: ControlRef = ControlState::new(&config);
+                spawn_control_server(addr, Arc::clone(&control)).unwrap();
+                let pipeline: Pipeline = run_controlled(config, &query_catalog(), control).unwrap();
+                pipeline.stats().report(&mut std::io::stderr()).unwrap();
+                if let Some(audit) = &pipeline.audit {
+                    audit.report(&mut std::io::stderr()).unwrap();
+                }
+                return;
+            }
+            let mut pipeline: Pipeline =
+                Pipeline::from_pipeline_config_with_catalog(config, &query_catalog()).unwrap();
+            pipeline.run().unwrap();
+            pipeline.stats().report(&mut std::io::stderr()).unwrap();
+            if let Some(audit) = &pipeline.audit {
+                audit.report(&mut std::io::stderr()).unwrap();
+            }
+            return;
+        }
+        None => {}
+    }
     let mut _query: OperatorRef = create_query();
     for i in 0..20 {
-        let mut header: BTreeMap<String, OpResult> = BTreeMap::new();
-        header.insert("time".to_string(), OpResult::Float(OrderedFloat(i as f64)));
-        header.insert("eth.src".to_string(), OpResult::MAC([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]));
-        header.insert("eth.dst".to_string(), OpResult::MAC([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]));
-        header.insert("eth.ethertype".to_string(), OpResult::Int(0x0800));
-        header.insert("ipv4.hlen".to_string(), OpResult::Int(20));
-        header.insert("ipv4.proto".to_string(), OpResult::Int(6));
-        header.insert("ipv4.len".to_string(), OpResult::Int(60));
-        header.insert("ipv4.src".to_string(), OpResult::IPv4("127.0.0.1".parse().unwrap()));
-        header.insert("ipv4.dst".to_string(), OpResult::IPv4("127.0.0.1".parse().unwrap()));
-        header.insert("l4.sport".to_string(), OpResult::Int(440));
-        header.insert("l4.dport".to_string(), OpResult::Int(50000));
-        header.insert("l4.flags".to_string(), OpResult::Int(10));
-        (_query.borrow_mut().next)(&mut header)
+        (_query.borrow_mut().next)(&mut synthetic_headers(i))
+    }
+    (_query.borrow_mut().reset)(&mut Headers::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ordered_float::OrderedFloat;
+    use translation::testing::{TestSink, run_trace, tuple};
+
+    fn ssh_attempt(time: f64, src: Ipv4Addr) -> Headers {
+        tuple(&[
+            ("time", OpResult::Float(OrderedFloat(time))),
+            ("ipv4.src", OpResult::IPv4(src)),
+            ("ipv4.dst", OpResult::IPv4(Ipv4Addr::new(10, 0, 0, 1))),
+            ("ipv4.proto", OpResult::Int(6)),
+            ("ipv4.len", OpResult::Int(60)),
+            ("l4.dport", OpResult::Int(22)),
+        ])
+    }
+
+    fn ssh_attempts(n_srcs: u8, per_src: usize) -> Vec<Headers> {
+        (0..n_srcs)
+            .flat_map(|i: u8| {
+                let src: Ipv4Addr = Ipv4Addr::new(192, 168, 1, i);
+                (0..per_src).map(move |_| ssh_attempt(100.0 + 0.01 * i as f64, src))
+            })
+            .collect()
+    }
+
+    fn run_ssh_brute_force(tuples: Vec<Headers>) -> TestSink {
+        let params: QueryParams = QueryParams::default();
+        run_trace(|next_op: OperatorRef| ssh_brute_force(&params, next_op), tuples)
+    }
+
+    #[test]
+    fn ssh_brute_force_fires_after_forty_distinct_sources() {
+        run_ssh_brute_force(ssh_attempts(39, 1)).assert_emitted_count(0);
+        run_ssh_brute_force(ssh_attempts(40, 1))
+            .assert_emitted_count(1)
+            .assert_emitted_where(|headers: &Headers| {
+                headers.get("srcs") == Some(&OpResult::Int(40))
+                    && headers.get("ipv4.dst") == Some(&OpResult::IPv4(Ipv4Addr::new(10, 0, 0, 1)))
+            });
+    }
+
+    #[test]
+    fn ssh_brute_force_ignores_repeated_attempts_from_one_source() {
+        run_ssh_brute_force(ssh_attempts(39, 3)).assert_emitted_count(0);
     }
 }
@@ -0,0 +1,250 @@
+#![allow(dead_code)]
+
+use crate::error::{InputKind, StreamError};
+use crate::json::json_of_headers;
+use crate::trace_event;
+use crate::utils::{Headers, Operator, OperatorRef, render_template};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub const MQTT_DEFAULT_BROKER: &str = "127.0.0.1:1883";
+pub const MQTT_KEEP_ALIVE_SECS: u16 = 60;
+pub const MQTT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+pub const MQTT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+pub const MQTT_MAX_PENDING: usize = 1024;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+
+fn mqtt_error(msg: String) -> StreamError {
+    StreamError::Io(Error::new(ErrorKind::InvalidData, msg))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MqttOptions {
+    pub broker: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub qos: u8,
+    pub retain: bool,
+}
+
+pub fn encode_remaining_length(mut len: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut byte: u8 = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn push_str(s: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut out: Vec<u8> = vec![header];
+    encode_remaining_length(body.len(), &mut out);
+    out.extend(body);
+    out
+}
+
+pub fn encode_connect(options: &MqttOptions) -> Vec<u8> {
+    let mut flags: u8 = 0x02;
+    if options.username.is_some() {
+        flags |= 0x80;
+    }
+    if options.password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body: Vec<u8> = Vec::new();
+    push_str("MQTT", &mut body);
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&MQTT_KEEP_ALIVE_SECS.to_be_bytes());
+    push_str(&options.client_id, &mut body);
+    if let Some(username) = &options.username {
+        push_str(username, &mut body);
+    }
+    if let Some(password) = &options.password {
+        push_str(password, &mut body);
+    }
+    packet(CONNECT, body)
+}
+
+pub fn encode_publish(
+    topic: &str,
+    payload: &[u8],
+    qos: u8,
+    retain: bool,
+    packet_id: u16,
+) -> Vec<u8> {
+    let mut body: Vec<u8> = Vec::new();
+    push_str(topic, &mut body);
+    if qos > 0 {
+        body.extend_from_slice(&packet_id.to_be_bytes());
+    }
+    body.extend_from_slice(payload);
+    packet(PUBLISH | (qos << 1) | retain as u8, body)
+}
+
+fn read_packet(stream: &mut TcpStream) -> Result<(u8, Vec<u8>), StreamError> {
+    let mut header: [u8; 1] = [0];
+    stream.read_exact(&mut header)?;
+    let mut len: usize = 0;
+    let mut shift: u32 = 0;
+    loop {
+        let mut byte: [u8; 1] = [0];
+        stream.read_exact(&mut byte)?;
+        len |= ((byte[0] & 0x7f) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(mqtt_error("malformed remaining length".to_string()));
+        }
+    }
+    let mut body: Vec<u8> = vec![0; len];
+    stream.read_exact(&mut body)?;
+    Ok((header[0], body))
+}
+
+pub struct MqttClient {
+    pub options: MqttOptions,
+    stream: Option<TcpStream>,
+    next_packet_id: u16,
+    pending: VecDeque<(String, Vec<u8>)>,
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    pub dropped: usize,
+}
+
+impl MqttClient {
+    pub fn new(options: MqttOptions) -> Result<MqttClient, StreamError> {
+        if options.qos > 1 {
+            return Err(StreamError::unexpected(
+                InputKind::Config,
+                "mqtt qos 0 or 1",
+                options.qos.to_string(),
+            ));
+        }
+        Ok(MqttClient {
+            options,
+            stream: None,
+            next_packet_id: 1,
+            pending: VecDeque::new(),
+            retry_at: None,
+            backoff: MQTT_RETRY_BACKOFF,
+            dropped: 0,
+        })
+    }
+
+    fn connect(&mut self) -> Result<&mut TcpStream, StreamError> {
+        if self.stream.is_none() {
+            let mut stream: TcpStream = TcpStream::connect(&self.options.broker)?;
+            stream.write_all(&encode_connect(&self.options))?;
+            match read_packet(&mut stream)? {
+                (CONNACK, body) if body.get(1) == Some(&0) => {}
+                (CONNACK, body) => {
+                    return Err(mqtt_error(format!(
+                        "mqtt broker refused connection with code {}",
+                        body.get(1).copied().unwrap_or(0xff)
+                    )));
+                }
+                (kind, _) => {
+                    return Err(mqtt_error(format!(
+                        "expected CONNACK, found 0x{:02x}",
+                        kind
+                    )));
+                }
+            }
+            self.stream = Some(stream);
+        }
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    fn send(&mut self, topic: &str, payload: &[u8]) -> Result<(), StreamError> {
+        let qos: u8 = self.options.qos;
+        let retain: bool = self.options.retain;
+        let packet_id: u16 = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.checked_add(1).unwrap_or(1);
+        let stream: &mut TcpStream = self.connect()?;
+        stream.write_all(&encode_publish(topic, payload, qos, retain, packet_id))?;
+        if qos == 1 {
+            match read_packet(stream)? {
+                (PUBACK, body) if body[..] == packet_id.to_be_bytes() => {}
+                (kind, _) => {
+                    return Err(mqtt_error(format!("expected PUBACK, found 0x{:02x}", kind)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn publish(&mut self, topic: String, payload: Vec<u8>) -> Result<(), StreamError> {
+        if self.pending.len() >= MQTT_MAX_PENDING {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending.push_back((topic, payload));
+        if self.retry_at.is_some_and(|at: Instant| Instant::now() < at) {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    pub fn flush(&mut self) -> Result<(), StreamError> {
+        while let Some((topic, payload)) = self.pending.pop_front() {
+            if let Err(e) = self.send(&topic, &payload) {
+                self.pending.push_front((topic, payload));
+                self.stream = None;
+                self.retry_at = Some(Instant::now() + self.backoff);
+                self.backoff = (self.backoff * 2).min(MQTT_MAX_BACKOFF);
+                return Err(e);
+            }
+            self.retry_at = None;
+            self.backoff = MQTT_RETRY_BACKOFF;
+        }
+        Ok(())
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+pub fn dump_mqtt(client: MqttClient, topic_template: String) -> OperatorRef {
+    let label: String = format!("mqtt({})", topic_template);
+    let client: Rc<RefCell<MqttClient>> = Rc::new(RefCell::new(client));
+    let client_ref_clone = Rc::clone(&client);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let topic: String = render_template(&topic_template, headers);
+        let payload: Vec<u8> = json_of_headers(headers).to_string().into_bytes();
+        if let Err(_e) = client.borrow_mut().publish(topic, payload) {
+            trace_event!(error = %_e, "mqtt: publish failed, retrying later");
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |_headers: &mut Headers| {
+        if let Err(_e) = client_ref_clone.borrow_mut().flush() {
+            trace_event!(error = %_e, "mqtt: flush failed, retrying later");
+        }
+    });
+
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label(label)))
+}
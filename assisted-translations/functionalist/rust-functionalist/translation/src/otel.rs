@@ -0,0 +1,211 @@
+#![allow(dead_code)]
+
+use serde_json::{Value, json};
+
+use crate::builtins::Severity;
+use crate::error::StreamError;
+use crate::trace_event;
+use crate::utils::{
+    Headers, OpResult, Operator, OperatorRef, string_of_headers, string_of_op_result,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const OTLP_DEFAULT_ENDPOINT: &str = "http://127.0.0.1:4318";
+pub const OTLP_SCOPE: &str = "sonata";
+pub const OTLP_MAX_BATCH: usize = 512;
+
+#[derive(Clone, Debug)]
+pub struct OtlpExporter {
+    pub endpoint: String,
+    pub service_name: String,
+    pub headers: BTreeMap<String, String>,
+}
+
+impl OtlpExporter {
+    pub fn new(endpoint: &str, service_name: &str) -> Self {
+        OtlpExporter {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            service_name: service_name.to_string(),
+            headers: BTreeMap::new(),
+        }
+    }
+
+    fn resource(&self) -> Value {
+        let service_name: OpResult = OpResult::Str(self.service_name.clone());
+        json!({ "attributes": [otlp_attribute("service.name", &service_name)] })
+    }
+
+    pub fn logs_request(&self, records: Vec<Value>) -> Value {
+        json!({
+            "resourceLogs": [{
+                "resource": self.resource(),
+                "scopeLogs": [{ "scope": { "name": OTLP_SCOPE }, "logRecords": records }],
+            }]
+        })
+    }
+
+    pub fn metrics_request(&self, metrics: Vec<Value>) -> Value {
+        json!({
+            "resourceMetrics": [{
+                "resource": self.resource(),
+                "scopeMetrics": [{ "scope": { "name": OTLP_SCOPE }, "metrics": metrics }],
+            }]
+        })
+    }
+
+    pub fn post(&self, signal: &str, body: &Value) -> Result<(), StreamError> {
+        let mut request: ureq::Request = ureq::post(&format!("{}/v1/{}", self.endpoint, signal))
+            .set("Content-Type", "application/json");
+        for (name, value) in self.headers.iter() {
+            request = request.set(name, value);
+        }
+        request
+            .send_string(&body.to_string())
+            .map_err(|e| Error::other(format!("otlp {}: {}", signal, e)))?;
+        Ok(())
+    }
+}
+
+pub fn otlp_value(val: &OpResult) -> Value {
+    match val {
+        OpResult::Int(i) => json!({ "intValue": i.to_string() }),
+        OpResult::Float(f) => json!({ "doubleValue": f.0 }),
+        other => json!({ "stringValue": string_of_op_result(other) }),
+    }
+}
+
+pub fn otlp_attribute(key: &str, val: &OpResult) -> Value {
+    json!({ "key": key, "value": otlp_value(val) })
+}
+
+pub fn otlp_attributes(headers: &Headers, skip: &[String]) -> Vec<Value> {
+    let mut fields: Vec<(&String, &OpResult)> = headers
+        .iter()
+        .filter(|(key, _)| !skip.contains(key))
+        .collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    fields
+        .into_iter()
+        .map(|(key, val)| otlp_attribute(key, val))
+        .collect()
+}
+
+pub fn unix_nanos(headers: &Headers) -> String {
+    let secs: f64 = match headers.get("time") {
+        Some(OpResult::Float(time)) => time.0,
+        _ => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0),
+    };
+    ((secs * 1e9) as u128).to_string()
+}
+
+pub fn otlp_severity(severity: Severity) -> (i32, &'static str) {
+    match severity {
+        Severity::Normal => (9, "INFO"),
+        Severity::Low => (13, "WARN"),
+        Severity::Medium => (17, "ERROR"),
+        Severity::High => (21, "FATAL"),
+    }
+}
+
+pub fn log_record_of_headers(headers: &Headers, severity: Severity) -> Value {
+    let (number, text) = otlp_severity(severity);
+    json!({
+        "timeUnixNano": unix_nanos(headers),
+        "severityNumber": number,
+        "severityText": text,
+        "body": { "stringValue": string_of_headers(headers) },
+        "attributes": otlp_attributes(headers, &[]),
+    })
+}
+
+pub fn data_point_of_headers(headers: &Headers, field: &str, skip: &[String]) -> Option<Value> {
+    let mut point: Value = json!({
+        "timeUnixNano": unix_nanos(headers),
+        "attributes": otlp_attributes(headers, skip),
+    });
+    match headers.get(field)? {
+        OpResult::Int(i) => point["asInt"] = json!(i.to_string()),
+        OpResult::Float(f) => point["asDouble"] = json!(f.0),
+        _ => return None,
+    }
+    Some(point)
+}
+
+pub struct OtlpOptions {
+    pub severity_key: Option<String>,
+    pub threshold: f64,
+    pub metrics: Vec<String>,
+    pub logs: bool,
+}
+
+#[derive(Default)]
+struct OtlpBatch {
+    records: Vec<Value>,
+    points: BTreeMap<String, Vec<Value>>,
+}
+
+fn flush_batch(exporter: &OtlpExporter, batch: &mut OtlpBatch) {
+    let records: Vec<Value> = std::mem::take(&mut batch.records);
+    if !records.is_empty()
+        && let Err(_e) = exporter.post("logs", &exporter.logs_request(records))
+    {
+        trace_event!(error = %_e, "otlp: dropped a logs batch");
+    }
+    let metrics: Vec<Value> = std::mem::take(&mut batch.points)
+        .into_iter()
+        .map(|(field, points)| {
+            json!({
+                "name": format!("{}.{}", OTLP_SCOPE, field),
+                "gauge": { "dataPoints": points },
+            })
+        })
+        .collect();
+    if !metrics.is_empty()
+        && let Err(_e) = exporter.post("metrics", &exporter.metrics_request(metrics))
+    {
+        trace_event!(error = %_e, "otlp: dropped a metrics batch");
+    }
+}
+
+pub fn create_otlp_operator(exporter: OtlpExporter, options: OtlpOptions) -> OperatorRef {
+    let batch: Rc<RefCell<OtlpBatch>> = Rc::new(RefCell::new(OtlpBatch::default()));
+    let batch_ref_clone = Rc::clone(&batch);
+    let exporter_ref_clone: OtlpExporter = exporter.clone();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let mut batch = batch.borrow_mut();
+        let severity: Severity = match options
+            .severity_key
+            .as_ref()
+            .and_then(|key| headers.get(key))
+        {
+            Some(OpResult::Int(i)) => Severity::of_value(*i as f64, options.threshold),
+            Some(OpResult::Float(f)) => Severity::of_value(f.0, options.threshold),
+            _ => Severity::Normal,
+        };
+        if options.logs {
+            batch.records.push(log_record_of_headers(headers, severity));
+        }
+        for field in options.metrics.iter() {
+            if let Some(point) = data_point_of_headers(headers, field, &options.metrics) {
+                batch.points.entry(field.clone()).or_default().push(point);
+            }
+        }
+        if batch.records.len() >= OTLP_MAX_BATCH {
+            flush_batch(&exporter, &mut batch);
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |_headers: &mut Headers| {
+        flush_batch(&exporter_ref_clone, &mut batch_ref_clone.borrow_mut())
+    });
+
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label("otlp")))
+}
@@ -0,0 +1,533 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::dns::{DnsMessage, is_dns};
+use crate::error::{ParseError, StreamError};
+use crate::http::{HttpRequest, is_http};
+use crate::keys::WellKnownKey;
+use crate::schema::{Schema, SchemaError};
+use crate::tls::{TlsHello, is_tls_handshake};
+use crate::utils::{Headers, OpResult};
+use std::net::Ipv4Addr;
+
+pub const ETHERTYPE_IPV4: i32 = 0x0800;
+pub const ETHERTYPE_ARP: i32 = 0x0806;
+pub const ETHERTYPE_VLAN: i32 = 0x8100;
+pub const ETHERTYPE_QINQ: i32 = 0x88A8;
+pub const ETHERTYPE_TEB: i32 = 0x6558;
+
+pub const IPPROTO_ICMP: i32 = 1;
+pub const IPPROTO_TCP: i32 = 6;
+pub const IPPROTO_UDP: i32 = 17;
+pub const IPPROTO_GRE: i32 = 47;
+
+pub const NTP_PORT: i32 = 123;
+pub const VXLAN_PORT: i32 = 4789;
+
+pub const OUTER_PREFIX: &str = "outer.";
+
+pub const IPV4_MORE_FRAGMENTS: u16 = 0x2000;
+pub const IPV4_FRAGMENT_OFFSET: u16 = 0x1fff;
+
+pub const ICMP_ECHO_REPLY: i32 = 0;
+pub const ICMP_DEST_UNREACHABLE: i32 = 3;
+pub const ICMP_ECHO_REQUEST: i32 = 8;
+
+pub const ARP_REQUEST: i32 = 1;
+pub const ARP_REPLY: i32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthHeader {
+    pub src: [u8; 6],
+    pub dst: [u8; 6],
+    pub ethertype: i32,
+}
+
+impl Default for EthHeader {
+    fn default() -> Self {
+        EthHeader {
+            src: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+            dst: [0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF],
+            ethertype: ETHERTYPE_IPV4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ipv4Header {
+    pub hlen: i32,
+    pub proto: i32,
+    pub len: i32,
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+}
+
+impl Default for Ipv4Header {
+    fn default() -> Self {
+        Ipv4Header {
+            hlen: 20,
+            proto: IPPROTO_TCP,
+            len: 60,
+            src: Ipv4Addr::UNSPECIFIED,
+            dst: Ipv4Addr::UNSPECIFIED,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct L4Header {
+    pub sport: i32,
+    pub dport: i32,
+    pub flags: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcpHeader {
+    pub seq: u32,
+    pub ack: u32,
+    pub len: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IcmpHeader {
+    pub icmp_type: i32,
+    pub code: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArpHeader {
+    pub op: i32,
+    pub spa: Ipv4Addr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tunnel {
+    Gre,
+    Vxlan,
+}
+
+impl Tunnel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tunnel::Gre => "gre",
+            Tunnel::Vxlan => "vxlan",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OuterRecord {
+    pub tunnel: Tunnel,
+    pub vni: Option<i32>,
+    pub packet: PacketRecord,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PacketRecord {
+    pub time: f64,
+    pub eth: EthHeader,
+    pub vlan: Option<i32>,
+    pub ipv4: Ipv4Header,
+    pub l4: L4Header,
+    pub tcp: Option<TcpHeader>,
+    pub icmp: Option<IcmpHeader>,
+    pub arp: Option<ArpHeader>,
+    pub payload: Vec<u8>,
+    pub fragments: i32,
+    pub outer: Option<Box<OuterRecord>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DecodeOptions {
+    pub decapsulate: bool,
+    pub fragment_timeout: Option<f64>,
+}
+
+fn frame_error(protocol: &'static str, msg: &str) -> StreamError {
+    StreamError::Parse(ParseError::Packet {
+        protocol,
+        msg: msg.to_string(),
+    })
+}
+
+struct Reader<'a> {
+    protocol: &'static str,
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(protocol: &'static str, buf: &'a [u8]) -> Self {
+        Reader { protocol, buf }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], StreamError> {
+        if self.buf.len() < n {
+            return Err(frame_error(self.protocol, "truncated"));
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, StreamError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StreamError> {
+        let bytes: &[u8] = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, StreamError> {
+        let bytes: &[u8] = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn mac(&mut self) -> Result<[u8; 6], StreamError> {
+        let mut mac: [u8; 6] = [0; 6];
+        mac.copy_from_slice(self.take(6)?);
+        Ok(mac)
+    }
+
+    fn ipv4(&mut self) -> Result<Ipv4Addr, StreamError> {
+        Ok(Ipv4Addr::from(self.u32()?))
+    }
+}
+
+impl PacketRecord {
+    pub fn decode(
+        time: f64,
+        frame: &[u8],
+        options: &DecodeOptions,
+    ) -> Result<PacketRecord, StreamError> {
+        let mut reader: Reader = Reader::new("ethernet", frame);
+        let dst: [u8; 6] = reader.mac()?;
+        let src: [u8; 6] = reader.mac()?;
+        let mut ethertype: i32 = reader.u16()? as i32;
+        let mut vlan: Option<i32> = None;
+        while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+            let tci: i32 = reader.u16()? as i32;
+            vlan.get_or_insert(tci & 0x0fff);
+            ethertype = reader.u16()? as i32;
+        }
+        let record: PacketRecord = PacketRecord {
+            time,
+            eth: EthHeader {
+                src,
+                dst,
+                ethertype,
+            },
+            vlan,
+            ..PacketRecord::default()
+        };
+        record.decode_network(reader.buf, options)
+    }
+
+    fn decode_network(
+        mut self,
+        buf: &[u8],
+        options: &DecodeOptions,
+    ) -> Result<PacketRecord, StreamError> {
+        match self.eth.ethertype {
+            ETHERTYPE_ARP => {
+                let mut reader: Reader = Reader::new("ARP", buf);
+                reader.take(6)?;
+                let op: i32 = reader.u16()? as i32;
+                reader.take(6)?;
+                let spa: Ipv4Addr = reader.ipv4()?;
+                self.arp = Some(ArpHeader { op, spa });
+                Ok(self)
+            }
+            ETHERTYPE_IPV4 => self.decode_ipv4(buf, options),
+            _ => Err(frame_error("ethernet", "unsupported ethertype")),
+        }
+    }
+
+    fn decode_ipv4(
+        mut self,
+        buf: &[u8],
+        options: &DecodeOptions,
+    ) -> Result<PacketRecord, StreamError> {
+        let mut reader: Reader = Reader::new("IPv4", buf);
+        let hlen: usize = ((reader.u8()? & 0x0f) as usize) * 4;
+        reader.take(1)?;
+        let len: usize = reader.u16()? as usize;
+        reader.take(2)?;
+        let frag: u16 = reader.u16()?;
+        reader.take(1)?;
+        let proto: i32 = reader.u8()? as i32;
+        reader.take(2)?;
+        let src: Ipv4Addr = reader.ipv4()?;
+        let dst: Ipv4Addr = reader.ipv4()?;
+        if hlen < 20 || len < hlen || buf.len() < hlen {
+            return Err(frame_error("IPv4", "bad header length"));
+        }
+        self.ipv4 = Ipv4Header {
+            hlen: hlen as i32,
+            proto,
+            len: len as i32,
+            src,
+            dst,
+        };
+        let fragmented: bool = frag & (IPV4_MORE_FRAGMENTS | IPV4_FRAGMENT_OFFSET) != 0;
+        if fragmented {
+            self.fragments = 1;
+        }
+        let mut reader: Reader = Reader::new("IPv4", &buf[hlen..len.min(buf.len())]);
+        match proto {
+            _ if frag & IPV4_FRAGMENT_OFFSET != 0 => self.payload = reader.buf.to_vec(),
+            IPPROTO_TCP => {
+                let sport: i32 = reader.u16()? as i32;
+                let dport: i32 = reader.u16()? as i32;
+                let seq: u32 = reader.u32()?;
+                let ack: u32 = reader.u32()?;
+                let offset: usize = ((reader.u8()? >> 4) as usize) * 4;
+                let flags: i32 = reader.u8()? as i32;
+                reader.take(offset.saturating_sub(14))?;
+                self.l4 = L4Header {
+                    sport,
+                    dport,
+                    flags,
+                };
+                let len: i32 = (len - hlen).saturating_sub(offset) as i32;
+                self.tcp = Some(TcpHeader { seq, ack, len });
+                self.payload = reader.buf.to_vec();
+            }
+            IPPROTO_UDP => {
+                let sport: i32 = reader.u16()? as i32;
+                let dport: i32 = reader.u16()? as i32;
+                reader.take(4)?;
+                self.l4 = L4Header {
+                    sport,
+                    dport,
+                    flags: 0,
+                };
+                if options.decapsulate && !fragmented && dport == VXLAN_PORT {
+                    return self.decapsulate_vxlan(reader.buf);
+                }
+                self.payload = reader.buf.to_vec();
+            }
+            IPPROTO_ICMP => {
+                let icmp_type: i32 = reader.u8()? as i32;
+                let code: i32 = reader.u8()? as i32;
+                self.icmp = Some(IcmpHeader { icmp_type, code });
+            }
+            IPPROTO_GRE if options.decapsulate && !fragmented => {
+                return self.decapsulate_gre(reader.buf);
+            }
+            _ => self.payload = reader.buf.to_vec(),
+        }
+        Ok(self)
+    }
+
+    fn decapsulate_vxlan(self, buf: &[u8]) -> Result<PacketRecord, StreamError> {
+        let mut reader: Reader = Reader::new("VXLAN", buf);
+        let flags: u8 = reader.u8()?;
+        reader.take(3)?;
+        let vni: Option<i32> = Some((reader.u32()? >> 8) as i32).filter(|_| flags & 0x08 != 0);
+        let inner: PacketRecord =
+            PacketRecord::decode(self.time, reader.buf, &DecodeOptions::default())?;
+        Ok(inner.with_outer(Tunnel::Vxlan, vni, self))
+    }
+
+    fn decapsulate_gre(self, buf: &[u8]) -> Result<PacketRecord, StreamError> {
+        let mut reader: Reader = Reader::new("GRE", buf);
+        let flags: u16 = reader.u16()?;
+        let ethertype: i32 = reader.u16()? as i32;
+        if flags & 0x8000 != 0 {
+            reader.take(4)?;
+        }
+        let key: Option<i32> = match flags & 0x2000 {
+            0 => None,
+            _ => Some(reader.u32()? as i32),
+        };
+        if flags & 0x1000 != 0 {
+            reader.take(4)?;
+        }
+        let inner: PacketRecord = match ethertype {
+            ETHERTYPE_TEB => {
+                PacketRecord::decode(self.time, reader.buf, &DecodeOptions::default())?
+            }
+            _ => PacketRecord {
+                time: self.time,
+                eth: EthHeader {
+                    ethertype,
+                    ..self.eth.clone()
+                },
+                vlan: self.vlan,
+                ..PacketRecord::default()
+            }
+            .decode_network(reader.buf, &DecodeOptions::default())?,
+        };
+        Ok(inner.with_outer(Tunnel::Gre, key, self))
+    }
+
+    fn with_outer(mut self, tunnel: Tunnel, vni: Option<i32>, packet: PacketRecord) -> Self {
+        self.outer = Some(Box::new(OuterRecord {
+            tunnel,
+            vni,
+            packet,
+        }));
+        self
+    }
+}
+
+impl From<PacketRecord> for Headers {
+    fn from(record: PacketRecord) -> Headers {
+        let mut headers: Headers = Headers::new();
+        headers.insert(
+            WellKnownKey::Time.into(),
+            OpResult::Float(OrderedFloat(record.time)),
+        );
+        headers.insert(WellKnownKey::EthSrc.into(), OpResult::MAC(record.eth.src));
+        headers.insert(WellKnownKey::EthDst.into(), OpResult::MAC(record.eth.dst));
+        headers.insert(
+            WellKnownKey::EthEthertype.into(),
+            OpResult::Int(record.eth.ethertype),
+        );
+        headers.insert(
+            WellKnownKey::Ipv4Hlen.into(),
+            OpResult::Int(record.ipv4.hlen),
+        );
+        headers.insert(
+            WellKnownKey::Ipv4Proto.into(),
+            OpResult::Int(record.ipv4.proto),
+        );
+        headers.insert(WellKnownKey::Ipv4Len.into(), OpResult::Int(record.ipv4.len));
+        headers.insert(
+            WellKnownKey::Ipv4Src.into(),
+            OpResult::IPv4(record.ipv4.src),
+        );
+        headers.insert(
+            WellKnownKey::Ipv4Dst.into(),
+            OpResult::IPv4(record.ipv4.dst),
+        );
+        headers.insert(WellKnownKey::L4Sport.into(), OpResult::Int(record.l4.sport));
+        headers.insert(WellKnownKey::L4Dport.into(), OpResult::Int(record.l4.dport));
+        headers.insert(WellKnownKey::L4Flags.into(), OpResult::Int(record.l4.flags));
+        if let Some(icmp) = record.icmp {
+            headers.insert(WellKnownKey::IcmpType.into(), OpResult::Int(icmp.icmp_type));
+            headers.insert(WellKnownKey::IcmpCode.into(), OpResult::Int(icmp.code));
+        }
+        if let Some(arp) = record.arp {
+            headers.insert(WellKnownKey::ArpOp.into(), OpResult::Int(arp.op));
+            headers.insert(WellKnownKey::ArpSpa.into(), OpResult::IPv4(arp.spa));
+        }
+        if let Some(tcp) = record.tcp {
+            headers.insert(WellKnownKey::TcpSeq.into(), OpResult::Int(tcp.seq as i32));
+            headers.insert(WellKnownKey::TcpAck.into(), OpResult::Int(tcp.ack as i32));
+            headers.insert(WellKnownKey::TcpLen.into(), OpResult::Int(tcp.len));
+        }
+        if record.fragments > 0 {
+            headers.insert(
+                WellKnownKey::Ipv4Frags.into(),
+                OpResult::Int(record.fragments),
+            );
+        }
+        if let Some(vlan) = record.vlan {
+            headers.insert(WellKnownKey::EthVlan.into(), OpResult::Int(vlan));
+        }
+        if let Some(outer) = record.outer {
+            headers.insert(
+                format!("{}tunnel", OUTER_PREFIX),
+                OpResult::Str(outer.tunnel.as_str().to_string()),
+            );
+            if let Some(vni) = outer.vni {
+                headers.insert(format!("{}vni", OUTER_PREFIX), OpResult::Int(vni));
+            }
+            for (key, val) in Headers::from(outer.packet).iter() {
+                if key != WellKnownKey::Time.as_str() {
+                    headers.insert(format!("{}{}", OUTER_PREFIX, key), val.clone());
+                }
+            }
+        }
+        if is_dns(record.ipv4.proto, record.l4.sport, record.l4.dport)
+            && let Ok(msg) = DnsMessage::decode(&record.payload)
+        {
+            msg.insert_into(record.payload.len(), &mut headers);
+        }
+        if is_http(record.ipv4.proto, record.l4.dport)
+            && let Ok(request) = HttpRequest::decode(&record.payload)
+        {
+            request.insert_into(&mut headers);
+        }
+        if is_tls_handshake(record.ipv4.proto, &record.payload)
+            && let Ok(hello) = TlsHello::decode(&record.payload)
+        {
+            hello.insert_into(&mut headers);
+        }
+        headers
+    }
+}
+
+impl TryFrom<&Headers> for PacketRecord {
+    type Error = SchemaError;
+
+    fn try_from(headers: &Headers) -> Result<PacketRecord, SchemaError> {
+        let schema: Schema = Schema::decoded();
+        Ok(PacketRecord {
+            time: schema.get_float(WellKnownKey::Time.as_str(), headers)?.0,
+            eth: EthHeader {
+                src: schema.get_mac(WellKnownKey::EthSrc.as_str(), headers)?,
+                dst: schema.get_mac(WellKnownKey::EthDst.as_str(), headers)?,
+                ethertype: schema.get_int(WellKnownKey::EthEthertype.as_str(), headers)?,
+            },
+            ipv4: Ipv4Header {
+                hlen: schema.get_int(WellKnownKey::Ipv4Hlen.as_str(), headers)?,
+                proto: schema.get_int(WellKnownKey::Ipv4Proto.as_str(), headers)?,
+                len: schema.get_int(WellKnownKey::Ipv4Len.as_str(), headers)?,
+                src: schema.get_ipv4(WellKnownKey::Ipv4Src.as_str(), headers)?,
+                dst: schema.get_ipv4(WellKnownKey::Ipv4Dst.as_str(), headers)?,
+            },
+            l4: L4Header {
+                sport: schema.get_int(WellKnownKey::L4Sport.as_str(), headers)?,
+                dport: schema.get_int(WellKnownKey::L4Dport.as_str(), headers)?,
+                flags: schema.get_int(WellKnownKey::L4Flags.as_str(), headers)?,
+            },
+            tcp: if headers.contains_known(WellKnownKey::TcpSeq) {
+                Some(TcpHeader {
+                    seq: schema.get_int(WellKnownKey::TcpSeq.as_str(), headers)? as u32,
+                    ack: schema.get_int(WellKnownKey::TcpAck.as_str(), headers)? as u32,
+                    len: schema.get_int(WellKnownKey::TcpLen.as_str(), headers)?,
+                })
+            } else {
+                None
+            },
+            icmp: if headers.contains_known(WellKnownKey::IcmpType) {
+                Some(IcmpHeader {
+                    icmp_type: schema.get_int(WellKnownKey::IcmpType.as_str(), headers)?,
+                    code: schema.get_int(WellKnownKey::IcmpCode.as_str(), headers)?,
+                })
+            } else {
+                None
+            },
+            arp: if headers.contains_known(WellKnownKey::ArpOp) {
+                Some(ArpHeader {
+                    op: schema.get_int(WellKnownKey::ArpOp.as_str(), headers)?,
+                    spa: schema.get_ipv4(WellKnownKey::ArpSpa.as_str(), headers)?,
+                })
+            } else {
+                None
+            },
+            payload: Vec::new(),
+            vlan: match headers.get_known(WellKnownKey::EthVlan) {
+                Some(_) => Some(schema.get_int(WellKnownKey::EthVlan.as_str(), headers)?),
+                None => None,
+            },
+            fragments: match headers.get_known(WellKnownKey::Ipv4Frags) {
+                Some(_) => schema.get_int(WellKnownKey::Ipv4Frags.as_str(), headers)?,
+                None => 0,
+            },
+            outer: None,
+        })
+    }
+}
+
+impl TryFrom<Headers> for PacketRecord {
+    type Error = SchemaError;
+
+    fn try_from(headers: Headers) -> Result<PacketRecord, SchemaError> {
+        PacketRecord::try_from(&headers)
+    }
+}
@@ -0,0 +1,152 @@
+#![allow(dead_code)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::builtins::{EpochOptions, MissingTimePolicy};
+use crate::error::{InputKind, StreamError};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueryParams {
+    pub epoch_dur: f64,
+    pub long_epoch_dur: f64,
+    pub align_to_epoch_boundary: bool,
+    pub epoch_offset: f64,
+    pub missing_time_policy: MissingTimePolicy,
+    pub new_cons_threshold: i32,
+    pub ssh_brute_force_threshold: i32,
+    pub super_spreader_threshold: i32,
+    pub port_scan_threshold: i32,
+    pub ddos_threshold: i32,
+    pub syn_flood_threshold: i32,
+    pub completed_flows_threshold: i32,
+    pub completed_flows_epoch_dur: f64,
+    pub slowloris_min_conns: i32,
+    pub slowloris_min_bytes: i32,
+    pub slowloris_max_bytes_per_conn: i32,
+    pub fin_scan_threshold: i32,
+    pub null_scan_threshold: i32,
+    pub xmas_scan_threshold: i32,
+    pub ping_sweep_threshold: i32,
+    pub arp_spoof_threshold: i32,
+    pub dns_suspect_names_threshold: i32,
+    pub dns_txt_queries_threshold: i32,
+    pub http_flood_threshold: i32,
+    pub ja3_blocklist: Vec<String>,
+    pub beacon_window_gaps: usize,
+    pub beacon_max_cv: f64,
+    pub beacon_max_gap_secs: f64,
+    pub beacon_min_mean_gap: f64,
+    pub amplification_epoch_dur: f64,
+    pub amplification_max_request_len: i32,
+    pub amplification_factor: f64,
+    pub internal_nets: Vec<String>,
+    pub exfiltration_epoch_dur: f64,
+    pub exfiltration_bytes: i32,
+    pub oui_db: String,
+    pub fragment_threshold: i32,
+}
+
+impl Default for QueryParams {
+    fn default() -> Self {
+        QueryParams {
+            epoch_dur: 1.0,
+            long_epoch_dur: 10.0,
+            align_to_epoch_boundary: false,
+            epoch_offset: 0.0,
+            missing_time_policy: MissingTimePolicy::Fail,
+            new_cons_threshold: 40,
+            ssh_brute_force_threshold: 40,
+            super_spreader_threshold: 40,
+            port_scan_threshold: 40,
+            ddos_threshold: 45,
+            syn_flood_threshold: 3,
+            completed_flows_threshold: 1,
+            completed_flows_epoch_dur: 30.0,
+            slowloris_min_conns: 5,
+            slowloris_min_bytes: 500,
+            slowloris_max_bytes_per_conn: 90,
+            fin_scan_threshold: 20,
+            null_scan_threshold: 10,
+            xmas_scan_threshold: 10,
+            ping_sweep_threshold: 20,
+            arp_spoof_threshold: 2,
+            dns_suspect_names_threshold: 10,
+            dns_txt_queries_threshold: 20,
+            http_flood_threshold: 100,
+            ja3_blocklist: Vec::new(),
+            beacon_window_gaps: 8,
+            beacon_max_cv: 0.1,
+            beacon_max_gap_secs: 300.0,
+            beacon_min_mean_gap: 1.0,
+            amplification_epoch_dur: 5.0,
+            amplification_max_request_len: 100,
+            amplification_factor: 10.0,
+            internal_nets: vec![
+                "10.0.0.0/8".to_string(),
+                "172.16.0.0/12".to_string(),
+                "192.168.0.0/16".to_string(),
+            ],
+            exfiltration_epoch_dur: 3600.0,
+            exfiltration_bytes: 1_000_000_000,
+            oui_db: String::new(),
+            fragment_threshold: 100,
+        }
+    }
+}
+
+fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<BTreeMap<String, toml::Value>>(&format!("v = {}", value))
+        .ok()
+        .and_then(|mut table: BTreeMap<String, toml::Value>| table.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+impl QueryParams {
+    pub fn epoch_options(&self) -> EpochOptions {
+        EpochOptions {
+            align: self.align_to_epoch_boundary,
+            offset: self.epoch_offset,
+            missing_time: self.missing_time_policy,
+        }
+    }
+
+    pub fn to_map(&self) -> BTreeMap<String, toml::Value> {
+        match toml::Value::try_from(self) {
+            Ok(toml::Value::Table(table)) => table.into_iter().collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    pub fn from_map(map: BTreeMap<String, toml::Value>) -> Result<Self, StreamError> {
+        toml::Value::Table(map.into_iter().collect())
+            .try_into()
+            .map_err(|e: toml::de::Error| StreamError::syntax(InputKind::Param, "parameters", e))
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), StreamError> {
+        let mut map: BTreeMap<String, toml::Value> = self.to_map();
+        if !map.contains_key(name) {
+            return Err(StreamError::unknown(
+                InputKind::Param,
+                "query parameter",
+                name,
+            ));
+        }
+        map.insert(name.to_string(), parse_value(value));
+        *self = QueryParams::from_map(map).map_err(|e: StreamError| e.within("parameter", name))?;
+        Ok(())
+    }
+
+    pub fn set_assignment(&mut self, assignment: &str) -> Result<(), StreamError> {
+        match assignment.split_once('=') {
+            Some((name, value)) => self.set(name.trim(), value.trim()),
+            None => Err(StreamError::unexpected(
+                InputKind::Param,
+                "name=value",
+                format!("'{}'", assignment),
+            )),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+#![allow(dead_code)]
+
+use crate::error::{ParseError, StreamError};
+use crate::packet::{DecodeOptions, PacketRecord};
+use crate::reassembly::{Datagram, Reassembler};
+use crate::utils::Headers;
+use std::fs;
+
+pub const PCAP_MAGIC_MICROS: u32 = 0xa1b2c3d4;
+pub const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+
+fn pcap_error(msg: &str) -> StreamError {
+    StreamError::Parse(ParseError::Packet {
+        protocol: "pcap",
+        msg: msg.to_string(),
+    })
+}
+
+pub struct PcapFrame<'a> {
+    pub time: f64,
+    pub data: &'a [u8],
+}
+
+pub fn read_frames(bytes: &[u8]) -> Result<Vec<PcapFrame<'_>>, StreamError> {
+    if bytes.len() < 24 {
+        return Err(pcap_error("truncated file header"));
+    }
+    let magic: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    let (big_endian, frac_scale): (bool, f64) = match magic {
+        _ if u32::from_le_bytes(magic) == PCAP_MAGIC_MICROS => (false, 1e-6),
+        _ if u32::from_le_bytes(magic) == PCAP_MAGIC_NANOS => (false, 1e-9),
+        _ if u32::from_be_bytes(magic) == PCAP_MAGIC_MICROS => (true, 1e-6),
+        _ if u32::from_be_bytes(magic) == PCAP_MAGIC_NANOS => (true, 1e-9),
+        _ => return Err(pcap_error("bad magic number")),
+    };
+    let read_u32 = |buf: &[u8]| -> u32 {
+        let word: [u8; 4] = [buf[0], buf[1], buf[2], buf[3]];
+        if big_endian {
+            u32::from_be_bytes(word)
+        } else {
+            u32::from_le_bytes(word)
+        }
+    };
+    if read_u32(&bytes[20..24]) != LINKTYPE_ETHERNET {
+        return Err(pcap_error("unsupported link type"));
+    }
+    let mut frames: Vec<PcapFrame> = Vec::new();
+    let mut rest: &[u8] = &bytes[24..];
+    while !rest.is_empty() {
+        if rest.len() < 16 {
+            return Err(pcap_error("truncated record header"));
+        }
+        let secs: u32 = read_u32(&rest[0..4]);
+        let frac: u32 = read_u32(&rest[4..8]);
+        let incl_len: usize = read_u32(&rest[8..12]) as usize;
+        if rest.len() < 16 + incl_len {
+            return Err(pcap_error("truncated record"));
+        }
+        frames.push(PcapFrame {
+            time: secs as f64 + frac as f64 * frac_scale,
+            data: &rest[16..16 + incl_len],
+        });
+        rest = &rest[16 + incl_len..];
+    }
+    Ok(frames)
+}
+
+fn decode_datagram(datagram: Datagram, options: &DecodeOptions) -> Option<Headers> {
+    let mut record: PacketRecord =
+        PacketRecord::decode(datagram.time, &datagram.frame, options).ok()?;
+    record.fragments = record.fragments.max(datagram.fragments);
+    Some(Headers::from(record))
+}
+
+pub fn decode_pcap(bytes: &[u8], options: &DecodeOptions) -> Result<Vec<Headers>, StreamError> {
+    let frames: Vec<PcapFrame> = read_frames(bytes)?;
+    let Some(timeout) = options.fragment_timeout else {
+        return Ok(frames
+            .into_iter()
+            .filter_map(|frame: PcapFrame| {
+                PacketRecord::decode(frame.time, frame.data, options).ok()
+            })
+            .map(Headers::from)
+            .collect());
+    };
+    let mut reassembler: Reassembler = Reassembler::new(timeout);
+    let mut datagrams: Vec<Datagram> = Vec::new();
+    for frame in frames {
+        datagrams.extend(reassembler.push(frame.time, frame.data));
+    }
+    datagrams.extend(reassembler.flush());
+    Ok(datagrams
+        .into_iter()
+        .filter_map(|datagram: Datagram| decode_datagram(datagram, options))
+        .collect())
+}
+
+pub fn load_pcap(path: &str, options: &DecodeOptions) -> Result<Vec<Headers>, StreamError> {
+    decode_pcap(&fs::read(path)?, options)
+}
@@ -0,0 +1,639 @@
+#![allow(dead_code)]
+
+use crate::biflow::{BiflowKeys, create_biflow_operator};
+use crate::budget::MemoryBudget;
+use crate::builtins::{
+    EpochOptions, FilterFunc, GroupingFunc, MapFunc, MissingTimePolicy, ReductionFunc,
+    SAMPLE_RATE_KEY, StatelessStep, create_count_epoch_operator, create_distinct_operator,
+    create_distinct_operator_with_backend, create_distinct_operator_with_budget,
+    create_distinct_ttl_operator, create_epoch_operator_with_options, create_every_nth_operator,
+    create_filter_operator, create_fused_operator, create_groupby_operator,
+    create_groupby_operator_with_backend, create_groupby_operator_with_budget,
+    create_incremental_groupby_operator, create_map_operator, create_sample_operator,
+    create_sort_operator, create_split_operator, create_throttle_operator,
+};
+use crate::dot::to_dot;
+use crate::first_seen::{SeenTable, create_first_seen_operator};
+use crate::schema::{FieldType, Schema, SchemaError};
+use crate::state::StateBackend;
+use crate::stats::PipelineStats;
+use crate::tcp_stream::{TcpStreamOptions, create_tcp_stream_operator, tcp_stream_schema};
+use crate::utils::{Headers, OpResult, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub type StageBuilder = Box<dyn FnOnce(OperatorRef) -> OperatorRef>;
+pub type SchemaCheck = Box<dyn Fn(&Schema, &str) -> Result<Schema, SchemaError>>;
+
+pub enum StageBody {
+    Builder(StageBuilder),
+    Stateless(Vec<StatelessStep>),
+}
+
+impl StageBody {
+    pub fn build(self, next_op: OperatorRef) -> OperatorRef {
+        match self {
+            StageBody::Builder(build) => build(next_op),
+            StageBody::Stateless(mut steps) if steps.len() == 1 => match steps.remove(0) {
+                StatelessStep::Filter(f) => create_filter_operator(f, next_op),
+                StatelessStep::Map(f) => create_map_operator(f, next_op),
+            },
+            StageBody::Stateless(steps) => create_fused_operator(steps, next_op),
+        }
+    }
+}
+
+pub struct PlanStage {
+    pub label: String,
+    pub body: StageBody,
+    pub check: Option<SchemaCheck>,
+    pub identity: Option<String>,
+}
+
+impl PlanStage {
+    pub fn new(label: String, build: StageBuilder) -> Self {
+        PlanStage {
+            label,
+            body: StageBody::Builder(build),
+            check: None,
+            identity: None,
+        }
+    }
+
+    pub fn stateless(label: String, step: StatelessStep) -> Self {
+        PlanStage {
+            label,
+            body: StageBody::Stateless(vec![step]),
+            check: None,
+            identity: None,
+        }
+    }
+
+    pub fn build(self, next_op: OperatorRef) -> OperatorRef {
+        self.body.build(next_op)
+    }
+
+    pub fn is_stateless(&self) -> bool {
+        matches!(self.body, StageBody::Stateless(_))
+    }
+
+    pub fn try_fuse(&mut self, other: PlanStage) -> Option<PlanStage> {
+        if !self.is_stateless() {
+            return Some(other);
+        }
+        match other.body {
+            StageBody::Stateless(other_steps) => {
+                if let StageBody::Stateless(steps) = &mut self.body {
+                    steps.extend(other_steps);
+                }
+                self.label = format!("{} . {}", self.label, other.label);
+                self.check = match (self.check.take(), other.check) {
+                    (Some(first), Some(second)) => {
+                        Some(Box::new(move |input: &Schema, stage: &str| {
+                            second(&first(input, stage)?, stage)
+                        }))
+                    }
+                    (first, second) => first.or(second),
+                };
+                self.identity = match (self.identity.take(), other.identity) {
+                    (Some(first), Some(second)) => Some(format!("{} | {}", first, second)),
+                    _ => None,
+                };
+                None
+            }
+            body => Some(PlanStage {
+                label: other.label,
+                body,
+                check: other.check,
+                identity: other.identity,
+            }),
+        }
+    }
+
+    pub fn with_check(mut self, check: SchemaCheck) -> Self {
+        self.check = Some(check);
+        self
+    }
+
+    pub fn with_identity(mut self, identity: String) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    fn shareable(self) -> Self {
+        let identity: String = self.label.clone();
+        self.with_identity(identity)
+    }
+
+    pub fn check_schema(&self, input: &Schema) -> Result<Schema, SchemaError> {
+        match &self.check {
+            Some(check) => check(input, &self.label),
+            None => Ok(input.clone()),
+        }
+    }
+
+    pub fn epoch(epoch_width: f64, key_out: String) -> Self {
+        PlanStage::epoch_with_options(epoch_width, key_out, EpochOptions::default())
+    }
+
+    pub fn epoch_with_options(epoch_width: f64, key_out: String, options: EpochOptions) -> Self {
+        let key_out_cp: String = key_out.clone();
+        let identity: String = format!("epoch({}, {}, {:?})", epoch_width, key_out, options);
+        let label: String = if options.align {
+            format!(
+                "epoch({}, {}, aligned {})",
+                epoch_width, key_out, options.offset
+            )
+        } else {
+            format!("epoch({}, {})", epoch_width, key_out)
+        };
+        PlanStage::new(
+            label,
+            Box::new(move |next_op: OperatorRef| {
+                create_epoch_operator_with_options(epoch_width, key_out, options, next_op)
+            }),
+        )
+        .with_identity(identity)
+        .with_check(Box::new(move |input: &Schema, stage: &str| {
+            let mut output: Schema = match options.missing_time {
+                MissingTimePolicy::Fail => {
+                    input.require("time", FieldType::Float, stage)?;
+                    input.clone()
+                }
+                _ => input.clone().with("time", FieldType::Float),
+            }
+            .with(&key_out_cp, FieldType::Int);
+            output.reset_keys.insert(key_out_cp.clone());
+            Ok(output)
+        }))
+    }
+
+    pub fn count_epoch(n: usize, key_out: String) -> Self {
+        let key_out_cp: String = key_out.clone();
+        PlanStage::new(
+            format!("count_epoch({}, {})", n, key_out),
+            Box::new(move |next_op: OperatorRef| create_count_epoch_operator(n, key_out, next_op)),
+        )
+        .shareable()
+        .with_check(Box::new(move |input: &Schema, _stage: &str| {
+            let mut output: Schema = input.clone().with(&key_out_cp, FieldType::Int);
+            output.reset_keys.insert(key_out_cp.clone());
+            Ok(output)
+        }))
+    }
+
+    pub fn filter(label: String, f: FilterFunc) -> Self {
+        PlanStage::stateless(format!("filter({})", label), StatelessStep::Filter(f))
+    }
+
+    pub fn map(label: String, f: MapFunc) -> Self {
+        PlanStage::stateless(format!("map({})", label), StatelessStep::Map(f))
+    }
+
+    pub fn groupby(
+        label: String,
+        groupby: GroupingFunc,
+        reduce: ReductionFunc,
+        out_key: String,
+    ) -> Self {
+        PlanStage::new(
+            format!("groupby({}, {})", label, out_key),
+            Box::new(move |next_op: OperatorRef| {
+                create_groupby_operator(groupby, reduce, out_key, next_op)
+            }),
+        )
+    }
+
+    pub fn groupby_with_budget(
+        label: String,
+        groupby: GroupingFunc,
+        reduce: ReductionFunc,
+        out_key: String,
+        budget: MemoryBudget,
+    ) -> Self {
+        PlanStage::new(
+            format!(
+                "groupby({}, {}, budget {}b {:?})",
+                label, out_key, budget.limit_bytes, budget.action
+            ),
+            Box::new(move |next_op: OperatorRef| {
+                create_groupby_operator_with_budget(groupby, reduce, out_key, budget, next_op)
+            }),
+        )
+    }
+
+    pub fn groupby_incremental(
+        label: String,
+        groupby: GroupingFunc,
+        reduce: ReductionFunc,
+        out_key: String,
+        partitions: usize,
+    ) -> Self {
+        PlanStage::new(
+            format!(
+                "groupby({}, {}, incremental {})",
+                label, out_key, partitions
+            ),
+            Box::new(move |next_op: OperatorRef| {
+                create_incremental_groupby_operator(groupby, reduce, out_key, partitions, next_op)
+            }),
+        )
+    }
+
+    pub fn groupby_with_backend(
+        label: String,
+        groupby: GroupingFunc,
+        reduce: ReductionFunc,
+        out_key: String,
+        backend: Box<dyn StateBackend<OpResult>>,
+    ) -> Self {
+        PlanStage::new(
+            format!("groupby({}, {}, spilled)", label, out_key),
+            Box::new(move |next_op: OperatorRef| {
+                create_groupby_operator_with_backend(groupby, reduce, out_key, backend, next_op)
+            }),
+        )
+    }
+
+    pub fn distinct(label: String, groupby: GroupingFunc) -> Self {
+        PlanStage::new(
+            format!("distinct({})", label),
+            Box::new(move |next_op: OperatorRef| create_distinct_operator(groupby, next_op)),
+        )
+    }
+
+    pub fn distinct_with_budget(
+        label: String,
+        groupby: GroupingFunc,
+        budget: MemoryBudget,
+    ) -> Self {
+        PlanStage::new(
+            format!(
+                "distinct({}, budget {}b {:?})",
+                label, budget.limit_bytes, budget.action
+            ),
+            Box::new(move |next_op: OperatorRef| {
+                create_distinct_operator_with_budget(groupby, budget, next_op)
+            }),
+        )
+    }
+
+    pub fn distinct_with_backend(
+        label: String,
+        groupby: GroupingFunc,
+        backend: Box<dyn StateBackend<bool>>,
+    ) -> Self {
+        PlanStage::new(
+            format!("distinct({}, spilled)", label),
+            Box::new(move |next_op: OperatorRef| {
+                create_distinct_operator_with_backend(groupby, backend, next_op)
+            }),
+        )
+    }
+
+    pub fn throttle(
+        label: String,
+        groupby: GroupingFunc,
+        max_per_interval: usize,
+        interval_secs: f64,
+    ) -> Self {
+        PlanStage::new(
+            format!(
+                "throttle({}, {}, {})",
+                label, max_per_interval, interval_secs
+            ),
+            Box::new(move |next_op: OperatorRef| {
+                create_throttle_operator(groupby, max_per_interval, interval_secs, next_op)
+            }),
+        )
+    }
+
+    pub fn sort(key: String, ascending: bool, limit: Option<usize>) -> Self {
+        let key_cp: String = key.clone();
+        PlanStage::new(
+            format!(
+                "sort({}, {}, {})",
+                key,
+                if ascending { "asc" } else { "desc" },
+                limit.map_or("all".to_string(), |limit: usize| limit.to_string())
+            ),
+            Box::new(move |next_op: OperatorRef| {
+                create_sort_operator(key, ascending, limit, next_op)
+            }),
+        )
+        .shareable()
+        .with_check(Box::new(move |input: &Schema, stage: &str| {
+            input.require(&key_cp, FieldType::Any, stage)?;
+            Ok(input.clone())
+        }))
+    }
+
+    pub fn sample(prob: f64, seed: u64) -> Self {
+        PlanStage::new(
+            format!("sample({}, {})", prob, seed),
+            Box::new(move |next_op: OperatorRef| create_sample_operator(prob, seed, next_op)),
+        )
+        .shareable()
+        .with_check(Box::new(|input: &Schema, _stage: &str| {
+            Ok(input.clone().with(SAMPLE_RATE_KEY, FieldType::Float))
+        }))
+    }
+
+    pub fn every_nth(n: usize) -> Self {
+        PlanStage::new(
+            format!("every_nth({})", n),
+            Box::new(move |next_op: OperatorRef| create_every_nth_operator(n, next_op)),
+        )
+        .shareable()
+        .with_check(Box::new(|input: &Schema, _stage: &str| {
+            Ok(input.clone().with(SAMPLE_RATE_KEY, FieldType::Float))
+        }))
+    }
+
+    pub fn biflow(key_fields: Vec<String>, timeout: f64) -> Self {
+        let keys: BiflowKeys = BiflowKeys::of_fields(&key_fields);
+        PlanStage::new(
+            format!("biflow({}, {})", key_fields.join(", "), timeout),
+            Box::new(move |next_op: OperatorRef| {
+                create_biflow_operator(key_fields, timeout, next_op)
+            }),
+        )
+        .shareable()
+        .with_check(Box::new(move |input: &Schema, stage: &str| {
+            keys.check(input, stage)
+        }))
+    }
+
+    pub fn tcp_streams(options: TcpStreamOptions) -> Self {
+        PlanStage::new(
+            format!("tcp_streams({})", options.idle_timeout),
+            Box::new(move |next_op: OperatorRef| create_tcp_stream_operator(options, next_op)),
+        )
+        .with_identity(format!("tcp_streams({:?})", options))
+        .with_check(Box::new(|input: &Schema, stage: &str| {
+            for key in ["ipv4.proto", "l4.flags", "l4.sport", "l4.dport"] {
+                input.require(key, FieldType::Int, stage)?;
+            }
+            for key in ["ipv4.src", "ipv4.dst"] {
+                input.require(key, FieldType::IPv4, stage)?;
+            }
+            Ok(tcp_stream_schema())
+        }))
+    }
+
+    pub fn first_seen(label: String, groupby: GroupingFunc, table: SeenTable) -> Self {
+        PlanStage::new(
+            format!("first_seen({})", label),
+            Box::new(move |next_op: OperatorRef| {
+                create_first_seen_operator(groupby, table, next_op)
+            }),
+        )
+    }
+
+    pub fn distinct_ttl(label: String, groupby: GroupingFunc, ttl_secs: f64) -> Self {
+        PlanStage::new(
+            format!("distinct_ttl({}, {})", label, ttl_secs),
+            Box::new(move |next_op: OperatorRef| {
+                create_distinct_ttl_operator(groupby, ttl_secs, next_op)
+            }),
+        )
+    }
+}
+
+struct PlanNode {
+    stage: PlanStage,
+    children: Vec<PlanNode>,
+    sinks: Vec<OperatorRef>,
+}
+
+impl PlanNode {
+    fn compile(self, mut stats: Option<&mut PipelineStats>) -> OperatorRef {
+        let mut outputs: Vec<OperatorRef> = self.sinks;
+        for child in self.children {
+            outputs.push(child.compile(stats.as_deref_mut()));
+        }
+        match stats {
+            Some(stats) => {
+                let body: StageBody = self.stage.body;
+                stats.instrument(
+                    self.stage.label,
+                    move |next_op: OperatorRef| body.build(next_op),
+                    fan_out(outputs),
+                )
+            }
+            None => self.stage.build(fan_out(outputs)),
+        }
+    }
+
+    fn optimize(mut self) -> PlanNode {
+        while self.stage.is_stateless()
+            && self.sinks.is_empty()
+            && self.children.len() == 1
+            && self.children[0].stage.is_stateless()
+        {
+            let child: PlanNode = self.children.remove(0);
+            self.stage.try_fuse(child.stage);
+            self.children = child.children;
+            self.sinks = child.sinks;
+        }
+        self.children = self.children.into_iter().map(PlanNode::optimize).collect();
+        self
+    }
+
+    fn count(&self) -> usize {
+        1 + self.children.iter().map(PlanNode::count).sum::<usize>()
+    }
+}
+
+pub fn check_stages(stages: &[PlanStage], input: &Schema) -> Result<Schema, SchemaError> {
+    stages
+        .iter()
+        .try_fold(input.clone(), |schema: Schema, stage: &PlanStage| {
+            stage.check_schema(&schema)
+        })
+}
+
+pub fn fuse_stages(stages: Vec<PlanStage>) -> Vec<PlanStage> {
+    let mut fused: Vec<PlanStage> = Vec::new();
+    for stage in stages {
+        let unfused: Option<PlanStage> = match fused.last_mut() {
+            Some(prev) => prev.try_fuse(stage),
+            None => Some(stage),
+        };
+        fused.extend(unfused);
+    }
+    fused
+}
+
+pub fn create_noop_operator() -> OperatorRef {
+    Rc::new(RefCell::new(Operator::new(
+        Box::new(|_headers: &mut Headers| {}),
+        Box::new(|_headers: &mut Headers| {}),
+    )))
+}
+
+pub fn fan_out(mut ops: Vec<OperatorRef>) -> OperatorRef {
+    match ops.len() {
+        0 => create_noop_operator(),
+        1 => ops.remove(0),
+        _ => {
+            let last: OperatorRef = ops.pop().unwrap();
+            ops.into_iter()
+                .rev()
+                .fold(last, |acc: OperatorRef, op: OperatorRef| {
+                    create_split_operator(op, acc)
+                })
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PlanBuilder {
+    roots: Vec<PlanNode>,
+    sinks: Vec<OperatorRef>,
+}
+
+impl PlanBuilder {
+    pub fn new() -> Self {
+        PlanBuilder::default()
+    }
+
+    pub fn add_query(mut self, stages: Vec<PlanStage>, sink: OperatorRef) -> Self {
+        let mut nodes: &mut Vec<PlanNode> = &mut self.roots;
+        let mut sinks: &mut Vec<OperatorRef> = &mut self.sinks;
+        for stage in stages {
+            let idx: usize = match nodes.iter().position(|node: &PlanNode| {
+                stage.identity.is_some() && node.stage.identity == stage.identity
+            }) {
+                Some(idx) => idx,
+                None => {
+                    nodes.push(PlanNode {
+                        stage,
+                        children: Vec::new(),
+                        sinks: Vec::new(),
+                    });
+                    nodes.len() - 1
+                }
+            };
+            let node: &mut PlanNode = &mut nodes[idx];
+            nodes = &mut node.children;
+            sinks = &mut node.sinks;
+        }
+        sinks.push(sink);
+        self
+    }
+
+    pub fn stage_count(&self) -> usize {
+        self.roots.iter().map(PlanNode::count).sum()
+    }
+
+    pub fn optimize(mut self) -> Self {
+        self.roots = self.roots.into_iter().map(PlanNode::optimize).collect();
+        self
+    }
+
+    pub fn compile(self) -> OperatorRef {
+        let mut outputs: Vec<OperatorRef> = self.sinks;
+        outputs.extend(self.roots.into_iter().map(|root| root.compile(None)));
+        fan_out(outputs)
+    }
+
+    pub fn into_dot(self, name: &str) -> String {
+        to_dot(name, &[self.compile()])
+    }
+
+    pub fn compile_with_stats(self, stats: &mut PipelineStats) -> OperatorRef {
+        let mut outputs: Vec<OperatorRef> = self.sinks;
+        for root in self.roots {
+            outputs.push(root.compile(Some(stats)));
+        }
+        fan_out(outputs)
+    }
+}
+
+#[derive(Default)]
+pub struct PipelineBuilder {
+    stages: Vec<PlanStage>,
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        PipelineBuilder::default()
+    }
+
+    pub fn stage(mut self, stage: PlanStage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn op(self, label: &str, build: impl FnOnce(OperatorRef) -> OperatorRef + 'static) -> Self {
+        self.stage(PlanStage::new(label.to_string(), Box::new(build)))
+    }
+
+    pub fn epoch(self, epoch_width: f64, key_out: &str) -> Self {
+        self.stage(PlanStage::epoch(epoch_width, key_out.to_string()))
+    }
+
+    pub fn epoch_with_options(
+        self,
+        epoch_width: f64,
+        key_out: &str,
+        options: EpochOptions,
+    ) -> Self {
+        self.stage(PlanStage::epoch_with_options(
+            epoch_width,
+            key_out.to_string(),
+            options,
+        ))
+    }
+
+    pub fn count_epoch(self, n: usize, key_out: &str) -> Self {
+        self.stage(PlanStage::count_epoch(n, key_out.to_string()))
+    }
+
+    pub fn filter(self, f: FilterFunc) -> Self {
+        let label: String = self.stages.len().to_string();
+        self.stage(PlanStage::filter(label, f))
+    }
+
+    pub fn map(self, f: MapFunc) -> Self {
+        let label: String = self.stages.len().to_string();
+        self.stage(PlanStage::map(label, f))
+    }
+
+    pub fn groupby(self, groupby: GroupingFunc, reduce: ReductionFunc, out_key: &str) -> Self {
+        let label: String = self.stages.len().to_string();
+        self.stage(PlanStage::groupby(
+            label,
+            groupby,
+            reduce,
+            out_key.to_string(),
+        ))
+    }
+
+    pub fn distinct(self, groupby: GroupingFunc) -> Self {
+        let label: String = self.stages.len().to_string();
+        self.stage(PlanStage::distinct(label, groupby))
+    }
+
+    pub fn sort(self, key: &str, ascending: bool, limit: Option<usize>) -> Self {
+        self.stage(PlanStage::sort(key.to_string(), ascending, limit))
+    }
+
+    pub fn check(&self, input: &Schema) -> Result<Schema, SchemaError> {
+        check_stages(&self.stages, input)
+    }
+
+    pub fn into_stages(self) -> Vec<PlanStage> {
+        self.stages
+    }
+
+    pub fn sink(self, next_op: OperatorRef) -> OperatorRef {
+        self.stages
+            .into_iter()
+            .rev()
+            .fold(next_op, |acc: OperatorRef, stage: PlanStage| {
+                stage.build(acc)
+            })
+    }
+}
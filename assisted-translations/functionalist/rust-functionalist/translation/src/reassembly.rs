@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+
+use crate::packet::{
+    ETHERTYPE_IPV4, ETHERTYPE_QINQ, ETHERTYPE_VLAN, IPV4_FRAGMENT_OFFSET, IPV4_MORE_FRAGMENTS,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+
+pub const DEFAULT_FRAGMENT_TIMEOUT: f64 = 30.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FragmentKey {
+    pub id: u16,
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+    pub proto: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Datagram {
+    pub time: f64,
+    pub frame: Vec<u8>,
+    pub fragments: i32,
+}
+
+struct PendingDatagram {
+    time: f64,
+    link: Vec<u8>,
+    headers: BTreeMap<usize, Vec<u8>>,
+    parts: BTreeMap<usize, Vec<u8>>,
+    total: Option<usize>,
+    fragments: i32,
+}
+
+fn contiguous(parts: &BTreeMap<usize, Vec<u8>>, from: usize) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    for (offset, part) in parts.range(from..) {
+        let end: usize = from + payload.len();
+        if *offset > end {
+            break;
+        }
+        payload.extend(part.iter().skip(end - offset));
+    }
+    payload
+}
+
+impl PendingDatagram {
+    fn is_complete(&self) -> bool {
+        self.total
+            .is_some_and(|total: usize| contiguous(&self.parts, 0).len() >= total)
+    }
+
+    fn build(self) -> Datagram {
+        let complete: bool = self.is_complete();
+        let (first, mut header): (usize, Vec<u8>) = self.headers.into_iter().next().unwrap();
+        let mut payload: Vec<u8> = contiguous(&self.parts, first);
+        let mut frag: u16 = u16::from_be_bytes([header[6], header[7]]);
+        match self.total.filter(|_| complete) {
+            Some(total) => {
+                payload.truncate(total);
+                frag &= !(IPV4_MORE_FRAGMENTS | IPV4_FRAGMENT_OFFSET);
+            }
+            None => frag |= IPV4_MORE_FRAGMENTS,
+        }
+        let len: u16 = (header.len() + payload.len()) as u16;
+        header[2..4].copy_from_slice(&len.to_be_bytes());
+        header[6..8].copy_from_slice(&frag.to_be_bytes());
+        let mut frame: Vec<u8> = self.link;
+        frame.extend(header);
+        frame.extend(payload);
+        Datagram {
+            time: self.time,
+            frame,
+            fragments: self.fragments,
+        }
+    }
+}
+
+fn ipv4_offset(frame: &[u8]) -> Option<usize> {
+    let mut offset: usize = 12;
+    loop {
+        let ethertype: i32 =
+            u16::from_be_bytes([*frame.get(offset)?, *frame.get(offset + 1)?]) as i32;
+        offset += 2;
+        match ethertype {
+            ETHERTYPE_VLAN | ETHERTYPE_QINQ => offset += 2,
+            ETHERTYPE_IPV4 => return Some(offset),
+            _ => return None,
+        }
+    }
+}
+
+pub struct Reassembler {
+    pub timeout: f64,
+    pending: HashMap<FragmentKey, PendingDatagram>,
+}
+
+impl Reassembler {
+    pub fn new(timeout: f64) -> Self {
+        Reassembler {
+            timeout,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn push(&mut self, time: f64, frame: &[u8]) -> Vec<Datagram> {
+        let mut out: Vec<Datagram> = self.expire(time);
+        let unfragmented: Datagram = Datagram {
+            time,
+            frame: frame.to_vec(),
+            fragments: 0,
+        };
+        let Some(ip) = ipv4_offset(frame) else {
+            out.push(unfragmented);
+            return out;
+        };
+        let packet: &[u8] = &frame[ip..];
+        if packet.len() < 20 {
+            out.push(unfragmented);
+            return out;
+        }
+        let hlen: usize = ((packet[0] & 0x0f) as usize) * 4;
+        let len: usize = (u16::from_be_bytes([packet[2], packet[3]]) as usize).min(packet.len());
+        let frag: u16 = u16::from_be_bytes([packet[6], packet[7]]);
+        if frag & (IPV4_MORE_FRAGMENTS | IPV4_FRAGMENT_OFFSET) == 0 || hlen < 20 || len < hlen {
+            out.push(unfragmented);
+            return out;
+        }
+        let key: FragmentKey = FragmentKey {
+            id: u16::from_be_bytes([packet[4], packet[5]]),
+            src: Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]),
+            dst: Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]),
+            proto: packet[9],
+        };
+        let offset: usize = ((frag & IPV4_FRAGMENT_OFFSET) as usize) * 8;
+        let pending: &mut PendingDatagram =
+            self.pending.entry(key).or_insert_with(|| PendingDatagram {
+                time,
+                link: frame[..ip].to_vec(),
+                headers: BTreeMap::new(),
+                parts: BTreeMap::new(),
+                total: None,
+                fragments: 0,
+            });
+        pending.fragments += 1;
+        pending.headers.insert(offset, packet[..hlen].to_vec());
+        pending.parts.insert(offset, packet[hlen..len].to_vec());
+        if frag & IPV4_MORE_FRAGMENTS == 0 {
+            pending.total = Some(offset + len - hlen);
+        }
+        if pending.is_complete() {
+            out.extend(self.pending.remove(&key).map(PendingDatagram::build));
+        }
+        out
+    }
+
+    pub fn expire(&mut self, now: f64) -> Vec<Datagram> {
+        let expired: Vec<FragmentKey> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now - pending.time >= self.timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        self.drain(expired)
+    }
+
+    pub fn flush(&mut self) -> Vec<Datagram> {
+        let keys: Vec<FragmentKey> = self.pending.keys().copied().collect();
+        self.drain(keys)
+    }
+
+    fn drain(&mut self, keys: Vec<FragmentKey>) -> Vec<Datagram> {
+        let mut datagrams: Vec<Datagram> = keys
+            .iter()
+            .filter_map(|key: &FragmentKey| self.pending.remove(key))
+            .map(PendingDatagram::build)
+            .collect();
+        datagrams.sort_by(|a: &Datagram, b: &Datagram| a.time.total_cmp(&b.time));
+        datagrams
+    }
+}
@@ -0,0 +1,139 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::schema::{Schema, SchemaError};
+use crate::utils::{Headers, OpResult};
+use std::net::Ipv4Addr;
+
+pub trait FromTuple: Sized {
+    fn from_tuple(headers: &Headers) -> Result<Self, SchemaError>;
+}
+
+pub trait IntoTuple {
+    fn into_tuple(self) -> Headers;
+}
+
+pub trait TupleField: Sized {
+    fn from_field(key: &str, headers: &Headers) -> Result<Self, SchemaError>;
+    fn into_field(self) -> OpResult;
+}
+
+impl TupleField for i32 {
+    fn from_field(key: &str, headers: &Headers) -> Result<Self, SchemaError> {
+        Schema::new().get_int(key, headers)
+    }
+
+    fn into_field(self) -> OpResult {
+        OpResult::Int(self)
+    }
+}
+
+impl TupleField for f64 {
+    fn from_field(key: &str, headers: &Headers) -> Result<Self, SchemaError> {
+        Schema::new()
+            .get_float(key, headers)
+            .map(|f: OrderedFloat<f64>| f.0)
+    }
+
+    fn into_field(self) -> OpResult {
+        OpResult::from(self)
+    }
+}
+
+impl TupleField for Ipv4Addr {
+    fn from_field(key: &str, headers: &Headers) -> Result<Self, SchemaError> {
+        Schema::new().get_ipv4(key, headers)
+    }
+
+    fn into_field(self) -> OpResult {
+        OpResult::IPv4(self)
+    }
+}
+
+impl TupleField for [u8; 6] {
+    fn from_field(key: &str, headers: &Headers) -> Result<Self, SchemaError> {
+        Schema::new().get_mac(key, headers)
+    }
+
+    fn into_field(self) -> OpResult {
+        OpResult::MAC(self)
+    }
+}
+
+impl TupleField for String {
+    fn from_field(key: &str, headers: &Headers) -> Result<Self, SchemaError> {
+        Schema::new()
+            .get_str(key, headers)
+            .map(|s: &str| s.to_string())
+    }
+
+    fn into_field(self) -> OpResult {
+        OpResult::Str(self)
+    }
+}
+
+impl<T: TupleField> TupleField for Option<T> {
+    fn from_field(key: &str, headers: &Headers) -> Result<Self, SchemaError> {
+        match headers.get(key) {
+            Some(_) => T::from_field(key, headers).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn into_field(self) -> OpResult {
+        self.map_or(OpResult::Empty, T::into_field)
+    }
+}
+
+#[macro_export]
+macro_rules! tuple_record {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($key:literal => $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $crate::record::FromTuple for $name {
+            fn from_tuple(
+                headers: &$crate::utils::Headers,
+            ) -> Result<Self, $crate::schema::SchemaError> {
+                Ok($name {
+                    $($field: <$ty as $crate::record::TupleField>::from_field($key, headers)?),*
+                })
+            }
+        }
+
+        impl $crate::record::IntoTuple for $name {
+            fn into_tuple(self) -> $crate::utils::Headers {
+                let mut headers: $crate::utils::Headers = $crate::utils::Headers::new();
+                $(
+                    match $crate::record::TupleField::into_field(self.$field) {
+                        $crate::utils::OpResult::Empty => {}
+                        val => {
+                            headers.insert($key.to_string(), val);
+                        }
+                    }
+                )*
+                headers
+            }
+        }
+    };
+}
+
+impl FromTuple for Headers {
+    fn from_tuple(headers: &Headers) -> Result<Self, SchemaError> {
+        Ok(headers.clone())
+    }
+}
+
+impl IntoTuple for Headers {
+    fn into_tuple(self) -> Headers {
+        self
+    }
+}
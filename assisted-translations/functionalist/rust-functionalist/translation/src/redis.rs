@@ -0,0 +1,260 @@
+#![allow(dead_code)]
+
+use crate::error::StreamError;
+use crate::trace_event;
+use crate::utils::{
+    Headers, OpResult, Operator, OperatorRef, render_template, string_of_op_result,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::net::TcpStream;
+use std::rc::Rc;
+
+pub const REDIS_DEFAULT_ADDR: &str = "127.0.0.1:6379";
+pub const REDIS_PIPELINE_DEPTH: usize = 256;
+pub const REDIS_MAX_IDLE: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RespValue {
+    Simple(String),
+    Error(String),
+    Int(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<RespValue>>),
+}
+
+fn resp_error(msg: String) -> StreamError {
+    StreamError::Io(Error::new(ErrorKind::InvalidData, msg))
+}
+
+pub fn encode_command(args: &[String]) -> Vec<u8> {
+    let mut out: Vec<u8> = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        out.extend_from_slice(arg.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+pub fn read_reply(reader: &mut impl BufRead) -> Result<RespValue, StreamError> {
+    let mut line: String = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(resp_error("connection closed".to_string()));
+    }
+    let line: &str = line.trim_end_matches(['\r', '\n']);
+    let (kind, rest) = line.split_at(line.len().min(1));
+    let len = || {
+        rest.parse::<i64>()
+            .map_err(|_| resp_error(format!("invalid length '{}'", rest)))
+    };
+    Ok(match kind {
+        "+" => RespValue::Simple(rest.to_string()),
+        "-" => RespValue::Error(rest.to_string()),
+        ":" => RespValue::Int(len()?),
+        "$" => match len()? {
+            n if n < 0 => RespValue::Bulk(None),
+            n => {
+                let mut data: Vec<u8> = vec![0; n as usize + 2];
+                reader.read_exact(&mut data)?;
+                data.truncate(n as usize);
+                RespValue::Bulk(Some(data))
+            }
+        },
+        "*" => {
+            match len()? {
+                n if n < 0 => RespValue::Array(None),
+                n => RespValue::Array(Some((0..n).map(|_| read_reply(reader)).collect::<Result<
+                    Vec<RespValue>,
+                    StreamError,
+                >>(
+                )?)),
+            }
+        }
+        _ => return Err(resp_error(format!("unexpected reply '{}'", line))),
+    })
+}
+
+pub struct RedisConn {
+    reader: BufReader<TcpStream>,
+}
+
+impl RedisConn {
+    pub fn connect(addr: &str) -> Result<RedisConn, StreamError> {
+        Ok(RedisConn {
+            reader: BufReader::new(TcpStream::connect(addr)?),
+        })
+    }
+
+    pub fn pipeline(&mut self, commands: &[Vec<String>]) -> Result<Vec<RespValue>, StreamError> {
+        let payload: Vec<u8> = commands
+            .iter()
+            .flat_map(|args: &Vec<String>| encode_command(args))
+            .collect();
+        self.reader.get_mut().write_all(&payload)?;
+        commands
+            .iter()
+            .map(|_| read_reply(&mut self.reader))
+            .collect()
+    }
+
+    pub fn command(&mut self, args: Vec<String>) -> Result<RespValue, StreamError> {
+        Ok(self.pipeline(&[args])?.remove(0))
+    }
+}
+
+#[derive(Clone)]
+pub struct RedisPool {
+    pub addr: String,
+    idle: Rc<RefCell<Vec<RedisConn>>>,
+}
+
+impl RedisPool {
+    pub fn new(addr: &str) -> Self {
+        RedisPool {
+            addr: addr.to_string(),
+            idle: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn get(&self) -> Result<RedisConn, StreamError> {
+        match self.idle.borrow_mut().pop() {
+            Some(conn) => Ok(conn),
+            None => RedisConn::connect(&self.addr),
+        }
+    }
+
+    pub fn put(&self, conn: RedisConn) {
+        let mut idle = self.idle.borrow_mut();
+        if idle.len() < REDIS_MAX_IDLE {
+            idle.push(conn);
+        }
+    }
+
+    pub fn pipeline(&self, commands: &[Vec<String>]) -> Result<Vec<RespValue>, StreamError> {
+        let mut conn: RedisConn = self.get()?;
+        let replies: Vec<RespValue> = conn.pipeline(commands)?;
+        self.put(conn);
+        Ok(replies)
+    }
+}
+
+pub fn redis_commands_of_headers(
+    key: &str,
+    headers: &Headers,
+    ttl: Option<u64>,
+) -> Vec<Vec<String>> {
+    let mut hset: Vec<String> = vec!["HSET".to_string(), key.to_string()];
+    for (field, val) in headers.iter() {
+        hset.push(field.clone());
+        hset.push(string_of_op_result(val));
+    }
+    let mut commands: Vec<Vec<String>> = vec![vec!["DEL".to_string(), key.to_string()], hset];
+    if let Some(ttl) = ttl {
+        commands.push(vec!["EXPIRE".to_string(), key.to_string(), ttl.to_string()]);
+    }
+    commands
+}
+
+fn check_replies(replies: Vec<RespValue>) -> Result<(), StreamError> {
+    match replies
+        .into_iter()
+        .find_map(|reply: RespValue| match reply {
+            RespValue::Error(msg) => Some(msg),
+            _ => None,
+        }) {
+        Some(msg) => Err(resp_error(format!("redis: {}", msg))),
+        None => Ok(()),
+    }
+}
+
+pub fn dump_redis(pool: RedisPool, key_template: String, ttl: Option<u64>) -> OperatorRef {
+    let label: String = format!("redis({})", key_template);
+    let rows: Rc<RefCell<Vec<Headers>>> = Rc::new(RefCell::new(Vec::new()));
+    let rows_ref_clone = Rc::clone(&rows);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| rows.borrow_mut().push(headers.clone()));
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |_headers: &mut Headers| {
+        let commands: Vec<Vec<String>> = rows_ref_clone
+            .borrow_mut()
+            .drain(..)
+            .flat_map(|row: Headers| {
+                redis_commands_of_headers(&render_template(&key_template, &row), &row, ttl)
+            })
+            .collect();
+        for chunk in commands.chunks(REDIS_PIPELINE_DEPTH) {
+            if let Err(_e) = pool.pipeline(chunk).and_then(check_replies) {
+                trace_event!(error = %_e, "redis: dropped the rest of the epoch's writes");
+                break;
+            }
+        }
+    });
+
+    Rc::new(RefCell::new(Operator::new(next, reset).with_label(label)))
+}
+
+pub fn op_result_of_redis(reply: RespValue) -> Option<OpResult> {
+    match reply {
+        RespValue::Bulk(Some(data)) => {
+            let s: String = String::from_utf8_lossy(&data).into_owned();
+            Some(match s.parse::<i32>() {
+                Ok(i) => OpResult::Int(i),
+                Err(_) => OpResult::Str(s),
+            })
+        }
+        RespValue::Simple(s) => Some(OpResult::Str(s)),
+        RespValue::Int(i) => i32::try_from(i).ok().map(OpResult::Int),
+        _ => None,
+    }
+}
+
+pub type LookupKeyFunc = Box<dyn Fn(&Headers) -> Option<String>>;
+pub type LookupCache = HashMap<String, Option<OpResult>>;
+
+pub fn redis_get(pool: &RedisPool, key: &str) -> Option<OpResult> {
+    match pool.pipeline(&[vec!["GET".to_string(), key.to_string()]]) {
+        Ok(mut replies) => op_result_of_redis(replies.remove(0)),
+        Err(_e) => {
+            trace_event!(key, error = %_e, "redis: lookup failed");
+            None
+        }
+    }
+}
+
+pub fn create_redis_lookup_operator(
+    pool: RedisPool,
+    key_fn: LookupKeyFunc,
+    out_key: String,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let cache: Rc<RefCell<LookupCache>> = Rc::new(RefCell::new(HashMap::new()));
+    let cache_ref_clone = Rc::clone(&cache);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        if let Some(key) = key_fn(headers)
+            && let Some(val) = cache
+                .borrow_mut()
+                .entry(key)
+                .or_insert_with_key(|key: &String| redis_get(&pool, key))
+        {
+            headers.insert(out_key.clone(), val.clone());
+        }
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        cache_ref_clone.borrow_mut().clear();
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("redis_lookup")
+            .with_downstream(downstream),
+    ))
+}
@@ -0,0 +1,222 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::builtins::{ReductionFunc, get_mapped_int};
+use crate::schema::{FieldType, SchemaError};
+use crate::utils::{Headers, OpResult};
+
+const DIGEST_COMPRESSION: f64 = 100.0;
+const DIGEST_BUFFER_LIMIT: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Statistic {
+    Mean,
+    Variance,
+    StdDev,
+    Percentile(OrderedFloat<f64>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Summary {
+    pub stat: Statistic,
+    pub count: i64,
+    pub mean: OrderedFloat<f64>,
+    pub m2: OrderedFloat<f64>,
+    pub centroids: Vec<(OrderedFloat<f64>, i64)>,
+}
+
+impl Summary {
+    pub fn new(stat: Statistic) -> Summary {
+        Summary {
+            stat,
+            count: 0,
+            mean: OrderedFloat(0.0),
+            m2: OrderedFloat(0.0),
+            centroids: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta: f64 = x - self.mean.0;
+        self.mean = OrderedFloat(self.mean.0 + delta / self.count as f64);
+        self.m2 = OrderedFloat(self.m2.0 + delta * (x - self.mean.0));
+
+        if let Statistic::Percentile(_) = self.stat {
+            self.centroids.push((OrderedFloat(x), 1));
+            if self.centroids.len() > DIGEST_BUFFER_LIMIT {
+                self.compress();
+            }
+        }
+    }
+
+    fn compress(&mut self) {
+        self.centroids.sort_by_key(|(mean, _)| *mean);
+        let total: f64 = self.count as f64;
+        let mut merged: Vec<(OrderedFloat<f64>, i64)> = Vec::new();
+        let mut cumulative: f64 = 0.0;
+
+        for (mean, weight) in self.centroids.drain(..) {
+            if let Some((last_mean, last_weight)) = merged.last_mut() {
+                let proposed: f64 = (*last_weight + weight) as f64;
+                let q: f64 = (cumulative + proposed / 2.0) / total;
+                let limit: f64 = (4.0 * total * q * (1.0 - q) / DIGEST_COMPRESSION).max(1.0);
+                if proposed <= limit {
+                    let new_mean: f64 =
+                        (last_mean.0 * *last_weight as f64 + mean.0 * weight as f64) / proposed;
+                    *last_mean = OrderedFloat(new_mean);
+                    *last_weight += weight;
+                    continue;
+                }
+                cumulative += *last_weight as f64;
+            }
+            merged.push((mean, weight));
+        }
+        self.centroids = merged;
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2.0 / (self.count - 1) as f64
+        }
+    }
+
+    pub fn quantile(&self, p: f64) -> f64 {
+        let mut centroids: Vec<(OrderedFloat<f64>, i64)> = self.centroids.clone();
+        centroids.sort_by_key(|(mean, _)| *mean);
+        match centroids.len() {
+            0 => f64::NAN,
+            1 => centroids[0].0.0,
+            _ => {
+                let target: f64 = p.clamp(0.0, 1.0) * self.count as f64;
+                let mut cumulative: f64 = 0.0;
+                let mut prev_center: f64 = 0.0;
+                let mut prev_mean: f64 = centroids[0].0.0;
+                for (mean, weight) in centroids.iter() {
+                    let center: f64 = cumulative + *weight as f64 / 2.0;
+                    if target <= center {
+                        if center == prev_center {
+                            return mean.0;
+                        }
+                        let frac: f64 = ((target - prev_center) / (center - prev_center)).max(0.0);
+                        return prev_mean + frac * (mean.0 - prev_mean);
+                    }
+                    cumulative += *weight as f64;
+                    prev_center = center;
+                    prev_mean = mean.0;
+                }
+                prev_mean
+            }
+        }
+    }
+
+    pub fn value(&self) -> OpResult {
+        OpResult::Float(OrderedFloat(match self.stat {
+            Statistic::Mean => self.mean.0,
+            Statistic::Variance => self.variance(),
+            Statistic::StdDev => self.variance().sqrt(),
+            Statistic::Percentile(p) => self.quantile(p.0),
+        }))
+    }
+}
+
+pub fn finalize_op_result(input: OpResult) -> OpResult {
+    match input {
+        OpResult::Summary(summary) => summary.value(),
+        other => other,
+    }
+}
+
+pub fn get_mapped_number(key: &str, headers: &Headers) -> Result<f64, SchemaError> {
+    match headers.get(key) {
+        Some(OpResult::Int(i)) => Ok(*i as f64),
+        Some(OpResult::Float(f)) => Ok(f.0),
+        Some(other) => Err(SchemaError::TypeMismatch {
+            key: key.to_string(),
+            expected: FieldType::Float,
+            found: FieldType::of_op_result(other),
+        }),
+        None => Err(SchemaError::Missing(key.to_string())),
+    }
+}
+
+pub fn min_int(search_key: String) -> ReductionFunc {
+    Box::new(move |init_val: OpResult, headers: &mut Headers| {
+        let n: i32 = get_mapped_int(&search_key, headers);
+        match init_val {
+            OpResult::Int(i) => OpResult::Int(i.min(n)),
+            _ => OpResult::Int(n),
+        }
+    })
+}
+
+pub fn max_int(search_key: String) -> ReductionFunc {
+    Box::new(move |init_val: OpResult, headers: &mut Headers| {
+        let n: i32 = get_mapped_int(&search_key, headers);
+        match init_val {
+            OpResult::Int(i) => OpResult::Int(i.max(n)),
+            _ => OpResult::Int(n),
+        }
+    })
+}
+
+pub fn sum_int(search_key: String) -> ReductionFunc {
+    Box::new(move |init_val: OpResult, headers: &mut Headers| {
+        let n: i32 = get_mapped_int(&search_key, headers);
+        match init_val {
+            OpResult::Int(i) => OpResult::Int(i.saturating_add(n)),
+            _ => OpResult::Int(n),
+        }
+    })
+}
+
+pub fn summarize(search_key: String, stat: Statistic) -> ReductionFunc {
+    Box::new(move |init_val: OpResult, headers: &mut Headers| {
+        let mut summary: Box<Summary> = match init_val {
+            OpResult::Summary(summary) => summary,
+            _ => Box::new(Summary::new(stat)),
+        };
+        if let Ok(x) = get_mapped_number(&search_key, headers) {
+            summary.add(x);
+        }
+        OpResult::Summary(summary)
+    })
+}
+
+pub fn mean_float(search_key: String) -> ReductionFunc {
+    summarize(search_key, Statistic::Mean)
+}
+
+pub fn variance(search_key: String) -> ReductionFunc {
+    summarize(search_key, Statistic::Variance)
+}
+
+pub fn stddev(search_key: String) -> ReductionFunc {
+    summarize(search_key, Statistic::StdDev)
+}
+
+pub fn percentile(search_key: String, p: f64) -> ReductionFunc {
+    summarize(search_key, Statistic::Percentile(OrderedFloat(p)))
+}
+
+pub type MultiReductionFunc = Box<dyn Fn(Headers, &mut Headers) -> Headers>;
+
+pub fn multi_reduce(reductions: Vec<(ReductionFunc, String)>) -> MultiReductionFunc {
+    Box::new(move |mut init_vals: Headers, headers: &mut Headers| {
+        for (reduce, out_key) in reductions.iter() {
+            let init_val: OpResult = init_vals.remove(out_key).unwrap_or(OpResult::Empty);
+            init_vals.insert(out_key.clone(), reduce(init_val, headers));
+        }
+        init_vals
+    })
+}
+
+pub fn finalize_headers(headers: Headers) -> Headers {
+    headers
+        .into_iter()
+        .map(|(key, val)| (key, finalize_op_result(val)))
+        .collect()
+}
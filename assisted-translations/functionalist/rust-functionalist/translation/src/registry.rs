@@ -0,0 +1,201 @@
+#![allow(dead_code)]
+
+use crate::error::StreamError;
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+pub struct RegisteredQuery {
+    pub op: OperatorRef,
+    pub boundary: Rc<Cell<bool>>,
+}
+
+pub enum RegistryChange {
+    Attach(String, RegisteredQuery),
+    Detach(String),
+}
+
+impl RegistryChange {
+    pub fn name(&self) -> &str {
+        match self {
+            RegistryChange::Attach(name, _) | RegistryChange::Detach(name) => name,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct QueryRegistry {
+    pub queries: BTreeMap<String, RegisteredQuery>,
+    pub pending: Vec<RegistryChange>,
+}
+
+pub fn create_boundary_tap(boundary: Rc<Cell<bool>>, next_op: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op.borrow_mut().next)(headers));
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        boundary.set(true);
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("boundary_tap")
+            .with_downstream(downstream),
+    ))
+}
+
+impl QueryRegistry {
+    pub fn new() -> Self {
+        QueryRegistry::default()
+    }
+
+    pub fn attach(
+        &mut self,
+        name: String,
+        sink: OperatorRef,
+        compile: impl FnOnce(OperatorRef) -> Result<OperatorRef, StreamError>,
+    ) -> Result<(), StreamError> {
+        let boundary: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        let op: OperatorRef = compile(create_boundary_tap(Rc::clone(&boundary), sink))?;
+        self.pending.push(RegistryChange::Attach(
+            name,
+            RegisteredQuery { op, boundary },
+        ));
+        Ok(())
+    }
+
+    pub fn detach(&mut self, name: String) {
+        self.pending.push(RegistryChange::Detach(name));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.pending.iter().fold(
+            self.queries.contains_key(name),
+            |present, change| match change {
+                RegistryChange::Attach(n, _) if n == name => true,
+                RegistryChange::Detach(n) if n == name => false,
+                _ => present,
+            },
+        )
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.queries.keys().cloned().collect()
+    }
+
+    /// Applies the pending changes whose query is free to change: a running
+    /// query only once its own epoch has closed, a new name only at a boundary
+    /// of some running query. Returns the names attached by this call.
+    pub fn apply_pending(&mut self, closed: &BTreeSet<String>, at_boundary: bool) -> Vec<String> {
+        let mut free: BTreeSet<String> = closed.clone();
+        let mut blocked: BTreeSet<String> = BTreeSet::new();
+        let mut attached: Vec<String> = Vec::new();
+        for change in std::mem::take(&mut self.pending) {
+            let name: String = change.name().to_string();
+            let ready: bool = if self.queries.contains_key(&name) {
+                free.contains(&name)
+            } else {
+                at_boundary
+            };
+            if !ready || blocked.contains(&name) {
+                blocked.insert(name);
+                self.pending.push(change);
+                continue;
+            }
+            match change {
+                RegistryChange::Attach(name, query) => {
+                    self.queries.insert(name.clone(), query);
+                    attached.push(name);
+                }
+                RegistryChange::Detach(name) => {
+                    self.queries.remove(&name);
+                    attached.retain(|n: &String| *n != name);
+                }
+            }
+            free.insert(name);
+        }
+        attached
+    }
+
+    pub fn next(&mut self, headers: &mut Headers) {
+        if self.queries.is_empty() {
+            self.apply_pending(&BTreeSet::new(), true);
+        }
+        for query in self.queries.values() {
+            (query.op.borrow_mut().next)(&mut headers.clone());
+        }
+        let closed: BTreeSet<String> = self
+            .queries
+            .iter()
+            .filter(|(_, query)| query.boundary.replace(false))
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !closed.is_empty() {
+            // the tuple that closed the epoch opens the next one, so queries
+            // attached at this boundary start with it
+            for name in self.apply_pending(&closed, true) {
+                (self.queries[&name].op.borrow_mut().next)(&mut headers.clone());
+            }
+        }
+    }
+
+    pub fn reset(&mut self, headers: &mut Headers) {
+        for query in self.queries.values() {
+            (query.op.borrow_mut().reset)(&mut headers.clone());
+            query.boundary.set(false);
+        }
+        let names: BTreeSet<String> = self.queries.keys().cloned().collect();
+        self.apply_pending(&names, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::{create_epoch_operator, get_mapped_int};
+    use crate::testing::{TestSink, tuple};
+    use crate::utils::OpResult;
+    use ordered_float::OrderedFloat;
+
+    fn at(time: f64) -> Headers {
+        tuple(&[("time", OpResult::Float(OrderedFloat(time)))])
+    }
+
+    fn eids(rows: &[Headers]) -> Vec<i32> {
+        rows.iter()
+            .map(|headers: &Headers| get_mapped_int("eid", headers))
+            .collect()
+    }
+
+    #[test]
+    fn registry_swaps_queries_when_their_epoch_closes() {
+        let old: TestSink = TestSink::new();
+        let new: TestSink = TestSink::new();
+        let epoch = |next_op: OperatorRef| -> Result<OperatorRef, StreamError> {
+            Ok(create_epoch_operator(1.0, "eid".to_string(), next_op))
+        };
+        let mut registry: QueryRegistry = QueryRegistry::new();
+        registry
+            .attach("q".to_string(), old.operator(), epoch)
+            .unwrap();
+        registry.next(&mut at(100.0));
+        registry.next(&mut at(100.5));
+        registry.detach("q".to_string());
+        registry
+            .attach("q".to_string(), new.operator(), epoch)
+            .unwrap();
+        registry.next(&mut at(100.7));
+        registry.next(&mut at(101.2));
+        registry.next(&mut at(101.5));
+        registry.reset(&mut Headers::new());
+        assert_eq!(old.emitted().len(), 4);
+        assert_eq!(eids(&old.resets()), vec![0]);
+        assert_eq!(new.emitted().len(), 2);
+        assert_eq!(new.epochs(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+
+use crate::builtins::create_dump_operator;
+use crate::config::{SigintSubscription, subscribe_sigint};
+use crate::dsl::{check_query, compile_query};
+use crate::error::{InputKind, StreamError};
+use crate::registry::QueryRegistry;
+use crate::schema::Schema;
+use crate::traffic_gen::synthetic_headers;
+use crate::utils::{Headers, OperatorRef};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write, stdin, stdout};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+pub enum ReplEvent {
+    Tuple(Box<Headers>),
+    Line(String),
+    Eof,
+}
+
+#[derive(Default)]
+pub struct Repl {
+    pub sources: BTreeMap<String, String>,
+    pub registry: QueryRegistry,
+    pub paused: bool,
+}
+
+const REPL_HELP: &str = "commands:
+  add <name> <query>   attach a query at the next epoch boundary
+  set <name> <query>   replace the query once its current epoch closes
+  drop <name>          detach a query once its current epoch closes
+  list                 show attached queries
+  pause | resume       stop or restart feeding tuples to queries
+  help                 show this message
+  quit                 exit the repl";
+
+impl Repl {
+    pub fn new() -> Self {
+        Repl::default()
+    }
+
+    pub fn attach(&mut self, name: &str, src: &str, replace: bool) -> Result<(), StreamError> {
+        if !replace && self.registry.contains(name) {
+            return Err(StreamError::query(format!(
+                "query '{}' already exists, use 'set' to modify it",
+                name
+            )));
+        }
+        check_query(src, &Schema::decoded())?;
+        let sink: OperatorRef = create_dump_operator(false, Box::new(stdout()));
+        self.registry
+            .attach(name.to_string(), sink, |sink: OperatorRef| {
+                compile_query(src, sink)
+            })?;
+        self.sources.insert(name.to_string(), src.to_string());
+        Ok(())
+    }
+
+    pub fn detach(&mut self, name: &str) -> Result<(), StreamError> {
+        if !self.registry.contains(name) {
+            return Err(StreamError::unknown(InputKind::Query, "query", name));
+        }
+        self.registry.detach(name.to_string());
+        self.sources.remove(name);
+        Ok(())
+    }
+
+    pub fn push(&mut self, headers: &mut Headers) {
+        if !self.paused {
+            self.registry.next(headers);
+        }
+    }
+
+    pub fn handle_line(&mut self, line: &str, out: &mut dyn Write) -> Result<bool, StreamError> {
+        let line: &str = line.trim();
+        let (cmd, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest: &str = rest.trim();
+        match cmd {
+            "" => {}
+            "add" | "set" => match rest.split_once(char::is_whitespace) {
+                Some((name, src)) => self.attach(name, src.trim(), cmd == "set")?,
+                None => return Err(StreamError::query(format!("usage: {} <name> <query>", cmd))),
+            },
+            "drop" => self.detach(rest)?,
+            "list" => {
+                for (name, src) in self.sources.iter() {
+                    writeln!(out, "{}: {}", name, src)?;
+                }
+            }
+            "pause" => self.paused = true,
+            "resume" => self.paused = false,
+            "help" => writeln!(out, "{}", REPL_HELP)?,
+            "quit" | "exit" => return Ok(false),
+            _ => return Err(StreamError::unknown(InputKind::Query, "command", cmd)),
+        }
+        Ok(true)
+    }
+}
+
+pub fn spawn_synthetic_source(tx: Sender<ReplEvent>, interval: Duration) {
+    thread::spawn(move || {
+        let mut i: i32 = 0;
+        while tx
+            .send(ReplEvent::Tuple(Box::new(synthetic_headers(i))))
+            .is_ok()
+        {
+            i += 1;
+            thread::sleep(interval);
+        }
+    });
+}
+
+pub fn spawn_stdin_reader(tx: Sender<ReplEvent>) {
+    thread::spawn(move || {
+        for line in stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(ReplEvent::Line(line)).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        let _ = tx.send(ReplEvent::Eof);
+    });
+}
+
+pub fn run_repl(interval: Duration) -> Result<(), StreamError> {
+    let (tx, rx): (Sender<ReplEvent>, Receiver<ReplEvent>) = mpsc::channel();
+    spawn_synthetic_source(tx.clone(), interval);
+    spawn_stdin_reader(tx.clone());
+    let _sigint: SigintSubscription = subscribe_sigint(move || {
+        let _ = tx.send(ReplEvent::Eof);
+    })?;
+
+    let mut repl: Repl = Repl::new();
+    let mut out = stdout();
+    writeln!(out, "{}", REPL_HELP)?;
+    for event in rx.iter() {
+        match event {
+            ReplEvent::Tuple(mut headers) => repl.push(&mut headers),
+            ReplEvent::Line(line) => match repl.handle_line(&line, &mut out) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => writeln!(out, "error: {}", e)?,
+            },
+            ReplEvent::Eof => break,
+        }
+    }
+    repl.registry.reset(&mut Headers::new());
+    Ok(())
+}
@@ -0,0 +1,49 @@
+#![allow(dead_code)]
+
+use crate::keys::{HeaderKey, WellKnownKey};
+use crate::utils::{Headers, OpResult, OperatorRef};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct Pacer {
+    speed_factor: f64,
+    origin: Option<(Instant, f64)>,
+}
+
+impl Pacer {
+    pub fn new(speed_factor: f64) -> Self {
+        Pacer {
+            speed_factor,
+            origin: None,
+        }
+    }
+
+    pub fn pace(&mut self, headers: &Headers) {
+        if self.speed_factor <= 0.0 {
+            return;
+        }
+        let time: f64 = match WellKnownKey::Time.lookup(headers) {
+            Some(OpResult::Float(time)) => time.0,
+            _ => return,
+        };
+        let (start, first_time) = *self.origin.get_or_insert((Instant::now(), time));
+        let offset: f64 = (time - first_time) / self.speed_factor;
+        if offset <= 0.0 {
+            return;
+        }
+        let due: Duration = Duration::from_secs_f64(offset);
+        let elapsed: Duration = start.elapsed();
+        if due > elapsed {
+            thread::sleep(due - elapsed);
+        }
+    }
+}
+
+pub fn replay(source: impl IntoIterator<Item = Headers>, speed_factor: f64, next_op: &OperatorRef) {
+    let mut pacer: Pacer = Pacer::new(speed_factor);
+    for mut headers in source {
+        pacer.pace(&headers);
+        (next_op.borrow_mut().next)(&mut headers)
+    }
+    (next_op.borrow_mut().reset)(&mut Headers::new());
+}
@@ -0,0 +1,273 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+use thiserror::Error;
+
+use crate::packet::OUTER_PREFIX;
+use crate::utils::{Headers, OpResult};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::Ipv4Addr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Float,
+    Int,
+    IPv4,
+    MAC,
+    Str,
+    Any,
+}
+
+impl FieldType {
+    pub fn of_op_result(val: &OpResult) -> Option<FieldType> {
+        match val {
+            OpResult::Float(_) | OpResult::Summary(_) => Some(FieldType::Float),
+            OpResult::Int(_) => Some(FieldType::Int),
+            OpResult::IPv4(_) => Some(FieldType::IPv4),
+            OpResult::MAC(_) => Some(FieldType::MAC),
+            OpResult::Str(_) => Some(FieldType::Str),
+            OpResult::Empty => None,
+        }
+    }
+
+    pub fn accepts(&self, other: FieldType) -> bool {
+        *self == FieldType::Any || other == FieldType::Any || *self == other
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum SchemaError {
+    #[error("'{0}' is missing from the tuple")]
+    Missing(String),
+    #[error("'{0}' not produced upstream of '{1}'")]
+    NotProduced(String, String),
+    #[error(
+        "'{key}' expected to be {expected:?} but found {}",
+        .found.map_or("Empty".to_string(), |found: FieldType| format!("{:?}", found))
+    )]
+    TypeMismatch {
+        key: String,
+        expected: FieldType,
+        found: Option<FieldType>,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schema {
+    pub fields: BTreeMap<String, FieldType>,
+    pub reset_keys: BTreeSet<String>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    pub fn with(mut self, key: &str, ty: FieldType) -> Self {
+        self.fields.insert(key.to_string(), ty);
+        self
+    }
+
+    pub fn packet() -> Self {
+        Schema::new()
+            .with("time", FieldType::Float)
+            .with("eth.src", FieldType::MAC)
+            .with("eth.dst", FieldType::MAC)
+            .with("eth.ethertype", FieldType::Int)
+            .with("ipv4.hlen", FieldType::Int)
+            .with("ipv4.proto", FieldType::Int)
+            .with("ipv4.len", FieldType::Int)
+            .with("ipv4.src", FieldType::IPv4)
+            .with("ipv4.dst", FieldType::IPv4)
+            .with("l4.sport", FieldType::Int)
+            .with("l4.dport", FieldType::Int)
+            .with("l4.flags", FieldType::Int)
+    }
+
+    pub fn with_icmp(self) -> Self {
+        self.with("icmp.type", FieldType::Int)
+            .with("icmp.code", FieldType::Int)
+    }
+
+    pub fn with_arp(self) -> Self {
+        self.with("arp.op", FieldType::Int)
+            .with("arp.spa", FieldType::IPv4)
+    }
+
+    pub fn with_dns(self) -> Self {
+        self.with("dns.qname", FieldType::Str)
+            .with("dns.qtype", FieldType::Int)
+            .with("dns.rcode", FieldType::Int)
+            .with("dns.size", FieldType::Int)
+    }
+
+    pub fn with_http(self) -> Self {
+        self.with("http.method", FieldType::Str)
+            .with("http.host", FieldType::Str)
+            .with("http.path", FieldType::Str)
+    }
+
+    pub fn with_tls(self) -> Self {
+        self.with("tls.ja3", FieldType::Str)
+            .with("tls.ja3s", FieldType::Str)
+    }
+
+    pub fn with_vlan(self) -> Self {
+        self.with("eth.vlan", FieldType::Int)
+    }
+
+    pub fn with_tcp(self) -> Self {
+        self.with("tcp.seq", FieldType::Int)
+            .with("tcp.ack", FieldType::Int)
+            .with("tcp.len", FieldType::Int)
+    }
+
+    pub fn with_fragments(self) -> Self {
+        self.with("ipv4.frags", FieldType::Int)
+    }
+
+    pub fn with_tunnel(self) -> Self {
+        Schema::packet()
+            .with_vlan()
+            .fields
+            .into_iter()
+            .filter(|(key, _)| key != "time")
+            .fold(self, |schema: Schema, (key, ty)| {
+                schema.with(&format!("{}{}", OUTER_PREFIX, key), ty)
+            })
+            .with(&format!("{}tunnel", OUTER_PREFIX), FieldType::Str)
+            .with(&format!("{}vni", OUTER_PREFIX), FieldType::Int)
+    }
+
+    pub fn decoded() -> Self {
+        Schema::packet()
+            .with_icmp()
+            .with_arp()
+            .with_dns()
+            .with_http()
+            .with_tls()
+            .with_vlan()
+            .with_fragments()
+            .with_tcp()
+            .with_tunnel()
+    }
+
+    pub fn field_type(&self, key: &str) -> Option<FieldType> {
+        self.fields.get(key).copied()
+    }
+
+    pub fn require(&self, key: &str, ty: FieldType, stage: &str) -> Result<FieldType, SchemaError> {
+        match self.field_type(key) {
+            Some(found) if ty.accepts(found) => Ok(found),
+            Some(found) => Err(SchemaError::TypeMismatch {
+                key: key.to_string(),
+                expected: ty,
+                found: Some(found),
+            }),
+            None => Err(SchemaError::NotProduced(key.to_string(), stage.to_string())),
+        }
+    }
+
+    pub fn project(&self, keys: &[String], stage: &str) -> Result<Schema, SchemaError> {
+        let mut projected: Schema = Schema {
+            fields: BTreeMap::new(),
+            reset_keys: self.reset_keys.clone(),
+        };
+        for key in self.reset_keys.iter() {
+            if let Some(ty) = self.field_type(key) {
+                projected.fields.insert(key.clone(), ty);
+            }
+        }
+        for key in keys.iter() {
+            let ty: FieldType = self.require(key, FieldType::Any, stage)?;
+            projected.fields.insert(key.clone(), ty);
+        }
+        Ok(projected)
+    }
+
+    pub fn validate(&self, headers: &Headers) -> Result<(), SchemaError> {
+        for (key, ty) in self.fields.iter() {
+            match headers.get(key) {
+                Some(val) => {
+                    let found: Option<FieldType> = FieldType::of_op_result(val);
+                    if !found.is_some_and(|found: FieldType| ty.accepts(found)) {
+                        return Err(SchemaError::TypeMismatch {
+                            key: key.clone(),
+                            expected: *ty,
+                            found,
+                        });
+                    }
+                }
+                None => return Err(SchemaError::Missing(key.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup<'a>(
+        &self,
+        key: &str,
+        ty: FieldType,
+        headers: &'a Headers,
+    ) -> Result<&'a OpResult, SchemaError> {
+        if let Some(expected) = self.field_type(key)
+            && !expected.accepts(ty)
+        {
+            return Err(SchemaError::TypeMismatch {
+                key: key.to_string(),
+                expected,
+                found: Some(ty),
+            });
+        }
+        headers
+            .get(key)
+            .ok_or_else(|| SchemaError::Missing(key.to_string()))
+    }
+
+    fn mismatch(key: &str, expected: FieldType, val: &OpResult) -> SchemaError {
+        SchemaError::TypeMismatch {
+            key: key.to_string(),
+            expected,
+            found: FieldType::of_op_result(val),
+        }
+    }
+
+    pub fn get_int(&self, key: &str, headers: &Headers) -> Result<i32, SchemaError> {
+        match self.lookup(key, FieldType::Int, headers)? {
+            OpResult::Int(i) => Ok(*i),
+            other => Err(Schema::mismatch(key, FieldType::Int, other)),
+        }
+    }
+
+    pub fn get_float(
+        &self,
+        key: &str,
+        headers: &Headers,
+    ) -> Result<OrderedFloat<f64>, SchemaError> {
+        match self.lookup(key, FieldType::Float, headers)? {
+            OpResult::Float(f) => Ok(*f),
+            other => Err(Schema::mismatch(key, FieldType::Float, other)),
+        }
+    }
+
+    pub fn get_ipv4(&self, key: &str, headers: &Headers) -> Result<Ipv4Addr, SchemaError> {
+        match self.lookup(key, FieldType::IPv4, headers)? {
+            OpResult::IPv4(a) => Ok(*a),
+            other => Err(Schema::mismatch(key, FieldType::IPv4, other)),
+        }
+    }
+
+    pub fn get_mac(&self, key: &str, headers: &Headers) -> Result<[u8; 6], SchemaError> {
+        match self.lookup(key, FieldType::MAC, headers)? {
+            OpResult::MAC(m) => Ok(*m),
+            other => Err(Schema::mismatch(key, FieldType::MAC, other)),
+        }
+    }
+
+    pub fn get_str<'a>(&self, key: &str, headers: &'a Headers) -> Result<&'a str, SchemaError> {
+        match self.lookup(key, FieldType::Str, headers)? {
+            OpResult::Str(s) => Ok(s),
+            other => Err(Schema::mismatch(key, FieldType::Str, other)),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::demo::{DEMO_QUERIES, demo_query};
+use crate::dsl::compile_query;
+use crate::error::StreamError;
+use crate::packet::{DecodeOptions, PacketRecord};
+use crate::plan::fan_out;
+use crate::utils::{Headers, OpResult, Operator, OperatorRef, mac_of_string, string_of_mac};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::rc::Rc;
+use std::thread;
+
+pub mod proto {
+    tonic::include_proto!("sonata");
+}
+
+use proto::run_request::Item;
+use proto::sonata_server::Sonata;
+use proto::value::Kind;
+use proto::{
+    Alert, ListQueriesRequest, ListQueriesResponse, QueryInfo, QuerySelection, RunRequest, Tuple,
+    Value,
+};
+
+pub const SESSION_BUFFER: usize = 1024;
+
+pub fn value_of_op_result(val: &OpResult) -> Value {
+    let kind: Option<Kind> = match val {
+        OpResult::Int(i) => Some(Kind::Int(*i)),
+        OpResult::Float(f) => Some(Kind::Float(f.0)),
+        OpResult::IPv4(addr) => Some(Kind::Ipv4(addr.to_string())),
+        OpResult::MAC(mac) => Some(Kind::Mac(string_of_mac(mac))),
+        OpResult::Str(s) => Some(Kind::Str(s.clone())),
+        OpResult::Summary(summary) => Some(Kind::Float(match summary.value() {
+            OpResult::Float(f) => f.0,
+            _ => f64::NAN,
+        })),
+        OpResult::Empty => None,
+    };
+    Value { kind }
+}
+
+pub fn op_result_of_value(key: &str, val: Value) -> Result<OpResult, StreamError> {
+    let invalid =
+        |what: &str| StreamError::value(format!("field '{}' is not a valid {}", key, what));
+    Ok(match val.kind {
+        Some(Kind::Int(i)) => OpResult::Int(i),
+        Some(Kind::Float(f)) => OpResult::Float(OrderedFloat(f)),
+        Some(Kind::Ipv4(addr)) => OpResult::IPv4(
+            addr.parse::<Ipv4Addr>()
+                .map_err(|_| invalid("IPv4 address"))?,
+        ),
+        Some(Kind::Mac(mac)) => OpResult::MAC(mac_of_string(&mac).ok_or_else(|| invalid("MAC"))?),
+        Some(Kind::Str(s)) => OpResult::Str(s),
+        None => OpResult::Empty,
+    })
+}
+
+pub fn tuple_of_headers(headers: &Headers) -> Tuple {
+    Tuple {
+        fields: headers
+            .iter()
+            .map(|(key, val)| (key.clone(), value_of_op_result(val)))
+            .collect::<HashMap<String, Value>>(),
+    }
+}
+
+pub fn headers_of_tuple(tuple: Tuple) -> Result<Headers, StreamError> {
+    tuple
+        .fields
+        .into_iter()
+        .map(|(key, val)| {
+            let val: OpResult = op_result_of_value(&key, val)?;
+            Ok((key, val))
+        })
+        .collect()
+}
+
+fn create_alert_operator(
+    query: String,
+    alerts: mpsc::Sender<Result<Alert, Status>>,
+) -> OperatorRef {
+    Rc::new(RefCell::new(
+        Operator::new(
+            Box::new(move |headers: &mut Headers| {
+                let alert: Alert = Alert {
+                    query: query.clone(),
+                    tuple: Some(tuple_of_headers(headers)),
+                };
+                let _ = alerts.blocking_send(Ok(alert));
+            }),
+            Box::new(|_: &mut Headers| {}),
+        )
+        .with_label("grpc_alerts"),
+    ))
+}
+
+fn compile_selection(
+    selection: &QuerySelection,
+    alerts: &mpsc::Sender<Result<Alert, Status>>,
+) -> Result<OperatorRef, StreamError> {
+    if selection.queries.is_empty() {
+        return Err(StreamError::query("no queries selected"));
+    }
+    let queries: Vec<OperatorRef> = selection
+        .queries
+        .iter()
+        .map(|name: &String| {
+            compile_query(
+                demo_query(name),
+                create_alert_operator(name.clone(), alerts.clone()),
+            )
+        })
+        .collect::<Result<Vec<OperatorRef>, StreamError>>()?;
+    Ok(fan_out(queries))
+}
+
+fn run_session(
+    selection: QuerySelection,
+    mut inputs: mpsc::Receiver<Item>,
+    alerts: mpsc::Sender<Result<Alert, Status>>,
+    ready: oneshot::Sender<Result<(), StreamError>>,
+) {
+    let query: OperatorRef = match compile_selection(&selection, &alerts) {
+        Ok(query) => query,
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+    let _ = ready.send(Ok(()));
+    let options: DecodeOptions = DecodeOptions {
+        decapsulate: selection.decapsulate,
+        fragment_timeout: None,
+    };
+    while let Some(item) = inputs.blocking_recv() {
+        let headers: Result<Headers, StreamError> = match item {
+            Item::Tuple(tuple) => headers_of_tuple(tuple),
+            Item::Packet(packet) => {
+                PacketRecord::decode(packet.time, &packet.frame, &options).map(Headers::from)
+            }
+            Item::Select(_) => Err(StreamError::query("queries are already selected")),
+        };
+        match headers {
+            Ok(mut headers) => (query.borrow_mut().next)(&mut headers),
+            Err(e) => {
+                let _ = alerts.blocking_send(Err(Status::invalid_argument(e.to_string())));
+            }
+        }
+    }
+    (query.borrow_mut().reset)(&mut Headers::new());
+}
+
+#[derive(Default)]
+pub struct SonataService;
+
+#[tonic::async_trait]
+impl Sonata for SonataService {
+    async fn list_queries(
+        &self,
+        _request: Request<ListQueriesRequest>,
+    ) -> Result<Response<ListQueriesResponse>, Status> {
+        Ok(Response::new(ListQueriesResponse {
+            queries: DEMO_QUERIES
+                .iter()
+                .map(|(name, query)| QueryInfo {
+                    name: name.to_string(),
+                    query: query.to_string(),
+                })
+                .collect(),
+        }))
+    }
+
+    type RunStream = ReceiverStream<Result<Alert, Status>>;
+
+    async fn run(
+        &self,
+        request: Request<Streaming<RunRequest>>,
+    ) -> Result<Response<Self::RunStream>, Status> {
+        let mut stream: Streaming<RunRequest> = request.into_inner();
+        let selection: QuerySelection = match stream.message().await?.and_then(|r| r.item) {
+            Some(Item::Select(selection)) => selection,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "the first message must select queries",
+                ));
+            }
+        };
+        let (alerts_tx, alerts_rx) = mpsc::channel(SESSION_BUFFER);
+        let (inputs_tx, inputs_rx) = mpsc::channel(SESSION_BUFFER);
+        let (ready_tx, ready_rx) = oneshot::channel();
+        thread::spawn(move || run_session(selection, inputs_rx, alerts_tx, ready_tx));
+        ready_rx
+            .await
+            .map_err(|_| Status::internal("query session exited"))?
+            .map_err(|e: StreamError| Status::invalid_argument(e.to_string()))?;
+        tokio::spawn(async move {
+            while let Ok(Some(request)) = stream.message().await {
+                if let Some(item) = request.item
+                    && inputs_tx.send(item).await.is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(alerts_rx)))
+    }
+}
+
+#[cfg(all(test, feature = "grpc"))]
+mod tests {
+    use super::*;
+    use proto::sonata_client::SonataClient;
+    use proto::sonata_server::SonataServer;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, Server};
+
+    fn port_tuple(port: i32) -> RunRequest {
+        let field = |kind: Kind| Value { kind: Some(kind) };
+        RunRequest {
+            item: Some(Item::Tuple(Tuple {
+                fields: HashMap::from([
+                    ("time".to_string(), field(Kind::Float(1.0))),
+                    ("l4.dport".to_string(), field(Kind::Int(port))),
+                ]),
+            })),
+        }
+    }
+
+    #[tokio::test]
+    async fn run_streams_alerts_for_the_selected_query() {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: String = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::builder()
+                .add_service(SonataServer::new(SonataService))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let mut client: SonataClient<Channel> = SonataClient::connect(addr).await.unwrap();
+        let query: String = "filter l4.dport == 22".to_string();
+        let requests: Vec<RunRequest> = vec![
+            RunRequest {
+                item: Some(Item::Select(QuerySelection {
+                    queries: vec![query.clone()],
+                    decapsulate: false,
+                })),
+            },
+            port_tuple(22),
+            port_tuple(80),
+        ];
+        let mut alerts: Streaming<Alert> = client
+            .run(tokio_stream::iter(requests))
+            .await
+            .unwrap()
+            .into_inner();
+        let alert: Alert = alerts.message().await.unwrap().unwrap();
+        assert_eq!(alert.query, query);
+        let headers: Headers = headers_of_tuple(alert.tuple.unwrap()).unwrap();
+        assert_eq!(headers.get("l4.dport"), Some(&OpResult::Int(22)));
+        assert!(alerts.message().await.unwrap().is_none());
+    }
+}
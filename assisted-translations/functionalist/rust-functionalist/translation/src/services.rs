@@ -0,0 +1,101 @@
+#![allow(dead_code)]
+
+use crate::builtins::{MapFunc, create_map_operator};
+use crate::utils::{Headers, OpResult, OperatorRef};
+
+pub const SERVICE_KEY: &str = "service";
+
+pub const IANA_SERVICES: [(i32, &str); 64] = [
+    (7, "echo"),
+    (20, "ftp-data"),
+    (21, "ftp"),
+    (22, "ssh"),
+    (23, "telnet"),
+    (25, "smtp"),
+    (37, "time"),
+    (43, "whois"),
+    (49, "tacacs"),
+    (53, "dns"),
+    (67, "dhcp-server"),
+    (68, "dhcp-client"),
+    (69, "tftp"),
+    (79, "finger"),
+    (80, "http"),
+    (88, "kerberos"),
+    (110, "pop3"),
+    (111, "sunrpc"),
+    (119, "nntp"),
+    (123, "ntp"),
+    (135, "msrpc"),
+    (137, "netbios-ns"),
+    (138, "netbios-dgm"),
+    (139, "netbios-ssn"),
+    (143, "imap"),
+    (161, "snmp"),
+    (162, "snmp-trap"),
+    (179, "bgp"),
+    (389, "ldap"),
+    (443, "https"),
+    (445, "smb"),
+    (464, "kpasswd"),
+    (465, "smtps"),
+    (500, "isakmp"),
+    (514, "syslog"),
+    (515, "printer"),
+    (520, "rip"),
+    (546, "dhcpv6-client"),
+    (547, "dhcpv6-server"),
+    (554, "rtsp"),
+    (587, "submission"),
+    (631, "ipp"),
+    (636, "ldaps"),
+    (853, "dns-over-tls"),
+    (873, "rsync"),
+    (902, "vmware-auth"),
+    (993, "imaps"),
+    (995, "pop3s"),
+    (1080, "socks"),
+    (1194, "openvpn"),
+    (1433, "ms-sql"),
+    (1521, "oracle"),
+    (1723, "pptp"),
+    (1812, "radius"),
+    (1883, "mqtt"),
+    (2049, "nfs"),
+    (3306, "mysql"),
+    (3389, "rdp"),
+    (4789, "vxlan"),
+    (5060, "sip"),
+    (5432, "postgresql"),
+    (5900, "vnc"),
+    (6379, "redis"),
+    (8080, "http-alt"),
+];
+
+pub fn service_name(port: i32) -> Option<&'static str> {
+    IANA_SERVICES
+        .binary_search_by_key(&port, |(p, _)| *p)
+        .ok()
+        .map(|i: usize| IANA_SERVICES[i].1)
+}
+
+pub fn service_map(port_key: String, out_key: String) -> MapFunc {
+    Box::new(move |mut headers: Headers| {
+        if let Some(OpResult::Int(port)) = headers.get(&port_key) {
+            let service: String = match service_name(*port) {
+                Some(name) => name.to_string(),
+                None => port.to_string(),
+            };
+            headers.insert(out_key.clone(), OpResult::Str(service));
+        }
+        headers
+    })
+}
+
+pub fn create_service_operator(
+    port_key: String,
+    out_key: String,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    create_map_operator(service_map(port_key, out_key), next_op)
+}
@@ -0,0 +1,416 @@
+#![allow(dead_code)]
+
+use smallvec::SmallVec;
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
+use std::slice;
+use std::sync::Arc;
+
+pub const INLINE_ENTRIES: usize = 16;
+pub const DELTA_ENTRIES: usize = 4;
+
+type Entries<K, V> = SmallVec<[(K, V); INLINE_ENTRIES]>;
+
+#[derive(Clone)]
+pub struct SmallMap<K, V> {
+    base: Arc<Entries<K, V>>,
+    delta: SmallVec<[(K, Option<V>); DELTA_ENTRIES]>,
+    len: usize,
+}
+
+impl<K, V> Default for SmallMap<K, V> {
+    fn default() -> Self {
+        SmallMap {
+            base: Arc::new(SmallVec::new()),
+            delta: SmallVec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<K: fmt::Debug + Ord, V: fmt::Debug> fmt::Debug for SmallMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V: PartialEq> PartialEq for SmallMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Ord, V: Eq> Eq for SmallMap<K, V> {}
+
+impl<K: Ord, V: PartialOrd> PartialOrd for SmallMap<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord> Ord for SmallMap<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Ord + Hash, V: Hash> Hash for SmallMap<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for entry in self.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K: Ord, V> SmallMap<K, V> {
+    pub fn new() -> Self {
+        SmallMap::default()
+    }
+
+    fn base_position<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.base.binary_search_by(|(k, _)| k.borrow().cmp(key))
+    }
+
+    fn delta_position<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.delta.binary_search_by(|(k, _)| k.borrow().cmp(key))
+    }
+
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.base) > 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.base = Arc::new(SmallVec::new());
+        self.delta.clear();
+        self.len = 0
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.delta_position(key) {
+            Ok(idx) => self.delta[idx].1.as_ref(),
+            Err(_) => self.base_position(key).ok().map(|idx| &self.base[idx].1),
+        }
+    }
+
+    pub fn get_hinted<Q>(&self, hint: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if self.delta.is_empty()
+            && let Some((k, v)) = self.base.get(hint)
+            && k.borrow() == key
+        {
+            return Some(v);
+        }
+        self.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            base: self.base.iter().peekable(),
+            delta: self.delta.iter().peekable(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> SmallMap<K, V> {
+    fn entries_mut(&mut self) -> &mut Entries<K, V> {
+        if self.is_shared() {
+            let merged: Entries<K, V> = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            self.base = Arc::new(merged);
+            self.delta.clear();
+        }
+        let base: &mut Entries<K, V> = Arc::make_mut(&mut self.base);
+        for (key, val) in self.delta.drain(..) {
+            match (base.binary_search_by(|(k, _)| k.cmp(&key)), val) {
+                (Ok(idx), Some(val)) => base[idx].1 = val,
+                (Ok(idx), None) => {
+                    base.remove(idx);
+                }
+                (Err(idx), Some(val)) => base.insert(idx, (key, val)),
+                (Err(_), None) => {}
+            }
+        }
+        base
+    }
+
+    fn record(&mut self, key: K, val: Option<V>) -> Option<V> {
+        let present: bool = val.is_some();
+        let prev: Option<V> = match self.delta_position(&key) {
+            Ok(idx) => std::mem::replace(&mut self.delta[idx].1, val),
+            Err(idx) => {
+                let prev: Option<V> = self.get(&key).cloned();
+                if val.is_some() || prev.is_some() {
+                    self.delta.insert(idx, (key, val));
+                }
+                prev
+            }
+        };
+        match (prev.is_some(), present) {
+            (false, true) => self.len += 1,
+            (true, false) => self.len -= 1,
+            _ => {}
+        }
+        if self.delta.len() > DELTA_ENTRIES {
+            self.entries_mut();
+        }
+        prev
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if let Ok(idx) = self.delta_position(key) {
+            return self.delta[idx].1.as_mut();
+        }
+        let entries: &mut Entries<K, V> = self.entries_mut();
+        match entries.binary_search_by(|(k, _)| k.borrow().cmp(key)) {
+            Ok(idx) => Some(&mut entries[idx].1),
+            Err(_) => None,
+        }
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+        if !self.is_shared() && self.delta.is_empty() {
+            let base: &mut Entries<K, V> = Arc::make_mut(&mut self.base);
+            return match base.binary_search_by(|(k, _)| k.cmp(&key)) {
+                Ok(idx) => Some(std::mem::replace(&mut base[idx].1, val)),
+                Err(idx) => {
+                    base.insert(idx, (key, val));
+                    self.len += 1;
+                    None
+                }
+            };
+        }
+        self.record(key, Some(val))
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.is_shared() && self.delta.is_empty() {
+            let base: &mut Entries<K, V> = Arc::make_mut(&mut self.base);
+            return match base.binary_search_by(|(k, _)| k.borrow().cmp(key)) {
+                Ok(idx) => {
+                    self.len -= 1;
+                    Some(base.remove(idx).1)
+                }
+                Err(_) => None,
+            };
+        }
+        let owned: K = self
+            .delta
+            .iter()
+            .map(|(k, _)| k)
+            .chain(self.base.iter().map(|(k, _)| k))
+            .find(|k| (*k).borrow() == key)?
+            .clone();
+        self.record(owned, None)
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        let entries: &mut Entries<K, V> = self.entries_mut();
+        entries.retain(|(k, v)| f(k, v));
+        self.len = entries.len()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.entries_mut().iter_mut().map(|(k, v)| (&*k, v))
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    base: Peekable<slice::Iter<'a, (K, V)>>,
+    delta: Peekable<slice::Iter<'a, (K, Option<V>)>>,
+}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order: Option<Ordering> = match (self.base.peek(), self.delta.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Some(Ordering::Less),
+                (None, Some(_)) => None,
+                (Some((bk, _)), Some((dk, _))) => Some(bk.cmp(dk)),
+            };
+            if order == Some(Ordering::Less) {
+                return self.base.next().map(|(k, v)| (k, v));
+            }
+            if order == Some(Ordering::Equal) {
+                self.base.next();
+            }
+            if let Some((k, Some(v))) = self.delta.next() {
+                return Some((k, v));
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Extend<(K, V)> for SmallMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> FromIterator<(K, V)> for SmallMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map: SmallMap<K, V> = SmallMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, const N: usize> From<[(K, V); N]> for SmallMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> IntoIterator for SmallMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = smallvec::IntoIter<[(K, V); INLINE_ENTRIES]>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.entries_mut();
+        Arc::unwrap_or_clone(self.base).into_iter()
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a SmallMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared(
+        entries: &[(&'static str, i32)],
+    ) -> (SmallMap<&'static str, i32>, SmallMap<&'static str, i32>) {
+        let base: SmallMap<&'static str, i32> = entries.iter().copied().collect();
+        let overlay: SmallMap<&'static str, i32> = base.clone();
+        assert!(overlay.is_shared());
+        (base, overlay)
+    }
+
+    fn entries(map: &SmallMap<&'static str, i32>) -> Vec<(&'static str, i32)> {
+        map.iter().map(|(k, v)| (*k, *v)).collect()
+    }
+
+    #[test]
+    fn delta_overlay_shadows_the_base() {
+        let (base, mut overlay) = shared(&[("a", 1), ("b", 2)]);
+        assert_eq!(overlay.insert("b", 20), Some(2));
+        assert!(overlay.is_shared());
+        assert_eq!(overlay.get("b"), Some(&20));
+        assert_eq!(overlay.get("a"), Some(&1));
+        assert_eq!(overlay.len(), 2);
+        assert_eq!(base.get("b"), Some(&2));
+    }
+
+    #[test]
+    fn removing_a_base_only_key_hides_it() {
+        let (base, mut overlay) = shared(&[("a", 1), ("b", 2), ("c", 3)]);
+        assert_eq!(overlay.remove("b"), Some(2));
+        assert_eq!(overlay.remove("b"), None);
+        assert_eq!(overlay.get("b"), None);
+        assert!(!overlay.contains_key("b"));
+        assert_eq!(overlay.len(), 2);
+        assert_eq!(entries(&overlay), vec![("a", 1), ("c", 3)]);
+        assert_eq!(base.len(), 3);
+        assert_eq!(overlay.insert("b", 5), None);
+        assert_eq!(entries(&overlay), vec![("a", 1), ("b", 5), ("c", 3)]);
+    }
+
+    #[test]
+    fn overlay_iterates_in_key_order_without_duplicates() {
+        let (_base, mut overlay) = shared(&[("a", 1), ("c", 3), ("e", 5)]);
+        overlay.insert("f", 6);
+        overlay.insert("c", 30);
+        overlay.insert("b", 2);
+        overlay.remove("a");
+        let expected: Vec<(&'static str, i32)> = vec![("b", 2), ("c", 30), ("e", 5), ("f", 6)];
+        assert_eq!(entries(&overlay), expected);
+        assert_eq!(overlay.len(), expected.len());
+        // spilling past the overlay capacity folds it into the base unchanged
+        for (i, key) in ["g", "h", "i", "j", "k"].into_iter().enumerate() {
+            overlay.insert(key, i as i32);
+        }
+        assert!(!overlay.is_shared());
+        let keys: Vec<&'static str> = overlay.keys().copied().collect();
+        assert_eq!(keys, vec!["b", "c", "e", "f", "g", "h", "i", "j", "k"]);
+        assert_eq!(overlay.len(), keys.len());
+    }
+
+    #[test]
+    fn clones_do_not_alias_mutations() {
+        let original: SmallMap<&'static str, i32> = SmallMap::from([("a", 1), ("b", 2)]);
+        let mut copy: SmallMap<&'static str, i32> = original.clone();
+        *copy.get_mut("a").unwrap() = 10;
+        copy.insert("c", 3);
+        copy.retain(|k, _| *k != "b");
+        assert_eq!(entries(&original), vec![("a", 1), ("b", 2)]);
+        assert_eq!(entries(&copy), vec![("a", 10), ("c", 3)]);
+
+        let mut original: SmallMap<&'static str, i32> = original;
+        original.remove("a");
+        original.iter_mut().for_each(|(_, v)| *v += 100);
+        assert_eq!(entries(&original), vec![("b", 102)]);
+        assert_eq!(entries(&copy), vec![("a", 10), ("c", 3)]);
+    }
+}
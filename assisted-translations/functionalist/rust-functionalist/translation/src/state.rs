@@ -0,0 +1,470 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::error::{StateError, StreamError};
+use crate::reducers::{Statistic, Summary};
+use crate::utils::{Headers, OpResult, OperatorRef};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv4Addr;
+use std::rc::Rc;
+
+pub trait StateCodec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError>;
+}
+
+fn codec_error(msg: &str) -> StreamError {
+    StreamError::State(StateError::Codec(msg.to_string()))
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> Result<&'a [u8], StreamError> {
+    if input.len() < n {
+        return Err(codec_error("unexpected end of input"));
+    }
+    let (head, rest) = input.split_at(n);
+    *input = rest;
+    Ok(head)
+}
+
+fn take_array<const N: usize>(input: &mut &[u8]) -> Result<[u8; N], StreamError> {
+    let mut buf: [u8; N] = [0; N];
+    buf.copy_from_slice(take(input, N)?);
+    Ok(buf)
+}
+
+fn encode_str(s: &str, out: &mut Vec<u8>) {
+    out.extend((s.len() as u32).to_le_bytes());
+    out.extend(s.as_bytes());
+}
+
+fn decode_str(input: &mut &[u8]) -> Result<String, StreamError> {
+    let len: usize = u32::from_le_bytes(take_array(input)?) as usize;
+    String::from_utf8(take(input, len)?.to_vec()).map_err(|_| codec_error("invalid utf-8"))
+}
+
+fn encode_f64(f: f64, out: &mut Vec<u8>) {
+    out.extend(f.to_le_bytes());
+}
+
+fn decode_f64(input: &mut &[u8]) -> Result<f64, StreamError> {
+    Ok(f64::from_le_bytes(take_array(input)?))
+}
+
+impl StateCodec for Summary {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self.stat {
+            Statistic::Mean => out.push(0),
+            Statistic::Variance => out.push(1),
+            Statistic::StdDev => out.push(2),
+            Statistic::Percentile(p) => {
+                out.push(3);
+                encode_f64(p.0, out);
+            }
+        }
+        out.extend(self.count.to_le_bytes());
+        encode_f64(self.mean.0, out);
+        encode_f64(self.m2.0, out);
+        out.extend((self.centroids.len() as u32).to_le_bytes());
+        for (mean, weight) in self.centroids.iter() {
+            encode_f64(mean.0, out);
+            out.extend(weight.to_le_bytes());
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError> {
+        let stat: Statistic = match take(input, 1)?[0] {
+            0 => Statistic::Mean,
+            1 => Statistic::Variance,
+            2 => Statistic::StdDev,
+            3 => Statistic::Percentile(OrderedFloat(decode_f64(input)?)),
+            _ => return Err(codec_error("unknown statistic")),
+        };
+        let mut summary: Summary = Summary::new(stat);
+        summary.count = i64::from_le_bytes(take_array(input)?);
+        summary.mean = OrderedFloat(decode_f64(input)?);
+        summary.m2 = OrderedFloat(decode_f64(input)?);
+        let n: usize = u32::from_le_bytes(take_array(input)?) as usize;
+        for _ in 0..n {
+            let mean: f64 = decode_f64(input)?;
+            let weight: i64 = i64::from_le_bytes(take_array(input)?);
+            summary.centroids.push((OrderedFloat(mean), weight));
+        }
+        Ok(summary)
+    }
+}
+
+impl StateCodec for OpResult {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            OpResult::Float(f) => {
+                out.push(0);
+                encode_f64(f.0, out);
+            }
+            OpResult::Int(i) => {
+                out.push(1);
+                out.extend(i.to_le_bytes());
+            }
+            OpResult::IPv4(a) => {
+                out.push(2);
+                out.extend(a.octets());
+            }
+            OpResult::MAC(m) => {
+                out.push(3);
+                out.extend(m);
+            }
+            OpResult::Str(s) => {
+                out.push(4);
+                encode_str(s, out);
+            }
+            OpResult::Summary(summary) => {
+                out.push(5);
+                summary.encode(out);
+            }
+            OpResult::Empty => out.push(6),
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError> {
+        Ok(match take(input, 1)?[0] {
+            0 => OpResult::Float(OrderedFloat(decode_f64(input)?)),
+            1 => OpResult::Int(i32::from_le_bytes(take_array(input)?)),
+            2 => OpResult::IPv4(Ipv4Addr::from(take_array::<4>(input)?)),
+            3 => OpResult::MAC(take_array(input)?),
+            4 => OpResult::Str(decode_str(input)?),
+            5 => OpResult::Summary(Box::new(Summary::decode(input)?)),
+            6 => OpResult::Empty,
+            _ => return Err(codec_error("unknown value tag")),
+        })
+    }
+}
+
+impl StateCodec for Headers {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend((self.len() as u32).to_le_bytes());
+        for (key, val) in self.iter() {
+            encode_str(key, out);
+            val.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError> {
+        let n: usize = u32::from_le_bytes(take_array(input)?) as usize;
+        let mut headers: Headers = Headers::new();
+        for _ in 0..n {
+            let key: String = decode_str(input)?;
+            headers.insert(key, OpResult::decode(input)?);
+        }
+        Ok(headers)
+    }
+}
+
+impl StateCodec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError> {
+        Ok(take(input, 1)?[0] != 0)
+    }
+}
+
+impl StateCodec for f64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_f64(*self, out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError> {
+        decode_f64(input)
+    }
+}
+
+impl<A: StateCodec, B: StateCodec> StateCodec for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl<T: StateCodec> StateCodec for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend((self.len() as u32).to_le_bytes());
+        for item in self.iter() {
+            item.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, StreamError> {
+        let n: usize = u32::from_le_bytes(take_array(input)?) as usize;
+        (0..n).map(|_| T::decode(input)).collect()
+    }
+}
+
+pub fn encode_state<T: StateCodec>(val: &T) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::new();
+    val.encode(&mut out);
+    out
+}
+
+pub fn decode_state<T: StateCodec>(bytes: &[u8]) -> Result<T, StreamError> {
+    let mut input: &[u8] = bytes;
+    let val: T = T::decode(&mut input)?;
+    if !input.is_empty() {
+        return Err(codec_error("trailing bytes"));
+    }
+    Ok(val)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OperatorFault {
+    error: Rc<RefCell<Option<StreamError>>>,
+}
+
+impl OperatorFault {
+    pub fn new() -> Self {
+        OperatorFault::default()
+    }
+
+    pub fn record(&self, e: impl Into<StreamError>) {
+        self.error.borrow_mut().get_or_insert(e.into());
+    }
+
+    pub fn failed(&self) -> bool {
+        self.error.borrow().is_some()
+    }
+
+    pub fn take(&self) -> Option<StreamError> {
+        self.error.borrow_mut().take()
+    }
+}
+
+pub fn collect_faults(root: &OperatorRef) -> Vec<OperatorFault> {
+    let mut seen: HashSet<*const ()> = HashSet::new();
+    let mut pending: Vec<OperatorRef> = vec![Rc::clone(root)];
+    let mut faults: Vec<OperatorFault> = Vec::new();
+    while let Some(op) = pending.pop() {
+        if !seen.insert(Rc::as_ptr(&op) as *const ()) {
+            continue;
+        }
+        let node = op.borrow();
+        faults.extend(node.fault.clone());
+        pending.extend(node.downstream.iter().cloned());
+    }
+    faults
+}
+
+pub fn first_fault(faults: &[OperatorFault]) -> Option<StreamError> {
+    faults.iter().find_map(OperatorFault::take)
+}
+
+pub const DRAIN_CHUNK: usize = 4096;
+
+pub trait StateBackend<V> {
+    fn get(&self, key: &Headers) -> Result<Option<V>, StateError>;
+    fn insert(&mut self, key: Headers, val: V) -> Result<(), StateError>;
+    fn remove(&mut self, key: &Headers) -> Result<Option<V>, StateError>;
+    fn len(&self) -> usize;
+    fn keys(&self) -> Result<Vec<Headers>, StateError>;
+    fn drain_chunk(&mut self, limit: usize) -> Result<Vec<(Headers, V)>, StateError>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn drain_each(&mut self, f: &mut dyn FnMut(Headers, V)) -> Result<(), StateError> {
+        loop {
+            let chunk: Vec<(Headers, V)> = self.drain_chunk(DRAIN_CHUNK)?;
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            for (key, val) in chunk {
+                f(key, val);
+            }
+        }
+    }
+}
+
+pub type BackendFactory<V> = Box<dyn Fn(&str) -> Box<dyn StateBackend<V>>>;
+
+pub struct MemoryBackend<V> {
+    table: HashMap<Headers, V>,
+}
+
+impl<V> MemoryBackend<V> {
+    pub fn new() -> Self {
+        MemoryBackend {
+            table: HashMap::new(),
+        }
+    }
+}
+
+impl<V> Default for MemoryBackend<V> {
+    fn default() -> Self {
+        MemoryBackend::new()
+    }
+}
+
+impl<V: Clone> StateBackend<V> for MemoryBackend<V> {
+    fn get(&self, key: &Headers) -> Result<Option<V>, StateError> {
+        Ok(self.table.get(key).cloned())
+    }
+
+    fn insert(&mut self, key: Headers, val: V) -> Result<(), StateError> {
+        self.table.insert(key, val);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &Headers) -> Result<Option<V>, StateError> {
+        Ok(self.table.remove(key))
+    }
+
+    fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    fn keys(&self) -> Result<Vec<Headers>, StateError> {
+        Ok(self.table.keys().cloned().collect())
+    }
+
+    fn drain_chunk(&mut self, limit: usize) -> Result<Vec<(Headers, V)>, StateError> {
+        if limit >= self.table.len() {
+            return Ok(self.table.drain().collect());
+        }
+        let keys: Vec<Headers> = self.table.keys().take(limit).cloned().collect();
+        Ok(keys
+            .into_iter()
+            .filter_map(|key: Headers| self.table.remove_entry(&key))
+            .collect())
+    }
+}
+
+pub fn memory_backend<V: Clone + 'static>() -> Box<dyn StateBackend<V>> {
+    Box::new(MemoryBackend::new())
+}
+
+pub fn spill_backend<V: StateCodec + 'static>(
+    dir: &str,
+) -> Result<Box<dyn StateBackend<V>>, StreamError> {
+    #[cfg(feature = "rocksdb")]
+    {
+        Ok(Box::new(rocks::RocksDbBackend::open(dir)?))
+    }
+    #[cfg(not(feature = "rocksdb"))]
+    {
+        Err(StreamError::config(format!(
+            "spilling state to '{}' needs the rocksdb feature",
+            dir
+        )))
+    }
+}
+
+fn state_error(e: StreamError) -> StateError {
+    match e {
+        StreamError::State(e) => e,
+        e => StateError::Codec(e.to_string()),
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+pub mod rocks {
+    use super::{StateBackend, StateCodec, decode_state, encode_state, state_error};
+    use crate::error::StateError;
+    use crate::utils::Headers;
+    use rocksdb::{DB, IteratorMode, Options, WriteBatch};
+    use std::marker::PhantomData;
+    use std::path::Path;
+
+    fn rocks_error(e: rocksdb::Error) -> StateError {
+        StateError::Backend(e.to_string())
+    }
+
+    fn decode<T: StateCodec>(bytes: &[u8]) -> Result<T, StateError> {
+        decode_state(bytes).map_err(state_error)
+    }
+
+    pub struct RocksDbBackend<V> {
+        db: DB,
+        len: usize,
+        _marker: PhantomData<V>,
+    }
+
+    impl<V> RocksDbBackend<V> {
+        pub fn open(path: impl AsRef<Path>) -> Result<Self, StateError> {
+            let mut opts: Options = Options::default();
+            opts.create_if_missing(true);
+            let db: DB = DB::open(&opts, path).map_err(rocks_error)?;
+            let mut len: usize = 0;
+            for entry in db.iterator(IteratorMode::Start) {
+                entry.map_err(rocks_error)?;
+                len += 1;
+            }
+            Ok(RocksDbBackend {
+                db,
+                len,
+                _marker: PhantomData,
+            })
+        }
+
+        pub fn open_fresh(path: impl AsRef<Path>) -> Result<Self, StateError> {
+            DB::destroy(&Options::default(), path.as_ref()).map_err(rocks_error)?;
+            RocksDbBackend::open(path)
+        }
+    }
+
+    impl<V: StateCodec> StateBackend<V> for RocksDbBackend<V> {
+        fn get(&self, key: &Headers) -> Result<Option<V>, StateError> {
+            match self.db.get_pinned(encode_state(key)).map_err(rocks_error)? {
+                Some(bytes) => decode(&bytes).map(Some),
+                None => Ok(None),
+            }
+        }
+
+        fn insert(&mut self, key: Headers, val: V) -> Result<(), StateError> {
+            let key: Vec<u8> = encode_state(&key);
+            let is_new: bool = self.db.get_pinned(&key).map_err(rocks_error)?.is_none();
+            self.db.put(key, encode_state(&val)).map_err(rocks_error)?;
+            if is_new {
+                self.len += 1;
+            }
+            Ok(())
+        }
+
+        fn remove(&mut self, key: &Headers) -> Result<Option<V>, StateError> {
+            let val: Option<V> = self.get(key)?;
+            if val.is_some() {
+                self.db.delete(encode_state(key)).map_err(rocks_error)?;
+                self.len -= 1;
+            }
+            Ok(val)
+        }
+
+        fn len(&self) -> usize {
+            self.len
+        }
+
+        fn keys(&self) -> Result<Vec<Headers>, StateError> {
+            self.db
+                .iterator(IteratorMode::Start)
+                .map(|entry| decode(&entry.map_err(rocks_error)?.0))
+                .collect()
+        }
+
+        fn drain_chunk(&mut self, limit: usize) -> Result<Vec<(Headers, V)>, StateError> {
+            let mut batch: WriteBatch = WriteBatch::default();
+            let mut chunk: Vec<(Headers, V)> = Vec::new();
+            for entry in self.db.iterator(IteratorMode::Start).take(limit) {
+                let (key, val) = entry.map_err(rocks_error)?;
+                chunk.push((decode(&key)?, decode(&val)?));
+                batch.delete(&key);
+            }
+            self.db.write(batch).map_err(rocks_error)?;
+            self.len = self.len.saturating_sub(chunk.len());
+            Ok(chunk)
+        }
+    }
+}
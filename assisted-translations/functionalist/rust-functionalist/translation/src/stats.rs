@@ -0,0 +1,118 @@
+#![allow(dead_code)]
+
+use crate::error::StreamError;
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::cell::RefCell;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OperatorStats {
+    pub tuples_in: usize,
+    pub tuples_out: usize,
+    pub resets: usize,
+    pub errors: usize,
+    pub drops: usize,
+}
+
+pub type StatsRef = Rc<RefCell<OperatorStats>>;
+
+#[derive(Default)]
+pub struct PipelineStats {
+    operators: Vec<(String, StatsRef, Option<Arc<AtomicUsize>>)>,
+}
+
+impl PipelineStats {
+    pub fn new() -> Self {
+        PipelineStats::default()
+    }
+
+    pub fn register(&mut self, name: String, drops: Option<Arc<AtomicUsize>>) -> StatsRef {
+        let stats: StatsRef = Rc::new(RefCell::new(OperatorStats::default()));
+        self.operators.push((name, Rc::clone(&stats), drops));
+        stats
+    }
+
+    pub fn instrument(
+        &mut self,
+        name: String,
+        stage: impl FnOnce(OperatorRef) -> OperatorRef,
+        next_op: OperatorRef,
+    ) -> OperatorRef {
+        let stats: StatsRef = self.register(name, None);
+        create_instrumented_operator(stats, stage, next_op)
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, OperatorStats)> {
+        self.operators
+            .iter()
+            .map(|(name, stats, drops)| {
+                let mut snapshot: OperatorStats = stats.borrow().clone();
+                if let Some(drops) = drops {
+                    snapshot.drops += drops.load(Ordering::SeqCst);
+                }
+                (name.clone(), snapshot)
+            })
+            .collect()
+    }
+
+    pub fn report(&self, outc: &mut dyn Write) -> Result<(), StreamError> {
+        writeln!(outc, "operator, in, out, resets, errors, drops")?;
+        for (name, stats) in self.snapshot() {
+            writeln!(
+                outc,
+                "{}, {}, {}, {}, {}, {}",
+                name, stats.tuples_in, stats.tuples_out, stats.resets, stats.errors, stats.drops
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn create_counting_operator(stats: StatsRef, next_op: OperatorRef) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        stats.borrow_mut().tuples_out += 1;
+        (next_op.borrow_mut().next)(headers)
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+        Box::new(move |headers: &mut Headers| (next_op_ref_clone.borrow_mut().reset)(headers));
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset).with_downstream(downstream),
+    ))
+}
+
+pub fn create_instrumented_operator(
+    stats: StatsRef,
+    stage: impl FnOnce(OperatorRef) -> OperatorRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let inner: OperatorRef = stage(create_counting_operator(Rc::clone(&stats), next_op));
+    let inner_ref_clone = Rc::clone(&inner);
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&inner)];
+    let stats_ref_clone = Rc::clone(&stats);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        stats.borrow_mut().tuples_in += 1;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| (inner.borrow_mut().next)(headers)));
+        if result.is_err() {
+            stats.borrow_mut().errors += 1;
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        stats_ref_clone.borrow_mut().resets += 1;
+        (inner_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset).with_downstream(downstream),
+    ))
+}
@@ -0,0 +1,374 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+
+use crate::builtins::tuple_time;
+use crate::clock::{ClockRef, elapsed_clock};
+use crate::keys::WellKnownKey;
+use crate::packet::IPPROTO_TCP;
+use crate::schema::{FieldType, Schema};
+use crate::utils::{
+    Headers, OpResult, Operator, OperatorRef, TCP_ACK, TCP_FIN, TCP_RST, TCP_SYN, TCP_SYNACK,
+};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+use std::rc::Rc;
+
+pub const TCP_EVENT_KEY: &str = "tcp.event";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TcpStreamOptions {
+    pub idle_timeout: f64,
+    pub max_buffered: usize,
+}
+
+impl Default for TcpStreamOptions {
+    fn default() -> Self {
+        TcpStreamOptions {
+            idle_timeout: 60.0,
+            max_buffered: 64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Teardown {
+    Fin,
+    Rst,
+    Timeout,
+    Eof,
+}
+
+impl Teardown {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Teardown::Fin => "fin",
+            Teardown::Rst => "rst",
+            Teardown::Timeout => "timeout",
+            Teardown::Eof => "eof",
+        }
+    }
+}
+
+pub type Endpoint = (Ipv4Addr, i32);
+
+#[derive(Clone, Debug, Default)]
+pub struct StreamDirection {
+    pub next_seq: Option<u32>,
+    pub bytes: i64,
+    pub pending: BTreeMap<u32, u32>,
+    pub fin: bool,
+}
+
+fn seq_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+impl StreamDirection {
+    pub fn segment(&mut self, seq: u32, len: u32, flags: i32, max_buffered: usize) {
+        let syn: u32 = (flags & TCP_SYN != 0) as u32;
+        let fin: u32 = (flags & TCP_FIN != 0) as u32;
+        self.fin |= fin != 0;
+        let next: u32 = match self.next_seq {
+            Some(next) => next,
+            None => {
+                self.next_seq = Some(seq.wrapping_add(syn));
+                seq.wrapping_add(syn)
+            }
+        };
+        let start: u32 = seq.wrapping_add(syn);
+        if seq_after(start, next) {
+            if self.pending.len() < max_buffered {
+                self.pending.insert(start, len + fin);
+            }
+            return;
+        }
+        self.deliver(start, len, fin);
+        while let Some((&start, &len)) = self.pending.first_key_value() {
+            let next: u32 = self.next_seq.unwrap_or(start);
+            if seq_after(start, next) {
+                break;
+            }
+            self.pending.remove(&start);
+            self.deliver(start, len, 0);
+        }
+    }
+
+    fn deliver(&mut self, start: u32, len: u32, fin: u32) {
+        let next: u32 = self.next_seq.unwrap_or(start);
+        let end: u32 = start.wrapping_add(len);
+        if seq_after(end, next) {
+            self.bytes += end.wrapping_sub(next) as i64;
+            self.next_seq = Some(end.wrapping_add(fin));
+        } else if fin != 0 && end == next {
+            self.next_seq = Some(end.wrapping_add(fin));
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Connection {
+    pub client: Endpoint,
+    pub server: Endpoint,
+    pub start: f64,
+    pub last: f64,
+    pub packets: i32,
+    pub syn: bool,
+    pub synack: bool,
+    pub established: bool,
+    pub outbound: StreamDirection,
+    pub inbound: StreamDirection,
+}
+
+impl Connection {
+    fn new(client: Endpoint, server: Endpoint, time: f64) -> Self {
+        Connection {
+            client,
+            server,
+            start: time,
+            last: time,
+            packets: 0,
+            syn: false,
+            synack: false,
+            established: false,
+            outbound: StreamDirection::default(),
+            inbound: StreamDirection::default(),
+        }
+    }
+
+    fn event_headers(&self, event: &str, time: f64) -> Headers {
+        let mut headers: Headers = Headers::new();
+        headers.insert(
+            WellKnownKey::Time.into(),
+            OpResult::Float(OrderedFloat(time)),
+        );
+        headers.insert(WellKnownKey::Ipv4Src.into(), OpResult::IPv4(self.client.0));
+        headers.insert(WellKnownKey::Ipv4Dst.into(), OpResult::IPv4(self.server.0));
+        headers.insert(WellKnownKey::L4Sport.into(), OpResult::Int(self.client.1));
+        headers.insert(WellKnownKey::L4Dport.into(), OpResult::Int(self.server.1));
+        headers.insert(TCP_EVENT_KEY.to_string(), OpResult::Str(event.to_string()));
+        headers
+    }
+
+    pub fn closed_headers(&self, teardown: Teardown) -> Headers {
+        let mut headers: Headers = self.event_headers("closed", self.last);
+        headers.insert(
+            "tcp.established".to_string(),
+            OpResult::Int(self.established as i32),
+        );
+        headers.insert(
+            "bytes_out".to_string(),
+            OpResult::Int(self.outbound.bytes.min(i32::MAX as i64) as i32),
+        );
+        headers.insert(
+            "bytes_in".to_string(),
+            OpResult::Int(self.inbound.bytes.min(i32::MAX as i64) as i32),
+        );
+        headers.insert(
+            "duration".to_string(),
+            OpResult::Float(OrderedFloat(self.last - self.start)),
+        );
+        headers.insert(
+            "teardown".to_string(),
+            OpResult::Str(teardown.as_str().to_string()),
+        );
+        headers.insert("packets".to_string(), OpResult::Int(self.packets));
+        headers
+    }
+}
+
+pub fn tcp_stream_schema() -> Schema {
+    Schema::new()
+        .with("time", FieldType::Float)
+        .with("ipv4.src", FieldType::IPv4)
+        .with("ipv4.dst", FieldType::IPv4)
+        .with("l4.sport", FieldType::Int)
+        .with("l4.dport", FieldType::Int)
+        .with(TCP_EVENT_KEY, FieldType::Str)
+        .with("tcp.established", FieldType::Int)
+        .with("bytes_out", FieldType::Int)
+        .with("bytes_in", FieldType::Int)
+        .with("duration", FieldType::Float)
+        .with("teardown", FieldType::Str)
+        .with("packets", FieldType::Int)
+}
+
+fn endpoints(headers: &Headers) -> Option<(Endpoint, Endpoint)> {
+    match (
+        headers.get_known(WellKnownKey::Ipv4Src),
+        headers.get_known(WellKnownKey::L4Sport),
+        headers.get_known(WellKnownKey::Ipv4Dst),
+        headers.get_known(WellKnownKey::L4Dport),
+    ) {
+        (
+            Some(OpResult::IPv4(src)),
+            Some(OpResult::Int(sport)),
+            Some(OpResult::IPv4(dst)),
+            Some(OpResult::Int(dport)),
+        ) => Some(((*src, *sport), (*dst, *dport))),
+        _ => None,
+    }
+}
+
+fn int_field(headers: &Headers, key: WellKnownKey) -> Option<i32> {
+    match headers.get_known(key) {
+        Some(OpResult::Int(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+pub struct TcpStreamTable {
+    pub options: TcpStreamOptions,
+    pub connections: HashMap<(Endpoint, Endpoint), Connection>,
+    last_sweep: f64,
+}
+
+impl TcpStreamTable {
+    pub fn new(options: TcpStreamOptions) -> Self {
+        TcpStreamTable {
+            options,
+            connections: HashMap::new(),
+            last_sweep: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn push(&mut self, headers: &Headers, time: f64) -> Vec<Headers> {
+        let mut events: Vec<Headers> = self.sweep(time);
+        if int_field(headers, WellKnownKey::Ipv4Proto) != Some(IPPROTO_TCP) {
+            return events;
+        }
+        let Some((src, dst)) = endpoints(headers) else {
+            return events;
+        };
+        let flags: i32 = int_field(headers, WellKnownKey::L4Flags).unwrap_or(0);
+        let key: (Endpoint, Endpoint) = if src <= dst { (src, dst) } else { (dst, src) };
+        let max_buffered: usize = self.options.max_buffered;
+        let conn: &mut Connection = self.connections.entry(key).or_insert_with(|| {
+            if flags & TCP_SYNACK == TCP_SYNACK {
+                Connection::new(dst, src, time)
+            } else {
+                Connection::new(src, dst, time)
+            }
+        });
+        let outbound: bool = src == conn.client;
+        conn.last = time;
+        conn.packets += 1;
+        let was_established: bool = conn.established;
+        match (outbound, flags & TCP_SYNACK) {
+            (true, TCP_SYN) => conn.syn = true,
+            (false, TCP_SYNACK) => conn.synack = true,
+            (true, TCP_ACK) if conn.synack => conn.established = true,
+            _ => {}
+        }
+        let len: u32 = match int_field(headers, WellKnownKey::TcpLen) {
+            Some(len) => len.max(0) as u32,
+            None => {
+                let ip_len: i32 = int_field(headers, WellKnownKey::Ipv4Len).unwrap_or(0);
+                let hlen: i32 = int_field(headers, WellKnownKey::Ipv4Hlen).unwrap_or(20);
+                (ip_len - hlen - 20).max(0) as u32
+            }
+        };
+        let direction: &mut StreamDirection = if outbound {
+            &mut conn.outbound
+        } else {
+            &mut conn.inbound
+        };
+        match int_field(headers, WellKnownKey::TcpSeq) {
+            Some(seq) => direction.segment(seq as u32, len, flags, max_buffered),
+            None => {
+                direction.bytes += len as i64;
+                direction.fin |= flags & TCP_FIN != 0;
+            }
+        }
+        if conn.established && !was_established {
+            events.push(conn.event_headers("established", time));
+        }
+        let teardown: Option<Teardown> = if flags & TCP_RST != 0 {
+            Some(Teardown::Rst)
+        } else if conn.outbound.fin && conn.inbound.fin {
+            Some(Teardown::Fin)
+        } else {
+            None
+        };
+        if let Some(teardown) = teardown
+            && let Some(conn) = self.connections.remove(&key)
+        {
+            events.push(conn.closed_headers(teardown));
+        }
+        events
+    }
+
+    pub fn sweep(&mut self, now: f64) -> Vec<Headers> {
+        if now - self.last_sweep < self.options.idle_timeout.min(1.0) {
+            return Vec::new();
+        }
+        self.last_sweep = now;
+        let timeout: f64 = self.options.idle_timeout;
+        let expired: Vec<(Endpoint, Endpoint)> = self
+            .connections
+            .iter()
+            .filter(|(_, conn)| now - conn.last >= timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        self.close(expired, Teardown::Timeout)
+    }
+
+    pub fn flush(&mut self) -> Vec<Headers> {
+        let keys: Vec<(Endpoint, Endpoint)> = self.connections.keys().copied().collect();
+        self.close(keys, Teardown::Eof)
+    }
+
+    fn close(&mut self, keys: Vec<(Endpoint, Endpoint)>, teardown: Teardown) -> Vec<Headers> {
+        let mut conns: Vec<Connection> = keys
+            .iter()
+            .filter_map(|key| self.connections.remove(key))
+            .collect();
+        conns.sort_by(|a: &Connection, b: &Connection| {
+            a.start
+                .total_cmp(&b.start)
+                .then_with(|| (a.client, a.server).cmp(&(b.client, b.server)))
+        });
+        conns
+            .iter()
+            .map(|conn: &Connection| conn.closed_headers(teardown))
+            .collect()
+    }
+}
+
+pub fn create_tcp_stream_operator(options: TcpStreamOptions, next_op: OperatorRef) -> OperatorRef {
+    create_tcp_stream_operator_with_clock(options, elapsed_clock(), next_op)
+}
+
+pub fn create_tcp_stream_operator_with_clock(
+    options: TcpStreamOptions,
+    clock: ClockRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let table: Rc<RefCell<TcpStreamTable>> = Rc::new(RefCell::new(TcpStreamTable::new(options)));
+    let reset_table_ref = Rc::clone(&table);
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let time: f64 = tuple_time(headers, clock.as_ref());
+        let events: Vec<Headers> = table.borrow_mut().push(headers, time);
+        for mut event in events {
+            (next_op.borrow_mut().next)(&mut event)
+        }
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        let events: Vec<Headers> = reset_table_ref.borrow_mut().flush();
+        for mut event in events {
+            (next_op_ref_clone.borrow_mut().next)(&mut event)
+        }
+        (next_op_ref_clone.borrow_mut().reset)(headers)
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label("tcp_streams")
+            .with_downstream(downstream),
+    ))
+}
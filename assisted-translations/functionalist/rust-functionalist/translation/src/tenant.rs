@@ -0,0 +1,141 @@
+#![allow(dead_code)]
+
+use crate::budget::{BudgetAction, MemoryBudget};
+use crate::builtins::tuple_time;
+use crate::state::OperatorFault;
+use crate::stats::StatsRef;
+use crate::trace_event;
+use crate::utils::{Headers, Operator, OperatorRef};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::time::Instant;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TenantQuota {
+    pub max_tuples_per_sec: Option<f64>,
+    pub max_state_bytes: Option<usize>,
+    pub state_action: BudgetAction,
+}
+
+pub struct Tenant {
+    pub name: String,
+    pub quota: TenantQuota,
+    pub budget: Option<MemoryBudget>,
+    failed: Rc<Cell<bool>>,
+}
+
+impl Tenant {
+    pub fn new(name: &str, quota: TenantQuota) -> Self {
+        let budget: Option<MemoryBudget> = quota
+            .max_state_bytes
+            .map(|limit: usize| MemoryBudget::new(limit, quota.state_action));
+        Tenant {
+            name: name.to_string(),
+            quota,
+            budget,
+            failed: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed.get()
+    }
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<String>() {
+        Some(msg) => msg.clone(),
+        None => payload
+            .downcast_ref::<&str>()
+            .map(|msg: &&str| msg.to_string())
+            .unwrap_or_else(|| "unknown panic".to_string()),
+    }
+}
+
+struct RateWindow {
+    start: f64,
+    admitted: f64,
+}
+
+pub fn create_tenant_operator(
+    tenant: &Tenant,
+    stats: StatsRef,
+    next_op: OperatorRef,
+) -> OperatorRef {
+    let downstream: Vec<OperatorRef> = vec![Rc::clone(&next_op)];
+    let label: String = format!("tenant({})", tenant.name);
+    let max_rate: Option<f64> = tenant.quota.max_tuples_per_sec;
+    let start: Instant = Instant::now();
+    let window: RefCell<RateWindow> = RefCell::new(RateWindow {
+        start: f64::NEG_INFINITY,
+        admitted: 0.0,
+    });
+    let name: Rc<String> = Rc::new(tenant.name.clone());
+    let (reset_name, reset_stats) = (Rc::clone(&name), Rc::clone(&stats));
+    let failed: Rc<Cell<bool>> = Rc::clone(&tenant.failed);
+    let reset_failed: Rc<Cell<bool>> = Rc::clone(&failed);
+    let fault: Option<OperatorFault> = tenant
+        .budget
+        .as_ref()
+        .map(|budget: &MemoryBudget| budget.fault().clone());
+    let next_op_ref_clone = Rc::clone(&next_op);
+
+    let isolate = move |_name: &str, stats: &StatsRef, failed: &Cell<bool>, f: &mut dyn FnMut()| {
+        let failure: Option<String> = match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Err(payload) => Some(panic_message(payload.as_ref())),
+            Ok(()) => fault
+                .as_ref()
+                .and_then(OperatorFault::take)
+                .map(|e| e.to_string()),
+        };
+        if let Some(_failure) = failure {
+            stats.borrow_mut().errors += 1;
+            failed.set(true);
+            trace_event!(tenant = _name, failure = %_failure, "tenant disabled after a failure");
+        }
+    };
+    let reset_isolate = isolate.clone();
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        stats.borrow_mut().tuples_in += 1;
+        if failed.get() {
+            stats.borrow_mut().drops += 1;
+            return;
+        }
+        if let Some(max_rate) = max_rate {
+            let time: f64 = tuple_time(headers, &start);
+            let mut window = window.borrow_mut();
+            if time - window.start >= 1.0 {
+                window.start = time;
+                window.admitted = 0.0;
+            }
+            if window.admitted >= max_rate {
+                stats.borrow_mut().drops += 1;
+                return;
+            }
+            window.admitted += 1.0;
+        }
+        stats.borrow_mut().tuples_out += 1;
+        isolate(&name, &stats, &failed, &mut || {
+            (next_op.borrow_mut().next)(headers)
+        });
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        reset_stats.borrow_mut().resets += 1;
+        if reset_failed.get() {
+            return;
+        }
+        reset_isolate(&reset_name, &reset_stats, &reset_failed, &mut || {
+            (next_op_ref_clone.borrow_mut().reset)(headers)
+        });
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset)
+            .with_label(label)
+            .with_downstream(downstream),
+    ))
+}
@@ -0,0 +1,152 @@
+#![allow(dead_code)]
+
+use crate::utils::{
+    Headers, OpResult, Operator, OperatorRef, finish_operators, headers_of_list, string_of_headers,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone, Default)]
+pub struct TestSink {
+    emitted: Rc<RefCell<Vec<Headers>>>,
+    resets: Rc<RefCell<Vec<Headers>>>,
+}
+
+impl TestSink {
+    pub fn new() -> Self {
+        TestSink::default()
+    }
+
+    pub fn operator(&self) -> OperatorRef {
+        create_test_sink_operator(self)
+    }
+
+    pub fn emitted(&self) -> Vec<Headers> {
+        self.emitted.borrow().clone()
+    }
+
+    pub fn epochs(&self) -> usize {
+        self.resets.borrow().len()
+    }
+
+    pub fn resets(&self) -> Vec<Headers> {
+        self.resets.borrow().clone()
+    }
+
+    pub fn clear(&self) {
+        self.emitted.borrow_mut().clear();
+        self.resets.borrow_mut().clear();
+    }
+
+    fn describe(&self) -> String {
+        let emitted = self.emitted.borrow();
+        match emitted.len() {
+            0 => "no tuples were emitted".to_string(),
+            n => format!(
+                "{} tuple(s) were emitted:\n{}",
+                n,
+                emitted
+                    .iter()
+                    .map(string_of_headers)
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            ),
+        }
+    }
+
+    pub fn assert_emitted_where(&self, pred: impl Fn(&Headers) -> bool) -> &Self {
+        if !self
+            .emitted
+            .borrow()
+            .iter()
+            .any(|headers: &Headers| pred(headers))
+        {
+            panic!(
+                "no emitted tuple matched the predicate; {}",
+                self.describe()
+            );
+        }
+        self
+    }
+
+    pub fn assert_not_emitted_where(&self, pred: impl Fn(&Headers) -> bool) -> &Self {
+        if let Some(headers) = self
+            .emitted
+            .borrow()
+            .iter()
+            .find(|headers: &&Headers| pred(headers))
+        {
+            panic!(
+                "expected no tuple to match the predicate, but {} did; {}",
+                string_of_headers(headers),
+                self.describe()
+            );
+        }
+        self
+    }
+
+    pub fn assert_emitted_count(&self, n: usize) -> &Self {
+        let count: usize = self.emitted.borrow().len();
+        if count != n {
+            panic!(
+                "expected {} emitted tuple(s), got {}; {}",
+                n,
+                count,
+                self.describe()
+            );
+        }
+        self
+    }
+
+    pub fn assert_epoch_count(&self, n: usize) -> &Self {
+        if self.epochs() != n {
+            panic!(
+                "expected {} epoch reset(s), got {}; {}",
+                n,
+                self.epochs(),
+                self.describe()
+            );
+        }
+        self
+    }
+}
+
+pub fn create_test_sink_operator(sink: &TestSink) -> OperatorRef {
+    let emitted: Rc<RefCell<Vec<Headers>>> = Rc::clone(&sink.emitted);
+    let resets: Rc<RefCell<Vec<Headers>>> = Rc::clone(&sink.resets);
+
+    let next: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        emitted.borrow_mut().push(headers.clone());
+    });
+
+    let reset: Box<dyn FnMut(&mut Headers) + 'static> = Box::new(move |headers: &mut Headers| {
+        resets.borrow_mut().push(headers.clone());
+    });
+
+    Rc::new(RefCell::new(
+        Operator::new(next, reset).with_label("test_sink"),
+    ))
+}
+
+pub fn tuple(fields: &[(&str, OpResult)]) -> Headers {
+    headers_of_list(
+        &fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<Vec<(String, OpResult)>>(),
+    )
+}
+
+pub fn run_trace(
+    query_ctor: impl FnOnce(OperatorRef) -> OperatorRef,
+    tuples: impl IntoIterator<Item = Headers>,
+) -> TestSink {
+    let sink: TestSink = TestSink::new();
+    let query: OperatorRef = query_ctor(sink.operator());
+    for mut headers in tuples {
+        (query.borrow_mut().next)(&mut headers);
+    }
+    (query.borrow_mut().reset)(&mut Headers::new());
+    finish_operators(&query);
+    sink
+}
@@ -0,0 +1,343 @@
+#![allow(dead_code)]
+
+use md5::{Digest, Md5};
+
+use crate::error::{ParseError, StreamError};
+use crate::keys::WellKnownKey;
+use crate::packet::IPPROTO_TCP;
+use crate::utils::{Headers, OpResult};
+
+pub const TLS_HANDSHAKE: u8 = 0x16;
+pub const TLS_CLIENT_HELLO: u8 = 1;
+pub const TLS_SERVER_HELLO: u8 = 2;
+
+pub const TLS_EXT_SUPPORTED_GROUPS: u16 = 10;
+pub const TLS_EXT_EC_POINT_FORMATS: u16 = 11;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HelloKind {
+    Client,
+    Server,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsHello {
+    pub kind: HelloKind,
+    pub version: u16,
+    pub ciphers: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub groups: Vec<u16>,
+    pub point_formats: Vec<u8>,
+}
+
+fn tls_error(msg: &str) -> StreamError {
+    StreamError::Parse(ParseError::Packet {
+        protocol: "TLS hello",
+        msg: msg.to_string(),
+    })
+}
+
+pub fn is_grease(val: u16) -> bool {
+    val & 0x0f0f == 0x0a0a && val >> 8 == val & 0xff
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], StreamError> {
+        if self.buf.len() < n {
+            return Err(tls_error("truncated"));
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, StreamError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StreamError> {
+        let bytes: &[u8] = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u24(&mut self) -> Result<usize, StreamError> {
+        let bytes: &[u8] = self.take(3)?;
+        Ok(((bytes[0] as usize) << 16) | ((bytes[1] as usize) << 8) | bytes[2] as usize)
+    }
+
+    fn vec8(&mut self) -> Result<Reader<'a>, StreamError> {
+        let len: usize = self.u8()? as usize;
+        Ok(Reader {
+            buf: self.take(len)?,
+        })
+    }
+
+    fn vec16(&mut self) -> Result<Reader<'a>, StreamError> {
+        let len: usize = self.u16()? as usize;
+        Ok(Reader {
+            buf: self.take(len)?,
+        })
+    }
+
+    fn u16s(mut self) -> Result<Vec<u16>, StreamError> {
+        let mut vals: Vec<u16> = Vec::new();
+        while !self.buf.is_empty() {
+            vals.push(self.u16()?);
+        }
+        Ok(vals)
+    }
+}
+
+fn join<T: ToString>(vals: &[T]) -> String {
+    vals.iter()
+        .map(|val: &T| val.to_string())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+fn put_vec16(out: &mut Vec<u8>, body: &[u8]) {
+    out.extend((body.len() as u16).to_be_bytes());
+    out.extend(body);
+}
+
+impl TlsHello {
+    pub fn client(ciphers: Vec<u16>, extensions: Vec<u16>, groups: Vec<u16>) -> Self {
+        TlsHello {
+            kind: HelloKind::Client,
+            version: 0x0303,
+            ciphers,
+            extensions,
+            groups,
+            point_formats: vec![0],
+        }
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<TlsHello, StreamError> {
+        let mut record: Reader = Reader { buf: payload };
+        if record.u8()? != TLS_HANDSHAKE {
+            return Err(tls_error("not a handshake record"));
+        }
+        record.take(2)?;
+        let mut handshake: Reader = record.vec16()?;
+        let kind: HelloKind = match handshake.u8()? {
+            TLS_CLIENT_HELLO => HelloKind::Client,
+            TLS_SERVER_HELLO => HelloKind::Server,
+            _ => return Err(tls_error("not a hello")),
+        };
+        let len: usize = handshake.u24()?;
+        let mut hello: Reader = Reader {
+            buf: handshake.take(len)?,
+        };
+        let version: u16 = hello.u16()?;
+        hello.take(32)?;
+        hello.vec8()?;
+        let ciphers: Vec<u16> = match kind {
+            HelloKind::Client => hello.vec16()?.u16s()?,
+            HelloKind::Server => vec![hello.u16()?],
+        };
+        match kind {
+            HelloKind::Client => hello.vec8().map(|_| ())?,
+            HelloKind::Server => hello.u8().map(|_| ())?,
+        }
+        let mut extensions: Vec<u16> = Vec::new();
+        let mut groups: Vec<u16> = Vec::new();
+        let mut point_formats: Vec<u8> = Vec::new();
+        if !hello.buf.is_empty() {
+            let mut exts: Reader = hello.vec16()?;
+            while !exts.buf.is_empty() {
+                let ext_type: u16 = exts.u16()?;
+                let mut body: Reader = exts.vec16()?;
+                match ext_type {
+                    TLS_EXT_SUPPORTED_GROUPS => groups = body.vec16()?.u16s()?,
+                    TLS_EXT_EC_POINT_FORMATS => point_formats = body.vec8()?.buf.to_vec(),
+                    _ => {}
+                }
+                extensions.push(ext_type);
+            }
+        }
+        Ok(TlsHello {
+            kind,
+            version,
+            ciphers,
+            extensions,
+            groups,
+            point_formats,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut hello: Vec<u8> = Vec::new();
+        hello.extend(self.version.to_be_bytes());
+        hello.extend([0; 32]);
+        hello.push(0);
+        match self.kind {
+            HelloKind::Client => {
+                let ciphers: Vec<u8> = self.ciphers.iter().flat_map(|c| c.to_be_bytes()).collect();
+                put_vec16(&mut hello, &ciphers);
+                hello.extend([1, 0]);
+            }
+            HelloKind::Server => {
+                hello.extend(self.ciphers.first().copied().unwrap_or(0).to_be_bytes());
+                hello.push(0);
+            }
+        }
+        let mut exts: Vec<u8> = Vec::new();
+        for ext_type in self.extensions.iter() {
+            let body: Vec<u8> = match *ext_type {
+                TLS_EXT_SUPPORTED_GROUPS => {
+                    let groups: Vec<u8> =
+                        self.groups.iter().flat_map(|g| g.to_be_bytes()).collect();
+                    let mut body: Vec<u8> = Vec::new();
+                    put_vec16(&mut body, &groups);
+                    body
+                }
+                TLS_EXT_EC_POINT_FORMATS => {
+                    let mut body: Vec<u8> = vec![self.point_formats.len() as u8];
+                    body.extend(self.point_formats.iter());
+                    body
+                }
+                _ => Vec::new(),
+            };
+            exts.extend(ext_type.to_be_bytes());
+            put_vec16(&mut exts, &body);
+        }
+        put_vec16(&mut hello, &exts);
+
+        let mut handshake: Vec<u8> = vec![match self.kind {
+            HelloKind::Client => TLS_CLIENT_HELLO,
+            HelloKind::Server => TLS_SERVER_HELLO,
+        }];
+        handshake.extend(&(hello.len() as u32).to_be_bytes()[1..]);
+        handshake.extend(hello);
+        let mut record: Vec<u8> = vec![TLS_HANDSHAKE, 0x03, 0x01];
+        put_vec16(&mut record, &handshake);
+        record
+    }
+
+    pub fn fingerprint_string(&self) -> String {
+        let ciphers: Vec<u16> = self
+            .ciphers
+            .iter()
+            .copied()
+            .filter(|c| !is_grease(*c))
+            .collect();
+        let extensions: Vec<u16> = self
+            .extensions
+            .iter()
+            .copied()
+            .filter(|e| !is_grease(*e))
+            .collect();
+        match self.kind {
+            HelloKind::Client => {
+                let groups: Vec<u16> = self
+                    .groups
+                    .iter()
+                    .copied()
+                    .filter(|g| !is_grease(*g))
+                    .collect();
+                format!(
+                    "{},{},{},{},{}",
+                    self.version,
+                    join(&ciphers),
+                    join(&extensions),
+                    join(&groups),
+                    join(&self.point_formats)
+                )
+            }
+            HelloKind::Server => {
+                format!("{},{},{}", self.version, join(&ciphers), join(&extensions))
+            }
+        }
+    }
+
+    pub fn fingerprint(&self) -> String {
+        Md5::digest(self.fingerprint_string().as_bytes())
+            .iter()
+            .map(|b: &u8| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn insert_into(&self, headers: &mut Headers) {
+        let key: WellKnownKey = match self.kind {
+            HelloKind::Client => WellKnownKey::TlsJa3,
+            HelloKind::Server => WellKnownKey::TlsJa3s,
+        };
+        headers.insert(key.into(), OpResult::Str(self.fingerprint()));
+    }
+}
+
+pub fn is_tls_handshake(proto: i32, payload: &[u8]) -> bool {
+    proto == IPPROTO_TCP && payload.first() == Some(&TLS_HANDSHAKE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ClientHello with a GREASE cipher and extension, SNI, supported groups
+    // x25519/secp256r1 and the uncompressed point format
+    const CLIENT_HELLO: [u8; 80] = [
+        0x16, 0x03, 0x01, 0x00, 0x4b, // record header
+        0x01, 0x00, 0x00, 0x47, // handshake header
+        0x03, 0x03, // version
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, // random
+        0x00, // session id
+        0x00, 0x06, 0x0a, 0x0a, 0x13, 0x01, 0xc0, 0x2f, // cipher suites
+        0x01, 0x00, // compression methods
+        0x00, 0x18, // extensions
+        0x00, 0x00, 0x00, 0x00, // server_name
+        0x00, 0x0a, 0x00, 0x06, 0x00, 0x04, 0x00, 0x1d, 0x00, 0x17, // supported_groups
+        0x00, 0x0b, 0x00, 0x02, 0x01, 0x00, // ec_point_formats
+        0x1a, 0x1a, 0x00, 0x00, // GREASE
+    ];
+
+    #[test]
+    fn decodes_a_client_hello_fixture_into_its_ja3() {
+        let hello: TlsHello = TlsHello::decode(&CLIENT_HELLO).unwrap();
+        assert_eq!(hello.kind, HelloKind::Client);
+        assert_eq!(hello.ciphers, vec![0x0a0a, 0x1301, 0xc02f]);
+        assert_eq!(hello.extensions, vec![0, 10, 11, 0x1a1a]);
+        assert_eq!(hello.fingerprint_string(), "771,4865-49199,0-10-11,29-23,0");
+        assert_eq!(hello.fingerprint(), "bca193bf3b6d2156cfbe0e6b4b306d3e");
+        assert_eq!(TlsHello::decode(&hello.encode()).unwrap(), hello);
+    }
+
+    #[test]
+    fn truncated_hellos_are_errors() {
+        for len in 0..CLIENT_HELLO.len() {
+            assert!(TlsHello::decode(&CLIENT_HELLO[..len]).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn malformed_hellos_are_errors() {
+        let mut not_handshake: [u8; 80] = CLIENT_HELLO;
+        not_handshake[0] = 0x17;
+        let mut not_hello: [u8; 80] = CLIENT_HELLO;
+        not_hello[5] = 0x0b;
+        let mut oversized_hello: [u8; 80] = CLIENT_HELLO;
+        oversized_hello[8] = 0xff;
+        let mut odd_ciphers: [u8; 80] = CLIENT_HELLO;
+        odd_ciphers[45] = 0x05;
+        let mut overrunning_extension: [u8; 80] = CLIENT_HELLO;
+        overrunning_extension[59] = 0x20;
+        for payload in [
+            not_handshake,
+            not_hello,
+            oversized_hello,
+            odd_ciphers,
+            overrunning_extension,
+        ] {
+            assert!(matches!(
+                TlsHello::decode(&payload),
+                Err(StreamError::Parse(ParseError::Packet { .. }))
+            ));
+        }
+    }
+}
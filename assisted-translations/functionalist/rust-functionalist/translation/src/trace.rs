@@ -0,0 +1,48 @@
+#![allow(dead_code)]
+
+#[macro_export]
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+#[cfg(feature = "tracing")]
+pub struct EpochSpan(tracing::Span);
+
+#[cfg(feature = "tracing")]
+impl EpochSpan {
+    pub fn new(key: &str, eid: i32) -> Self {
+        EpochSpan(tracing::debug_span!("epoch", key, eid))
+    }
+
+    pub fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.0.in_scope(f)
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub struct EpochSpan;
+
+#[cfg(not(feature = "tracing"))]
+impl EpochSpan {
+    pub fn new(_key: &str, _eid: i32) -> Self {
+        EpochSpan
+    }
+
+    pub fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub fn init_tracing() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+#[cfg(not(feature = "tracing"))]
+pub fn init_tracing() {}
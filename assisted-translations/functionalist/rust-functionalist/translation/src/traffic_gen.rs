@@ -0,0 +1,632 @@
+#![allow(dead_code)]
+
+use ordered_float::OrderedFloat;
+use serde::Deserialize;
+
+use crate::dns::{DNS_PORT, DNS_QTYPE_A, DNS_QTYPE_TXT, DnsMessage};
+use crate::http::HttpRequest;
+use crate::keys::WellKnownKey;
+use crate::packet::{
+    ARP_REPLY, ArpHeader, ETHERTYPE_ARP, EthHeader, ICMP_ECHO_REQUEST, IPPROTO_ICMP, IPPROTO_TCP,
+    IPPROTO_UDP, IcmpHeader, Ipv4Header, L4Header, PacketRecord,
+};
+use crate::tls::{HelloKind, TlsHello};
+use crate::utils::{Headers, OpResult, TCP_ACK, TCP_FIN, TCP_PSH, TCP_SYN, TCP_SYNACK};
+use std::net::Ipv4Addr;
+
+pub struct TrafficRng {
+    state: u64,
+}
+
+impl TrafficRng {
+    pub fn new(seed: u64) -> Self {
+        TrafficRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        lo + (self.next_u64() % (hi - lo).max(1) as u64) as i32
+    }
+
+    pub fn ipv4_in(&mut self, network: Ipv4Addr, prefix_len: u8) -> Ipv4Addr {
+        let host_bits: u32 = 32 - prefix_len.min(32) as u32;
+        let mask: u32 = if host_bits == 0 {
+            0
+        } else {
+            u32::MAX >> (32 - host_bits)
+        };
+        Ipv4Addr::from((u32::from(network) & !mask) | (self.next_u64() as u32 & mask))
+    }
+}
+
+pub fn browser_client_hello() -> TlsHello {
+    TlsHello::client(
+        vec![
+            0x0a0a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030,
+        ],
+        vec![0x0a0a, 0, 23, 65281, 10, 11, 35, 16, 5, 13, 18, 51, 45, 43],
+        vec![0x0a0a, 29, 23, 24],
+    )
+}
+
+pub fn c2_client_hello() -> TlsHello {
+    TlsHello::client(
+        vec![0xc014, 0xc013, 0x0035, 0x002f],
+        vec![10, 11],
+        vec![23, 24],
+    )
+}
+
+pub fn packet_record(
+    time: f64,
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    proto: i32,
+    l4: L4Header,
+    len: i32,
+) -> PacketRecord {
+    PacketRecord {
+        time,
+        ipv4: Ipv4Header {
+            proto,
+            len,
+            src,
+            dst,
+            ..Ipv4Header::default()
+        },
+        l4,
+        ..PacketRecord::default()
+    }
+}
+
+pub fn synthetic_headers(i: i32) -> Headers {
+    Headers::from(packet_record(
+        i as f64,
+        Ipv4Addr::new(127, 0, 0, 1),
+        Ipv4Addr::new(127, 0, 0, 1),
+        6,
+        L4Header {
+            sport: 440,
+            dport: 50000,
+            flags: 10,
+        },
+        60,
+    ))
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "scenario", rename_all = "snake_case")]
+pub enum Scenario {
+    Background {
+        packets: usize,
+    },
+    SynFlood {
+        n_hosts: usize,
+        rate: usize,
+    },
+    PortScan {
+        src: Ipv4Addr,
+        ports: Vec<i32>,
+    },
+    StealthScan {
+        src: Ipv4Addr,
+        ports: Vec<i32>,
+        flags: i32,
+    },
+    Slowloris {
+        conns: usize,
+    },
+    SshBruteForce {
+        n_srcs: usize,
+    },
+    SuperSpreader {
+        n_dsts: usize,
+    },
+    Ddos {
+        n_srcs: usize,
+    },
+    PingSweep {
+        n_dsts: usize,
+    },
+    ArpSpoof {
+        target: Ipv4Addr,
+        n_macs: usize,
+    },
+    DnsLookups {
+        n_queries: usize,
+    },
+    DnsTunnel {
+        n_queries: usize,
+    },
+    HttpBrowsing {
+        n_requests: usize,
+    },
+    HttpFlood {
+        n_srcs: usize,
+        rate: usize,
+    },
+    TlsHandshakes {
+        n_conns: usize,
+    },
+    TlsC2 {
+        n_conns: usize,
+    },
+    Exfiltration {
+        bytes: usize,
+    },
+    Beacon {
+        interval: f64,
+        jitter: f64,
+    },
+    Amplification {
+        port: i32,
+        n_reflectors: usize,
+        rate: usize,
+    },
+}
+
+pub struct TrafficGen {
+    pub rng: TrafficRng,
+    pub start: f64,
+    pub duration: f64,
+    pub victim: Ipv4Addr,
+}
+
+impl TrafficGen {
+    pub fn new(seed: u64, duration: f64) -> Self {
+        TrafficGen {
+            rng: TrafficRng::new(seed),
+            start: 0.0,
+            duration,
+            victim: Ipv4Addr::new(10, 0, 0, 1),
+        }
+    }
+
+    fn time_at(&mut self, i: usize, n: usize) -> f64 {
+        self.start + self.duration * (i as f64 + self.rng.next_f64()) / n.max(1) as f64
+    }
+
+    fn external_host(&mut self) -> Ipv4Addr {
+        self.rng.ipv4_in(Ipv4Addr::new(172, 16, 0, 0), 12)
+    }
+
+    fn tcp(&mut self, time: f64, src: Ipv4Addr, dst: Ipv4Addr, dport: i32, flags: i32) -> Headers {
+        let sport: i32 = self.rng.range(1024, 65535);
+        Headers::from(packet_record(
+            time,
+            src,
+            dst,
+            6,
+            L4Header {
+                sport,
+                dport,
+                flags,
+            },
+            60,
+        ))
+    }
+
+    pub fn background(&mut self, packets: usize) -> Vec<Headers> {
+        (0..packets)
+            .map(|i| {
+                let time: f64 = self.time_at(i, packets);
+                let src: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+                let dst: Ipv4Addr = self.external_host();
+                let flags: i32 =
+                    [TCP_SYN, TCP_ACK, TCP_FIN | TCP_ACK][self.rng.range(0, 3) as usize];
+                let dport: i32 = [80, 443, 53][self.rng.range(0, 3) as usize];
+                let len: i32 = self.rng.range(60, 1500);
+                let mut headers: Headers = self.tcp(time, src, dst, dport, flags);
+                headers.insert(WellKnownKey::Ipv4Len.into(), OpResult::Int(len));
+                headers
+            })
+            .collect()
+    }
+
+    pub fn syn_flood(&mut self, n_hosts: usize, rate: usize) -> Vec<Headers> {
+        let hosts: Vec<Ipv4Addr> = (0..n_hosts.max(1)).map(|_| self.external_host()).collect();
+        let total: usize = (rate as f64 * self.duration) as usize;
+        let victim: Ipv4Addr = self.victim;
+        let mut packets: Vec<Headers> = Vec::new();
+        for i in 0..total {
+            let time: f64 = self.time_at(i, total);
+            let src: Ipv4Addr = hosts[i % hosts.len()];
+            packets.push(self.tcp(time, src, victim, 80, TCP_SYN));
+            if i % 4 == 0 {
+                packets.push(self.tcp(time, victim, src, 80, TCP_SYNACK));
+            }
+        }
+        packets
+    }
+
+    pub fn port_scan(&mut self, src: Ipv4Addr, ports: Vec<i32>) -> Vec<Headers> {
+        let victim: Ipv4Addr = self.victim;
+        let n: usize = ports.len();
+        ports
+            .into_iter()
+            .enumerate()
+            .map(|(i, port)| {
+                let time: f64 = self.time_at(i, n);
+                self.tcp(time, src, victim, port, TCP_SYN)
+            })
+            .collect()
+    }
+
+    pub fn stealth_scan(&mut self, src: Ipv4Addr, ports: Vec<i32>, flags: i32) -> Vec<Headers> {
+        let victim: Ipv4Addr = self.victim;
+        let n: usize = ports.len();
+        ports
+            .into_iter()
+            .enumerate()
+            .map(|(i, port)| {
+                let time: f64 = self.time_at(i, n);
+                self.tcp(time, src, victim, port, flags)
+            })
+            .collect()
+    }
+
+    pub fn slowloris(&mut self, conns: usize) -> Vec<Headers> {
+        let src: Ipv4Addr = self.external_host();
+        let victim: Ipv4Addr = self.victim;
+        let per_conn: usize = (self.duration.ceil() as usize).max(1);
+        let total: usize = conns * per_conn;
+        let mut packets: Vec<Headers> = Vec::new();
+        for i in 0..total {
+            let time: f64 = self.time_at(i, total);
+            let len: i32 = self.rng.range(41, 60);
+            let l4: L4Header = L4Header {
+                sport: 20000 + (i % conns.max(1)) as i32,
+                dport: 80,
+                flags: TCP_ACK,
+            };
+            packets.push(Headers::from(packet_record(time, src, victim, 6, l4, len)));
+        }
+        packets
+    }
+
+    pub fn ssh_brute_force(&mut self, n_srcs: usize) -> Vec<Headers> {
+        let victim: Ipv4Addr = self.victim;
+        (0..n_srcs)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n_srcs);
+                let src: Ipv4Addr = self.external_host();
+                self.tcp(time, src, victim, 22, TCP_ACK)
+            })
+            .collect()
+    }
+
+    pub fn super_spreader(&mut self, n_dsts: usize) -> Vec<Headers> {
+        let src: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+        (0..n_dsts)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n_dsts);
+                let dst: Ipv4Addr = self.external_host();
+                self.tcp(time, src, dst, 443, TCP_SYN)
+            })
+            .collect()
+    }
+
+    pub fn ddos(&mut self, n_srcs: usize) -> Vec<Headers> {
+        let victim: Ipv4Addr = self.victim;
+        (0..n_srcs)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n_srcs);
+                let src: Ipv4Addr = self.external_host();
+                let sport: i32 = self.rng.range(1024, 65535);
+                let l4: L4Header = L4Header {
+                    sport,
+                    dport: 53,
+                    flags: 0,
+                };
+                Headers::from(packet_record(time, src, victim, 17, l4, 512))
+            })
+            .collect()
+    }
+
+    fn udp(
+        &mut self,
+        time: f64,
+        (src, sport): (Ipv4Addr, i32),
+        (dst, dport): (Ipv4Addr, i32),
+        payload: Vec<u8>,
+    ) -> Headers {
+        let l4: L4Header = L4Header {
+            sport,
+            dport,
+            flags: 0,
+        };
+        let len: i32 = 28 + payload.len() as i32;
+        Headers::from(PacketRecord {
+            payload,
+            ..packet_record(time, src, dst, IPPROTO_UDP, l4, len)
+        })
+    }
+
+    pub fn dns_lookups(&mut self, n_queries: usize) -> Vec<Headers> {
+        let resolver: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 53);
+        let names: [&str; 4] = [
+            "www.example.com",
+            "mail.example.org",
+            "cdn.example.net",
+            "api.example.com",
+        ];
+        let mut packets: Vec<Headers> = Vec::new();
+        for i in 0..n_queries {
+            let time: f64 = self.time_at(i, n_queries);
+            let client: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+            let sport: i32 = self.rng.range(1024, 65535);
+            let qname: &str = names[self.rng.range(0, names.len() as i32) as usize];
+            let query: DnsMessage = DnsMessage::query(i as u16, qname, DNS_QTYPE_A);
+            let mut answer: Vec<u8> = DnsMessage {
+                response: true,
+                ..query.clone()
+            }
+            .encode();
+            answer.resize(answer.len() + 16 * self.rng.range(1, 4) as usize, 0);
+            packets.push(self.udp(time, (client, sport), (resolver, DNS_PORT), query.encode()));
+            packets.push(self.udp(time, (resolver, DNS_PORT), (client, sport), answer));
+        }
+        packets
+    }
+
+    pub fn dns_tunnel(&mut self, n_queries: usize) -> Vec<Headers> {
+        const BASE32: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+        let resolver: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 53);
+        let client: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+        (0..n_queries)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n_queries);
+                let label: String = (0..48)
+                    .map(|_| BASE32[self.rng.range(0, BASE32.len() as i32) as usize] as char)
+                    .collect();
+                let qname: String = format!("{}.t.exfil.example", label);
+                let query: DnsMessage = DnsMessage::query(i as u16, &qname, DNS_QTYPE_TXT);
+                let sport: i32 = self.rng.range(1024, 65535);
+                self.udp(time, (client, sport), (resolver, DNS_PORT), query.encode())
+            })
+            .collect()
+    }
+
+    fn http(&mut self, time: f64, src: Ipv4Addr, dst: Ipv4Addr, request: HttpRequest) -> Headers {
+        let payload: Vec<u8> = request.encode();
+        let l4: L4Header = L4Header {
+            sport: self.rng.range(1024, 65535),
+            dport: 80,
+            flags: TCP_PSH | TCP_ACK,
+        };
+        let len: i32 = 40 + payload.len() as i32;
+        Headers::from(PacketRecord {
+            payload,
+            ..packet_record(time, src, dst, IPPROTO_TCP, l4, len)
+        })
+    }
+
+    pub fn http_browsing(&mut self, n_requests: usize) -> Vec<Headers> {
+        let hosts: [&str; 3] = ["www.example.com", "news.example.org", "shop.example.net"];
+        let paths: [&str; 4] = ["/", "/index.html", "/static/app.js", "/api/items"];
+        (0..n_requests)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n_requests);
+                let src: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+                let dst: Ipv4Addr = self.external_host();
+                let host: &str = hosts[self.rng.range(0, hosts.len() as i32) as usize];
+                let path: &str = paths[self.rng.range(0, paths.len() as i32) as usize];
+                self.http(time, src, dst, HttpRequest::new("GET", host, path))
+            })
+            .collect()
+    }
+
+    pub fn http_flood(&mut self, n_srcs: usize, rate: usize) -> Vec<Headers> {
+        let srcs: Vec<Ipv4Addr> = (0..n_srcs.max(1)).map(|_| self.external_host()).collect();
+        let total: usize = (rate as f64 * self.duration) as usize;
+        let victim: Ipv4Addr = self.victim;
+        (0..total)
+            .map(|i| {
+                let time: f64 = self.time_at(i, total);
+                let path: String = format!("/search?q={}", self.rng.next_u64() % 100_000);
+                let request: HttpRequest = HttpRequest::new("GET", "victim.example", &path);
+                self.http(time, srcs[i % srcs.len()], victim, request)
+            })
+            .collect()
+    }
+
+    fn tls(&mut self, time: f64, src: Ipv4Addr, dst: Ipv4Addr, hello: &TlsHello) -> Headers {
+        let payload: Vec<u8> = hello.encode();
+        let (sport, dport): (i32, i32) = match hello.kind {
+            HelloKind::Client => (self.rng.range(1024, 65535), 443),
+            HelloKind::Server => (443, self.rng.range(1024, 65535)),
+        };
+        let l4: L4Header = L4Header {
+            sport,
+            dport,
+            flags: TCP_PSH | TCP_ACK,
+        };
+        let len: i32 = 40 + payload.len() as i32;
+        Headers::from(PacketRecord {
+            payload,
+            ..packet_record(time, src, dst, IPPROTO_TCP, l4, len)
+        })
+    }
+
+    pub fn tls_handshakes(&mut self, n_conns: usize) -> Vec<Headers> {
+        let client_hello: TlsHello = browser_client_hello();
+        let server_hello: TlsHello = TlsHello {
+            kind: HelloKind::Server,
+            ciphers: vec![0x1301],
+            extensions: vec![43, 51],
+            groups: Vec::new(),
+            point_formats: Vec::new(),
+            ..client_hello.clone()
+        };
+        let mut packets: Vec<Headers> = Vec::new();
+        for i in 0..n_conns {
+            let time: f64 = self.time_at(i, n_conns);
+            let client: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+            let server: Ipv4Addr = self.external_host();
+            packets.push(self.tls(time, client, server, &client_hello));
+            packets.push(self.tls(time, server, client, &server_hello));
+        }
+        packets
+    }
+
+    pub fn tls_c2(&mut self, n_conns: usize) -> Vec<Headers> {
+        let client_hello: TlsHello = c2_client_hello();
+        let client: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+        let server: Ipv4Addr = self.external_host();
+        (0..n_conns)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n_conns);
+                self.tls(time, client, server, &client_hello)
+            })
+            .collect()
+    }
+
+    pub fn exfiltration(&mut self, bytes: usize) -> Vec<Headers> {
+        let src: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+        let dst: Ipv4Addr = self.external_host();
+        let n: usize = bytes.div_ceil(1500);
+        (0..n)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n);
+                let mut headers: Headers = self.tcp(time, src, dst, 443, TCP_PSH | TCP_ACK);
+                headers.insert(WellKnownKey::Ipv4Len.into(), OpResult::Int(1500));
+                headers
+            })
+            .collect()
+    }
+
+    pub fn beacon(&mut self, interval: f64, jitter: f64) -> Vec<Headers> {
+        let src: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 24);
+        let dst: Ipv4Addr = self.external_host();
+        let sport: i32 = self.rng.range(1024, 65535);
+        let n: usize = (self.duration / interval.max(f64::EPSILON)) as usize;
+        (0..n)
+            .map(|i| {
+                let offset: f64 = jitter * interval * (2.0 * self.rng.next_f64() - 1.0);
+                let time: f64 = (self.start + interval * i as f64 + offset).max(self.start);
+                let l4: L4Header = L4Header {
+                    sport,
+                    dport: 443,
+                    flags: TCP_PSH | TCP_ACK,
+                };
+                Headers::from(packet_record(time, src, dst, IPPROTO_TCP, l4, 120))
+            })
+            .collect()
+    }
+
+    pub fn amplification(&mut self, port: i32, n_reflectors: usize, rate: usize) -> Vec<Headers> {
+        let reflectors: Vec<Ipv4Addr> = (0..n_reflectors.max(1))
+            .map(|_| self.external_host())
+            .collect();
+        let total: usize = (rate as f64 * self.duration) as usize;
+        let victim: Ipv4Addr = self.victim;
+        let sport: i32 = self.rng.range(1024, 65535);
+        let mut packets: Vec<Headers> = Vec::new();
+        for i in 0..total {
+            let time: f64 = self.time_at(i, total);
+            let reflector: Ipv4Addr = reflectors[i % reflectors.len()];
+            packets.push(self.udp(time, (victim, sport), (reflector, port), vec![0; 20]));
+            packets.push(self.udp(time, (reflector, port), (victim, sport), vec![0; 1372]));
+        }
+        packets
+    }
+
+    pub fn ping_sweep(&mut self, n_dsts: usize) -> Vec<Headers> {
+        let src: Ipv4Addr = self.external_host();
+        (0..n_dsts)
+            .map(|i| {
+                let time: f64 = self.time_at(i, n_dsts);
+                let dst: Ipv4Addr = self.rng.ipv4_in(Ipv4Addr::new(10, 0, 0, 0), 16);
+                Headers::from(PacketRecord {
+                    icmp: Some(IcmpHeader {
+                        icmp_type: ICMP_ECHO_REQUEST,
+                        code: 0,
+                    }),
+                    ..packet_record(time, src, dst, IPPROTO_ICMP, L4Header::default(), 84)
+                })
+            })
+            .collect()
+    }
+
+    pub fn arp_spoof(&mut self, target: Ipv4Addr, n_macs: usize) -> Vec<Headers> {
+        let total: usize = n_macs * 4;
+        (0..total)
+            .map(|i| {
+                let time: f64 = self.time_at(i, total);
+                Headers::from(PacketRecord {
+                    time,
+                    eth: EthHeader {
+                        src: [0x02, 0x00, 0x00, 0x00, 0x00, (i % n_macs.max(1)) as u8],
+                        ethertype: ETHERTYPE_ARP,
+                        ..EthHeader::default()
+                    },
+                    arp: Some(ArpHeader {
+                        op: ARP_REPLY,
+                        spa: target,
+                    }),
+                    ..PacketRecord::default()
+                })
+            })
+            .collect()
+    }
+
+    pub fn scenario(&mut self, scenario: &Scenario) -> Vec<Headers> {
+        match scenario.clone() {
+            Scenario::Background { packets } => self.background(packets),
+            Scenario::SynFlood { n_hosts, rate } => self.syn_flood(n_hosts, rate),
+            Scenario::PortScan { src, ports } => self.port_scan(src, ports),
+            Scenario::StealthScan { src, ports, flags } => self.stealth_scan(src, ports, flags),
+            Scenario::Slowloris { conns } => self.slowloris(conns),
+            Scenario::SshBruteForce { n_srcs } => self.ssh_brute_force(n_srcs),
+            Scenario::SuperSpreader { n_dsts } => self.super_spreader(n_dsts),
+            Scenario::Ddos { n_srcs } => self.ddos(n_srcs),
+            Scenario::PingSweep { n_dsts } => self.ping_sweep(n_dsts),
+            Scenario::ArpSpoof { target, n_macs } => self.arp_spoof(target, n_macs),
+            Scenario::DnsLookups { n_queries } => self.dns_lookups(n_queries),
+            Scenario::DnsTunnel { n_queries } => self.dns_tunnel(n_queries),
+            Scenario::HttpBrowsing { n_requests } => self.http_browsing(n_requests),
+            Scenario::HttpFlood { n_srcs, rate } => self.http_flood(n_srcs, rate),
+            Scenario::TlsHandshakes { n_conns } => self.tls_handshakes(n_conns),
+            Scenario::TlsC2 { n_conns } => self.tls_c2(n_conns),
+            Scenario::Exfiltration { bytes } => self.exfiltration(bytes),
+            Scenario::Beacon { interval, jitter } => self.beacon(interval, jitter),
+            Scenario::Amplification {
+                port,
+                n_reflectors,
+                rate,
+            } => self.amplification(port, n_reflectors, rate),
+        }
+    }
+}
+
+pub fn merge_by_time(streams: Vec<Vec<Headers>>) -> Vec<Headers> {
+    let mut merged: Vec<Headers> = streams.into_iter().flatten().collect();
+    merged.sort_by_key(
+        |headers: &Headers| match headers.get_known(WellKnownKey::Time) {
+            Some(OpResult::Float(time)) => *time,
+            _ => OrderedFloat(0.0),
+        },
+    );
+    merged
+}
+
+pub fn generate(seed: u64, duration: f64, scenarios: &[Scenario]) -> Vec<Headers> {
+    let mut generator: TrafficGen = TrafficGen::new(seed, duration);
+    merge_by_time(
+        scenarios
+            .iter()
+            .map(|scenario| generator.scenario(scenario))
+            .collect(),
+    )
+}
@@ -1,11 +1,16 @@
 #![allow(dead_code)]
 
 use ordered_float::OrderedFloat;
-use std::cell::RefCell;
-use std::collections::BTreeMap;
+
+use crate::error::{InputKind, SchemaError, StreamError};
+use crate::reducers::Summary;
+use crate::small_map::SmallMap;
+use crate::state::OperatorFault;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::io::Write;
-use std::io::{Error, ErrorKind};
 use std::net::Ipv4Addr;
 use std::rc::Rc;
 
@@ -15,29 +20,161 @@ pub enum OpResult {
     Int(i32),
     IPv4(Ipv4Addr),
     MAC([u8; 6]),
+    Str(String),
+    Summary(Box<Summary>),
     Empty,
 }
 
+impl From<i32> for OpResult {
+    fn from(i: i32) -> OpResult {
+        OpResult::Int(i)
+    }
+}
+
+impl From<f64> for OpResult {
+    fn from(f: f64) -> OpResult {
+        OpResult::Float(OrderedFloat(f))
+    }
+}
+
+impl From<Ipv4Addr> for OpResult {
+    fn from(addr: Ipv4Addr) -> OpResult {
+        OpResult::IPv4(addr)
+    }
+}
+
+impl From<[u8; 6]> for OpResult {
+    fn from(mac: [u8; 6]) -> OpResult {
+        OpResult::MAC(mac)
+    }
+}
+
+impl From<&str> for OpResult {
+    fn from(s: &str) -> OpResult {
+        OpResult::Str(s.to_string())
+    }
+}
+
+impl From<String> for OpResult {
+    fn from(s: String) -> OpResult {
+        OpResult::Str(s)
+    }
+}
+
+#[macro_export]
+macro_rules! tuple {
+    ($($key:expr => $val:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut headers: $crate::utils::Headers = $crate::utils::Headers::new();
+        $(headers.insert($key.to_string(), $crate::utils::OpResult::from($val));)*
+        headers
+    }};
+}
+
+#[macro_export]
+macro_rules! ip {
+    ($addr:expr) => {
+        $addr.parse::<std::net::Ipv4Addr>().unwrap()
+    };
+}
+
 impl fmt::Display for OpResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", string_of_op_result(self))
     }
 }
 
-pub type Headers = BTreeMap<String, OpResult>;
+pub type Headers = SmallMap<String, OpResult>;
 pub struct Operator {
     pub next: Box<dyn FnMut(&mut Headers) -> () + 'static>,
     pub reset: Box<dyn FnMut(&mut Headers) -> () + 'static>,
+    pub label: String,
+    pub downstream: Vec<OperatorRef>,
+    pub fault: Option<OperatorFault>,
+    pub finish: Option<Box<dyn FnMut() + 'static>>,
+    pub epoch: Option<EpochState>,
 }
 
 pub type OperatorRef = Rc<RefCell<Operator>>;
+pub type EpochState = Rc<Cell<(f64, i32)>>;
 
 impl<'a> Operator {
     pub fn new(
         next: Box<dyn FnMut(&mut Headers) + 'static>,
         reset: Box<dyn FnMut(&mut Headers) + 'static>,
     ) -> Operator {
-        Operator { next, reset }
+        Operator {
+            next,
+            reset,
+            label: String::new(),
+            downstream: Vec::new(),
+            fault: None,
+            finish: None,
+            epoch: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Operator {
+        self.label = label.into();
+        self
+    }
+
+    pub fn with_downstream(mut self, downstream: Vec<OperatorRef>) -> Operator {
+        self.downstream = downstream;
+        self
+    }
+
+    pub fn with_fault(mut self, fault: OperatorFault) -> Operator {
+        self.fault = Some(fault);
+        self
+    }
+
+    pub fn with_finish(mut self, finish: Box<dyn FnMut() + 'static>) -> Operator {
+        self.finish = Some(finish);
+        self
+    }
+
+    pub fn with_epoch(mut self, epoch: EpochState) -> Operator {
+        self.epoch = Some(epoch);
+        self
+    }
+}
+
+pub fn collect_epochs(root: &OperatorRef) -> Vec<EpochState> {
+    let mut seen: HashSet<*const ()> = HashSet::new();
+    let mut pending: Vec<OperatorRef> = vec![Rc::clone(root)];
+    let mut epochs: Vec<EpochState> = Vec::new();
+    while let Some(op) = pending.pop() {
+        if !seen.insert(Rc::as_ptr(&op) as *const ()) {
+            continue;
+        }
+        let node = op.borrow();
+        epochs.extend(node.epoch.clone());
+        pending.extend(node.downstream.iter().cloned());
+    }
+    epochs
+}
+
+pub fn finish_operators(root: &OperatorRef) {
+    let mut seen: HashSet<*const ()> = HashSet::new();
+    let mut order: Vec<OperatorRef> = Vec::new();
+    let mut pending: Vec<(OperatorRef, bool)> = vec![(Rc::clone(root), false)];
+    while let Some((op, expanded)) = pending.pop() {
+        if expanded {
+            order.push(op);
+            continue;
+        }
+        if !seen.insert(Rc::as_ptr(&op) as *const ()) {
+            continue;
+        }
+        let downstream: Vec<OperatorRef> = op.borrow().downstream.clone();
+        pending.push((op, true));
+        pending.extend(downstream.into_iter().map(|op: OperatorRef| (op, false)));
+    }
+    for op in order.iter().rev() {
+        if let Some(finish) = op.borrow_mut().finish.as_mut() {
+            finish();
+        }
     }
 }
 
@@ -48,18 +185,39 @@ pub fn string_of_mac(buf: &[u8; 6]) -> String {
         .join(":")
 }
 
+pub fn mac_of_string(s: &str) -> Option<[u8; 6]> {
+    let mut mac: [u8; 6] = [0; 6];
+    let mut parts = s.split(':');
+    for byte in mac.iter_mut() {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    parts.next().is_none().then_some(mac)
+}
+
+pub const TCP_FIN: i32 = 1 << 0;
+pub const TCP_SYN: i32 = 1 << 1;
+pub const TCP_RST: i32 = 1 << 2;
+pub const TCP_PSH: i32 = 1 << 3;
+pub const TCP_ACK: i32 = 1 << 4;
+pub const TCP_URG: i32 = 1 << 5;
+pub const TCP_ECE: i32 = 1 << 6;
+pub const TCP_CWR: i32 = 1 << 7;
+pub const TCP_SYNACK: i32 = TCP_SYN | TCP_ACK;
+
+pub const TCP_FLAG_NAMES: [(&str, i32); 8] = [
+    ("FIN", TCP_FIN),
+    ("SYN", TCP_SYN),
+    ("RST", TCP_RST),
+    ("PSH", TCP_PSH),
+    ("ACK", TCP_ACK),
+    ("URG", TCP_URG),
+    ("ECE", TCP_ECE),
+    ("CWR", TCP_CWR),
+];
+
 pub fn tcp_flags_to_strings(flags: i32) -> String {
     let mut hmap: BTreeMap<&str, i32> = BTreeMap::new();
-    hmap.extend([
-        ("FIN", 1 << 0),
-        ("SYN", 1 << 1),
-        ("RST", 1 << 2),
-        ("PSH", 1 << 3),
-        ("ACK", 1 << 4),
-        ("URG", 1 << 5),
-        ("ECE", 1 << 6),
-        ("CWR", 1 << 7),
-    ]);
+    hmap.extend(TCP_FLAG_NAMES);
     hmap.iter()
         .filter(|(_, val)| (flags & **val) == **val)
         .fold(String::new(), |mut acc, (key, _)| {
@@ -71,32 +229,63 @@ pub fn tcp_flags_to_strings(flags: i32) -> String {
         })
 }
 
-pub fn int_of_op_result(input: &OpResult) -> Result<i32, Error> {
+pub fn tcp_flags_of_string(names: &str) -> Result<i32, StreamError> {
+    names
+        .split(['|', '+', ','])
+        .map(str::trim)
+        .filter(|name: &&str| !name.is_empty())
+        .try_fold(0, |flags: i32, name: &str| {
+            TCP_FLAG_NAMES
+                .iter()
+                .find(|(flag_name, _)| flag_name.eq_ignore_ascii_case(name))
+                .map(|(_, bit)| flags | bit)
+                .ok_or_else(|| StreamError::unknown(InputKind::Value, "TCP flag", name))
+        })
+}
+
+pub fn int_of_op_result(input: &OpResult) -> Result<i32, StreamError> {
     match *input {
         OpResult::Int(i) => Ok(i),
-        _ => Err(Error::new(
-            ErrorKind::InvalidInput,
-            "Trying to extract int from non-int result",
+        _ => Err(StreamError::unexpected(
+            InputKind::Value,
+            "an int",
+            format!("{:?}", input),
         )),
     }
 }
 
-pub fn float_of_op_result(input: &OpResult) -> Result<OrderedFloat<f64>, Error> {
+pub fn float_of_op_result(input: &OpResult) -> Result<OrderedFloat<f64>, StreamError> {
     match *input {
         OpResult::Float(f) => Ok(f),
-        _ => Err(Error::new(
-            ErrorKind::InvalidInput,
-            "Trying to extract float from non-float result",
+        _ => Err(StreamError::unexpected(
+            InputKind::Value,
+            "a float",
+            format!("{:?}", input),
         )),
     }
 }
 
+pub fn compare_op_results(lhs: &OpResult, rhs: &OpResult) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (OpResult::Int(a), OpResult::Int(b)) => Some(a.cmp(b)),
+        (OpResult::Int(a), OpResult::Float(b)) => OrderedFloat(*a as f64).partial_cmp(b),
+        (OpResult::Float(a), OpResult::Int(b)) => a.partial_cmp(&OrderedFloat(*b as f64)),
+        (OpResult::Float(a), OpResult::Float(b)) => a.partial_cmp(b),
+        (OpResult::IPv4(a), OpResult::IPv4(b)) => Some(a.cmp(b)),
+        (OpResult::MAC(a), OpResult::MAC(b)) => Some(a.cmp(b)),
+        (OpResult::Str(a), OpResult::Str(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
 pub fn string_of_op_result(input: &OpResult) -> String {
     match *input {
         OpResult::Float(f) => f.to_string(),
         OpResult::Int(i) => i.to_string(),
         OpResult::IPv4(a) => a.to_string(),
         OpResult::MAC(m) => string_of_mac(&m),
+        OpResult::Str(ref s) => s.clone(),
+        OpResult::Summary(ref s) => string_of_op_result(&s.value()),
         OpResult::Empty => String::from("Empty"),
     }
 }
@@ -110,35 +299,40 @@ pub fn string_of_headers(input_headers: &Headers) -> String {
         })
 }
 
+pub fn render_template(template: &str, headers: &Headers) -> String {
+    headers
+        .iter()
+        .fold(template.to_string(), |acc, (key, val)| {
+            acc.replace(&format!("{{{}}}", key), &string_of_op_result(val))
+        })
+}
+
 pub fn headers_of_list(header_list: &[(String, OpResult)]) -> Headers {
-    let mut hmap: BTreeMap<String, OpResult> = BTreeMap::new();
+    let mut hmap: Headers = Headers::new();
     for (key, val) in header_list {
         hmap.insert(key.clone(), val.clone());
     }
     hmap
 }
 
-pub fn dump_headers<'a, W: Write>(outc: &'a mut W, headers: &Headers) -> Result<&'a W, Error> {
-    writeln!(outc, "{}", string_of_headers(headers)).unwrap();
+pub fn dump_headers<'a, W: Write>(
+    outc: &'a mut W,
+    headers: &Headers,
+) -> Result<&'a W, StreamError> {
+    writeln!(outc, "{}", string_of_headers(headers))?;
     Ok(outc)
 }
 
-pub fn lookup_int(key: &String, headers: &Headers) -> Result<i32, Error> {
+pub fn lookup_int(key: &String, headers: &Headers) -> Result<i32, StreamError> {
     match headers.get(key) {
         Some(i) => int_of_op_result(i),
-        None => Err(Error::new(
-            ErrorKind::InvalidData,
-            "key given as argument is not a valid key of the given BTreeMap",
-        )),
+        None => Err(SchemaError::Missing(key.clone()).into()),
     }
 }
 
-pub fn lookup_float(key: &String, headers: &Headers) -> Result<OrderedFloat<f64>, Error> {
+pub fn lookup_float(key: &String, headers: &Headers) -> Result<OrderedFloat<f64>, StreamError> {
     match headers.get(key) {
         Some(f) => float_of_op_result(f),
-        None => Err(Error::new(
-            ErrorKind::InvalidData,
-            "key given as argument is not a valid key of the given BTreeMap",
-        )),
+        None => Err(SchemaError::Missing(key.clone()).into()),
     }
 }
@@ -0,0 +1,59 @@
+#![allow(dead_code)]
+
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::demo::{DEMO_QUERIES, DemoPipeline};
+use crate::error::StreamError;
+use crate::json::json_of_headers;
+use crate::utils::Headers;
+
+fn js_error(e: StreamError) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+fn json_of_rows(rows: Vec<Headers>) -> String {
+    Value::Array(rows.iter().map(json_of_headers).collect()).to_string()
+}
+
+#[wasm_bindgen]
+pub fn demo_queries() -> String {
+    Value::Object(
+        DEMO_QUERIES
+            .iter()
+            .map(|(name, query)| (name.to_string(), Value::String(query.to_string())))
+            .collect(),
+    )
+    .to_string()
+}
+
+#[wasm_bindgen]
+pub struct Pipeline {
+    inner: DemoPipeline,
+}
+
+#[wasm_bindgen]
+impl Pipeline {
+    #[wasm_bindgen(constructor)]
+    pub fn new(name_or_query: &str) -> Result<Pipeline, JsError> {
+        Ok(Pipeline {
+            inner: DemoPipeline::new(name_or_query).map_err(js_error)?,
+        })
+    }
+
+    pub fn process_packet(&mut self, json: &str) -> Result<String, JsError> {
+        Ok(json_of_rows(
+            self.inner.process_packet(json).map_err(js_error)?,
+        ))
+    }
+
+    pub fn process_pcap(&mut self, bytes: &[u8]) -> Result<String, JsError> {
+        Ok(json_of_rows(
+            self.inner.process_pcap(bytes).map_err(js_error)?,
+        ))
+    }
+
+    pub fn finish(&mut self) -> String {
+        json_of_rows(self.inner.finish())
+    }
+}
@@ -0,0 +1,661 @@
+#![allow(dead_code)]
+
+use crate::batch::ColumnBatch;
+use crate::bpf::KernelFilter;
+use crate::error::StreamError;
+use crate::live::{CAPTURE_POLL_INTERVAL, CaptureStats, capture_time, live_capture_stopped};
+use crate::packet::{DecodeOptions, PacketRecord};
+use crate::utils::Headers;
+use std::ffi::CString;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::mem::{size_of, zeroed};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr::{self, NonNull};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, sync_channel};
+use std::thread::{self, JoinHandle};
+
+pub const XDP_FRAME_SIZE: usize = 4096;
+pub const XDP_DEFAULT_FRAMES: u32 = 4096;
+pub const XDP_DEFAULT_BATCH: usize = 64;
+pub const XDP_CHANNEL_BATCHES: usize = 256;
+
+const BPF_MAP_CREATE: u32 = 0;
+const BPF_MAP_UPDATE_ELEM: u32 = 2;
+const BPF_PROG_LOAD: u32 = 5;
+const BPF_LINK_CREATE: u32 = 28;
+const BPF_MAP_TYPE_XSKMAP: u32 = 17;
+const BPF_PROG_TYPE_XDP: u32 = 6;
+const BPF_XDP: u32 = 37;
+const XDP_FLAGS_SKB_MODE: u32 = 1 << 1;
+const XDP_FLAGS_DRV_MODE: u32 = 1 << 2;
+const XDP_PASS: i32 = 2;
+const BPF_FUNC_REDIRECT_MAP: i32 = 51;
+const BPF_PSEUDO_MAP_FD: u8 = 1;
+
+fn last_error() -> StreamError {
+    StreamError::Io(Error::last_os_error())
+}
+
+#[derive(Clone, Debug)]
+pub struct XdpOptions {
+    pub interface: String,
+    pub queues: Vec<u32>,
+    pub frames: u32,
+    pub batch_size: usize,
+    pub zero_copy: bool,
+    pub native: bool,
+    pub decode: DecodeOptions,
+    pub filter: KernelFilter,
+}
+
+impl XdpOptions {
+    pub fn new(interface: &str, decode: DecodeOptions) -> Self {
+        XdpOptions {
+            interface: interface.to_string(),
+            queues: rx_queues(interface),
+            frames: XDP_DEFAULT_FRAMES,
+            batch_size: XDP_DEFAULT_BATCH,
+            zero_copy: false,
+            native: false,
+            decode,
+            filter: KernelFilter::Accept,
+        }
+    }
+}
+
+pub fn rx_queues(interface: &str) -> Vec<u32> {
+    let mut queues: Vec<u32> = fs::read_dir(format!("/sys/class/net/{}/queues", interface))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| {
+                    entry
+                        .ok()?
+                        .file_name()
+                        .to_str()?
+                        .strip_prefix("rx-")?
+                        .parse()
+                        .ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if queues.is_empty() {
+        queues.push(0);
+    }
+    queues.sort_unstable();
+    queues
+}
+
+fn ifindex_of(interface: &str) -> Result<u32, StreamError> {
+    let name: CString = CString::new(interface)
+        .map_err(|_| StreamError::Io(Error::new(ErrorKind::InvalidInput, "bad interface name")))?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(last_error()),
+        index => Ok(index),
+    }
+}
+
+fn bpf<T>(cmd: u32, attr: &T) -> Result<i32, StreamError> {
+    let rc: libc::c_long =
+        unsafe { libc::syscall(libc::SYS_bpf, cmd, attr as *const T, size_of::<T>() as u32) };
+    if rc < 0 {
+        Err(last_error())
+    } else {
+        Ok(rc as i32)
+    }
+}
+
+fn bpf_fd<T>(cmd: u32, attr: &T) -> Result<OwnedFd, StreamError> {
+    bpf(cmd, attr).map(|fd: i32| unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+#[repr(C)]
+struct MapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+}
+
+#[repr(C)]
+struct MapUpdateAttr {
+    map_fd: u32,
+    pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+#[repr(C)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+    prog_ifindex: u32,
+    expected_attach_type: u32,
+}
+
+#[repr(C)]
+struct LinkCreateAttr {
+    prog_fd: u32,
+    target_ifindex: u32,
+    attach_type: u32,
+    flags: u32,
+}
+
+fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> u64 {
+    code as u64
+        | ((dst | src << 4) as u64) << 8
+        | (off as u16 as u64) << 16
+        | (imm as u32 as u64) << 32
+}
+
+pub fn redirect_program(map_fd: i32) -> Vec<u64> {
+    vec![
+        insn(0x61, 2, 1, 16, 0),
+        insn(0x18, 1, BPF_PSEUDO_MAP_FD, 0, map_fd),
+        0,
+        insn(0xb7, 3, 0, 0, XDP_PASS),
+        insn(0x85, 0, 0, 0, BPF_FUNC_REDIRECT_MAP),
+        insn(0x95, 0, 0, 0, 0),
+    ]
+}
+
+pub struct XdpProgram {
+    pub ifindex: u32,
+    map: OwnedFd,
+    prog: OwnedFd,
+    link: OwnedFd,
+}
+
+impl XdpProgram {
+    pub fn attach(ifindex: u32, max_queue: u32, native: bool) -> Result<XdpProgram, StreamError> {
+        let map: OwnedFd = bpf_fd(
+            BPF_MAP_CREATE,
+            &MapCreateAttr {
+                map_type: BPF_MAP_TYPE_XSKMAP,
+                key_size: 4,
+                value_size: 4,
+                max_entries: max_queue + 1,
+                map_flags: 0,
+            },
+        )?;
+        let insns: Vec<u64> = redirect_program(map.as_raw_fd());
+        let license: &[u8] = b"GPL\0";
+        let mut name: [u8; 16] = [0; 16];
+        name[..9].copy_from_slice(b"xsk_redir");
+        let prog: OwnedFd = bpf_fd(
+            BPF_PROG_LOAD,
+            &ProgLoadAttr {
+                prog_type: BPF_PROG_TYPE_XDP,
+                insn_cnt: insns.len() as u32,
+                insns: insns.as_ptr() as u64,
+                license: license.as_ptr() as u64,
+                log_level: 0,
+                log_size: 0,
+                log_buf: 0,
+                kern_version: 0,
+                prog_flags: 0,
+                prog_name: name,
+                prog_ifindex: 0,
+                expected_attach_type: BPF_XDP,
+            },
+        )?;
+        let link: OwnedFd = bpf_fd(
+            BPF_LINK_CREATE,
+            &LinkCreateAttr {
+                prog_fd: prog.as_raw_fd() as u32,
+                target_ifindex: ifindex,
+                attach_type: BPF_XDP,
+                flags: match native {
+                    true => XDP_FLAGS_DRV_MODE,
+                    false => XDP_FLAGS_SKB_MODE,
+                },
+            },
+        )?;
+        Ok(XdpProgram {
+            ifindex,
+            map,
+            prog,
+            link,
+        })
+    }
+
+    pub fn register(&self, queue: u32, socket: &XskSocket) -> Result<(), StreamError> {
+        let fd: u32 = socket.fd.as_raw_fd() as u32;
+        bpf(
+            BPF_MAP_UPDATE_ELEM,
+            &MapUpdateAttr {
+                map_fd: self.map.as_raw_fd() as u32,
+                pad: 0,
+                key: &queue as *const u32 as u64,
+                value: &fd as *const u32 as u64,
+                flags: 0,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(len: usize, fd: i32, offset: i64) -> Result<Mmap, StreamError> {
+        let flags: i32 = match fd {
+            -1 => libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            _ => libc::MAP_SHARED | libc::MAP_POPULATE,
+        };
+        let ptr: *mut libc::c_void = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags,
+                fd,
+                offset,
+            )
+        };
+        match NonNull::new(ptr as *mut u8) {
+            Some(ptr) if ptr.as_ptr() as *mut libc::c_void != libc::MAP_FAILED => {
+                Ok(Mmap { ptr, len })
+            }
+            _ => Err(last_error()),
+        }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len) };
+    }
+}
+
+struct Ring<T> {
+    producer: *const AtomicU32,
+    consumer: *const AtomicU32,
+    flags: *const AtomicU32,
+    descs: *mut T,
+    mask: u32,
+    cached: u32,
+    _map: Mmap,
+}
+
+impl<T: Copy> Ring<T> {
+    fn map(
+        fd: &OwnedFd,
+        size: u32,
+        offsets: &libc::xdp_ring_offset,
+        pgoff: i64,
+    ) -> Result<Ring<T>, StreamError> {
+        let len: usize = offsets.desc as usize + size as usize * size_of::<T>();
+        let map: Mmap = Mmap::new(len, fd.as_raw_fd(), pgoff)?;
+        let base: *mut u8 = map.ptr.as_ptr();
+        Ok(unsafe {
+            Ring {
+                producer: base.add(offsets.producer as usize) as *const AtomicU32,
+                consumer: base.add(offsets.consumer as usize) as *const AtomicU32,
+                flags: base.add(offsets.flags as usize) as *const AtomicU32,
+                descs: base.add(offsets.desc as usize) as *mut T,
+                mask: size - 1,
+                cached: 0,
+                _map: map,
+            }
+        })
+    }
+
+    fn available(&mut self) -> u32 {
+        let producer: u32 = unsafe { (*self.producer).load(Ordering::Acquire) };
+        producer.wrapping_sub(self.cached)
+    }
+
+    fn read(&self, i: u32) -> T {
+        unsafe {
+            *self
+                .descs
+                .add((self.cached.wrapping_add(i) & self.mask) as usize)
+        }
+    }
+
+    fn release(&mut self, n: u32) {
+        self.cached = self.cached.wrapping_add(n);
+        unsafe { (*self.consumer).store(self.cached, Ordering::Release) };
+    }
+
+    fn free(&mut self) -> u32 {
+        let consumer: u32 = unsafe { (*self.consumer).load(Ordering::Acquire) };
+        self.mask + 1 - self.cached.wrapping_sub(consumer)
+    }
+
+    fn write(&mut self, i: u32, val: T) {
+        unsafe {
+            *self
+                .descs
+                .add((self.cached.wrapping_add(i) & self.mask) as usize) = val
+        };
+    }
+
+    fn submit(&mut self, n: u32) {
+        self.cached = self.cached.wrapping_add(n);
+        unsafe { (*self.producer).store(self.cached, Ordering::Release) };
+    }
+}
+
+fn setsockopt<T>(fd: &OwnedFd, name: i32, val: &T) -> Result<(), StreamError> {
+    let rc: i32 = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_XDP,
+            name,
+            val as *const T as *const libc::c_void,
+            size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 { Err(last_error()) } else { Ok(()) }
+}
+
+fn getsockopt<T>(fd: &OwnedFd, name: i32) -> Result<T, StreamError> {
+    let mut val: T = unsafe { zeroed() };
+    let mut len: libc::socklen_t = size_of::<T>() as libc::socklen_t;
+    let rc: i32 = unsafe {
+        libc::getsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_XDP,
+            name,
+            &mut val as *mut T as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rc < 0 { Err(last_error()) } else { Ok(val) }
+}
+
+pub struct XskSocket {
+    pub queue: u32,
+    fd: OwnedFd,
+    fill: Ring<u64>,
+    completion: Ring<u64>,
+    rx: Ring<libc::xdp_desc>,
+    umem: Mmap,
+}
+
+unsafe impl Send for XskSocket {}
+
+impl XskSocket {
+    pub fn open(
+        ifindex: u32,
+        queue: u32,
+        frames: u32,
+        zero_copy: bool,
+    ) -> Result<XskSocket, StreamError> {
+        if !frames.is_power_of_two() {
+            return Err(StreamError::config(format!(
+                "xdp frame count {} is not a power of two",
+                frames
+            )));
+        }
+        let raw: i32 = unsafe { libc::socket(libc::AF_XDP, libc::SOCK_RAW, 0) };
+        if raw < 0 {
+            return Err(last_error());
+        }
+        let fd: OwnedFd = unsafe { OwnedFd::from_raw_fd(raw) };
+        let umem: Mmap = Mmap::new(frames as usize * XDP_FRAME_SIZE, -1, 0)?;
+        let reg: libc::xdp_umem_reg = libc::xdp_umem_reg {
+            addr: umem.ptr.as_ptr() as u64,
+            len: umem.len as u64,
+            chunk_size: XDP_FRAME_SIZE as u32,
+            headroom: 0,
+            flags: 0,
+            tx_metadata_len: 0,
+        };
+        setsockopt(&fd, libc::XDP_UMEM_REG, &reg)?;
+        setsockopt(&fd, libc::XDP_UMEM_FILL_RING, &frames)?;
+        setsockopt(&fd, libc::XDP_UMEM_COMPLETION_RING, &frames)?;
+        setsockopt(&fd, libc::XDP_RX_RING, &frames)?;
+        let offsets: libc::xdp_mmap_offsets = getsockopt(&fd, libc::XDP_MMAP_OFFSETS)?;
+        let mut fill: Ring<u64> = Ring::map(
+            &fd,
+            frames,
+            &offsets.fr,
+            libc::XDP_UMEM_PGOFF_FILL_RING as i64,
+        )?;
+        let completion: Ring<u64> = Ring::map(
+            &fd,
+            frames,
+            &offsets.cr,
+            libc::XDP_UMEM_PGOFF_COMPLETION_RING as i64,
+        )?;
+        let rx: Ring<libc::xdp_desc> =
+            Ring::map(&fd, frames, &offsets.rx, libc::XDP_PGOFF_RX_RING)?;
+        for i in 0..frames {
+            fill.write(i, i as u64 * XDP_FRAME_SIZE as u64);
+        }
+        fill.submit(frames);
+
+        let mut addr: libc::sockaddr_xdp = unsafe { zeroed() };
+        addr.sxdp_family = libc::AF_XDP as u16;
+        addr.sxdp_flags = libc::XDP_USE_NEED_WAKEUP
+            | match zero_copy {
+                true => libc::XDP_ZEROCOPY,
+                false => libc::XDP_COPY,
+            };
+        addr.sxdp_ifindex = ifindex;
+        addr.sxdp_queue_id = queue;
+        let rc: i32 = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_xdp as *const libc::sockaddr,
+                size_of::<libc::sockaddr_xdp>() as libc::socklen_t,
+            )
+        };
+        if rc < 0 {
+            return Err(last_error());
+        }
+        Ok(XskSocket {
+            queue,
+            fd,
+            fill,
+            completion,
+            rx,
+            umem,
+        })
+    }
+
+    fn frame(&self, desc: &libc::xdp_desc) -> &[u8] {
+        let len: usize = (desc.len as usize).min(self.umem.len.saturating_sub(desc.addr as usize));
+        unsafe { std::slice::from_raw_parts(self.umem.ptr.as_ptr().add(desc.addr as usize), len) }
+    }
+
+    fn wait(&self) -> bool {
+        let mut pfd: libc::pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut pfd, 1, CAPTURE_POLL_INTERVAL.as_millis() as i32) > 0 }
+    }
+
+    pub fn receive(&mut self, max: usize, mut each: impl FnMut(&[u8])) -> usize {
+        let n: u32 = self.rx.available().min(max as u32);
+        if n == 0 {
+            return 0;
+        }
+        for i in 0..n {
+            let desc: libc::xdp_desc = self.rx.read(i);
+            each(self.frame(&desc));
+            self.fill
+                .write(i, desc.addr - desc.addr % XDP_FRAME_SIZE as u64);
+        }
+        self.rx.release(n);
+        self.fill.submit(n);
+        n as usize
+    }
+
+    pub fn dropped(&self) -> u64 {
+        getsockopt::<libc::xdp_statistics>(&self.fd, libc::XDP_STATISTICS)
+            .map(|stats: libc::xdp_statistics| stats.rx_dropped + stats.rx_ring_full)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct QueueStats {
+    pub received: AtomicU64,
+    pub dropped: AtomicU64,
+    pub decode_errors: AtomicU64,
+    pub filtered: AtomicU64,
+}
+
+fn rx_loop(
+    mut socket: XskSocket,
+    options: XdpOptions,
+    stats: Arc<QueueStats>,
+    stop: Arc<AtomicBool>,
+    batches: SyncSender<Vec<Headers>>,
+) {
+    while !stop.load(Ordering::SeqCst) && !live_capture_stopped() {
+        let time: f64 = capture_time();
+        let mut rows: Vec<Headers> = Vec::with_capacity(options.batch_size);
+        let n: usize = socket.receive(
+            options.batch_size,
+            |frame: &[u8]| match PacketRecord::decode(time, frame, &options.decode) {
+                Ok(record) => rows.push(Headers::from(record)),
+                Err(_) => {
+                    stats.decode_errors.fetch_add(1, Ordering::Relaxed);
+                }
+            },
+        );
+        if n == 0 {
+            if !socket.wait() {
+                stats.dropped.store(socket.dropped(), Ordering::Relaxed);
+            }
+            continue;
+        }
+        stats.received.fetch_add(n as u64, Ordering::Relaxed);
+        let decoded: usize = rows.len();
+        let rows: Vec<Headers> = ColumnBatch::new(rows).into_filtered(&options.filter);
+        stats
+            .filtered
+            .fetch_add((decoded - rows.len()) as u64, Ordering::Relaxed);
+        if !rows.is_empty() && batches.send(rows).is_err() {
+            return;
+        }
+    }
+}
+
+pub struct XdpSource {
+    pub limit: Option<usize>,
+    batches: Receiver<Vec<Headers>>,
+    current: std::vec::IntoIter<Headers>,
+    stats: Vec<Arc<QueueStats>>,
+    stop: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+    program: XdpProgram,
+    delivered: usize,
+}
+
+impl XdpSource {
+    pub fn open(options: &XdpOptions) -> Result<XdpSource, StreamError> {
+        let ifindex: u32 = ifindex_of(&options.interface)?;
+        let max_queue: u32 = options.queues.iter().copied().max().unwrap_or(0);
+        let program: XdpProgram = XdpProgram::attach(ifindex, max_queue, options.native)?;
+        let mut sockets: Vec<XskSocket> = Vec::new();
+        for queue in options.queues.iter() {
+            let socket: XskSocket =
+                XskSocket::open(ifindex, *queue, options.frames, options.zero_copy)?;
+            program.register(*queue, &socket)?;
+            sockets.push(socket);
+        }
+
+        let (tx, batches) = sync_channel::<Vec<Headers>>(XDP_CHANNEL_BATCHES);
+        let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let mut stats: Vec<Arc<QueueStats>> = Vec::new();
+        let mut workers: Vec<JoinHandle<()>> = Vec::new();
+        for socket in sockets {
+            let queue_stats: Arc<QueueStats> = Arc::new(QueueStats::default());
+            let options: XdpOptions = options.clone();
+            let (worker_stats, worker_stop, worker_tx) =
+                (Arc::clone(&queue_stats), Arc::clone(&stop), tx.clone());
+            let worker: JoinHandle<()> = thread::Builder::new()
+                .name(format!("xdp-rx-{}", socket.queue))
+                .spawn(move || rx_loop(socket, options, worker_stats, worker_stop, worker_tx))?;
+            stats.push(queue_stats);
+            workers.push(worker);
+        }
+        Ok(XdpSource {
+            limit: None,
+            batches,
+            current: Vec::new().into_iter(),
+            stats,
+            stop,
+            workers,
+            program,
+            delivered: 0,
+        })
+    }
+
+    pub fn stats(&self) -> CaptureStats {
+        self.stats
+            .iter()
+            .fold(CaptureStats::default(), |acc: CaptureStats, queue| {
+                CaptureStats {
+                    received: acc.received + queue.received.load(Ordering::Relaxed),
+                    dropped: acc.dropped + queue.dropped.load(Ordering::Relaxed),
+                }
+            })
+    }
+
+    pub fn next_batch(&mut self) -> Option<Vec<Headers>> {
+        while !live_capture_stopped() {
+            match self.batches.recv_timeout(CAPTURE_POLL_INTERVAL) {
+                Ok(batch) => return Some(batch),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for XdpSource {
+    type Item = Headers;
+
+    fn next(&mut self) -> Option<Headers> {
+        if self
+            .limit
+            .is_some_and(|limit: usize| self.delivered >= limit)
+        {
+            return None;
+        }
+        loop {
+            if let Some(headers) = self.current.next() {
+                self.delivered += 1;
+                return Some(headers);
+            }
+            self.current = self.next_batch()?.into_iter();
+        }
+    }
+}
+
+impl Drop for XdpSource {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let (_, closed) = sync_channel::<Vec<Headers>>(0);
+        drop(std::mem::replace(&mut self.batches, closed));
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -1,12 +1,12 @@
 #![allow(dead_code)]
 
+use crate::error::{ParseError, SchemaError, StreamError};
 use crate::utils::{
     Headers, OpResult, Operator, OperatorRef, dump_headers, float_of_op_result, int_of_op_result,
     string_of_op_result,
 };
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
-use std::error::Error;
 use std::fs::File;
 use std::io::{Write, stdout};
 use std::net::Ipv4Addr;
@@ -15,77 +15,42 @@ use std::str::FromStr;
 
 pub type OpCreator = Rc<RefCell<Box<dyn FnMut(Rc<RefCell<Operator>>) -> OperatorRef + 'static>>>;
 pub type OpPair = (OperatorRef, OperatorRef);
-pub type DblOpAcceptor = Rc<RefCell<Box<dyn FnMut(OpPair) -> OperatorRef + 'static>>>;
 pub type DblOpCreator = Rc<RefCell<Box<dyn FnMut(Rc<RefCell<Operator>>) -> OpPair + 'static>>>;
 pub type FilterFunc = Box<dyn Fn(&Headers) -> bool>;
 pub type GroupingFunc = Box<dyn Fn(Headers) -> Headers>;
 pub type ReductionFunc = Box<dyn Fn(OpResult, &mut Headers) -> OpResult>;
 pub type KeyExtractor = Box<dyn FnMut(Headers) -> (Headers, Headers)>;
 
-pub enum OpKind {
-    OpCreator(OpCreator),
-    DblOpAcceptor(DblOpAcceptor),
-    DblOpCreator(DblOpCreator),
-    Operator(OperatorRef),
-}
-
 pub struct Query {
-    ops: Vec<OpKind>,
+    ops: Vec<OpCreator>,
     end_op: Option<OperatorRef>,
 }
 
-pub enum QueryKind {
-    Query(Query),
-    Op(OpKind),
-    OpPair(OpPair),
+pub struct JoinQueryBuilder {
+    join_op: DblOpCreator,
+    next_q: Query,
 }
 
 impl Query {
-    pub fn new(middle_op: Option<OpKind>, end_op: Option<OperatorRef>) -> Self {
-        let mut ops: Vec<OpKind> = Vec::new();
-        if let Some(op_kind) = middle_op {
-            ops.push(op_kind);
+    pub fn new(middle_op: Option<OpCreator>, end_op: Option<OperatorRef>) -> Self {
+        let mut ops: Vec<OpCreator> = Vec::new();
+        if let Some(op_func) = middle_op {
+            ops.push(op_func);
         }
 
         Query { ops, end_op }
     }
 
-    pub fn collect(mut self) -> Result<QueryKind, Box<dyn Error>> {
+    pub fn collect(self) -> Result<OperatorRef, StreamError> {
         if self.is_empty() {
-            return Err("This query is empty, cannot collect on an empty query".into());
+            return Err(ParseError::EmptyQuery.into());
         }
 
-        if self.end_op.is_none() || self.ops.len() == 0 {
-            return Ok(QueryKind::Query(self));
-        }
-
-        let mut curr_op: OperatorRef = self.end_op.unwrap();
-        let mut join_res: Option<OpPair> = None;
-        for op in self.ops.iter_mut().rev() {
-            match op {
-                OpKind::OpCreator(op_func) => {
-                    curr_op = op_func.borrow_mut()(curr_op.clone());
-                }
-                OpKind::DblOpCreator(op_func) => {
-                    join_res = Some(op_func.borrow_mut()(curr_op.clone()));
-                }
-                OpKind::DblOpAcceptor(op_func) => {
-                    if join_res.is_some() {
-                        curr_op = op_func.borrow_mut()(join_res.take().unwrap());
-                    } else {
-                        return Err("Must call split method immediately after join if piping 
-                            the result of join into another set of operations"
-                            .into());
-                    }
-                }
-                _ => return Err("There is an operator in the ops list of a collected query".into()),
-            }
-        }
-        if let Some(join_op) = join_res {
-            Ok(QueryKind::OpPair(join_op))
-        } else {
-            Ok(QueryKind::Op(OpKind::Operator(curr_op)))
+        let mut curr_op: OperatorRef = self.end_op.ok_or(ParseError::MissingEndOp)?;
+        for op_func in self.ops.iter().rev() {
+            curr_op = op_func.borrow_mut()(curr_op.clone());
         }
+        Ok(curr_op)
     }
 
     pub fn add_query(mut self, other: Query) -> Self {
@@ -251,7 +216,7 @@ impl Query {
 
                 Rc::new(RefCell::new(Operator::new(next, reset)))
             })));
-        self.ops.push(OpKind::OpCreator(creator_func));
+        self.ops.push(creator_func);
         self
     }
 
@@ -301,7 +266,7 @@ impl Query {
 
                 Rc::new(RefCell::new(Operator::new(next, reset)))
             })));
-        self.ops.push(OpKind::OpCreator(creator_func));
+        self.ops.push(creator_func);
         self
     }
 
@@ -323,7 +288,7 @@ impl Query {
 
                 Rc::new(RefCell::new(Operator::new(next, reset)))
             })));
-        self.ops.push(OpKind::OpCreator(creator_func));
+        self.ops.push(creator_func);
         self
     }
 
@@ -354,7 +319,7 @@ impl Query {
                 Rc::new(RefCell::new(Operator::new(next, reset)))
             })));
 
-        self.ops.push(OpKind::OpCreator(creator_func));
+        self.ops.push(creator_func);
         self
     }
 
@@ -409,7 +374,7 @@ impl Query {
                 Rc::new(RefCell::new(Operator::new(next, reset)))
             })));
 
-        self.ops.push(OpKind::OpCreator(creator_func));
+        self.ops.push(creator_func);
         self
     }
 
@@ -449,39 +414,35 @@ impl Query {
                 Rc::new(RefCell::new(Operator::new(next, reset)))
             })));
 
-        self.ops.push(OpKind::OpCreator(creator_func));
+        self.ops.push(creator_func);
         self
     }
 
-    pub fn split(mut self) -> Self {
-        let creator_func: DblOpAcceptor = Rc::new(RefCell::new(Box::new(move |(l, r)| {
-            let l_ref_clone = Rc::clone(&l);
-            let r_ref_clone = Rc::clone(&r);
-
-            let next: Box<dyn FnMut(&mut Headers) + 'static> =
-                Box::new(move |headers: &mut Headers| {
-                    (Rc::clone(&l).borrow_mut().next)(headers);
-                    (Rc::clone(&r).borrow_mut().next)(headers);
-                });
-
-            let reset: Box<dyn FnMut(&mut Headers) + 'static> =
-                Box::new(move |headers: &mut Headers| {
-                    (l_ref_clone.borrow_mut().reset)(headers);
-                    (r_ref_clone.borrow_mut().reset)(headers);
-                });
-
-            Rc::new(RefCell::new(Operator::new(next, reset)))
-        })));
-        self.ops.push(OpKind::DblOpAcceptor(creator_func));
+    pub fn split(mut self, (l, r): OpPair) -> Self {
+        let l_ref_clone = Rc::clone(&l);
+        let r_ref_clone = Rc::clone(&r);
+
+        let next: Box<dyn FnMut(&mut Headers) + 'static> =
+            Box::new(move |headers: &mut Headers| {
+                (Rc::clone(&l).borrow_mut().next)(headers);
+                (Rc::clone(&r).borrow_mut().next)(headers);
+            });
+
+        let reset: Box<dyn FnMut(&mut Headers) + 'static> =
+            Box::new(move |headers: &mut Headers| {
+                (l_ref_clone.borrow_mut().reset)(headers);
+                (r_ref_clone.borrow_mut().reset)(headers);
+            });
+
+        self.end_op = Some(Rc::new(RefCell::new(Operator::new(next, reset))));
         self
     }
 
     pub fn join(
-        mut self,
         eid_key: Option<String>,
         left_extractor: KeyExtractor,
         right_extractor: KeyExtractor,
-    ) -> Self {
+    ) -> JoinQueryBuilder {
         let left_extractor_ref = Rc::new(RefCell::new(left_extractor));
         let right_extractor_ref = Rc::new(RefCell::new(right_extractor));
         let creator_func: DblOpCreator =
@@ -614,9 +575,53 @@ impl Query {
                     ),
                 )
             })));
-        self.ops.push(OpKind::DblOpCreator(creator_func));
+        JoinQueryBuilder {
+            join_op: creator_func,
+            next_q: Query::new(None, None),
+        }
+    }
+}
+
+impl JoinQueryBuilder {
+    pub fn epoch(mut self, epoch_width: f64, key_out: String) -> Self {
+        self.next_q = self.next_q.epoch(epoch_width, key_out);
         self
     }
+
+    pub fn filter(mut self, f: FilterFunc) -> Self {
+        self.next_q = self.next_q.filter(f);
+        self
+    }
+
+    pub fn map(mut self, f: Box<dyn Fn(Headers) -> Headers + 'static>) -> Self {
+        self.next_q = self.next_q.map(f);
+        self
+    }
+
+    pub fn groupby(
+        mut self,
+        groupby: GroupingFunc,
+        reduce: ReductionFunc,
+        out_key: String,
+    ) -> Self {
+        self.next_q = self.next_q.groupby(groupby, reduce, out_key);
+        self
+    }
+
+    pub fn distinct(mut self, groupby: GroupingFunc) -> Self {
+        self.next_q = self.next_q.distinct(groupby);
+        self
+    }
+
+    pub fn add_query(mut self, other: Query) -> Self {
+        self.next_q = self.next_q.add_query(other);
+        self
+    }
+
+    pub fn collect(self) -> Result<OpPair, StreamError> {
+        let next_op: OperatorRef = self.next_q.collect()?;
+        Ok(self.join_op.borrow_mut()(next_op))
+    }
 }
 pub fn key_geq_int(key: String, threshold: i32, headers: &Headers) -> bool {
     int_of_op_result(headers.get(&key).unwrap_or(&OpResult::Empty)).unwrap() >= threshold
@@ -669,14 +674,12 @@ pub fn sum_ints(
     search_key: String,
     init_val: OpResult,
     headers: &mut Headers,
-) -> Result<OpResult, Box<dyn Error>> {
+) -> Result<OpResult, StreamError> {
     match init_val {
         OpResult::Empty => Ok(OpResult::Int(1)),
         OpResult::Int(i) => match headers.headers.get_mut(&search_key) {
             Some(OpResult::Int(n)) => Ok(OpResult::Int(*n + i)),
-            _ => Err("'sum_vals' function failed to find integer 
-                        value mapped to the incorrect type"
-                .into()),
+            _ => Err(SchemaError::TypeMismatch("int").into()),
         },
         _ => Ok(init_val),
     }
@@ -2,19 +2,23 @@
 
 // use std::{cell::RefCell, io::stdout, rc::Rc};
 
-use std::{error::Error, io::stdout};
+use std::io::stdout;
 
 use builtins::{
-    FilterFunc, GroupingFunc, OpKind, Query, QueryKind, ReductionFunc, counter, filter_groups,
-    key_geq_int, rename_filtered_keys, single_group, sum_ints,
+    FilterFunc, GroupingFunc, JoinQueryBuilder, OpPair, Query, ReductionFunc, counter,
+    filter_groups, key_geq_int, rename_filtered_keys, single_group, sum_ints,
 };
-use ordered_float::OrderedFloat;
+use error::StreamError;
 use utils::{Headers, OpResult, OperatorRef};
 
 mod builtins;
+#[path = "../../../../functionalist/rust-functionalist/translation/src/error.rs"]
+mod error;
+mod schema;
 mod utils;
 
-type QueryCreator = Box<dyn Fn(Query) -> Result<QueryKind, Box<dyn Error>> + 'static>;
+type QueryCreator = Box<dyn Fn(Query) -> Result<OperatorRef, StreamError> + 'static>;
+type JoinQueryCreator = Box<dyn FnOnce(Query) -> JoinQueryBuilder>;
 
 fn ident() -> QueryCreator {
     Box::new(move |next_q: Query| {
@@ -23,7 +27,7 @@ fn ident() -> QueryCreator {
             headers.remove("eth.dst".to_string());
             headers
         });
-        Ok(Query::new(None, None).map(f).add_query(next_q).collect()?)
+        Query::new(None, None).map(f).add_query(next_q).collect()
     })
 }
 
@@ -33,11 +37,11 @@ fn count_pkts() -> QueryCreator {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .groupby(groupby_func, Box::new(counter), "pkts".to_string())
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
@@ -47,11 +51,11 @@ fn pkts_per_source_dst() -> QueryCreator {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .groupby(groupby_func, Box::new(counter), "pkts".to_string())
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
@@ -86,13 +90,13 @@ fn tcp_new_cons() -> QueryCreator {
         let filter_func2: FilterFunc =
             Box::new(move |headers: &Headers| key_geq_int("cons".to_string(), threshold, headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .filter(filter_func)
             .groupby(groupby_func, Box::new(counter), "cons".to_string())
             .filter(filter_func2)
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
@@ -116,14 +120,14 @@ fn ssh_brute_force() -> QueryCreator {
         let filter_func2: FilterFunc =
             Box::new(move |headers: &Headers| key_geq_int("srcs".to_string(), threshold, headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .filter(filter_func)
             .distinct(groupby_func)
             .groupby(groupby_func2, Box::new(counter), "srcs".to_string())
             .filter(filter_func2)
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
@@ -139,13 +143,13 @@ fn super_spreader() -> QueryCreator {
         let filter_func: FilterFunc =
             Box::new(move |headers: &Headers| key_geq_int("dsts".to_string(), threshold, headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .distinct(groupby_func)
             .groupby(groupby_func2, Box::new(counter), "dsts".to_string())
             .filter(filter_func)
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
@@ -161,13 +165,13 @@ fn port_scan() -> QueryCreator {
         let filter_func: FilterFunc =
             Box::new(move |headers: &Headers| key_geq_int("ports".to_string(), threshold, headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .distinct(groupby_func)
             .groupby(groupby_func2, Box::new(counter), "ports".to_string())
             .filter(filter_func)
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
@@ -183,17 +187,17 @@ fn ddos() -> QueryCreator {
         let filter_func: FilterFunc =
             Box::new(move |headers: &Headers| key_geq_int("ports".to_string(), threshold, headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .distinct(groupby_func)
             .groupby(groupby_func2, Box::new(counter), "srcs".to_string())
             .filter(filter_func)
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
-fn syn_flood_sonata(next_q: Query) -> Result<[QueryKind; 3], Box<dyn Error>> {
+fn syn_flood_sonata(next_q: Query) -> Result<[OperatorRef; 3], StreamError> {
     let threshold: i32 = 5;
 
     let syns: QueryCreator = Box::new(move |next_q: Query| {
@@ -205,12 +209,12 @@ fn syn_flood_sonata(next_q: Query) -> Result<[QueryKind; 3], Box<dyn Error>> {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .filter(filter_func)
             .groupby(groupby_func, Box::new(counter), "syns".to_string())
             .add_query(next_q)
-            .collect()?)
+            .collect()
     });
 
     let acks: QueryCreator = Box::new(move |next_q: Query| {
@@ -222,12 +226,12 @@ fn syn_flood_sonata(next_q: Query) -> Result<[QueryKind; 3], Box<dyn Error>> {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .filter(filter_func)
             .groupby(groupby_func, Box::new(counter), "acks".to_string())
             .add_query(next_q)
-            .collect()?)
+            .collect()
     });
 
     let synacks: QueryCreator = Box::new(move |next_q: Query| {
@@ -239,15 +243,15 @@ fn syn_flood_sonata(next_q: Query) -> Result<[QueryKind; 3], Box<dyn Error>> {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(1.0, "eid".to_string())
             .filter(filter_func)
             .groupby(groupby_func, Box::new(counter), "synacks".to_string())
             .add_query(next_q)
-            .collect()?)
+            .collect()
     });
 
-    let first_join_ops: Box<dyn FnOnce(Query) -> Result<QueryKind, Box<dyn Error>>> =
+    let first_join_ops: JoinQueryCreator =
         Box::new(move |next_q_inner: Query| {
             let incl_keys: Vec<String> = Vec::from(["host".to_string()]);
             let incl_keys2: Vec<String> = Vec::from(["syns+synacks".to_string()]);
@@ -273,25 +277,25 @@ fn syn_flood_sonata(next_q: Query) -> Result<[QueryKind; 3], Box<dyn Error>> {
                 Box::new(move |mut headers: Headers| {
                     headers
                         .insert(
-                            "syns+synacks".to_string(),
-                            utils::OpResult::Int(headers.get_mapped_int("acks".to_string())),
-                        )
-                        .unwrap();
+                            "syns+synacks-acks".to_string(),
+                            utils::OpResult::Int(
+                                headers.get_mapped_int("syns+synacks".to_string())
+                                    - headers.get_mapped_int("acks".to_string()),
+                            ),
+                        );
                     headers
                 });
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
                 key_geq_int("syns+synacks-acks".to_string(), threshold, headers)
             });
 
-            Ok(Query::new(None, None)
-                .join(None, left_extractor_func, right_extractor_func)
+            Query::join(None, left_extractor_func, right_extractor_func)
                 .map(mapping_func)
                 .filter(filter_func)
                 .add_query(next_q_inner)
-                .collect()?)
         });
 
-    let second_join_ops: Box<dyn FnOnce(Query) -> Result<QueryKind, Box<dyn Error>>> =
+    let second_join_ops: JoinQueryCreator =
         Box::new(move |next_q_inner: Query| {
             let incl_keys: Vec<String> = Vec::from(["syns".to_string()]);
             let incl_keys2: Vec<String> = Vec::from(["synacks".to_string()]);
@@ -324,51 +328,27 @@ fn syn_flood_sonata(next_q: Query) -> Result<[QueryKind; 3], Box<dyn Error>> {
                                 headers.get_mapped_int("syns".to_string())
                                     + headers.get_mapped_int("synacks".to_string()),
                             ),
-                        )
-                        .unwrap();
+                        );
                     headers
                 });
-            Ok(Query::new(None, None)
-                .join(None, left_extractor_func, right_extractor_func)
+            Query::join(None, left_extractor_func, right_extractor_func)
                 .map(mapping_func)
                 .add_query(next_q_inner)
-                .collect()?)
         });
 
-    let first_join_query: QueryKind = first_join_ops(next_q)?;
-    let (mut _join_op1, mut _join_op2) = (None, None);
-
-    match first_join_query {
-        QueryKind::OpPair((op1, op2)) => {
-            _join_op1 = Some(op1);
-            _join_op2 = Some(op2);
-        }
-        _ => {
-            return Err("first_join_ops did not return QueryKind::OpPair".into());
-        }
-    }
-
-    let second_join_query: QueryKind = second_join_ops(Query::new(None, _join_op1))?;
-    let (mut _join_op3, mut _join_op4) = (None, None);
-
-    match second_join_query {
-        QueryKind::OpPair((op3, op4)) => {
-            _join_op3 = Some(op3);
-            _join_op4 = Some(op4);
-        }
-        _ => {
-            return Err("first_join_ops did not return QueryKind::OpPair".into());
-        }
-    }
+    let (_join_op1, _join_op2): OpPair = first_join_ops(next_q).collect()?;
+
+    let (_join_op3, _join_op4): OpPair =
+        second_join_ops(Query::new(None, Some(_join_op1))).collect()?;
 
     Ok([
-        syns(Query::new(None, _join_op3))?,
-        synacks(Query::new(None, _join_op4))?,
-        acks(Query::new(None, _join_op2))?,
+        syns(Query::new(None, Some(_join_op3)))?,
+        synacks(Query::new(None, Some(_join_op4)))?,
+        acks(Query::new(None, Some(_join_op2)))?,
     ])
 }
 
-fn completed_flows(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
+fn completed_flows(next_q: Query) -> Result<[OperatorRef; 2], StreamError> {
     let threshold: i32 = 1;
     let epoch_dur: f64 = 30.0;
 
@@ -381,12 +361,12 @@ fn completed_flows(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(epoch_dur, "eid".to_string())
             .filter(filter_func)
             .groupby(groupby_func, Box::new(counter), "syns".to_string())
             .add_query(next_q)
-            .collect()?)
+            .collect()
     });
 
     let fins: QueryCreator = Box::new(move |next_q_inner: Query| {
@@ -398,15 +378,15 @@ fn completed_flows(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(epoch_dur, "eid".to_string())
             .filter(filter_func)
             .groupby(groupby_func, Box::new(counter), "fins".to_string())
             .add_query(next_q_inner)
-            .collect()?)
+            .collect()
     });
 
-    let join_query: Box<dyn FnOnce(Query) -> Result<QueryKind, Box<dyn Error>>> =
+    let join_query: JoinQueryCreator =
         Box::new(move |next_q_inner: Query| {
             let incl_keys: Vec<String> = Vec::from(["syns".to_string()]);
             let left_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
@@ -435,43 +415,32 @@ fn completed_flows(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
                     headers
                         .insert(
                             "diff".to_string(),
-                            utils::OpResult::Int(headers.get_mapped_int("syns".to_string())),
-                        )
-                        .unwrap();
+                            utils::OpResult::Int(
+                                headers.get_mapped_int("syns".to_string())
+                                    - headers.get_mapped_int("fins".to_string()),
+                            ),
+                        );
                     headers
                 });
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
                 key_geq_int("diff".to_string(), threshold, headers)
             });
 
-            Ok(Query::new(None, None)
-                .join(None, left_extractor_func, right_extractor_func)
+            Query::join(None, left_extractor_func, right_extractor_func)
                 .map(mapping_func)
                 .filter(filter_func)
                 .add_query(next_q_inner)
-                .collect()?)
         });
 
-    let join_query: QueryKind = join_query(next_q)?;
-    let (mut _join_op1, mut _join_op2) = (None, None);
-
-    match join_query {
-        QueryKind::OpPair((op1, op2)) => {
-            _join_op1 = Some(op1);
-            _join_op2 = Some(op2);
-        }
-        _ => {
-            return Err("first_join_ops did not return QueryKind::OpPair".into());
-        }
-    }
+    let (_join_op1, _join_op2): OpPair = join_query(next_q).collect()?;
 
     Ok([
-        syns(Query::new(None, _join_op1))?,
-        fins(Query::new(None, _join_op2))?,
+        syns(Query::new(None, Some(_join_op1)))?,
+        fins(Query::new(None, Some(_join_op2)))?,
     ])
 }
 
-fn slowloris(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
+fn slowloris(next_q: Query) -> Result<[OperatorRef; 2], StreamError> {
     let t1: i32 = 5;
     let t2: i32 = 500;
     let t3: i32 = 90;
@@ -494,14 +463,14 @@ fn slowloris(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
         let groupby_func2: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys2.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(epoch_dur, "eid".to_string())
             .filter(filter_func)
             .distinct(groupby_func)
             .groupby(groupby_func2, Box::new(counter), "n_conns".to_string())
             .filter(filter_func2)
             .add_query(next_q_inner)
-            .collect()?)
+            .collect()
     });
 
     let n_bytes: QueryCreator = Box::new(move |next_q_inner: Query| {
@@ -518,16 +487,16 @@ fn slowloris(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
                 sum_ints("ipv4.len".to_string(), init_val, headers).unwrap()
             });
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(epoch_dur, "eid".to_string())
             .filter(filter_func)
             .groupby(groupby_func, reduce_func, "n_bytes".to_string())
             .filter(filter_func2)
             .add_query(next_q_inner)
-            .collect()?)
+            .collect()
     });
 
-    let join_query: Box<dyn FnOnce(Query) -> Result<QueryKind, Box<dyn Error>>> =
+    let join_query: JoinQueryCreator =
         Box::new(move |next_q_inner: Query| {
             let left_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
                 Box::new(move |mut headers: Headers| {
@@ -556,42 +525,28 @@ fn slowloris(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
                                 headers.get_mapped_int("n_bytes".to_string())
                                     / headers.get_mapped_int("n_conns".to_string()),
                             ),
-                        )
-                        .unwrap();
+                        );
                     headers
                 });
             let filter_func: FilterFunc = Box::new(move |headers: &Headers| {
                 headers.get_mapped_int("bytes_per_conn".to_string()) <= t3
             });
 
-            Ok(Query::new(None, None)
-                .join(None, left_extractor_func, right_extractor_func)
+            Query::join(None, left_extractor_func, right_extractor_func)
                 .map(mapping_func)
                 .filter(filter_func)
                 .add_query(next_q_inner)
-                .collect()?)
         });
 
-    let join_query: QueryKind = join_query(next_q)?;
-    let (mut _join_op1, mut _join_op2) = (None, None);
-
-    match join_query {
-        QueryKind::OpPair((op1, op2)) => {
-            _join_op1 = Some(op1);
-            _join_op2 = Some(op2);
-        }
-        _ => {
-            return Err("first_join_ops did not return QueryKind::OpPair".into());
-        }
-    }
+    let (_join_op1, _join_op2): OpPair = join_query(next_q).collect()?;
 
     Ok([
-        n_conns(Query::new(None, _join_op1))?,
-        n_bytes(Query::new(None, _join_op2))?,
+        n_conns(Query::new(None, Some(_join_op1)))?,
+        n_bytes(Query::new(None, Some(_join_op2)))?,
     ])
 }
 
-fn join_operator_test(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
+fn join_operator_test(next_q: Query) -> Result<[OperatorRef; 2], StreamError> {
     let epoch_dur: f64 = 1.0;
 
     let syns: QueryCreator = Box::new(move |next_q_inner: Query| {
@@ -600,11 +555,11 @@ fn join_operator_test(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
                 && headers.get_mapped_int("l4.flags".to_string()) == 2
         });
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(epoch_dur, "eid".to_string())
             .filter(filter_func)
             .add_query(next_q_inner)
-            .collect()?)
+            .collect()
     });
 
     let synacks: QueryCreator = Box::new(move |next_q_inner: Query| {
@@ -613,14 +568,14 @@ fn join_operator_test(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
                 && headers.get_mapped_int("l4.flags".to_string()) == 18
         });
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(epoch_dur, "eid".to_string())
             .filter(filter_func)
             .add_query(next_q_inner)
-            .collect()?)
+            .collect()
     });
 
-    let join_query: Box<dyn FnOnce(Query) -> Result<QueryKind, Box<dyn Error>>> =
+    let join_query: JoinQueryCreator =
         Box::new(move |next_q_inner: Query| {
             let left_extractor_func: Box<dyn FnMut(Headers) -> (Headers, Headers) + 'static> =
                 Box::new(move |mut headers: Headers| {
@@ -646,28 +601,15 @@ fn join_operator_test(next_q: Query) -> Result<[QueryKind; 2], Box<dyn Error>> {
                     )
                 });
 
-            Ok(Query::new(None, None)
-                .join(None, left_extractor_func, right_extractor_func)
+            Query::join(None, left_extractor_func, right_extractor_func)
                 .add_query(next_q_inner)
-                .collect()?)
         });
 
-    let join_query: QueryKind = join_query(next_q)?;
-    let (mut _join_op1, mut _join_op2) = (None, None);
-
-    match join_query {
-        QueryKind::OpPair((op1, op2)) => {
-            _join_op1 = Some(op1);
-            _join_op2 = Some(op2);
-        }
-        _ => {
-            return Err("first_join_ops did not return QueryKind::OpPair".into());
-        }
-    }
+    let (_join_op1, _join_op2): OpPair = join_query(next_q).collect()?;
 
     Ok([
-        syns(Query::new(None, _join_op1))?,
-        synacks(Query::new(None, _join_op2))?,
+        syns(Query::new(None, Some(_join_op1)))?,
+        synacks(Query::new(None, Some(_join_op2)))?,
     ])
 }
 
@@ -677,11 +619,11 @@ fn q3() -> QueryCreator {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(100.0, "eid".to_string())
             .distinct(groupby_func)
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
@@ -691,54 +633,35 @@ fn q4() -> QueryCreator {
         let groupby_func: GroupingFunc =
             Box::new(move |mut headers: Headers| filter_groups(incl_keys.clone(), &mut headers));
 
-        Ok(Query::new(None, None)
+        Query::new(None, None)
             .epoch(10000.0, "eid".to_string())
             .groupby(groupby_func, Box::new(counter), "pkts".to_string())
             .add_query(next_q)
-            .collect()?)
+            .collect()
     })
 }
 
-fn create_query() -> Result<OperatorRef, Box<dyn Error>> {
-    let query = ident()(Query::new(None, None).dump_as_csv(None, Some(true), Box::new(stdout())));
-
-    match query {
-        Ok(QueryKind::Op(op)) => match op {
-            OpKind::Operator(op) => Ok(op),
-            _ => Err("query in create_query is not an operator".into()),
-        },
-        _ => Err("query in create_query is not an op_kind".into()),
-    }
+fn create_query() -> Result<OperatorRef, StreamError> {
+    ident()(Query::new(None, None).dump_as_csv(None, Some(true), Box::new(stdout())))
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<(), StreamError> {
     let mut _query: OperatorRef = create_query()?;
     Ok(for i in 0..20 {
-        let mut header: Headers = Headers::new();
-        header.insert("time".to_string(), OpResult::Float(OrderedFloat(i as f64)));
-        header.insert(
-            "eth.src".to_string(),
-            OpResult::MAC([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]),
-        );
-        header.insert(
-            "eth.dst".to_string(),
-            OpResult::MAC([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]),
-        );
-        header.insert("eth.ethertype".to_string(), OpResult::Int(0x0800));
-        header.insert("ipv4.hlen".to_string(), OpResult::Int(20));
-        header.insert("ipv4.proto".to_string(), OpResult::Int(6));
-        header.insert("ipv4.len".to_string(), OpResult::Int(60));
-        header.insert(
-            "ipv4.src".to_string(),
-            OpResult::IPv4("127.0.0.1".parse().unwrap()),
-        );
-        header.insert(
-            "ipv4.dst".to_string(),
-            OpResult::IPv4("127.0.0.1".parse().unwrap()),
-        );
-        header.insert("l4.sport".to_string(), OpResult::Int(440));
-        header.insert("l4.dport".to_string(), OpResult::Int(50000));
-        header.insert("l4.flags".to_string(), OpResult::Int(10));
+        let mut header: Headers = tuple! {
+            "time" => i as f64,
+            "eth.src" => [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+            "eth.dst" => [0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF],
+            "eth.ethertype" => 0x0800,
+            "ipv4.hlen" => 20,
+            "ipv4.proto" => 6,
+            "ipv4.len" => 60,
+            "ipv4.src" => ip!("127.0.0.1"),
+            "ipv4.dst" => ip!("127.0.0.1"),
+            "l4.sport" => 440,
+            "l4.dport" => 50000,
+            "l4.flags" => 10,
+        };
         (_query.borrow_mut().next)(&mut header)
     })
 }
@@ -0,0 +1,11 @@
+#![allow(dead_code)]
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SchemaError {
+    #[error("'{0}' is missing from the tuple")]
+    Missing(String),
+    #[error("Trying to extract {0} from non-{0} result")]
+    TypeMismatch(&'static str),
+}
@@ -1,11 +1,12 @@
 #![allow(dead_code)]
 
 use ordered_float::OrderedFloat;
+
+use crate::error::{SchemaError, StreamError};
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::io::Write;
-use std::io::{Error, ErrorKind};
 use std::net::Ipv4Addr;
 use std::rc::Rc;
 
@@ -18,6 +19,47 @@ pub enum OpResult {
     Empty,
 }
 
+impl From<i32> for OpResult {
+    fn from(i: i32) -> OpResult {
+        OpResult::Int(i)
+    }
+}
+
+impl From<f64> for OpResult {
+    fn from(f: f64) -> OpResult {
+        OpResult::Float(OrderedFloat(f))
+    }
+}
+
+impl From<Ipv4Addr> for OpResult {
+    fn from(addr: Ipv4Addr) -> OpResult {
+        OpResult::IPv4(addr)
+    }
+}
+
+impl From<[u8; 6]> for OpResult {
+    fn from(mac: [u8; 6]) -> OpResult {
+        OpResult::MAC(mac)
+    }
+}
+
+#[macro_export]
+macro_rules! tuple {
+    ($($key:expr => $val:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut headers: $crate::utils::Headers = $crate::utils::Headers::new();
+        $(headers.insert($key.to_string(), $crate::utils::OpResult::from($val));)*
+        headers
+    }};
+}
+
+#[macro_export]
+macro_rules! ip {
+    ($addr:expr) => {
+        $addr.parse::<std::net::Ipv4Addr>().unwrap()
+    };
+}
+
 impl fmt::Display for OpResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", string_of_op_result(self))
@@ -109,23 +151,17 @@ impl Headers {
         self
     }
 
-    pub fn lookup_int(&self, key: &String) -> Result<i32, Error> {
+    pub fn lookup_int(&self, key: &String) -> Result<i32, StreamError> {
         match self.headers.get(key) {
             Some(i) => int_of_op_result(i),
-            None => Err(Error::new(
-                ErrorKind::InvalidData,
-                "key given as argument is not a valid key of the given BTreeMap",
-            )),
+            None => Err(SchemaError::Missing(key.clone()).into()),
         }
     }
 
-    pub fn lookup_float(&self, key: &String) -> Result<OrderedFloat<f64>, Error> {
+    pub fn lookup_float(&self, key: &String) -> Result<OrderedFloat<f64>, StreamError> {
         match self.headers.get(key) {
             Some(f) => float_of_op_result(f),
-            None => Err(Error::new(
-                ErrorKind::InvalidData,
-                "key given as argument is not a valid key of the given BTreeMap",
-            )),
+            None => Err(SchemaError::Missing(key.clone()).into()),
         }
     }
 }
@@ -169,23 +205,17 @@ pub fn tcp_flags_to_strings(flags: i32) -> String {
         })
 }
 
-pub fn int_of_op_result(input: &OpResult) -> Result<i32, Error> {
+pub fn int_of_op_result(input: &OpResult) -> Result<i32, StreamError> {
     match *input {
         OpResult::Int(i) => Ok(i),
-        _ => Err(Error::new(
-            ErrorKind::InvalidInput,
-            "Trying to extract int from non-int result",
-        )),
+        _ => Err(SchemaError::TypeMismatch("int").into()),
     }
 }
 
-pub fn float_of_op_result(input: &OpResult) -> Result<OrderedFloat<f64>, Error> {
+pub fn float_of_op_result(input: &OpResult) -> Result<OrderedFloat<f64>, StreamError> {
     match *input {
         OpResult::Float(f) => Ok(f),
-        _ => Err(Error::new(
-            ErrorKind::InvalidInput,
-            "Trying to extract float from non-float result",
-        )),
+        _ => Err(SchemaError::TypeMismatch("float").into()),
     }
 }
 
@@ -199,7 +229,10 @@ pub fn string_of_op_result(input: &OpResult) -> String {
     }
 }
 
-pub fn dump_headers<'a, W: Write>(outc: &'a mut W, headers: &Headers) -> Result<&'a W, Error> {
+pub fn dump_headers<'a, W: Write>(
+    outc: &'a mut W,
+    headers: &Headers,
+) -> Result<&'a W, StreamError> {
     writeln!(outc, "{}", headers.to_string()).unwrap();
     Ok(outc)
 }